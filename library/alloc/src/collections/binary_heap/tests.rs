@@ -487,3 +487,18 @@ fn panic_safe() {
         }
     }
 }
+
+#[test]
+fn test_new_in() {
+    let mut heap = BinaryHeap::new_in(Global);
+    heap.extend([3, 1, 4, 1, 5]);
+    assert_eq!(heap.peek(), Some(&5));
+    let v: Vec<i32, Global> = heap.into_sorted_vec();
+    assert_eq!(v, [1, 1, 3, 4, 5]);
+
+    let mut heap = BinaryHeap::with_capacity_in(10, Global);
+    assert!(heap.capacity() >= 10);
+    heap.push(2);
+    heap.push(7);
+    assert_eq!(heap.drain_sorted().collect::<Vec<_>>(), [7, 2]);
+}
@@ -854,3 +854,61 @@ fn test_range_panic_2() {
 
     let _invalid_range = set.range((Excluded(&5), Excluded(&5)));
 }
+
+#[test]
+fn test_cursor() {
+    let set = BTreeSet::from([1, 2, 3]);
+
+    let mut cur = set.lower_bound(Bound::<&i32>::Unbounded);
+    assert_eq!(cur.value(), Some(&1));
+    cur.move_next();
+    assert_eq!(cur.value(), Some(&2));
+    assert_eq!(cur.peek_next(), Some(&3));
+    cur.move_prev();
+    assert_eq!(cur.value(), Some(&1));
+    assert_eq!(cur.peek_prev(), None);
+
+    let mut cur = set.upper_bound(Excluded(&1));
+    assert_eq!(cur.value(), None);
+    cur.move_next();
+    assert_eq!(cur.value(), Some(&1));
+    cur.move_prev();
+    assert_eq!(cur.value(), None);
+    assert_eq!(cur.peek_prev(), Some(&3));
+}
+
+#[test]
+fn test_cursor_mut() {
+    let mut set = BTreeSet::from([1, 3, 5]);
+    let mut cur = set.lower_bound_mut(Excluded(&3));
+    assert_eq!(cur.value(), Some(&5));
+    cur.insert_before(4);
+    assert_eq!(cur.value(), Some(&5));
+    assert_eq!(cur.peek_prev(), Some(&4));
+    cur.move_next();
+    assert_eq!(cur.value(), None);
+    cur.insert_before(6);
+    assert_eq!(cur.value(), None);
+    assert_eq!(cur.remove_current(), None);
+    assert_eq!(cur.value(), None);
+    cur.insert_after(0);
+    assert_eq!(cur.value(), None);
+    assert_eq!(set, BTreeSet::from([0, 1, 3, 4, 5, 6]));
+
+    let mut cur = set.upper_bound_mut(Included(&5));
+    assert_eq!(cur.value(), Some(&5));
+    assert_eq!(cur.remove_current(), Some(5));
+    assert_eq!(cur.value(), Some(&6));
+    assert_eq!(cur.remove_current_and_move_back(), Some(6));
+    assert_eq!(cur.value(), Some(&4));
+    assert_eq!(cur.as_cursor().peek_prev(), Some(&3));
+    assert_eq!(set, BTreeSet::from([0, 1, 3, 4]));
+}
+
+#[should_panic(expected = "key must be ordered above the current element")]
+#[test]
+fn test_cursor_mut_insert_after_misordered() {
+    let mut set = BTreeSet::from([1, 2, 3]);
+    let mut cur = set.upper_bound_mut(Included(&2));
+    cur.insert_after(1);
+}
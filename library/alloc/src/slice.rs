@@ -203,7 +203,35 @@ impl<T> [T] {
     where
         T: Ord,
     {
-        merge_sort(self, T::lt);
+        merge_sort(self, T::lt, Global);
+    }
+
+    /// Sorts the slice, with temporary storage from `alloc`.
+    ///
+    /// This is [`sort`](slice::sort), but for where the temporary storage comes from: half the
+    /// size of `self`, for slices long enough to need it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    ///
+    /// use std::alloc::System;
+    ///
+    /// let mut v = [-5, 4, 1, -3, 2];
+    ///
+    /// v.sort_in(System);
+    /// assert!(v == [-5, -3, 1, 2, 4]);
+    /// ```
+    #[cfg(not(no_global_oom_handling))]
+    #[rustc_allow_incoherent_impl]
+    #[unstable(feature = "allocator_api", issue = "32838")]
+    #[inline]
+    pub fn sort_in<A: Allocator>(&mut self, alloc: A)
+    where
+        T: Ord,
+    {
+        merge_sort(self, T::lt, alloc);
     }
 
     /// Sorts the slice with a comparator function.
@@ -259,7 +287,33 @@ impl<T> [T] {
     where
         F: FnMut(&T, &T) -> Ordering,
     {
-        merge_sort(self, |a, b| compare(a, b) == Less);
+        merge_sort(self, |a, b| compare(a, b) == Less, Global);
+    }
+
+    /// Sorts the slice with a comparator function, with temporary storage from `alloc`.
+    ///
+    /// This is [`sort_by`](slice::sort_by), but for where the temporary storage comes from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    ///
+    /// use std::alloc::System;
+    ///
+    /// let mut v = [5, 4, 1, 3, 2];
+    /// v.sort_by_in(|a, b| b.cmp(a), System);
+    /// assert!(v == [5, 4, 3, 2, 1]);
+    /// ```
+    #[cfg(not(no_global_oom_handling))]
+    #[rustc_allow_incoherent_impl]
+    #[unstable(feature = "allocator_api", issue = "32838")]
+    #[inline]
+    pub fn sort_by_in<F, A: Allocator>(&mut self, mut compare: F, alloc: A)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        merge_sort(self, |a, b| compare(a, b) == Less, alloc);
     }
 
     /// Sorts the slice with a key extraction function.
@@ -302,7 +356,36 @@ impl<T> [T] {
         F: FnMut(&T) -> K,
         K: Ord,
     {
-        merge_sort(self, |a, b| f(a).lt(&f(b)));
+        merge_sort(self, |a, b| f(a).lt(&f(b)), Global);
+    }
+
+    /// Sorts the slice with a key extraction function, with temporary storage from `alloc`.
+    ///
+    /// This is [`sort_by_key`](slice::sort_by_key), but for where the temporary storage comes
+    /// from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    ///
+    /// use std::alloc::System;
+    ///
+    /// let mut v = [-5i32, 4, 1, -3, 2];
+    ///
+    /// v.sort_by_key_in(|k| k.abs(), System);
+    /// assert!(v == [1, 2, -3, 4, -5]);
+    /// ```
+    #[cfg(not(no_global_oom_handling))]
+    #[rustc_allow_incoherent_impl]
+    #[unstable(feature = "allocator_api", issue = "32838")]
+    #[inline]
+    pub fn sort_by_key_in<K, F, A: Allocator>(&mut self, mut f: F, alloc: A)
+    where
+        F: FnMut(&T) -> K,
+        K: Ord,
+    {
+        merge_sort(self, |a, b| f(a).lt(&f(b)), alloc);
     }
 
     /// Sorts the slice with a key extraction function.
@@ -345,6 +428,35 @@ impl<T> [T] {
     #[stable(feature = "slice_sort_by_cached_key", since = "1.34.0")]
     #[inline]
     pub fn sort_by_cached_key<K, F>(&mut self, f: F)
+    where
+        F: FnMut(&T) -> K,
+        K: Ord,
+    {
+        self.sort_by_cached_key_in(f, Global);
+    }
+
+    /// Sorts the slice with a key extraction function, with the keys stored in `alloc`.
+    ///
+    /// This is [`sort_by_cached_key`](slice::sort_by_cached_key), but for where the temporary
+    /// storage comes from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(allocator_api)]
+    ///
+    /// use std::alloc::System;
+    ///
+    /// let mut v = [-5i32, 4, 32, -3, 2];
+    ///
+    /// v.sort_by_cached_key_in(|k| k.to_string(), System);
+    /// assert!(v == [-3, -5, 2, 32, 4]);
+    /// ```
+    #[cfg(not(no_global_oom_handling))]
+    #[rustc_allow_incoherent_impl]
+    #[unstable(feature = "allocator_api", issue = "32838")]
+    #[inline]
+    pub fn sort_by_cached_key_in<K, F, A: Allocator>(&mut self, f: F, alloc: A)
     where
         F: FnMut(&T) -> K,
         K: Ord,
@@ -352,8 +464,8 @@ impl<T> [T] {
         // Helper macro for indexing our vector by the smallest possible type, to reduce allocation.
         macro_rules! sort_by_key {
             ($t:ty, $slice:ident, $f:ident) => {{
-                let mut indices: Vec<_> =
-                    $slice.iter().map($f).enumerate().map(|(i, k)| (k, i as $t)).collect();
+                let mut indices = Vec::with_capacity_in($slice.len(), alloc);
+                indices.extend($slice.iter().map($f).enumerate().map(|(i, k)| (k, i as $t)));
                 // The elements of `indices` are unique, as they are indexed, so any sort will be
                 // stable with respect to the original slice. We use `sort_unstable` here because
                 // it requires less memory allocation.
@@ -1006,7 +1118,7 @@ where
 ///
 /// The invariants ensure that the total running time is *O*(*n* \* log(*n*)) worst-case.
 #[cfg(not(no_global_oom_handling))]
-fn merge_sort<T, F>(v: &mut [T], mut is_less: F)
+fn merge_sort<T, F, A: Allocator>(v: &mut [T], mut is_less: F, alloc: A)
 where
     F: FnMut(&T, &T) -> bool,
 {
@@ -1036,13 +1148,13 @@ where
     // shallow copies of the contents of `v` without risking the dtors running on copies if
     // `is_less` panics. When merging two sorted runs, this buffer holds a copy of the shorter run,
     // which will always have length at most `len / 2`.
-    let mut buf = Vec::with_capacity(len / 2);
+    let mut buf = Vec::with_capacity_in(len / 2, &alloc);
 
     // In order to identify natural runs in `v`, we traverse it backwards. That might seem like a
     // strange decision, but consider the fact that merges more often go in the opposite direction
     // (forwards). According to benchmarks, merging forwards is slightly faster than merging
     // backwards. To conclude, identifying runs by traversing backwards improves performance.
-    let mut runs = vec![];
+    let mut runs = Vec::new_in(&alloc);
     let mut end = len;
     while end > 0 {
         // Find the next natural run, and reverse it if it's strictly descending.
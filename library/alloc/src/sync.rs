@@ -53,17 +53,108 @@ mod tests;
 /// necessarily) at _exactly_ `MAX_REFCOUNT + 1` references.
 const MAX_REFCOUNT: usize = (isize::MAX) as usize;
 
-#[cfg(not(sanitize = "thread"))]
+#[cfg(not(all(feature = "pg_local_arc", target_family = "postgres")))]
+use core::sync::atomic::AtomicUsize as Count;
+
+/// The reference counts of an `Arc` in a postgres backend, which only ever
+/// has one thread, with the `pg_local_arc` feature: plain integers, behind
+/// the same methods as `AtomicUsize`, whose orderings mean nothing here.
+#[cfg(all(feature = "pg_local_arc", target_family = "postgres"))]
+#[repr(transparent)]
+struct Count(core::cell::Cell<usize>);
+
+#[cfg(all(feature = "pg_local_arc", target_family = "postgres"))]
+impl Count {
+    #[inline]
+    const fn new(n: usize) -> Count {
+        Count(core::cell::Cell::new(n))
+    }
+
+    #[inline]
+    fn load(&self, _: atomic::Ordering) -> usize {
+        self.0.get()
+    }
+
+    #[inline]
+    fn store(&self, n: usize, _: atomic::Ordering) {
+        self.0.set(n)
+    }
+
+    #[inline]
+    fn fetch_add(&self, n: usize, _: atomic::Ordering) -> usize {
+        self.0.replace(self.0.get().wrapping_add(n))
+    }
+
+    #[inline]
+    fn fetch_sub(&self, n: usize, _: atomic::Ordering) -> usize {
+        self.0.replace(self.0.get().wrapping_sub(n))
+    }
+
+    #[inline]
+    fn compare_exchange(
+        &self,
+        current: usize,
+        new: usize,
+        _: atomic::Ordering,
+        _: atomic::Ordering,
+    ) -> Result<usize, usize> {
+        let old = self.0.get();
+        if old == current {
+            self.0.set(new);
+            Ok(old)
+        } else {
+            Err(old)
+        }
+    }
+
+    #[inline]
+    fn compare_exchange_weak(
+        &self,
+        current: usize,
+        new: usize,
+        success: atomic::Ordering,
+        failure: atomic::Ordering,
+    ) -> Result<usize, usize> {
+        self.compare_exchange(current, new, success, failure)
+    }
+
+    #[inline]
+    fn fetch_update(
+        &self,
+        _: atomic::Ordering,
+        _: atomic::Ordering,
+        mut f: impl FnMut(usize) -> Option<usize>,
+    ) -> Result<usize, usize> {
+        let old = self.0.get();
+        match f(old) {
+            Some(new) => {
+                self.0.set(new);
+                Ok(old)
+            }
+            None => Err(old),
+        }
+    }
+}
+
+#[cfg(not(any(sanitize = "thread", all(feature = "pg_local_arc", target_family = "postgres"))))]
 macro_rules! acquire {
     ($x:expr) => {
         atomic::fence(Acquire)
     };
 }
 
+// Plain integers have nothing to synchronize with.
+#[cfg(all(feature = "pg_local_arc", target_family = "postgres"))]
+macro_rules! acquire {
+    ($x:expr) => {
+        ()
+    };
+}
+
 // ThreadSanitizer does not support memory fences. To avoid false positive
 // reports in Arc / Weak implementation use atomic loads for synchronization
 // instead.
-#[cfg(sanitize = "thread")]
+#[cfg(all(sanitize = "thread", not(all(feature = "pg_local_arc", target_family = "postgres"))))]
 macro_rules! acquire {
     ($x:expr) => {
         $x.load(Acquire)
@@ -323,12 +414,12 @@ impl<T: ?Sized + fmt::Debug> fmt::Debug for Weak<T> {
 // inner types.
 #[repr(C)]
 struct ArcInner<T: ?Sized> {
-    strong: atomic::AtomicUsize,
+    strong: Count,
 
     // the value usize::MAX acts as a sentinel for temporarily "locking" the
     // ability to upgrade weak pointers or downgrade strong ones; this is used
     // to avoid races in `make_mut` and `get_mut`.
-    weak: atomic::AtomicUsize,
+    weak: Count,
 
     data: T,
 }
@@ -361,11 +452,7 @@ impl<T> Arc<T> {
     pub fn new(data: T) -> Arc<T> {
         // Start the weak pointer count as 1 which is the weak pointer that's
         // held by all the strong pointers (kinda), see std/rc.rs for more info
-        let x: Box<_> = Box::new(ArcInner {
-            strong: atomic::AtomicUsize::new(1),
-            weak: atomic::AtomicUsize::new(1),
-            data,
-        });
+        let x: Box<_> = Box::new(ArcInner { strong: Count::new(1), weak: Count::new(1), data });
         unsafe { Self::from_inner(Box::leak(x).into()) }
     }
 
@@ -430,8 +517,8 @@ impl<T> Arc<T> {
         // Construct the inner in the "uninitialized" state with a single
         // weak reference.
         let uninit_ptr: NonNull<_> = Box::leak(Box::new(ArcInner {
-            strong: atomic::AtomicUsize::new(0),
-            weak: atomic::AtomicUsize::new(1),
+            strong: Count::new(0),
+            weak: Count::new(1),
             data: mem::MaybeUninit::<T>::uninit(),
         }))
         .into();
@@ -574,11 +661,8 @@ impl<T> Arc<T> {
     pub fn try_new(data: T) -> Result<Arc<T>, AllocError> {
         // Start the weak pointer count as 1 which is the weak pointer that's
         // held by all the strong pointers (kinda), see std/rc.rs for more info
-        let x: Box<_> = Box::try_new(ArcInner {
-            strong: atomic::AtomicUsize::new(1),
-            weak: atomic::AtomicUsize::new(1),
-            data,
-        })?;
+        let x: Box<_> =
+            Box::try_new(ArcInner { strong: Count::new(1), weak: Count::new(1), data })?;
         unsafe { Ok(Self::from_inner(Box::leak(x).into())) }
     }
 
@@ -1190,8 +1274,8 @@ impl<T: ?Sized> Arc<T> {
         debug_assert_eq!(unsafe { Layout::for_value(&*inner) }, layout);
 
         unsafe {
-            ptr::write(&mut (*inner).strong, atomic::AtomicUsize::new(1));
-            ptr::write(&mut (*inner).weak, atomic::AtomicUsize::new(1));
+            ptr::write(&mut (*inner).strong, Count::new(1));
+            ptr::write(&mut (*inner).weak, Count::new(1));
         }
 
         Ok(inner)
@@ -1848,8 +1932,8 @@ impl<T> Weak<T> {
 /// Helper type to allow accessing the reference counts without
 /// making any assertions about the data field.
 struct WeakInner<'a> {
-    weak: &'a atomic::AtomicUsize,
-    strong: &'a atomic::AtomicUsize,
+    weak: &'a Count,
+    strong: &'a Count,
 }
 
 impl<T: ?Sized> Weak<T> {
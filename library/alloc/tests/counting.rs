@@ -0,0 +1,46 @@
+//! An allocator that counts what is allocated with it, for tests of the
+//! `_in` APIs. std's own tests include this file as well, so it only names
+//! `core`.
+
+use core::alloc::{AllocError, Allocator, Layout};
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+/// Forwards to `A`, and counts the allocations made: all of them, and those
+/// not freed yet. Collections take it by reference, which is `Copy`.
+pub struct CountingAlloc<A> {
+    inner: A,
+    allocs: Cell<usize>,
+    live: Cell<usize>,
+}
+
+impl<A> CountingAlloc<A> {
+    pub const fn new(inner: A) -> Self {
+        CountingAlloc { inner, allocs: Cell::new(0), live: Cell::new(0) }
+    }
+
+    /// How many allocations were made, freed or not.
+    pub fn allocs(&self) -> usize {
+        self.allocs.get()
+    }
+
+    /// How many allocations are not freed yet.
+    pub fn live(&self) -> usize {
+        self.live.get()
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for CountingAlloc<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.inner.allocate(layout)?;
+        self.allocs.set(self.allocs.get() + 1);
+        self.live.set(self.live.get() + 1);
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.live.set(self.live.get() - 1);
+        // Safety: Invariants passed to caller.
+        unsafe { self.inner.deallocate(ptr, layout) }
+    }
+}
@@ -21,6 +21,7 @@
 #![feature(pattern)]
 #![feature(trusted_len)]
 #![feature(try_reserve_kind)]
+#![feature(try_with_capacity)]
 #![feature(unboxed_closures)]
 #![feature(associated_type_bounds)]
 #![feature(binary_heap_into_iter_sorted)]
@@ -60,6 +61,7 @@ mod boxed;
 mod btree_set_hash;
 mod c_str;
 mod const_fns;
+mod counting;
 mod cow_str;
 mod fmt;
 mod heap;
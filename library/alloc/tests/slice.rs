@@ -1,3 +1,4 @@
+use std::alloc::Global;
 use std::cell::Cell;
 use std::cmp::Ordering::{self, Equal, Greater, Less};
 use std::convert::identity;
@@ -7,6 +8,8 @@ use std::panic;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
 
+use crate::counting::CountingAlloc;
+
 use rand::distributions::Standard;
 use rand::seq::SliceRandom;
 use rand::{thread_rng, Rng, RngCore};
@@ -463,6 +466,42 @@ fn test_sort() {
     assert!(v == [0xDEADBEEF]);
 }
 
+#[test]
+fn test_sort_in() {
+    let orig: Vec<i32> = (0..500).map(|i| (i * 7919) % 1009 - 500).collect();
+    let allocs = CountingAlloc::new(Global);
+
+    let mut v = orig.clone();
+    v.sort_in(&allocs);
+    assert!(v.windows(2).all(|w| w[0] <= w[1]));
+    assert!(allocs.allocs() > 0);
+
+    let mut v = orig.clone();
+    v.sort_by_in(|a, b| b.cmp(a), &allocs);
+    assert!(v.windows(2).all(|w| w[0] >= w[1]));
+
+    let mut v1 = orig.clone();
+    let mut v2 = orig.clone();
+    v1.sort_by_key_in(|x| x.to_string(), &allocs);
+    v2.sort_by_key(|x| x.to_string());
+    assert!(v1 == v2);
+
+    let before = allocs.allocs();
+    let mut v = orig.clone();
+    v.sort_by_cached_key_in(|x| x.to_string(), &allocs);
+    assert!(v == v2);
+    // Just the keys: the strings themselves are in `Global`.
+    assert_eq!(allocs.allocs(), before + 1);
+    assert_eq!(allocs.live(), 0);
+
+    // Short slices need no temporary storage.
+    let allocs = CountingAlloc::new(Global);
+    let mut v = [3, 1, 2];
+    v.sort_in(&allocs);
+    assert_eq!(v, [1, 2, 3]);
+    assert_eq!(allocs.allocs(), 0);
+}
+
 #[test]
 fn test_sort_stability() {
     // Miri is too slow
@@ -983,6 +983,27 @@ fn from_utf8_error() {
     test!(b"A\xC3\xA9 \xF1\x80\x80 ", 4, Some(3));
 }
 
+#[test]
+fn from_utf8_error_long() {
+    // errors at every offset of inputs long enough to be validated in blocks
+    let text = "aé€😀".repeat(40);
+    assert!(from_utf8(text.as_bytes()).is_ok());
+    for i in 0..text.len() {
+        if text.is_char_boundary(i) {
+            let mut data = text.as_bytes().to_vec();
+            data.insert(i, 0xFF);
+            let error = from_utf8(&data).unwrap_err();
+            assert_eq!(error.valid_up_to(), i);
+            assert_eq!(error.error_len(), Some(1));
+        } else {
+            let start = (0..i).rev().find(|&j| text.is_char_boundary(j)).unwrap();
+            let error = from_utf8(&text.as_bytes()[..i]).unwrap_err();
+            assert_eq!(error.valid_up_to(), start);
+            assert_eq!(error.error_len(), None);
+        }
+    }
+}
+
 #[test]
 fn test_as_bytes() {
     // no null
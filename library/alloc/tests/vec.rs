@@ -1628,6 +1628,25 @@ fn test_reserve_exact() {
     assert!(v.capacity() >= 33)
 }
 
+#[test]
+fn test_try_with_capacity() {
+    let mut vec: Vec<u32> = Vec::try_with_capacity(5).unwrap();
+    assert_eq!(0, vec.len());
+    assert!(vec.capacity() >= 5 && vec.capacity() <= isize::MAX as usize / 4);
+
+    assert_matches!(
+        Vec::<u16>::try_with_capacity(isize::MAX as usize + 1).map_err(|e| e.kind()),
+        Err(CapacityOverflow)
+    );
+    assert_matches!(
+        Vec::<u8>::try_with_capacity_in(usize::MAX, System).map_err(|e| e.kind()),
+        Err(CapacityOverflow)
+    );
+
+    vec.push(1);
+    assert_eq!(vec, [1]);
+}
+
 #[test]
 #[cfg_attr(miri, ignore)] // Miri does not support signalling OOM
 #[cfg_attr(target_os = "android", ignore)] // Android used in CI has a broken dlmalloc
@@ -1144,6 +1144,18 @@ fn test_reserve_exact_2() {
     assert!(v.capacity() >= 33)
 }
 
+#[test]
+fn test_try_with_capacity() {
+    let deque: VecDeque<u32> = VecDeque::try_with_capacity(10).unwrap();
+    assert!(deque.is_empty());
+    assert!(deque.capacity() >= 10);
+
+    assert_matches!(
+        VecDeque::<u64>::try_with_capacity(usize::MAX).map_err(|e| e.kind()),
+        Err(CapacityOverflow)
+    );
+}
+
 #[test]
 #[cfg_attr(miri, ignore)] // Miri does not support signalling OOM
 #[cfg_attr(target_os = "android", ignore)] // Android used in CI has a broken dlmalloc
@@ -124,7 +124,41 @@ const fn contains_nonascii(x: usize) -> bool {
 /// returning `Ok(())` in that case, or, if it is invalid, `Err(err)`.
 #[inline(always)]
 #[rustc_const_unstable(feature = "str_internals", issue = "none")]
+#[rustc_allow_const_fn_unstable(const_eval_select)]
 pub(super) const fn run_utf8_validation(v: &[u8]) -> Result<(), Utf8Error> {
+    // SAFETY: both functions validate `v` alike, and report the same error.
+    unsafe {
+        crate::intrinsics::const_eval_select(
+            (v,),
+            run_utf8_validation_scalar,
+            run_utf8_validation_rt,
+        )
+    }
+}
+
+cfg_if! {
+    // Postgres hands over text in bulk, which is worth validating with SIMD.
+    if #[cfg(all(
+        target_family = "postgres",
+        any(
+            target_arch = "x86",
+            target_arch = "x86_64",
+            all(target_arch = "aarch64", target_feature = "neon")
+        )
+    ))] {
+        mod simd;
+        use simd::run_utf8_validation as run_utf8_validation_rt;
+    } else {
+        #[inline(always)]
+        fn run_utf8_validation_rt(v: &[u8]) -> Result<(), Utf8Error> {
+            run_utf8_validation_scalar(v)
+        }
+    }
+}
+
+/// [`run_utf8_validation`] a byte, or a few words of ASCII, at a time.
+#[inline(always)]
+const fn run_utf8_validation_scalar(v: &[u8]) -> Result<(), Utf8Error> {
     let mut index = 0;
     let len = v.len();
 
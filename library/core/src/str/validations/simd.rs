@@ -0,0 +1,489 @@
+//! UTF-8 validation sixteen bytes at a time, with the lookup algorithm of
+//! "Validating UTF-8 In Less Than One Instruction Per Byte", by John Keiser
+//! and Daniel Lemire (<https://arxiv.org/abs/2010.03090>).
+//!
+//! The high and low nibbles of each byte, and the high nibble of the byte
+//! after it, each look up which errors that pair of bytes could be part of,
+//! and only an error all three agree on is one. The bytes two and three
+//! before each byte then tell whether it has to be a continuation byte,
+//! which is the only case the lookups leave out.
+//!
+//! This only tells whether there is an error, not where: once a block has
+//! one, [`run_utf8_validation_scalar`] goes over the input again, from the
+//! character before the block, to find it.
+//!
+//! It needs a byte shuffle: SSSE3 on x86, where the targets do not assume
+//! it, so it is checked for at run time, and NEON on AArch64.
+
+use super::{run_utf8_validation_scalar, utf8_is_cont_byte};
+use crate::str::Utf8Error;
+
+#[cfg(target_arch = "aarch64")]
+use crate::arch::aarch64 as arch;
+#[cfg(target_arch = "x86")]
+use crate::arch::x86 as arch;
+#[cfg(target_arch = "x86_64")]
+use crate::arch::x86_64 as arch;
+
+/// The bytes checked at once, in four vectors.
+const BLOCK: usize = 64;
+
+/// Walks through `v` like [`super::run_utf8_validation`], a block of
+/// [`BLOCK`] bytes at a time, when it is long enough for that to pay off,
+/// and the CPU can.
+#[inline]
+pub(super) fn run_utf8_validation(v: &[u8]) -> Result<(), Utf8Error> {
+    if v.len() >= BLOCK && has_shuffle() {
+        // SAFETY: the CPU has the instructions.
+        unsafe { validate(v) }
+    } else {
+        run_utf8_validation_scalar(v)
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn has_shuffle() -> bool {
+    use crate::sync::atomic::{AtomicU8, Ordering};
+
+    if cfg!(target_feature = "ssse3") {
+        return true;
+    }
+    // 0 until checked, then 1 without SSSE3, and 2 with it.
+    static SSSE3: AtomicU8 = AtomicU8::new(0);
+    match SSSE3.load(Ordering::Relaxed) {
+        0 => {
+            // SAFETY: every CPU these targets run on has `cpuid`, and its
+            // leaf 1, whose bit 9 of `ecx` is SSSE3.
+            let ssse3 = unsafe { arch::__cpuid(1) }.ecx & (1 << 9) != 0;
+            SSSE3.store(1 + ssse3 as u8, Ordering::Relaxed);
+            ssse3
+        }
+        checked => checked == 2,
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn has_shuffle() -> bool {
+    true
+}
+
+/// # Safety
+///
+/// The CPU has the instructions of `vector`.
+#[cfg_attr(any(target_arch = "x86", target_arch = "x86_64"), target_feature(enable = "ssse3"))]
+unsafe fn validate(v: &[u8]) -> Result<(), Utf8Error> {
+    let mut checker = unsafe { Checker::new() };
+    let mut index = 0;
+    while index + BLOCK <= v.len() {
+        // SAFETY: there are `BLOCK` bytes from `index` on.
+        unsafe { checker.check_block(v.as_ptr().add(index)) };
+        if unsafe { checker.has_error() } {
+            return resume(v, index);
+        }
+        index += BLOCK;
+    }
+    if index < v.len() {
+        // Padding with ASCII keeps a sequence left incomplete an error.
+        let mut tail = [0; BLOCK];
+        tail[..v.len() - index].copy_from_slice(&v[index..]);
+        unsafe { checker.check_block(tail.as_ptr()) };
+    }
+    unsafe { checker.check_eof() };
+    if unsafe { checker.has_error() } { resume(v, index) } else { Ok(()) }
+}
+
+/// Finds the error that the block at `index` has, or that a sequence left
+/// incomplete by the block before it has.
+#[cold]
+fn resume(v: &[u8], index: usize) -> Result<(), Utf8Error> {
+    // Everything before `index` is valid, but for the last character, which
+    // starts at most 4 bytes back, and might need bytes from the block.
+    let mut start = index.saturating_sub(1);
+    while start > index.saturating_sub(4) && utf8_is_cont_byte(v[start]) {
+        start -= 1;
+    }
+    match run_utf8_validation_scalar(&v[start..]) {
+        Ok(()) => Ok(()),
+        Err(Utf8Error { valid_up_to, error_len }) => {
+            Err(Utf8Error { valid_up_to: start + valid_up_to, error_len })
+        }
+    }
+}
+
+// Which errors a pair of bytes can be, by the high or low nibble of the
+// first byte, or the high nibble of the second. A continuation byte is
+// `10______`.
+/// `11______ 0_______` or `11______ 11______`
+const TOO_SHORT: u8 = 1 << 0;
+/// `0_______ 10______`
+const TOO_LONG: u8 = 1 << 1;
+/// `11100000 100_____`
+const OVERLONG_3: u8 = 1 << 2;
+/// `11110100 1001____`, `11110100 101_____`, or `111101__ 10______` and
+/// `11111___ 10______` past that
+const TOO_LARGE: u8 = 1 << 3;
+/// `11101101 101_____`
+const SURROGATE: u8 = 1 << 4;
+/// `1100000_ 10______`
+const OVERLONG_2: u8 = 1 << 5;
+/// `11110101 1000____` and past that, with [`TOO_LARGE`]
+const TOO_LARGE_1000: u8 = 1 << 6;
+/// `11110000 1000____`
+const OVERLONG_4: u8 = 1 << 6;
+/// `10______ 10______`, which is only an error without a lead byte two or
+/// three bytes before.
+const TWO_CONTS: u8 = 1 << 7;
+/// The errors the high nibble of the first byte decides on its own.
+const CARRY: u8 = TOO_SHORT | TOO_LONG | TWO_CONTS;
+
+const BYTE_1_HIGH: [u8; 16] = [
+    // 0_______ ________: ASCII
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    TOO_LONG,
+    // 10______ ________: continuation
+    TWO_CONTS,
+    TWO_CONTS,
+    TWO_CONTS,
+    TWO_CONTS,
+    // 1100____ ________: two-byte lead
+    TOO_SHORT | OVERLONG_2,
+    // 1101____ ________: two-byte lead
+    TOO_SHORT,
+    // 1110____ ________: three-byte lead
+    TOO_SHORT | OVERLONG_3 | SURROGATE,
+    // 1111____ ________: four-byte lead, or worse
+    TOO_SHORT | TOO_LARGE | TOO_LARGE_1000 | OVERLONG_4,
+];
+
+const BYTE_1_LOW: [u8; 16] = [
+    // ____0000 ________
+    CARRY | OVERLONG_2 | OVERLONG_3 | OVERLONG_4,
+    // ____0001 ________
+    CARRY | OVERLONG_2,
+    // ____001_ ________
+    CARRY,
+    CARRY,
+    // ____0100 ________
+    CARRY | TOO_LARGE,
+    // ____0101 ________ and up
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    // ____1101 ________
+    CARRY | TOO_LARGE | TOO_LARGE_1000 | SURROGATE,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+    CARRY | TOO_LARGE | TOO_LARGE_1000,
+];
+
+const BYTE_2_HIGH: [u8; 16] = [
+    // ________ 0_______: ASCII
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    // ________ 1000____
+    TOO_LONG | OVERLONG_2 | TWO_CONTS | OVERLONG_3 | TOO_LARGE_1000 | OVERLONG_4,
+    // ________ 1001____
+    TOO_LONG | OVERLONG_2 | TWO_CONTS | OVERLONG_3 | TOO_LARGE,
+    // ________ 101_____
+    TOO_LONG | OVERLONG_2 | TWO_CONTS | SURROGATE | TOO_LARGE,
+    TOO_LONG | OVERLONG_2 | TWO_CONTS | SURROGATE | TOO_LARGE,
+    // ________ 11______: lead
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+    TOO_SHORT,
+];
+
+/// Above which the last three bytes of a vector start a sequence that needs
+/// more bytes than there are left in it.
+const INCOMPLETE_MAX: [u8; 16] = [
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0b1111_0000 - 1,
+    0b1110_0000 - 1,
+    0b1100_0000 - 1,
+];
+
+use vector::Vector;
+
+/// The errors found so far, and what the next block needs of the last one.
+struct Checker {
+    error: Vector,
+    prev: Vector,
+    prev_incomplete: Vector,
+}
+
+// SAFETY, of everything below: the CPU has the instructions of `vector`, and
+// the pointers are valid for `BLOCK` bytes.
+impl Checker {
+    #[inline]
+    #[cfg_attr(any(target_arch = "x86", target_arch = "x86_64"), target_feature(enable = "ssse3"))]
+    unsafe fn new() -> Checker {
+        let zero = unsafe { vector::splat(0) };
+        Checker { error: zero, prev: zero, prev_incomplete: zero }
+    }
+
+    #[inline]
+    #[cfg_attr(any(target_arch = "x86", target_arch = "x86_64"), target_feature(enable = "ssse3"))]
+    unsafe fn check_block(&mut self, ptr: *const u8) {
+        use vector::*;
+
+        unsafe {
+            let input = [load(ptr), load(ptr.add(16)), load(ptr.add(32)), load(ptr.add(48))];
+            if is_ascii(or(or(input[0], input[1]), or(input[2], input[3]))) {
+                // Nothing in the block continues the last one, and nothing
+                // in it needs the next one.
+                self.error = or(self.error, self.prev_incomplete);
+                self.prev = splat(0);
+                self.prev_incomplete = splat(0);
+            } else {
+                for input in input {
+                    self.check_bytes(input);
+                    self.prev = input;
+                }
+                self.prev_incomplete = subs(self.prev, load(INCOMPLETE_MAX.as_ptr()));
+            }
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(any(target_arch = "x86", target_arch = "x86_64"), target_feature(enable = "ssse3"))]
+    unsafe fn check_bytes(&mut self, input: Vector) {
+        use vector::*;
+
+        unsafe {
+            let prev1 = prev1(input, self.prev);
+            let special_cases = and(
+                and(
+                    lookup(load(BYTE_1_HIGH.as_ptr()), high_nibbles(prev1)),
+                    lookup(load(BYTE_1_LOW.as_ptr()), low_nibbles(prev1)),
+                ),
+                lookup(load(BYTE_2_HIGH.as_ptr()), high_nibbles(input)),
+            );
+            // Only `111_____` two bytes before, or `1111____` three bytes
+            // before, end up at 0x80 or over.
+            let third = subs(prev2(input, self.prev), splat(0b1110_0000 - 0x80));
+            let fourth = subs(prev3(input, self.prev), splat(0b1111_0000 - 0x80));
+            let must_be_cont = and(or(third, fourth), splat(0x80));
+            self.error = or(self.error, xor(must_be_cont, special_cases));
+        }
+    }
+
+    /// Checks that the input does not end in the middle of a sequence.
+    #[inline]
+    #[cfg_attr(any(target_arch = "x86", target_arch = "x86_64"), target_feature(enable = "ssse3"))]
+    unsafe fn check_eof(&mut self) {
+        self.error = unsafe { vector::or(self.error, self.prev_incomplete) };
+    }
+
+    #[inline]
+    #[cfg_attr(any(target_arch = "x86", target_arch = "x86_64"), target_feature(enable = "ssse3"))]
+    unsafe fn has_error(&self) -> bool {
+        unsafe { !vector::is_zero(self.error) }
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod vector {
+    use super::arch::*;
+
+    pub(super) type Vector = __m128i;
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn load(ptr: *const u8) -> Vector {
+        unsafe { _mm_loadu_si128(ptr.cast()) }
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn splat(byte: u8) -> Vector {
+        unsafe { _mm_set1_epi8(byte as i8) }
+    }
+
+    /// The bytes of `table` at the indices in `indices`, which are all
+    /// under 16.
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn lookup(table: Vector, indices: Vector) -> Vector {
+        unsafe { _mm_shuffle_epi8(table, indices) }
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn high_nibbles(v: Vector) -> Vector {
+        // There are no 8-bit shifts, but the bits shifted in from the next
+        // byte are masked off anyway.
+        unsafe { _mm_and_si128(_mm_srli_epi16::<4>(v), splat(0x0F)) }
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn low_nibbles(v: Vector) -> Vector {
+        unsafe { _mm_and_si128(v, splat(0x0F)) }
+    }
+
+    /// The byte before each of `v`, the first one from the end of `prev`.
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn prev1(v: Vector, prev: Vector) -> Vector {
+        unsafe { _mm_alignr_epi8::<15>(v, prev) }
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn prev2(v: Vector, prev: Vector) -> Vector {
+        unsafe { _mm_alignr_epi8::<14>(v, prev) }
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn prev3(v: Vector, prev: Vector) -> Vector {
+        unsafe { _mm_alignr_epi8::<13>(v, prev) }
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn and(a: Vector, b: Vector) -> Vector {
+        unsafe { _mm_and_si128(a, b) }
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn or(a: Vector, b: Vector) -> Vector {
+        unsafe { _mm_or_si128(a, b) }
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn xor(a: Vector, b: Vector) -> Vector {
+        unsafe { _mm_xor_si128(a, b) }
+    }
+
+    /// `a - b` bytewise, saturating at 0.
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn subs(a: Vector, b: Vector) -> Vector {
+        unsafe { _mm_subs_epu8(a, b) }
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn is_ascii(v: Vector) -> bool {
+        unsafe { _mm_movemask_epi8(v) == 0 }
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn is_zero(v: Vector) -> bool {
+        unsafe { _mm_movemask_epi8(_mm_cmpeq_epi8(v, _mm_setzero_si128())) == 0xFFFF }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod vector {
+    use super::arch::*;
+
+    pub(super) type Vector = uint8x16_t;
+
+    #[inline]
+    pub(super) unsafe fn load(ptr: *const u8) -> Vector {
+        unsafe { vld1q_u8(ptr) }
+    }
+
+    #[inline]
+    pub(super) unsafe fn splat(byte: u8) -> Vector {
+        unsafe { vdupq_n_u8(byte) }
+    }
+
+    /// The bytes of `table` at the indices in `indices`, which are all
+    /// under 16.
+    #[inline]
+    pub(super) unsafe fn lookup(table: Vector, indices: Vector) -> Vector {
+        unsafe { vqtbl1q_u8(table, indices) }
+    }
+
+    #[inline]
+    pub(super) unsafe fn high_nibbles(v: Vector) -> Vector {
+        unsafe { vshrq_n_u8::<4>(v) }
+    }
+
+    #[inline]
+    pub(super) unsafe fn low_nibbles(v: Vector) -> Vector {
+        unsafe { vandq_u8(v, vdupq_n_u8(0x0F)) }
+    }
+
+    /// The byte before each of `v`, the first one from the end of `prev`.
+    #[inline]
+    pub(super) unsafe fn prev1(v: Vector, prev: Vector) -> Vector {
+        unsafe { vextq_u8::<15>(prev, v) }
+    }
+
+    #[inline]
+    pub(super) unsafe fn prev2(v: Vector, prev: Vector) -> Vector {
+        unsafe { vextq_u8::<14>(prev, v) }
+    }
+
+    #[inline]
+    pub(super) unsafe fn prev3(v: Vector, prev: Vector) -> Vector {
+        unsafe { vextq_u8::<13>(prev, v) }
+    }
+
+    #[inline]
+    pub(super) unsafe fn and(a: Vector, b: Vector) -> Vector {
+        unsafe { vandq_u8(a, b) }
+    }
+
+    #[inline]
+    pub(super) unsafe fn or(a: Vector, b: Vector) -> Vector {
+        unsafe { vorrq_u8(a, b) }
+    }
+
+    #[inline]
+    pub(super) unsafe fn xor(a: Vector, b: Vector) -> Vector {
+        unsafe { veorq_u8(a, b) }
+    }
+
+    /// `a - b` bytewise, saturating at 0.
+    #[inline]
+    pub(super) unsafe fn subs(a: Vector, b: Vector) -> Vector {
+        unsafe { vqsubq_u8(a, b) }
+    }
+
+    #[inline]
+    pub(super) unsafe fn is_ascii(v: Vector) -> bool {
+        unsafe { vmaxvq_u8(v) < 0x80 }
+    }
+
+    #[inline]
+    pub(super) unsafe fn is_zero(v: Vector) -> bool {
+        unsafe { vmaxvq_u8(v) == 0 }
+    }
+}
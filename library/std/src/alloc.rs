@@ -342,6 +342,10 @@ fn default_alloc_error_hook(layout: Layout) {
     if unsafe { __rust_alloc_error_handler_should_panic != 0 } {
         panic!("memory allocation of {} bytes failed\n", layout.size());
     } else {
+        // A backend runs out of memory like postgres itself does, with an
+        // `ERROR` rather than taking the whole server down.
+        #[cfg(target_family = "postgres")]
+        crate::sys::elog::out_of_memory(layout.size());
         rtprintpanic!("memory allocation of {} bytes failed\n", layout.size());
     }
 }
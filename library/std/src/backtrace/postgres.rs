@@ -21,6 +21,13 @@
 //! programs be compiled with debug information. Without debug information
 //! filenames/line numbers will not be reported.
 //!
+//! On postgres, backtraces never have filenames or line numbers: the stack is
+//! walked by following frame pointers, and frames are named after the
+//! nearest symbol the dynamic linker knows, or otherwise as an offset into
+//! their shared object, for `addr2line` to resolve against the debug info
+//! later. The walk stops at the first frame without a frame pointer, which
+//! is usually where postgres called into the extension.
+//!
 //! ## Platform support
 //!
 //! Not all platforms that libstd compiles for support capturing backtraces.
@@ -59,12 +66,17 @@
 //! how backtraces are captured.
 
 #![stable(feature = "backtrace", since = "1.65.0")]
-// Fully no-disabled version of `std::backtrace`, to avoid the various downsides
-// inherent in producing backtraces.
-//
-// FIXME(thom): this is a terrible way to stub this out, and risks accidentally
-// drifting from the public backtrace API :(
+
+// Like `supported.rs`, a capture only records return addresses, and they are
+// looked up with `dladdr` the first time the backtrace is printed.
+
+use crate::cell::UnsafeCell;
+use crate::env;
 use crate::fmt;
+use crate::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use crate::sync::Once;
+use crate::sys_common::backtrace::{self as sys, Symbol};
+use crate::vec::Vec;
 
 /// A captured OS thread stack backtrace.
 ///
@@ -101,32 +113,80 @@ pub enum BacktraceStatus {
 enum Inner {
     Unsupported,
     Disabled,
+    Captured(LazilyResolvedCapture),
+}
+
+struct Capture {
+    actual_start: usize,
+    resolved: bool,
+    frames: Vec<BacktraceFrame>,
+}
+
+fn _assert_send_sync() {
+    fn _assert<T: Send + Sync>() {}
+    _assert::<Backtrace>();
 }
 
 /// A single frame of a backtrace.
 #[unstable(feature = "backtrace_frames", issue = "79676")]
 pub struct BacktraceFrame {
-    _private: (),
+    ip: usize,
+    symbol: Option<Symbol>,
 }
 
 #[stable(feature = "backtrace", since = "1.65.0")]
 impl fmt::Debug for Backtrace {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match &self.inner {
-            Inner::Unsupported => fmt.write_str("<unsupported>"),
-            Inner::Disabled => fmt.write_str("<disabled>"),
-        }
+        let capture = match &self.inner {
+            Inner::Unsupported => return fmt.write_str("<unsupported>"),
+            Inner::Disabled => return fmt.write_str("<disabled>"),
+            Inner::Captured(c) => c.force(),
+        };
+
+        write!(fmt, "Backtrace ")?;
+        fmt.debug_list().entries(&capture.frames[capture.actual_start..]).finish()
     }
 }
 
 #[unstable(feature = "backtrace_frames", issue = "79676")]
 impl fmt::Debug for BacktraceFrame {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt.debug_list().finish()
+        write!(fmt, "{{ ")?;
+        match &self.symbol {
+            Some(Symbol { name: Some((name, _)), .. }) => write!(fmt, "fn: \"{name}\"")?,
+            _ => write!(fmt, "fn: <unknown>")?,
+        }
+        if let Some(Symbol { object: (object, offset), .. }) = &self.symbol {
+            write!(fmt, ", object: \"{object}\", offset: {offset:#x}")?;
+        }
+        write!(fmt, " }}")
     }
 }
 
 impl Backtrace {
+    /// Returns whether backtrace captures are enabled through environment
+    /// variables.
+    fn enabled() -> bool {
+        // Cache the result of reading the environment variables to make
+        // backtrace captures speedy, because otherwise reading environment
+        // variables every time can be somewhat slow.
+        static ENABLED: AtomicUsize = AtomicUsize::new(0);
+        match ENABLED.load(Relaxed) {
+            0 => {}
+            1 => return false,
+            _ => return true,
+        }
+        let enabled = match env::var("RUST_LIB_BACKTRACE") {
+            Ok(s) => s != "0",
+            Err(_) => match env::var("RUST_BACKTRACE") {
+                Ok(s) => s != "0",
+                Err(_) => false,
+            },
+        };
+        ENABLED.store(enabled as usize + 1, Relaxed);
+        enabled
+    }
+
     /// Capture a stack backtrace of the current thread.
     ///
     /// This function will capture a stack backtrace of the current OS thread of
@@ -144,8 +204,12 @@ impl Backtrace {
     /// To forcibly capture a backtrace regardless of environment variables, use
     /// the `Backtrace::force_capture` function.
     #[stable(feature = "backtrace", since = "1.65.0")]
+    #[inline(never)] // want to make sure there's a frame here to remove
     pub fn capture() -> Backtrace {
-        Backtrace { inner: Inner::Unsupported }
+        if !Backtrace::enabled() {
+            return Backtrace { inner: Inner::Disabled };
+        }
+        Backtrace::create()
     }
 
     /// Forcibly captures a full backtrace, regardless of environment variable
@@ -161,7 +225,7 @@ impl Backtrace {
     #[stable(feature = "backtrace", since = "1.65.0")]
     #[inline(never)] // want to make sure there's a frame here to remove
     pub fn force_capture() -> Backtrace {
-        Backtrace { inner: Inner::Unsupported }
+        Backtrace::create()
     }
 
     /// Forcibly captures a disabled backtrace, regardless of environment
@@ -172,6 +236,28 @@ impl Backtrace {
         Backtrace { inner: Inner::Disabled }
     }
 
+    // Capture a backtrace which starts at the caller of `capture` or
+    // `force_capture`.
+    #[inline(never)]
+    fn create() -> Backtrace {
+        let frames: Vec<_> =
+            sys::trace().into_iter().map(|ip| BacktraceFrame { ip, symbol: None }).collect();
+
+        // The first two frames are this function and the one calling it. If
+        // there are no frames past those, the walk must have failed.
+        let inner = if frames.len() <= 2 {
+            Inner::Unsupported
+        } else {
+            Inner::Captured(LazilyResolvedCapture::new(Capture {
+                actual_start: 2,
+                frames,
+                resolved: false,
+            }))
+        };
+
+        Backtrace { inner }
+    }
+
     /// Returns the status of this backtrace, indicating whether this backtrace
     /// request was unsupported, disabled, or a stack trace was actually
     /// captured.
@@ -181,6 +267,7 @@ impl Backtrace {
         match self.inner {
             Inner::Unsupported => BacktraceStatus::Unsupported,
             Inner::Disabled => BacktraceStatus::Disabled,
+            Inner::Captured(_) => BacktraceStatus::Captured,
         }
     }
 }
@@ -190,16 +277,73 @@ impl<'a> Backtrace {
     #[must_use]
     #[unstable(feature = "backtrace_frames", issue = "79676")]
     pub fn frames(&'a self) -> &'a [BacktraceFrame] {
-        &[]
+        if let Inner::Captured(c) = &self.inner { &c.force().frames } else { &[] }
     }
 }
 
 #[stable(feature = "backtrace", since = "1.65.0")]
 impl fmt::Display for Backtrace {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match &self.inner {
-            Inner::Unsupported => fmt.write_str("unsupported backtrace"),
-            Inner::Disabled => fmt.write_str("disabled backtrace"),
+        let capture = match &self.inner {
+            Inner::Unsupported => return fmt.write_str("unsupported backtrace"),
+            Inner::Disabled => return fmt.write_str("disabled backtrace"),
+            Inner::Captured(c) => c.force(),
+        };
+
+        let frames = if fmt.alternate() {
+            &capture.frames[..]
+        } else {
+            &capture.frames[capture.actual_start..]
+        };
+
+        writeln!(fmt, "stack backtrace:")?;
+        for (i, frame) in frames.iter().enumerate() {
+            match &frame.symbol {
+                Some(symbol) => writeln!(fmt, "{i:4}: {symbol}")?,
+                None => writeln!(fmt, "{i:4}: {:#x}", frame.ip)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct LazilyResolvedCapture {
+    sync: Once,
+    capture: UnsafeCell<Capture>,
+}
+
+impl LazilyResolvedCapture {
+    fn new(capture: Capture) -> Self {
+        LazilyResolvedCapture { sync: Once::new(), capture: UnsafeCell::new(capture) }
+    }
+
+    fn force(&self) -> &Capture {
+        self.sync.call_once(|| {
+            // SAFETY: This exclusive reference can't overlap with any others
+            // `Once` guarantees callers will block until this closure returns
+            // `Once` also guarantees only a single caller will enter this closure
+            unsafe { &mut *self.capture.get() }.resolve();
+        });
+
+        // SAFETY: This shared reference can't overlap with the exclusive reference above
+        unsafe { &*self.capture.get() }
+    }
+}
+
+// SAFETY: Access to the inner value is synchronized using a thread-safe `Once`
+// So long as `Capture` is `Sync`, `LazilyResolvedCapture` is too
+unsafe impl Sync for LazilyResolvedCapture where Capture: Sync {}
+
+impl Capture {
+    fn resolve(&mut self) {
+        // If we're already resolved, nothing to do!
+        if self.resolved {
+            return;
+        }
+        self.resolved = true;
+
+        for frame in self.frames.iter_mut() {
+            frame.symbol = sys::resolve(frame.ip);
         }
     }
 }
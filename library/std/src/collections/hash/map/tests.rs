@@ -1,4 +1,5 @@
 use super::Entry::{Occupied, Vacant};
+use super::EntryRef;
 use super::HashMap;
 use super::RandomState;
 use crate::assert_matches::assert_matches;
@@ -649,6 +650,42 @@ fn test_index_nonexistent() {
     map[&4];
 }
 
+#[test]
+fn test_entry_ref() {
+    let mut map: HashMap<String, i32> = HashMap::new();
+    map.insert("one".to_string(), 1);
+
+    match map.entry_ref("one") {
+        EntryRef::Vacant(_) => unreachable!(),
+        EntryRef::Occupied(mut view) => {
+            assert_eq!(view.key(), "one");
+            assert_eq!(view.get(), &1);
+            assert_eq!(view.insert(10), 1);
+        }
+    }
+    assert_eq!(map["one"], 10);
+
+    match map.entry_ref("two") {
+        EntryRef::Occupied(_) => unreachable!(),
+        EntryRef::Vacant(view) => {
+            assert_eq!(view.key(), "two");
+            assert_eq!(*view.insert(2), 2);
+        }
+    }
+    assert_eq!(map["two"], 2);
+
+    *map.entry_ref("two").and_modify(|v| *v += 1).or_insert(0) += 1;
+    assert_eq!(map["two"], 4);
+    assert_eq!(map.entry_ref("three").or_insert_with_key(|k| k.len() as i32), &mut 5);
+
+    match map.entry_ref("one") {
+        EntryRef::Vacant(_) => unreachable!(),
+        EntryRef::Occupied(view) => assert_eq!(view.remove_entry(), ("one".to_string(), 10)),
+    }
+    assert_eq!(map.get("one"), None);
+    assert_eq!(map.len(), 2);
+}
+
 #[test]
 fn test_entry() {
     let xs = [(1, 10), (2, 20), (3, 30), (4, 40), (5, 50), (6, 60)];
@@ -819,6 +856,19 @@ fn test_retain() {
     assert_eq!(map[&6], 60);
 }
 
+#[test]
+fn test_try_with_capacity() {
+    let mut map: HashMap<u8, u8> = HashMap::try_with_capacity(10).unwrap();
+    assert!(map.capacity() >= 10);
+    map.insert(1, 2);
+    assert_eq!(map[&1], 2);
+
+    assert_matches!(
+        HashMap::<u8, u8>::try_with_capacity(usize::MAX).map_err(|e| e.kind()),
+        Err(CapacityOverflow)
+    );
+}
+
 #[test]
 #[cfg_attr(miri, ignore)] // Miri does not support signalling OOM
 #[cfg_attr(target_os = "android", ignore)] // Android used in CI has a broken dlmalloc
@@ -1121,3 +1171,20 @@ fn const_with_hasher() {
     const X: HashMap<(), (), ()> = HashMap::with_hasher(());
     assert_eq!(X.len(), 0);
 }
+
+#[test]
+fn new_in() {
+    use crate::alloc::Global;
+    use crate::sys_common::io::test::CountingAlloc;
+
+    let alloc = CountingAlloc::new(Global);
+    let mut map = HashMap::new_in(&alloc);
+    assert_eq!(alloc.live(), 0);
+    map.insert(1, 2);
+    assert_eq!(alloc.live(), 1);
+    assert_eq!(map[&1], 2);
+    *map.entry(3).or_default() += 4;
+    assert_eq!(map.clone().into_iter().collect::<Vec<_>>().len(), 2);
+    drop(map);
+    assert_eq!(alloc.live(), 0);
+}
@@ -502,3 +502,17 @@ fn const_with_hasher() {
     const X: HashSet<(), ()> = HashSet::with_hasher(());
     assert_eq!(X.len(), 0);
 }
+
+#[test]
+fn with_capacity_in() {
+    use crate::alloc::Global;
+
+    let mut a = HashSet::with_capacity_in(4, Global);
+    assert!(a.capacity() >= 4);
+    a.extend([1, 2, 3]);
+    let mut b = HashSet::new_in(Global);
+    b.extend([2, 3, 4]);
+    assert_eq!(a.intersection(&b).count(), 2);
+    assert_eq!(a.difference(&b).collect::<Vec<_>>(), [&1]);
+    assert!(!a.is_subset(&b));
+}
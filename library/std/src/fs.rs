@@ -327,6 +327,97 @@ pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> io::Result
     inner(path.as_ref(), contents.as_ref())
 }
 
+/// Durably replace the entire contents of a file with a slice.
+///
+/// Unlike [`write()`], readers of `path` never observe a partially written
+/// file, and once this function returns `Ok` the new contents survive a crash.
+/// This follows the same steps as Postgres's `durable_rename`:
+///
+/// 1. The contents are written to a new sibling file named
+///    `<file name>.<random suffix>.tmp`, so that concurrent writers, and
+///    whatever else is next to `path`, are left alone.
+/// 2. The temporary file is flushed to disk with [`File::sync_all`].
+/// 3. The temporary file is [`rename`]d over `path`.
+/// 4. On Unix, the parent directory is flushed as well, so that the rename
+///    itself is durable.
+///
+/// If any step up to and including the rename fails, the temporary file is
+/// removed (on a best-effort basis) and `path` is left untouched. If only
+/// flushing the parent directory fails, `path` already has the new contents,
+/// but they may not survive a crash.
+///
+/// # Errors
+///
+/// This function will return an error if `path` has no file name, or if any
+/// of the underlying operations fail.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_write_atomic)]
+/// use std::fs;
+///
+/// fn main() -> std::io::Result<()> {
+///     fs::write_atomic("state.json", b"{\"version\": 2}")?;
+///     Ok(())
+/// }
+/// ```
+#[unstable(feature = "fs_write_atomic", issue = "none")]
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> io::Result<()> {
+    fn inner(path: &Path, contents: &[u8]) -> io::Result<()> {
+        let Some(file_name) = path.file_name() else {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "path for an atomic write must name a file",
+            ));
+        };
+        let (mut tmp, tmp_path) = create_sibling_tmp(path, file_name)?;
+
+        let result = (|| {
+            tmp.write_all(contents)?;
+            tmp.sync_all()?;
+            drop(tmp);
+            rename(&tmp_path, path)
+        })();
+        if let Err(e) = result {
+            let _ = remove_file(&tmp_path);
+            return Err(e);
+        }
+
+        // Make the rename itself durable. Directories cannot be opened (or
+        // flushed) through `File` on all platforms, so this is Unix-only.
+        #[cfg(unix)]
+        {
+            let parent = match path.parent() {
+                Some(p) if !p.as_os_str().is_empty() => p,
+                _ => Path::new("."),
+            };
+            File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    }
+    inner(path.as_ref(), contents.as_ref())
+}
+
+/// Creates a file next to `path`, named after `file_name` with a suffix no
+/// other file there has.
+fn create_sibling_tmp(path: &Path, file_name: &crate::ffi::OsStr) -> io::Result<(File, PathBuf)> {
+    use crate::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let (random, _) = crate::sys::hashmap_random_keys();
+    loop {
+        let mut tmp_name = file_name.to_os_string();
+        tmp_name.push(format!(".{random:016x}-{:x}.tmp", COUNTER.fetch_add(1, Relaxed)));
+        let tmp_path = path.with_file_name(tmp_name);
+        match OpenOptions::new().write(true).create_new(true).open(&tmp_path) {
+            Ok(file) => return Ok((file, tmp_path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 impl File {
     /// Attempts to open a file in read-only mode.
     ///
@@ -1214,6 +1214,25 @@ fn write_then_read() {
     assert_eq!(string, s);
 }
 
+#[test]
+fn write_atomic_replaces_contents() {
+    let tmpdir = tmpdir();
+    let path = tmpdir.join("state");
+
+    check!(fs::write_atomic(&path, b"first"));
+    assert_eq!(check!(fs::read(&path)), b"first");
+
+    // An unrelated file named like the old temporary one is left alone.
+    check!(fs::write(tmpdir.join("state.tmp"), b"unrelated"));
+    check!(fs::write_atomic(&path, b"second"));
+    assert_eq!(check!(fs::read(&path)), b"second");
+    assert_eq!(check!(fs::read(tmpdir.join("state.tmp"))), b"unrelated");
+    // And no temporary file is left behind.
+    assert_eq!(check!(fs::read_dir(tmpdir.path())).count(), 2);
+
+    error_contains!(fs::write_atomic(tmpdir.join(".."), b"nope"), "must name a file");
+}
+
 #[test]
 fn file_try_clone() {
     let tmpdir = tmpdir();
@@ -90,6 +90,9 @@ impl<I: Write> BufferedCopySpec for BufWriter<I> {
         let mut init = 0;
 
         loop {
+            #[cfg(target_family = "postgres")]
+            crate::sys::pg::check_interrupts()?;
+
             let buf = writer.buffer_mut();
             let mut read_buf: BorrowedBuf<'_> = buf.spare_capacity_mut().into();
 
@@ -138,6 +141,9 @@ fn stack_buffer_copy<R: Read + ?Sized, W: Write + ?Sized>(
     let mut len = 0;
 
     loop {
+        #[cfg(target_family = "postgres")]
+        crate::sys::pg::check_interrupts()?;
+
         match reader.read_buf(buf.unfilled()) {
             Ok(()) => {}
             Err(e) if e.kind() == ErrorKind::Interrupted => continue,
@@ -269,7 +269,7 @@ pub use self::stdio::set_output_capture;
 #[unstable(feature = "is_terminal", issue = "98070")]
 pub use self::stdio::IsTerminal;
 #[unstable(feature = "print_internals", issue = "none")]
-pub use self::stdio::{_eprint, _print};
+pub use self::stdio::{_dbg, _eprint, _print};
 #[stable(feature = "rust1", since = "1.0.0")]
 pub use self::{
     buffered::{BufReader, BufWriter, IntoInnerError, LineWriter},
@@ -1024,6 +1024,33 @@ fn print_to_buffer_if_capture_used(args: fmt::Arguments<'_>) -> bool {
         }) == Ok(Some(()))
 }
 
+#[unstable(
+    feature = "print_internals",
+    reason = "implementation detail which may disappear or be replaced at any time",
+    issue = "none"
+)]
+#[doc(hidden)]
+pub fn _dbg(file: &'static str, line: u32, args: Option<fmt::Arguments<'_>>) {
+    match args {
+        Some(args) => dbg_to(file, line, format_args!("[{file}:{line}] {args}")),
+        None => dbg_to(file, line, format_args!("[{file}:{line}]")),
+    }
+}
+
+fn dbg_to(file: &'static str, line: u32, message: fmt::Arguments<'_>) {
+    // A backend has no stderr, but a log that `log_min_messages` and
+    // `client_min_messages` filter.
+    #[cfg(target_family = "postgres")]
+    if !print_to_buffer_if_capture_used(format_args!("{message}\n")) {
+        stdio::debug(file, line, message);
+    }
+    #[cfg(not(target_family = "postgres"))]
+    {
+        let _ = (file, line);
+        _eprint(format_args!("{message}\n"));
+    }
+}
+
 /// Used by impl Termination for Result to print error after `main` or a test
 /// has returned. Should avoid panicking, although we can't help it if one of
 /// the Display impls inside args decides to.
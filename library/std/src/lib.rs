@@ -240,6 +240,7 @@
 #![feature(allow_internal_unstable)]
 #![feature(box_syntax)]
 #![feature(c_unwind)]
+#![feature(cfg_sanitize)]
 #![feature(cfg_target_thread_local)]
 #![feature(concat_idents)]
 #![feature(const_mut_refs)]
@@ -252,6 +253,7 @@
 #![feature(doc_notable_trait)]
 #![feature(dropck_eyepatch)]
 #![feature(exhaustive_patterns)]
+#![feature(ffi_returns_twice)]
 #![feature(if_let_guard)]
 #![feature(intra_doc_pointers)]
 #![feature(is_terminal)]
@@ -516,7 +518,7 @@ pub mod f64;
 #[macro_use]
 pub mod thread;
 pub mod ascii;
-#[cfg_attr(target_family = "postgres", path = "backtrace/unsupported.rs")]
+#[cfg_attr(target_family = "postgres", path = "backtrace/postgres.rs")]
 #[cfg_attr(not(target_family = "postgres"), path = "backtrace/supported.rs")]
 pub mod backtrace;
 pub mod collections;
@@ -242,6 +242,11 @@ macro_rules! eprintln {
 /// Debug output from production code is better done with other facilities
 /// such as the [`debug!`] macro from the [`log`] crate.
 ///
+/// On the postgres targets, which have no stderr, the output is reported
+/// to postgres at `DEBUG1` instead, attributed to the file and line of the
+/// `dbg!`: whether it shows up in the server log or the client is up to
+/// `log_min_messages` and `client_min_messages`.
+///
 /// # Stability
 ///
 /// The exact output printed by this macro should not be relied upon
@@ -249,7 +254,8 @@ macro_rules! eprintln {
 ///
 /// # Panics
 ///
-/// Panics if writing to `io::stderr` fails.
+/// Panics if writing to `io::stderr` fails, which is not written to on the
+/// postgres targets.
 ///
 /// # Further examples
 ///
@@ -336,21 +342,25 @@ macro_rules! eprintln {
 #[macro_export]
 #[cfg_attr(not(test), rustc_diagnostic_item = "dbg_macro")]
 #[stable(feature = "dbg_macro", since = "1.32.0")]
+#[allow_internal_unstable(print_internals)]
 macro_rules! dbg {
     // NOTE: We cannot use `concat!` to make a static string as a format argument
     // of `eprintln!` because `file!` could contain a `{` or
     // `$val` expression could be a block (`{ .. }`), in which case the `eprintln!`
     // will be malformed.
+    //
+    // `_dbg` prints to stderr, or on the postgres targets, reports at `DEBUG1`.
     () => {
-        $crate::eprintln!("[{}:{}]", $crate::file!(), $crate::line!())
+        $crate::io::_dbg($crate::file!(), $crate::line!(), $crate::option::Option::None)
     };
     ($val:expr $(,)?) => {
         // Use of `match` here is intentional because it affects the lifetimes
         // of temporaries - https://stackoverflow.com/a/48732525/1063961
         match $val {
             tmp => {
-                $crate::eprintln!("[{}:{}] {} = {:#?}",
-                    $crate::file!(), $crate::line!(), $crate::stringify!($val), &tmp);
+                $crate::io::_dbg($crate::file!(), $crate::line!(), $crate::option::Option::Some(
+                    $crate::format_args!("{} = {:#?}", $crate::stringify!($val), &tmp)
+                ));
                 tmp
             }
         }
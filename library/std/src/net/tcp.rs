@@ -154,6 +154,11 @@ impl TcpStream {
     /// ```
     #[stable(feature = "rust1", since = "1.0.0")]
     pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+        // Backends try all addresses concurrently, so that a dead path (e.g.
+        // broken IPv6) doesn't hold up the query until the kernel gives up.
+        #[cfg(target_family = "postgres")]
+        return net_imp::connect_any(addr.to_socket_addrs()?, None).map(TcpStream);
+        #[cfg(not(target_family = "postgres"))]
         super::each_addr(addr, net_imp::TcpStream::connect).map(TcpStream)
     }
 
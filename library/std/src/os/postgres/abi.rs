@@ -0,0 +1,121 @@
+//! A stable C ABI over the allocator and the panic bridge.
+//!
+//! The PL/Rust runtime and extension objects compiled on their own have to
+//! hand allocations to each other, and turn panics into `ERROR`s, the same
+//! way across minor upgrades of `std`, without relying on the mangled names
+//! of its Rust functions, which change from one build to the next. The
+//! functions here are exported under fixed, versioned names instead:
+//!
+//! | Symbol                       | Since |
+//! |------------------------------|-------|
+//! | `postgrestd_abi_version`     | 1     |
+//! | `postgrestd_alloc_v1`        | 1     |
+//! | `postgrestd_dealloc_v1`      | 1     |
+//! | `postgrestd_realloc_v1`      | 1     |
+//! | `postgrestd_guard_v1`        | 1     |
+//!
+//! A symbol, once exported, keeps its signature and behaviour for good. A
+//! change to either is a new symbol with the next version suffix, and
+//! [`ABI_VERSION`] goes up with every new symbol, for callers to check
+//! before looking any of them up.
+//!
+//! The allocation functions go through the global allocator of the binary
+//! this `std` is linked into, so memory from one side can be freed or
+//! reallocated on the other.
+
+use crate::alloc::{self, Layout};
+use crate::ffi::c_void;
+use crate::panic::AssertUnwindSafe;
+use crate::ptr;
+
+/// The version of the C ABI, which is the highest version suffix of the
+/// functions exported.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub const ABI_VERSION: u32 = 1;
+
+/// Returns [`ABI_VERSION`].
+#[unstable(feature = "postgres_ext", issue = "none")]
+#[no_mangle]
+pub extern "C" fn postgrestd_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+/// Allocates `size` bytes aligned to `align`, like [`alloc::alloc`].
+///
+/// Returns null if the allocation fails, or if `align` is not a power of
+/// two or `size` rounded up to it overflows an `isize`. For a `size` of
+/// zero, this returns a dangling pointer, which is not to be dereferenced.
+#[unstable(feature = "postgres_ext", issue = "none")]
+#[no_mangle]
+pub extern "C" fn postgrestd_alloc_v1(size: usize, align: usize) -> *mut u8 {
+    match Layout::from_size_align(size, align) {
+        Ok(layout) if layout.size() == 0 => ptr::invalid_mut(align),
+        Ok(layout) => unsafe { alloc::alloc(layout) },
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Frees memory from [`postgrestd_alloc_v1`] or [`postgrestd_realloc_v1`],
+/// like [`alloc::dealloc`].
+///
+/// # Safety
+///
+/// `ptr` was allocated with the same `size` and `align`, or is null, and is
+/// not freed twice.
+#[unstable(feature = "postgres_ext", issue = "none")]
+#[no_mangle]
+pub unsafe extern "C" fn postgrestd_dealloc_v1(ptr: *mut u8, size: usize, align: usize) {
+    if ptr.is_null() || size == 0 {
+        return;
+    }
+    unsafe { alloc::dealloc(ptr, Layout::from_size_align_unchecked(size, align)) }
+}
+
+/// Grows or shrinks memory from [`postgrestd_alloc_v1`] to `new_size`
+/// bytes, like [`alloc::realloc`].
+///
+/// Returns null, leaving the memory as it was, if the allocation fails or
+/// `new_size` rounded up to `align` overflows an `isize`.
+///
+/// # Safety
+///
+/// `ptr` was allocated with `old_size` and `align`.
+#[unstable(feature = "postgres_ext", issue = "none")]
+#[no_mangle]
+pub unsafe extern "C" fn postgrestd_realloc_v1(
+    ptr: *mut u8,
+    old_size: usize,
+    align: usize,
+    new_size: usize,
+) -> *mut u8 {
+    if old_size == 0 {
+        return postgrestd_alloc_v1(new_size, align);
+    }
+    if Layout::from_size_align(new_size, align).is_err() {
+        return ptr::null_mut();
+    }
+    if new_size == 0 {
+        unsafe { postgrestd_dealloc_v1(ptr, old_size, align) };
+        return ptr::invalid_mut(align);
+    }
+    unsafe { alloc::realloc(ptr, Layout::from_size_align_unchecked(old_size, align), new_size) }
+}
+
+/// Calls `f` with `data` under [`guard`](super::guard), turning a panic out
+/// of it into an `ERROR`.
+///
+/// This is the trampoline for functions postgres calls in an object that
+/// does not link this `std` itself: it declares them `extern "C-unwind"`,
+/// and has them called through here.
+///
+/// # Safety
+///
+/// `f` is safe to call with `data`.
+#[unstable(feature = "postgres_ext", issue = "none")]
+#[no_mangle]
+pub unsafe extern "C" fn postgrestd_guard_v1(
+    f: unsafe extern "C-unwind" fn(*mut c_void),
+    data: *mut c_void,
+) {
+    super::guard(AssertUnwindSafe(|| unsafe { f(data) }))
+}
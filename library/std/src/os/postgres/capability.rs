@@ -0,0 +1,87 @@
+//! Runtime capabilities for code running inside a backend.
+//!
+//! `postgrestd` keeps every facility that reaches outside of the backend
+//! switched off until the embedding runtime (normally PL/Rust) explicitly
+//! turns it on. While a capability is disabled the affected `std` APIs fail
+//! with [`ErrorKind::Unsupported`] and a message naming the capability, rather
+//! than quietly touching the operating system.
+//!
+//! Networking also has to be compiled into `std` in the first place, with
+//! the `pg_allow_net` feature; see [`is_built_in`].
+//!
+//! All of this is for the locked-down "trusted" `std`, which user code of a
+//! trusted language runs against. The same tree builds a permissive
+//! "untrusted" `std` too, with the `pg_untrusted` feature, where every
+//! capability is always enabled and the checks cost nothing; see
+//! [`is_untrusted`].
+//!
+//! [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+
+use crate::sys::capability as imp;
+
+/// A facility that is disabled by default inside a backend.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub enum Capability {
+    /// Outbound TCP connections and host name resolution through
+    /// [`std::net`](crate::net).
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    Net,
+    /// Sending datagrams with [`UdpSocket`](crate::net::UdpSocket), e.g. for
+    /// metrics. This is independent of [`Capability::Net`], and destinations
+    /// can be restricted further with
+    /// [`set_udp_policy`](crate::os::postgres::net::set_udp_policy).
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    Udp,
+}
+
+/// Enables `cap` for the rest of the backend's lifetime, or until
+/// [`disable`] is called.
+///
+/// # Safety
+///
+/// This does not affect memory safety, but it widens what *safe* code in the
+/// backend is able to do. Trusted language handlers rely on user code not
+/// being able to call `unsafe` functions, so only the embedding runtime
+/// should ever call this.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub unsafe fn enable(cap: Capability) {
+    imp::set_enabled(cap, true)
+}
+
+/// Disables `cap`. Already-open resources (e.g. connected sockets) are not
+/// affected, only attempts to acquire new ones.
+///
+/// In the untrusted build, this does nothing.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn disable(cap: Capability) {
+    imp::set_enabled(cap, false)
+}
+
+/// Returns whether `cap` was compiled into this `std`, by the `pg_allow_*`
+/// feature it belongs to.
+///
+/// One that was not is never enabled: [`enable`] does nothing for it, and
+/// the APIs it covers fail with an error naming the missing feature.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub const fn is_built_in(cap: Capability) -> bool {
+    imp::is_built_in(cap)
+}
+
+/// Returns whether this is the permissive "untrusted" `std`, built with the
+/// `pg_untrusted` feature, in which every subsystem is compiled in and
+/// every capability is always enabled.
+///
+/// An embedding runtime that only means to run trusted code can refuse to
+/// start against it.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub const fn is_untrusted() -> bool {
+    imp::is_untrusted()
+}
+
+/// Returns whether `cap` is currently enabled.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn is_enabled(cap: Capability) -> bool {
+    imp::is_enabled(cap)
+}
@@ -0,0 +1,40 @@
+//! Collections in dynamic shared memory.
+//!
+//! The collections in [`std::collections`] live in a backend's own memory,
+//! which no other backend can see. These instead live in a dynamic shared
+//! memory area (DSA), which any backend can attach to with a handle, and
+//! hold only [`Pod`] types, which mean the same in every backend.
+//!
+//! [`std::collections`]: crate::collections
+
+mod shared_vec;
+mod shm_hash_map;
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub use self::shared_vec::{SharedSlice, SharedSliceHandle, SharedVec};
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub use self::shm_hash_map::{ShmHashMap, ShmHashMapHandle, ShmRef, ShmRefMut};
+
+/// Types that can live in shared memory as plain bytes, and mean the same
+/// in every backend.
+///
+/// # Safety
+///
+/// The type must not contain pointers or references, nor padding, and every
+/// bit pattern of its size must be a valid value.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! pod {
+    ($($t:ty)*) => {
+        $(
+            #[unstable(feature = "postgres_ext", issue = "none")]
+            unsafe impl Pod for $t {}
+        )*
+    };
+}
+
+pod! { u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize f32 f64 }
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
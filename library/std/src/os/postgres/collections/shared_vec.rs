@@ -0,0 +1,294 @@
+use super::Pod;
+use crate::cmp;
+use crate::fmt;
+use crate::io;
+use crate::mem;
+use crate::ops::{Deref, DerefMut};
+use crate::os::postgres::sync::Tranche;
+use crate::ptr::{self, NonNull};
+use crate::slice;
+use crate::sys::dsa::{Area, DsaPointer, InvalidDsaPointer};
+use crate::sys::mcxt::MAXIMUM_ALIGNOF;
+
+/// A growable array in a DSA area, for a parallel leader to fill and then
+/// share with its workers.
+///
+/// It works like a [`Vec`], except that growing it can fail, and that it
+/// has no [`handle`](SharedSlice::handle) of its own: once filled, it turns
+/// [`into_shared`](SharedVec::into_shared), a read-only [`SharedSlice`]
+/// that workers [`attach`](SharedSlice::attach) to. The elements are never
+/// moved or changed after that, so workers read them without any locking,
+/// and for a scatter/gather workload, each worker can pick its own part.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::ffi::CStr;
+/// use std::os::postgres::collections::{SharedSlice, SharedSliceHandle, SharedVec};
+/// use std::os::postgres::sync::Tranche;
+///
+/// // Leader.
+/// fn share(rows: impl Iterator<Item = f64>) -> std::io::Result<SharedSlice<f64>> {
+///     let name = CStr::from_bytes_with_nul(b"my_extension\0").unwrap();
+///     let mut values = SharedVec::new(&Tranche::new(name)?)?;
+///     for row in rows {
+///         values.push(row)?;
+///     }
+///     // Pass `SharedSlice::handle(&shared)` to the workers, and keep
+///     // `shared` alive until they have attached.
+///     Ok(values.into_shared())
+/// }
+///
+/// // Worker `i` of `n`, given the handle as part of its input.
+/// fn sum(handle: SharedSliceHandle, i: usize, n: usize) -> std::io::Result<f64> {
+///     let values = unsafe { SharedSlice::<f64>::attach(handle)? };
+///     let chunk = (values.len() + n - 1) / n;
+///     Ok(values.iter().skip(i * chunk).take(chunk).sum())
+/// }
+/// ```
+///
+/// [`Vec`]: crate::vec::Vec
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct SharedVec<T: Pod> {
+    // Only ever attached to by this backend, so everything in it goes when
+    // it is dropped.
+    area: Area,
+    data: DsaPointer,
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+}
+
+/// A slice in a DSA area, shared with other backends, from
+/// [`SharedVec::into_shared`] or [`SharedSlice::attach`].
+///
+/// The area goes away once every backend has dropped its `SharedSlice`.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct SharedSlice<T: Pod> {
+    area: Area,
+    data: DsaPointer,
+    ptr: NonNull<T>,
+    len: usize,
+}
+
+/// What other backends [`attach`](SharedSlice::attach) to a [`SharedSlice`]
+/// with.
+///
+/// It is plain data, to store in shared memory or pass to parallel workers.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct SharedSliceHandle {
+    data: u64,
+    len: u64,
+    area: u32,
+    size: u32,
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl Pod for SharedSliceHandle {}
+
+fn check_layout<T>() -> io::Result<()> {
+    // DSA allocations are only aligned like `palloc`s.
+    if mem::align_of::<T>() > MAXIMUM_ALIGNOF {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "over-aligned types cannot be stored in a shared vector",
+        ));
+    }
+    Ok(())
+}
+
+impl<T: Pod> SharedVec<T> {
+    /// Creates an empty vector in a new DSA area, whose locks are in
+    /// `tranche`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new(tranche: &Tranche) -> io::Result<SharedVec<T>> {
+        SharedVec::with_capacity(tranche, 0)
+    }
+
+    /// Creates an empty vector with room for at least `capacity` elements,
+    /// in a new DSA area whose locks are in `tranche`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn with_capacity(tranche: &Tranche, capacity: usize) -> io::Result<SharedVec<T>> {
+        check_layout::<T>()?;
+        let area = Area::create(tranche.id())?;
+        let mut vec =
+            SharedVec { area, data: InvalidDsaPointer, ptr: NonNull::dangling(), len: 0, cap: 0 };
+        vec.reserve_exact(capacity)?;
+        Ok(vec)
+    }
+
+    /// Returns the number of elements the vector can hold without growing.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn capacity(&self) -> usize {
+        if mem::size_of::<T>() == 0 { usize::MAX } else { self.cap }
+    }
+
+    /// Reserves room for at least `additional` more elements, growing the
+    /// vector more than that to keep pushes cheap.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn reserve(&mut self, additional: usize) -> io::Result<()> {
+        if self.capacity() - self.len >= additional {
+            return Ok(());
+        }
+        let needed = self.len.checked_add(additional).ok_or_else(overflow)?;
+        self.grow(cmp::max(cmp::max(self.cap * 2, needed), 4))
+    }
+
+    /// Reserves room for exactly `additional` more elements.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn reserve_exact(&mut self, additional: usize) -> io::Result<()> {
+        if self.capacity() - self.len >= additional {
+            return Ok(());
+        }
+        self.grow(self.len.checked_add(additional).ok_or_else(overflow)?)
+    }
+
+    fn grow(&mut self, cap: usize) -> io::Result<()> {
+        let size = cap.checked_mul(mem::size_of::<T>()).ok_or_else(overflow)?;
+        let data = self.area.allocate(size, false)?;
+        let ptr = unsafe { self.area.address(data) }.cast::<T>();
+        if self.data != InvalidDsaPointer {
+            unsafe {
+                ptr::copy_nonoverlapping(self.ptr.as_ptr(), ptr, self.len);
+                self.area.free(self.data);
+            }
+        }
+        self.data = data;
+        self.ptr = unsafe { NonNull::new_unchecked(ptr) };
+        self.cap = cap;
+        Ok(())
+    }
+
+    /// Appends `value`, growing the vector if it is full.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn push(&mut self, value: T) -> io::Result<()> {
+        self.reserve(1)?;
+        unsafe { self.ptr.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Appends all of `values`, growing the vector as need be.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn extend_from_slice(&mut self, values: &[T]) -> io::Result<()> {
+        self.reserve(values.len())?;
+        unsafe {
+            ptr::copy_nonoverlapping(values.as_ptr(), self.ptr.as_ptr().add(self.len), values.len())
+        };
+        self.len += values.len();
+        Ok(())
+    }
+
+    /// Removes the last element and returns it, or `None` if the vector is
+    /// empty.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        Some(unsafe { self.ptr.as_ptr().add(self.len).read() })
+    }
+
+    /// Shortens the vector to `len` elements, if it is longer.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn truncate(&mut self, len: usize) {
+        self.len = cmp::min(self.len, len);
+    }
+
+    /// Turns the vector into a slice that other backends can attach to, and
+    /// that no one can change anymore.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn into_shared(self) -> SharedSlice<T> {
+        let SharedVec { area, data, ptr, len, cap: _ } = self;
+        SharedSlice { area, data, ptr, len }
+    }
+}
+
+fn overflow() -> io::Error {
+    io::const_io_error!(io::ErrorKind::OutOfMemory, "capacity overflow")
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Pod> Deref for SharedVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Pod> DerefMut for SharedVec<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Pod + fmt::Debug> fmt::Debug for SharedVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Pod> SharedSlice<T> {
+    /// Attaches to the slice shared under `handle`.
+    ///
+    /// Fails with the `ERROR` postgres raises if every backend has dropped
+    /// the slice already.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must come from [`SharedSlice::handle`] on a `SharedSlice<T>`
+    /// of the same `T`, built from the same code.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn attach(handle: SharedSliceHandle) -> io::Result<SharedSlice<T>> {
+        check_layout::<T>()?;
+        if handle.size as usize != mem::size_of::<T>() {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "the shared slice has elements of another type",
+            ));
+        }
+        let area = Area::attach(handle.area)?;
+        let ptr = if handle.data == InvalidDsaPointer {
+            NonNull::dangling()
+        } else {
+            unsafe { NonNull::new_unchecked(area.address(handle.data).cast()) }
+        };
+        Ok(SharedSlice { area, data: handle.data, ptr, len: handle.len as usize })
+    }
+
+    /// The handle other backends [`attach`](SharedSlice::attach) with.
+    ///
+    /// Like [`SharedArc::handle`], this is an associated function, called as
+    /// `SharedSlice::handle(&this)`, so as not to shadow a method of `[T]`.
+    ///
+    /// [`SharedArc::handle`]: crate::os::postgres::sync::SharedArc::handle
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn handle(this: &Self) -> SharedSliceHandle {
+        SharedSliceHandle {
+            data: this.data,
+            len: this.len as u64,
+            area: this.area.handle(),
+            size: mem::size_of::<T>() as u32,
+        }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Pod> Deref for SharedSlice<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Pod + fmt::Debug> fmt::Debug for SharedSlice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,339 @@
+use super::Pod;
+use crate::fmt;
+use crate::io;
+use crate::marker::PhantomData;
+use crate::mem;
+use crate::ops::{Deref, DerefMut};
+use crate::os::postgres::sync::Tranche;
+use crate::ptr::{addr_of, addr_of_mut};
+use crate::sys::dsa::Area;
+use crate::sys::dshash::Table;
+use crate::sys::mcxt::MAXIMUM_ALIGNOF;
+
+/// A hash map in dynamic shared memory, backed by postgres's `dshash`.
+///
+/// The map lives in a DSA area of its own, which grows as entries are
+/// inserted. Other backends [`attach`](ShmHashMap::attach) to it with its
+/// [`handle`](ShmHashMap::handle), so that extensions can keep a lookup
+/// table shared by all of them, growing and shrinking as they go.
+///
+/// Keys are hashed and compared as plain bytes: for floats, `0.0` and `-0.0`
+/// are different keys, and a NaN is a key like any other.
+///
+/// # Locking
+///
+/// The entries are spread over partitions, each with a lock of its own in
+/// the [`Tranche`] the map was created with. Looking up an entry locks its
+/// partition until the returned [`ShmRef`] or [`ShmRefMut`] is dropped:
+/// shared for the former, so that other backends can still read it, and
+/// exclusively for the latter. Every method that locks an entry borrows the
+/// map mutably, so a backend holds at most one such lock per attachment,
+/// and cannot deadlock against itself or others. Attaching twice to the same
+/// map in one backend gives that up.
+///
+/// Like any `LWLock`, the lock holds off interrupts, and waiting for it is
+/// not interruptible: guards should be dropped quickly.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::ffi::CStr;
+/// use std::os::postgres::collections::{ShmHashMap, ShmHashMapHandle};
+/// use std::os::postgres::sync::Tranche;
+///
+/// // Once, with the handle then stored in `ShmemInitStruct` memory.
+/// fn create() -> std::io::Result<ShmHashMapHandle> {
+///     let name = CStr::from_bytes_with_nul(b"my_extension\0").unwrap();
+///     let hits = ShmHashMap::<u32, u64>::new(&Tranche::new(name)?)?;
+///     hits.pin();
+///     Ok(hits.handle())
+/// }
+///
+/// // In any backend.
+/// fn hit(handle: ShmHashMapHandle, oid: u32) -> std::io::Result<u64> {
+///     let mut hits = unsafe { ShmHashMap::<u32, u64>::attach(handle)? };
+///     let mut count = hits.get_or_insert_with(oid, || 0);
+///     *count += 1;
+///     Ok(*count)
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct ShmHashMap<K: Pod, V: Pod> {
+    // Detached before the area it is in.
+    table: Table,
+    area: Area,
+    tranche: i32,
+    _marker: PhantomData<(K, V)>,
+}
+
+/// What other backends [`attach`](ShmHashMap::attach) to a [`ShmHashMap`]
+/// with.
+///
+/// It is plain data, to store in shared memory or pass to parallel workers.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct ShmHashMapHandle {
+    table: u64,
+    area: u32,
+    tranche: i32,
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl Pod for ShmHashMapHandle {}
+
+// What `dshash` stores, key first.
+#[repr(C)]
+struct Entry<K, V> {
+    key: K,
+    value: V,
+}
+
+/// Shared access to an entry of a [`ShmHashMap`], which keeps its partition
+/// locked until it is dropped.
+#[must_use = "if unused the entry will immediately unlock"]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct ShmRef<'a, K: Pod, V: Pod> {
+    table: &'a Table,
+    entry: *mut Entry<K, V>,
+}
+
+/// Exclusive access to an entry of a [`ShmHashMap`], which keeps its
+/// partition locked until it is dropped.
+#[must_use = "if unused the entry will immediately unlock"]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct ShmRefMut<'a, K: Pod, V: Pod> {
+    table: &'a Table,
+    entry: *mut Entry<K, V>,
+}
+
+impl<K: Pod, V: Pod> ShmHashMap<K, V> {
+    fn check_layout() -> io::Result<()> {
+        // `dshash` only aligns entries like `palloc` does.
+        if mem::align_of::<Entry<K, V>>() > MAXIMUM_ALIGNOF {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "over-aligned types cannot be stored in a shared hash map",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Creates an empty map in a new DSA area, whose locks are in `tranche`.
+    ///
+    /// The map goes away once every backend has detached from it, unless it
+    /// is [`pin`](ShmHashMap::pin)ned.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new(tranche: &Tranche) -> io::Result<ShmHashMap<K, V>> {
+        Self::check_layout()?;
+        let area = Area::create(tranche.id())?;
+        let table =
+            Table::create(&area, mem::size_of::<K>(), mem::size_of::<Entry<K, V>>(), tranche.id())?;
+        Ok(ShmHashMap { table, area, tranche: tranche.id(), _marker: PhantomData })
+    }
+
+    /// Attaches to the map shared under `handle`.
+    ///
+    /// Fails with the `ERROR` postgres raises if the map is gone.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must come from [`ShmHashMap::handle`] on a `ShmHashMap<K, V>`
+    /// of the same `K` and `V`, built from the same code.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn attach(handle: ShmHashMapHandle) -> io::Result<ShmHashMap<K, V>> {
+        Self::check_layout()?;
+        let area = Area::attach(handle.area)?;
+        let table = unsafe {
+            Table::attach(
+                &area,
+                handle.table,
+                mem::size_of::<K>(),
+                mem::size_of::<Entry<K, V>>(),
+                handle.tranche,
+            )?
+        };
+        Ok(ShmHashMap { table, area, tranche: handle.tranche, _marker: PhantomData })
+    }
+
+    /// The handle other backends [`attach`](ShmHashMap::attach) with.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn handle(&self) -> ShmHashMapHandle {
+        ShmHashMapHandle {
+            table: self.table.handle(),
+            area: self.area.handle(),
+            tranche: self.tranche,
+        }
+    }
+
+    /// Keeps the map until the server shuts down, even once no backend is
+    /// attached to it.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn pin(&self) {
+        self.area.pin()
+    }
+
+    /// Returns shared access to the entry for `key`, if there is one.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn get(&mut self, key: &K) -> Option<ShmRef<'_, K, V>> {
+        let entry = unsafe { self.table.find((key as *const K).cast(), false)? };
+        Some(ShmRef { table: &self.table, entry: entry.cast() })
+    }
+
+    /// Returns exclusive access to the entry for `key`, if there is one.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn get_mut(&mut self, key: &K) -> Option<ShmRefMut<'_, K, V>> {
+        let entry = unsafe { self.table.find((key as *const K).cast(), true)? };
+        Some(ShmRefMut { table: &self.table, entry: entry.cast() })
+    }
+
+    /// Returns `true` if the map has an entry for `key`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn contains_key(&mut self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns exclusive access to the entry for `key`, inserting the value
+    /// `f` returns if there is none.
+    ///
+    /// `f` runs with the entry's partition locked. If it panics, nothing is
+    /// inserted.
+    ///
+    /// When the area cannot grow, postgres raises an `ERROR`, which aborts
+    /// the transaction like any other.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: K, f: F) -> ShmRefMut<'_, K, V> {
+        let (entry, found) = unsafe { self.table.find_or_insert(addr_of!(key).cast()) };
+        let entry = ShmRefMut { table: &self.table, entry: entry.cast::<Entry<K, V>>() };
+        if !found {
+            // Until the value is there, the entry must go rather than be
+            // unlocked half-initialized.
+            struct Remove<'a, K: Pod, V: Pod>(Option<ShmRefMut<'a, K, V>>);
+
+            impl<K: Pod, V: Pod> Drop for Remove<'_, K, V> {
+                fn drop(&mut self) {
+                    if let Some(entry) = self.0.take() {
+                        entry.delete();
+                    }
+                }
+            }
+
+            let mut guard = Remove(Some(entry));
+            let value = f();
+            let entry = guard.0.take().unwrap();
+            unsafe { addr_of_mut!((*entry.entry).value).write(value) };
+            return entry;
+        }
+        entry
+    }
+
+    /// Inserts `value` for `key`, returning the value it replaced, if any.
+    ///
+    /// Raises an `ERROR` like [`get_or_insert_with`](ShmHashMap::get_or_insert_with).
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut inserted = false;
+        let mut entry = self.get_or_insert_with(key, || {
+            inserted = true;
+            value
+        });
+        if inserted { None } else { Some(mem::replace(&mut *entry, value)) }
+    }
+
+    /// Removes the entry for `key`, returning its value, if there was one.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.get_mut(key).map(ShmRefMut::remove)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<K: Pod, V: Pod> fmt::Debug for ShmHashMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShmHashMap").field("handle", &self.handle()).finish_non_exhaustive()
+    }
+}
+
+impl<K: Pod, V: Pod> ShmRef<'_, K, V> {
+    /// The key of the entry.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn key(&self) -> &K {
+        unsafe { &(*self.entry).key }
+    }
+}
+
+impl<K: Pod, V: Pod> ShmRefMut<'_, K, V> {
+    /// The key of the entry.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn key(&self) -> &K {
+        unsafe { &(*self.entry).key }
+    }
+
+    /// Removes the entry from the map, returning its value.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn remove(self) -> V {
+        let value = unsafe { (*self.entry).value };
+        self.delete();
+        value
+    }
+
+    // Deletes the entry, which also unlocks it.
+    fn delete(self) {
+        let this = mem::ManuallyDrop::new(self);
+        unsafe { this.table.delete_entry(this.entry.cast()) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<K: Pod, V: Pod> Deref for ShmRef<'_, K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        unsafe { &(*self.entry).value }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<K: Pod, V: Pod> Deref for ShmRefMut<'_, K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        unsafe { &(*self.entry).value }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<K: Pod, V: Pod> DerefMut for ShmRefMut<'_, K, V> {
+    fn deref_mut(&mut self) -> &mut V {
+        unsafe { &mut (*self.entry).value }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<K: Pod, V: Pod> Drop for ShmRef<'_, K, V> {
+    fn drop(&mut self) {
+        unsafe { self.table.release(self.entry.cast()) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<K: Pod, V: Pod> Drop for ShmRefMut<'_, K, V> {
+    fn drop(&mut self) {
+        unsafe { self.table.release(self.entry.cast()) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<K: Pod + fmt::Debug, V: Pod + fmt::Debug> fmt::Debug for ShmRef<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShmRef").field("key", self.key()).field("value", &**self).finish()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<K: Pod + fmt::Debug, V: Pod + fmt::Debug> fmt::Debug for ShmRefMut<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShmRefMut").field("key", self.key()).field("value", &**self).finish()
+    }
+}
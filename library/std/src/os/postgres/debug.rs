@@ -0,0 +1,274 @@
+//! Debugging aids for extension authors.
+//!
+//! The checks here cost something on paths that should be fast, so they are
+//! only compiled into a std built with the `pg_debug` feature. Without it,
+//! they do nothing, like a `debug_assert!` in a release build.
+//! [`dump_memory_contexts`] works in any build, and [`inject_fault`] in none
+//! but those.
+
+use crate::fmt::Write;
+use crate::io;
+use crate::sys::mcxt;
+
+#[cfg(test)]
+mod tests;
+
+/// Runs `f`, and panics if it allocated with the global allocator, for
+/// checking that a hot path, like a per-tuple callback, does not.
+///
+/// An allocator must not unwind, so the panic is raised once `f` returns,
+/// saying how many allocations there were, and, with a backtrace, where the
+/// first one was made. Under [`guard`](super::guard) it becomes an `ERROR`
+/// like any other. Scopes nest, and each one reports what was allocated in
+/// it, but not in the scopes inside it.
+///
+/// Only the global allocator is checked, and only if it is the default,
+/// [`System`](crate::alloc::System): palloc'ing is not counted.
+///
+/// Without the `pg_debug` feature, this just calls `f`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::debug::assert_no_alloc;
+///
+/// fn sum_row(values: &[i64]) -> i64 {
+///     assert_no_alloc(|| values.iter().sum())
+/// }
+/// ```
+#[track_caller]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn assert_no_alloc<F: FnOnce() -> R, R>(f: F) -> R {
+    #[cfg(feature = "pg_debug")]
+    {
+        use crate::backtrace::BacktraceStatus;
+        use crate::sys::alloc::no_alloc;
+
+        // What was allocated in the enclosing scope, put back on exit.
+        struct Scope(Option<no_alloc::Found>);
+
+        impl Drop for Scope {
+            // Only on unwind: `f` panicked, which is reported already.
+            fn drop(&mut self) {
+                no_alloc::exit(self.0.take());
+            }
+        }
+
+        let mut scope = Scope(no_alloc::enter());
+        let r = f();
+        let outer = scope.0.take();
+        crate::mem::forget(scope);
+        if let Some(found) = no_alloc::exit(outer) {
+            let s = if found.count == 1 { "" } else { "s" };
+            match found.first.status() {
+                BacktraceStatus::Captured => panic!(
+                    "{} allocation{s} ({} bytes) in an allocation-free scope, the first one at:\n{}",
+                    found.count, found.bytes, found.first
+                ),
+                _ => panic!(
+                    "{} allocation{s} ({} bytes) in an allocation-free scope",
+                    found.count, found.bytes
+                ),
+            }
+        }
+        r
+    }
+    #[cfg(not(feature = "pg_debug"))]
+    f()
+}
+
+/// Writes the tree of memory contexts to the server log, as one `LOG`
+/// message, like `MemoryContextStats(TopMemoryContext)` does for C code.
+///
+/// Each context is on a line of its own, indented under its parent, with
+/// the memory postgres got for it. Rust's view of the tree is marked on it:
+/// which contexts are `CurrentMemoryContext` and the transaction's, and,
+/// with the `pg_debug` feature, how much std has palloc'd in each, like for
+/// a [`CtxCString`](super::ffi::CtxCString), that it still owns. A context
+/// with more than 100 children only has the first 100 listed, like postgres
+/// does.
+///
+/// What Rust allocates with the global allocator is not in any context.
+///
+/// This fails with [`ErrorKind::Unsupported`] outside of a backend.
+///
+/// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+///
+/// # Examples
+///
+/// ```text
+/// LOG:  memory contexts:
+///       TopMemoryContext: 97664 bytes
+///         TopTransactionContext: 8192 bytes  <- CurrentMemoryContext
+///         ...
+///         PortalContext: <unnamed>: 1024 bytes  Rust owns 48 bytes in 2 chunks
+///       Grand total: 1296576 bytes in 95 contexts; Rust owns 48 bytes in 2 chunks
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn dump_memory_contexts() -> io::Result<()> {
+    const MAX_CHILDREN: usize = 100;
+
+    let top = mcxt::top()?;
+    let current = mcxt::current().ok();
+    let top_transaction = mcxt::top_transaction().ok();
+    let cur_transaction = mcxt::cur_transaction().ok();
+
+    let mut out = String::from("memory contexts:");
+    let (mut total, mut contexts) = (0, 0);
+    let mut owned_total: Option<(usize, usize)> = None;
+    // How many children of each context on the path down to the current one
+    // were seen, and where listing them stopped.
+    let mut children: Vec<usize> = Vec::new();
+    let mut skipped = None;
+    unsafe {
+        mcxt::walk(top, &mut |info| {
+            total += info.allocated;
+            contexts += 1;
+            if let Some((bytes, chunks)) = info.owned {
+                let (total_bytes, total_chunks) = owned_total.get_or_insert((0, 0));
+                *total_bytes += bytes;
+                *total_chunks += chunks;
+            }
+
+            children.truncate(info.depth);
+            if let Some(seen) = children.last_mut() {
+                *seen += 1;
+            }
+            children.push(0);
+            match skipped {
+                Some(depth) if info.depth >= depth => return,
+                _ => skipped = None,
+            }
+            if info.depth > 0 && children[info.depth - 1] > MAX_CHILDREN {
+                let _ = write!(out, "\n{:1$}...", "", info.depth * 2);
+                skipped = Some(info.depth);
+                return;
+            }
+
+            let _ = write!(out, "\n{:1$}{2}", "", info.depth * 2, info.name.to_string_lossy());
+            if let Some(ident) = info.ident {
+                let _ = write!(out, ": {}", ident.to_string_lossy());
+            }
+            let _ = write!(out, ": {} bytes", info.allocated);
+
+            let mut notes = Vec::new();
+            for (context, name) in [
+                (current, "CurrentMemoryContext"),
+                (top_transaction, "TopTransactionContext"),
+                (cur_transaction, "CurTransactionContext"),
+            ] {
+                // Named already, unless it is the current one as well.
+                if context == Some(info.context) && info.name.to_bytes() != name.as_bytes() {
+                    notes.push(format!("<- {name}"));
+                }
+            }
+            if let Some((bytes, chunks)) = info.owned.filter(|&(_, chunks)| chunks > 0) {
+                notes.push(format!("Rust owns {bytes} bytes in {chunks} chunks"));
+            }
+            if !notes.is_empty() {
+                let _ = write!(out, "  {}", notes.join("; "));
+            }
+        })
+    };
+    let _ = write!(out, "\nGrand total: {total} bytes in {contexts} contexts");
+    if let Some((bytes, chunks)) = owned_total {
+        let _ = write!(out, "; Rust owns {bytes} bytes in {chunks} chunks");
+    }
+    super::error::log(out)
+}
+
+/// A kind of I/O operation that [`inject_fault`] makes fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub enum IoOp {
+    /// Reading from a socket: `read`, `read_vectored`, `recv` and
+    /// `recv_from`. Peeking is left alone.
+    Read,
+    /// Writing to a socket: `write`, `write_vectored`, `send` and `send_to`.
+    Write,
+}
+
+/// A fault for [`inject_fault`] to make an I/O operation fail with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub enum Fault {
+    /// Fails with `EINTR`, [`ErrorKind::Interrupted`], which a signal
+    /// arriving in the call would cause, and which `read_exact` and
+    /// `write_all` retry.
+    ///
+    /// [`ErrorKind::Interrupted`]: crate::io::ErrorKind::Interrupted
+    Interrupted,
+    /// Transfers at most this many bytes, or, for a vectored operation, this
+    /// many of the first buffer that is not empty. A datagram read this short
+    /// is truncated.
+    Short(usize),
+    /// Fails with `ENOSPC`, [`ErrorKind::StorageFull`].
+    ///
+    /// [`ErrorKind::StorageFull`]: crate::io::ErrorKind::StorageFull
+    NoSpace,
+    /// Fails with `EAGAIN`, [`ErrorKind::WouldBlock`], which is what a socket
+    /// read or write fails with when its timeout expires.
+    ///
+    /// [`ErrorKind::WouldBlock`]: crate::io::ErrorKind::WouldBlock
+    TimedOut,
+}
+
+/// Makes the next `op` on a socket, on this thread, fail with `fault`, for
+/// testing the error handling of code that does I/O without breaking a
+/// network for real.
+///
+/// Faults are queued: each operation takes the first one queued for its
+/// kind, before anything goes to the kernel, so a test that queues the same
+/// faults and does the same I/O fails the same way every time. Faults that
+/// nothing took stay queued until [`clear_faults`].
+///
+/// The filesystem has no implementation on this target for faults to go
+/// into, so only sockets have any.
+///
+/// Without the `pg_debug` feature, this fails with
+/// [`ErrorKind::Unsupported`], so that a test does not pass for a fault it
+/// did not get.
+///
+/// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::io::{self, Write};
+/// use std::net::TcpStream;
+/// use std::os::postgres::debug::{clear_faults, inject_fault, Fault, IoOp};
+///
+/// fn send_all_survives_short_writes(stream: &mut TcpStream) -> io::Result<()> {
+///     inject_fault(IoOp::Write, Fault::Short(1))?;
+///     inject_fault(IoOp::Write, Fault::Interrupted)?;
+///     let result = stream.write_all(b"hello");
+///     clear_faults();
+///     result
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn inject_fault(op: IoOp, fault: Fault) -> io::Result<()> {
+    #[cfg(feature = "pg_debug")]
+    {
+        crate::sys::fault::push(op, fault);
+        Ok(())
+    }
+    #[cfg(not(feature = "pg_debug"))]
+    {
+        let _ = (op, fault);
+        Err(io::const_io_error!(
+            io::ErrorKind::Unsupported,
+            "fault injection needs a std built with the `pg_debug` feature",
+        ))
+    }
+}
+
+/// Drops the faults [`inject_fault`] queued on this thread that no I/O took.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn clear_faults() {
+    #[cfg(feature = "pg_debug")]
+    crate::sys::fault::clear();
+}
@@ -0,0 +1,16 @@
+// These need a backend to run in, through `test::postgres::run_tests`.
+
+use super::assert_no_alloc;
+use crate::hint::black_box;
+
+#[test]
+#[cfg(feature = "pg_debug")]
+#[should_panic(expected = "1 allocation (8 bytes) in an allocation-free scope")]
+fn no_alloc_nested_scope_keeps_outer_allocations() {
+    assert_no_alloc(|| {
+        drop(black_box(Box::new(0u64)));
+        // The inner scope allocates nothing, and must not swallow what the
+        // outer one found before it.
+        assert_no_alloc(|| black_box(1 + 1));
+    });
+}
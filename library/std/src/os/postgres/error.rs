@@ -0,0 +1,500 @@
+//! Errors crossing between Rust and postgres.
+//!
+//! A panic out of Rust code becomes a postgres `ERROR` under [`guard`], and
+//! an `ERROR` out of postgres becomes a [`PgError`] under
+//! [`catch_pg_error`]. [`report`] raises any Rust error, with its chain of
+//! sources, as an `ERROR`. [`notice`] and [`log`] report messages that are
+//! not errors at all.
+
+use crate::error::Error;
+use crate::fmt;
+use crate::io;
+use crate::panic::{self, UnwindSafe};
+use crate::str;
+use crate::sys::elog;
+
+/// Runs `f`, and turns a panic escaping from it into a postgres `ERROR`.
+///
+/// Unwinding must not cross into postgres's C code, so the body of every
+/// function postgres calls (SQL functions, hooks, callbacks, background
+/// worker entry points) should run under `guard`. A panic is then reported
+/// like an `ereport(ERROR)` with SQLSTATE `XX000` (`internal_error`), the
+/// panic message, and where it panicked as context, or as the [`PgError`]
+/// it was raised with. That aborts the current transaction, and the backend
+/// carries on.
+///
+/// Panics that cannot unwind at all, such as one out of a function that
+/// cannot unwind, are turned into an `ERROR` on the spot rather than aborting
+/// the whole server. Destructors of the frames in between do not run then.
+/// A panic raised while already unwinding, such as from a destructor, is a
+/// `PANIC` instead, naming both panics: the server restarts, like after any
+/// backend crash, since there is no telling what the interrupted cleanup left
+/// behind.
+///
+/// Outside of a backend, the panic keeps unwinding.
+///
+/// A function postgres calls that does without `guard` has to be declared
+/// `extern "C-unwind"` for a panic to leave it at all. Such a panic, with no
+/// `catch_unwind` to unwind to short of postgres's C frames, becomes an
+/// `ERROR` on the spot as well, rather than unwinding through them, but the
+/// destructors of the Rust frames in between do not run.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::guard;
+///
+/// #[no_mangle]
+/// pub extern "C" fn my_extension_divide(a: i64, b: i64) -> i64 {
+///     // Dividing by zero raises `ERROR: attempt to divide by zero`.
+///     guard(|| a / b)
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn guard<F: FnOnce() -> R + UnwindSafe, R>(f: F) -> R {
+    match panic::catch_unwind(f) {
+        Ok(r) => r,
+        Err(payload) => {
+            if let Some(report) = elog::panic_report(Some(&*payload)) {
+                // `throw` does not return, so this is the last chance to free
+                // the payload.
+                drop(payload);
+                report.throw();
+            }
+            panic::resume_unwind(payload)
+        }
+    }
+}
+
+/// Runs `f`, which calls into postgres, and catches an `ERROR` raised in
+/// there, like `PG_TRY()` and `PG_CATCH()` do in C.
+///
+/// Postgres raises an error by longjmp-ing to the innermost `PG_TRY()`,
+/// which without this would be somewhere up the stack, past the Rust code
+/// that called into postgres. Here, the error state is copied into a
+/// [`PgError`] and cleared, as after `CopyErrorData()` and
+/// `FlushErrorState()`, and the transaction can carry on: re-raise the error
+/// if it is not recoverable, or roll back to a subtransaction if postgres
+/// was in the middle of modifying anything.
+///
+/// The longjmp still skips any Rust frames between `f` and the C code
+/// raising the error, without running their destructors, so keep `f` to
+/// calls into postgres, and values to be dropped outside of it. The
+/// converse holds too: a panic must not unwind through postgres's frames,
+/// so any Rust function called back from inside `f` has to run under
+/// [`guard`].
+///
+/// Outside of a backend, this just calls `f`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::ffi::{c_char, CStr};
+/// use std::os::postgres::catch_pg_error;
+///
+/// extern "C" {
+///     // utils/builtins.h
+///     fn pg_strtoint32(s: *const c_char) -> i32;
+/// }
+///
+/// fn parse_int4(input: &CStr) -> Option<i32> {
+///     match catch_pg_error(|| unsafe { pg_strtoint32(input.as_ptr()) }) {
+///         Ok(n) => Some(n),
+///         // 22P02 is invalid_text_representation, 22003 is
+///         // numeric_value_out_of_range.
+///         Err(e) if matches!(e.code(), "22P02" | "22003") => None,
+///         Err(e) => panic!("{e}"),
+///     }
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn catch_pg_error<F: FnOnce() -> R, R>(f: F) -> Result<R, PgError> {
+    elog::try_catch(f).map_err(|inner| PgError { inner })
+}
+
+/// Like [`catch_pg_error`], but runs `f` in an internal subtransaction,
+/// which is rolled back if postgres raises an `ERROR` in there, like a
+/// PL/pgSQL block with an `EXCEPTION` clause.
+///
+/// That undoes whatever `f` did in the database, and releases the locks and
+/// other resources it acquired, so the transaction can carry on whatever
+/// postgres was in the middle of. Otherwise, the subtransaction is committed
+/// into the enclosing one when `f` returns. What `f` allocates stays in the
+/// memory context that was current when this was called.
+///
+/// The same caveats about Rust frames inside `f` apply as for
+/// `catch_pg_error`.
+///
+/// Outside of a transaction, there is nothing to roll back to, and an
+/// `ERROR` raised inside `f` propagates as usual. Outside of a backend, this
+/// just calls `f`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::ffi::{c_char, c_int};
+/// use std::os::postgres::subtransaction;
+///
+/// extern "C" {
+///     // executor/spi.h
+///     fn SPI_exec(src: *const c_char, tcount: i64) -> c_int;
+/// }
+///
+/// // Inserts the row if it can, or leaves the table as it was.
+/// fn try_insert() -> bool {
+///     let query = b"INSERT INTO t VALUES (1)\0";
+///     subtransaction(|| unsafe { SPI_exec(query.as_ptr().cast(), 0) }).is_ok()
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn subtransaction<F: FnOnce() -> R, R>(f: F) -> Result<R, PgError> {
+    elog::subtransaction(f).map_err(|inner| PgError { inner })
+}
+
+/// Runs `f` with `value`, and drops `value` afterwards even if postgres
+/// raises an `ERROR` or `FATAL` inside `f`, like `PG_ENSURE_ERROR_CLEANUP()`
+/// does in C.
+///
+/// An `ERROR` longjmps straight past any Rust frames between the C code
+/// raising it and the `PG_TRY()` catching it, without running destructors:
+/// a lock guard or file in one of those frames stays locked or open for
+/// good, or at least until the backend exits. A value handed to
+/// `ensure_drop` is dropped when the `ERROR` goes past, and the `ERROR` then
+/// carries on. A `FATAL` exits the backend without going past anything, and
+/// `value` is dropped on the way out, before postgres detaches from shared
+/// memory.
+///
+/// Otherwise, `value` is dropped when `f` returns or panics, as usual. A
+/// panic in its destructor while an `ERROR` or `FATAL` is on the way is
+/// swallowed.
+///
+/// Outside of a backend, this just calls `f`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::{ensure_drop, nonpoison::Mutex};
+///
+/// extern "C" {
+///     // C code of the extension, which may raise an `ERROR`.
+///     fn my_extension_flush(pending: usize);
+/// }
+///
+/// static PENDING: Mutex<usize> = Mutex::new(0);
+///
+/// fn flush() {
+///     // Without `ensure_drop`, an `ERROR` would leave `PENDING` locked.
+///     ensure_drop(PENDING.lock(), |pending| {
+///         unsafe { my_extension_flush(**pending) };
+///         **pending = 0;
+///     });
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn ensure_drop<T, F: FnOnce(&mut T) -> R, R>(value: T, f: F) -> R {
+    let mut value = Some(value);
+    let slot = &mut value as *mut Option<T>;
+    // The longjmp leaves the borrow `f` got behind, so both get at `value`
+    // through the raw pointer.
+    elog::ensure_cleanup(&mut || drop(unsafe { (*slot).take() }), || {
+        f(unsafe { (*slot).as_mut().unwrap() })
+    })
+}
+
+/// An error as postgres reports it, with a SQLSTATE and optional detail,
+/// hint, and position.
+///
+/// This is what [`catch_pg_error`] returns for an error raised by postgres.
+/// Rust code can raise one too, with [`raise`](PgError::raise) or
+/// [`pg_error!`]: it is the payload of a panic that [`guard`] reports as
+/// this exact error, where any other panic becomes an `internal_error`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::{guard, PgError};
+///
+/// fn checked_div(a: i64, b: i64) -> i64 {
+///     if b == 0 {
+///         PgError::new("22012", "division by zero")
+///             .with_detail(format!("Tried to divide {a} by zero."))
+///             .with_hint("Filter out rows where the divisor is zero.")
+///             .raise();
+///     }
+///     a / b
+/// }
+///
+/// #[no_mangle]
+/// pub extern "C" fn my_extension_div(a: i64, b: i64) -> i64 {
+///     guard(|| checked_div(a, b))
+/// }
+/// ```
+#[derive(Clone)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct PgError {
+    pub(crate) inner: elog::ErrorData,
+}
+
+impl PgError {
+    /// Creates an error with the SQLSTATE `code`, like `"22012"` for
+    /// `division_by_zero`, and the primary `message`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `code` is not five digits or upper-case ASCII letters.
+    #[track_caller]
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new(code: &str, message: impl Into<String>) -> PgError {
+        let code: [u8; 5] = match code.as_bytes().try_into() {
+            Ok(code) if code.iter().all(|c| matches!(c, b'0'..=b'9' | b'A'..=b'Z')) => code,
+            _ => panic!("invalid SQLSTATE {code:?}"),
+        };
+        PgError {
+            inner: elog::ErrorData {
+                code,
+                message: message.into(),
+                detail: None,
+                hint: None,
+                context: None,
+                position: None,
+            },
+        }
+    }
+
+    /// Sets the `DETAIL` of the error.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn with_detail(mut self, detail: impl Into<String>) -> PgError {
+        self.inner.detail = Some(detail.into());
+        self
+    }
+
+    /// Sets the `HINT` of the error.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn with_hint(mut self, hint: impl Into<String>) -> PgError {
+        self.inner.hint = Some(hint.into());
+        self
+    }
+
+    /// Points the error at the character `position` of the query text,
+    /// counted from 1, like `errposition`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn with_position(mut self, position: u32) -> PgError {
+        self.inner.position = Some(position);
+        self
+    }
+
+    /// Raises this error, by panicking with it as the payload.
+    ///
+    /// Under [`guard`], this is like `ereport(ERROR)`, except that the stack
+    /// unwinds and destructors run before postgres gets the error.
+    #[track_caller]
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn raise(self) -> ! {
+        panic::panic_any(self)
+    }
+
+    /// The SQLSTATE of the error, like `"22012"` for `division_by_zero`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn code(&self) -> &str {
+        // SQLSTATEs are made of digits and upper-case letters.
+        str::from_utf8(&self.inner.code).unwrap_or("XX000")
+    }
+
+    /// The primary error message.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn message(&self) -> &str {
+        &self.inner.message
+    }
+
+    /// The `DETAIL` of the error, if any.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn detail(&self) -> Option<&str> {
+        self.inner.detail.as_deref()
+    }
+
+    /// The `HINT` of the error, if any.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn hint(&self) -> Option<&str> {
+        self.inner.hint.as_deref()
+    }
+
+    /// The `CONTEXT` of the error, one line per level of context, if any.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn context(&self) -> Option<&str> {
+        self.inner.context.as_deref()
+    }
+
+    /// The position of the error in the query text, in characters counted
+    /// from 1, if any.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn position(&self) -> Option<u32> {
+        self.inner.position
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for PgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PgError")
+            .field("code", &self.code())
+            .field("message", &self.message())
+            .field("detail", &self.detail())
+            .field("hint", &self.hint())
+            .field("context", &self.context())
+            .field("position", &self.position())
+            .finish()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Display for PgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Error for PgError {}
+
+/// Raises `error` as a postgres `ERROR`, so that Rust error chains all show
+/// up in the server log the same way.
+///
+/// The root cause, at the end of the chain of [`Error::source`]s, is the
+/// message: that is what actually went wrong. Each error wrapping it adds a
+/// `CONTEXT` line, innermost first like postgres's own, telling what was
+/// being done at the time. A root cause that is a [`PgError`] keeps its
+/// SQLSTATE, detail and hint; anything else is an `internal_error`.
+///
+/// Like [`PgError::raise`], this panics, for [`guard`] to report.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::fs;
+/// use std::io;
+/// use std::os::postgres::error::report;
+///
+/// #[derive(Debug)]
+/// struct LoadError(io::Error);
+///
+/// impl std::fmt::Display for LoadError {
+///     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+///         f.write_str("could not load the dictionary")
+///     }
+/// }
+///
+/// impl std::error::Error for LoadError {
+///     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+///         Some(&self.0)
+///     }
+/// }
+///
+/// fn load_dictionary(path: &str) -> Vec<u8> {
+///     // ERROR:  No such file or directory (os error 2)
+///     // CONTEXT:  could not load the dictionary
+///     fs::read(path).map_err(LoadError).unwrap_or_else(|e| report(&e))
+/// }
+/// ```
+#[track_caller]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn report(error: &(dyn Error + 'static)) -> ! {
+    let mut chain = vec![error];
+    while let Some(source) = chain[chain.len() - 1].source() {
+        chain.push(source);
+    }
+    let root = chain.pop().unwrap();
+    let mut pg = match root.downcast_ref::<PgError>() {
+        Some(pg) => pg.clone(),
+        None => PgError::new("XX000", root.to_string()),
+    };
+    for error in chain.iter().rev() {
+        let context = pg.inner.context.get_or_insert_with(String::new);
+        if !context.is_empty() {
+            context.push('\n');
+        }
+        context.push_str(&error.to_string());
+    }
+    pg.raise()
+}
+
+/// Raises a [`PgError`] with a SQLSTATE and a formatted message.
+///
+/// `pg_error!(code, "...", args...)` is short for
+/// `PgError::new(code, format!("...", args...)).raise()`. Build the
+/// [`PgError`] directly to add a detail, hint or position.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::pg_error;
+///
+/// fn parse_level(level: &str) -> u8 {
+///     match level {
+///         "low" => 1,
+///         "high" => 2,
+///         // invalid_parameter_value
+///         _ => pg_error!("22023", "invalid level: \"{level}\""),
+///     }
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+#[rustc_macro_transparency = "semitransparent"]
+#[allow_internal_unstable(postgres_ext)]
+pub macro pg_error($code:expr, $($arg:tt)+) {
+    $crate::os::postgres::PgError::new($code, $crate::format!($($arg)+)).raise()
+}
+
+/// Sends `message` to the client as a `NOTICE`, like `ereport(NOTICE)`.
+///
+/// Unlike an `ERROR`, this returns, and the statement carries on: `psql`
+/// shows the message as soon as it arrives. Whether it goes anywhere at all
+/// is up to `client_min_messages` and `log_min_messages`.
+///
+/// This fails with [`ErrorKind::Unsupported`] outside of a backend.
+///
+/// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::error::notice;
+///
+/// fn rebuild(tables: &[&str]) -> std::io::Result<()> {
+///     for table in tables {
+///         notice(format_args!("rebuilding \"{table}\""))?;
+///         // ...
+///     }
+///     Ok(())
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn notice(message: impl fmt::Display) -> io::Result<()> {
+    emit(elog::Level::Notice, message)
+}
+
+/// Writes `message` to the server log, like `elog(LOG)`, without sending it
+/// to the client.
+///
+/// This fails with [`ErrorKind::Unsupported`] outside of a backend.
+///
+/// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn log(message: impl fmt::Display) -> io::Result<()> {
+    emit(elog::Level::Log, message)
+}
+
+fn emit(level: elog::Level, message: impl fmt::Display) -> io::Result<()> {
+    if let Some(mut report) = elog::Report::start(level)? {
+        report.message(message);
+        report.finish();
+    }
+    Ok(())
+}
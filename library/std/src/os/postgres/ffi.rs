@@ -0,0 +1,554 @@
+//! Text in the database's `server_encoding`.
+//!
+//! An [`OsStr`] is any bytes at all, and a Rust string is UTF-8, but a text
+//! datum has to be valid in the encoding of the database, which need not be
+//! UTF-8. Handing postgres anything else makes for text that fails to
+//! convert, compare or dump later on, far from where it came from. The
+//! extension traits here check or convert bytes on their way in and out of
+//! SQL.
+//!
+//! Bytes that are no text at all, like a file name in some legacy encoding,
+//! can still go through SQL losslessly as text with backslash escapes, with
+//! [`to_escaped_text`] and [`from_escaped_text`]. In a database whose
+//! `server_encoding` is not `UTF8`, [`to_text_lossy_escaped`] escapes the
+//! characters it has no room for as well, and [`from_text_escaped`] turns
+//! them back.
+//!
+//! [`CStringExt`] and [`CStrExt`] do the same for nul-terminated strings
+//! on their way into postgres's C functions, which take them as they are.
+//! Those that keep the string past the call, or free it themselves, take
+//! it palloc'd in some memory context, as a [`CtxCString`].
+//!
+//! All of these, save the escaping, fail with [`ErrorKind::Unsupported`]
+//! outside of a backend.
+//!
+//! [`to_escaped_text`]: OsStrExt::to_escaped_text
+//! [`from_escaped_text`]: OsStringExt::from_escaped_text
+//! [`to_text_lossy_escaped`]: OsStrExt::to_text_lossy_escaped
+//! [`from_text_escaped`]: OsStringExt::from_text_escaped
+//! [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+//!
+//! # Examples
+//!
+//! ```no_run
+//! #![feature(postgres_ext)]
+//! use std::ffi::OsString;
+//! use std::os::postgres::ffi::{OsStrExt, OsStringExt};
+//!
+//! let name = OsString::from("café");
+//! // Bytes for a text datum, in the server encoding.
+//! let datum = name.to_server_encoding()?;
+//! assert_eq!(OsString::from_server_encoding(&datum)?, name);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::borrow::Cow;
+use crate::ffi::{c_char, CStr, CString, OsStr, OsString, VaList};
+use crate::fmt::{self, Write};
+use crate::io;
+use crate::ops::Deref;
+use crate::os::postgres::memory::MemoryContext;
+use crate::os::unix::ffi::{OsStrExt as _, OsStringExt as _};
+use crate::ptr::{self, NonNull};
+use crate::sealed::Sealed;
+use crate::str;
+use crate::sys::{encoding, mcxt, string_info};
+
+#[cfg(test)]
+mod tests;
+
+/// Postgres-specific extensions to [`OsStr`].
+///
+/// This trait is sealed: it cannot be implemented outside the standard library.
+/// This is so that future additional methods are not breaking changes.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub trait OsStrExt: Sealed {
+    /// Checks that these bytes are valid text in the server encoding as they
+    /// are, without any nul in them.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn check_server_encoding(&self) -> io::Result<()>;
+
+    /// Converts this string from UTF-8 into the server encoding, failing
+    /// with [`ErrorKind::InvalidData`] if it is not UTF-8, or has characters
+    /// the server encoding does not.
+    ///
+    /// This borrows the bytes if they need no conversion.
+    ///
+    /// [`ErrorKind::InvalidData`]: crate::io::ErrorKind::InvalidData
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn to_server_encoding(&self) -> io::Result<Cow<'_, [u8]>>;
+
+    /// Turns these bytes into text that [`OsStringExt::from_escaped_text`]
+    /// turns back into the very same bytes: whatever is not UTF-8, as well
+    /// as nul, becomes `\xHH`, and a backslash becomes `\\`.
+    ///
+    /// This borrows the bytes if there is nothing to escape.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(postgres_ext)]
+    /// use std::ffi::OsStr;
+    /// use std::os::postgres::ffi::OsStrExt as _;
+    /// use std::os::unix::ffi::OsStrExt;
+    ///
+    /// let path = OsStr::from_bytes(b"caf\xe9\\menu.txt");
+    /// assert_eq!(path.to_escaped_text(), r"caf\xe9\\menu.txt");
+    /// ```
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn to_escaped_text(&self) -> Cow<'_, str>;
+
+    /// Turns these bytes into text in the server encoding, which
+    /// [`OsStringExt::from_text_escaped`] turns back into the very same
+    /// bytes, for storing a path or the like in a text column.
+    ///
+    /// This escapes like [`to_escaped_text`] does, and escapes the UTF-8
+    /// bytes of every character the server encoding does not have as well.
+    /// It only fails outside of a backend.
+    ///
+    /// [`to_escaped_text`]: OsStrExt::to_escaped_text
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(postgres_ext)]
+    /// use std::ffi::{OsStr, OsString};
+    /// use std::os::postgres::ffi::{OsStrExt as _, OsStringExt as _};
+    /// use std::os::unix::ffi::OsStrExt;
+    ///
+    /// // In a `LATIN1` database, where the `€` does not fit.
+    /// let path = OsStr::from_bytes(b"caf\xc3\xa9-\xe2\x82\xac5-\xff");
+    /// let text = path.to_text_lossy_escaped()?;
+    /// assert_eq!(&*text, b"caf\xe9-\\xe2\\x82\\xac5-\\xff");
+    /// assert_eq!(OsString::from_text_escaped(&text)?, path);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn to_text_lossy_escaped(&self) -> io::Result<Cow<'_, [u8]>>;
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl OsStrExt for OsStr {
+    fn check_server_encoding(&self) -> io::Result<()> {
+        encoding::verify(self.as_bytes())
+    }
+
+    fn to_server_encoding(&self) -> io::Result<Cow<'_, [u8]>> {
+        match self.to_str() {
+            Some(s) => encoding::from_utf8(s),
+            None => Err(io::const_io_error!(
+                io::ErrorKind::InvalidData,
+                "string to convert is not valid unicode",
+            )),
+        }
+    }
+
+    fn to_escaped_text(&self) -> Cow<'_, str> {
+        escape(self.as_bytes(), |_| true)
+    }
+
+    fn to_text_lossy_escaped(&self) -> io::Result<Cow<'_, [u8]>> {
+        let text = self.to_escaped_text();
+        if !encoding::server_is_utf8()? {
+            match encoding::from_utf8(&text) {
+                Ok(Cow::Borrowed(_)) => {}
+                Ok(Cow::Owned(converted)) => return Ok(Cow::Owned(converted)),
+                Err(_) => {
+                    // Some character does not fit: find out which, one at a
+                    // time.
+                    let text = escape(self.as_bytes(), |c| {
+                        c.is_ascii() || encoding::from_utf8(c.encode_utf8(&mut [0; 4])).is_ok()
+                    });
+                    return encoding::from_utf8(&text).map(|c| Cow::Owned(c.into_owned()));
+                }
+            }
+        }
+        Ok(match text {
+            Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+            Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+        })
+    }
+}
+
+/// Escapes `bytes` for [`OsStrExt::to_escaped_text`], along with the UTF-8
+/// bytes of every character that is not `fits`.
+fn escape(mut bytes: &[u8], mut fits: impl FnMut(char) -> bool) -> Cow<'_, str> {
+    if let Ok(s) = str::from_utf8(bytes) {
+        if !s.contains(['\\', '\0']) && s.chars().all(&mut fits) {
+            return Cow::Borrowed(s);
+        }
+    }
+    let mut text = String::with_capacity(bytes.len());
+    loop {
+        let (valid, rest) = match str::from_utf8(bytes) {
+            Ok(valid) => (valid, &[][..]),
+            Err(e) => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                // Checked just now.
+                (unsafe { str::from_utf8_unchecked(valid) }, rest)
+            }
+        };
+        for c in valid.chars() {
+            match c {
+                '\\' => text.push_str("\\\\"),
+                '\0' => text.push_str("\\x00"),
+                c if fits(c) => text.push(c),
+                c => {
+                    for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                        let _ = write!(text, "\\x{byte:02x}");
+                    }
+                }
+            }
+        }
+        let Some((&byte, rest)) = rest.split_first() else { break };
+        let _ = write!(text, "\\x{byte:02x}");
+        bytes = rest;
+    }
+    Cow::Owned(text)
+}
+
+/// Postgres-specific extensions to [`OsString`].
+///
+/// This trait is sealed: it cannot be implemented outside the standard library.
+/// This is so that future additional methods are not breaking changes.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub trait OsStringExt: Sealed {
+    /// Converts `bytes` from the server encoding into a UTF-8 string,
+    /// failing with [`ErrorKind::InvalidData`] if they are not valid text in
+    /// the server encoding.
+    ///
+    /// [`ErrorKind::InvalidData`]: crate::io::ErrorKind::InvalidData
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn from_server_encoding(bytes: &[u8]) -> io::Result<Self>
+    where
+        Self: Sized;
+
+    /// Turns text made by [`OsStrExt::to_escaped_text`] back into the bytes
+    /// it was made of, failing with [`ErrorKind::InvalidData`] on a
+    /// backslash that is not followed by another one or by `xHH`.
+    ///
+    /// [`ErrorKind::InvalidData`]: crate::io::ErrorKind::InvalidData
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn from_escaped_text(text: &str) -> io::Result<Self>
+    where
+        Self: Sized;
+
+    /// Turns text in the server encoding made by
+    /// [`OsStrExt::to_text_lossy_escaped`] back into the bytes it was made
+    /// of, failing with [`ErrorKind::InvalidData`] if it is not valid in the
+    /// server encoding, or on a backslash that is not followed by another
+    /// one or by `xHH`.
+    ///
+    /// [`ErrorKind::InvalidData`]: crate::io::ErrorKind::InvalidData
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn from_text_escaped(bytes: &[u8]) -> io::Result<Self>
+    where
+        Self: Sized;
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl OsStringExt for OsString {
+    fn from_server_encoding(bytes: &[u8]) -> io::Result<OsString> {
+        encoding::to_utf8(bytes).map(|s| OsString::from(s.into_owned()))
+    }
+
+    fn from_escaped_text(text: &str) -> io::Result<OsString> {
+        let mut bytes = Vec::with_capacity(text.len());
+        let mut rest = text.as_bytes();
+        while let Some(i) = rest.iter().position(|&b| b == b'\\') {
+            bytes.extend_from_slice(&rest[..i]);
+            rest = &rest[i + 1..];
+            let hex_digit = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+            let (byte, len) = match *rest {
+                [b'\\', ..] => (b'\\', 1),
+                [b'x', hi, lo, ..] => match (hex_digit(hi), hex_digit(lo)) {
+                    (Some(hi), Some(lo)) => (hi << 4 | lo, 3),
+                    _ => return Err(invalid_escape()),
+                },
+                _ => return Err(invalid_escape()),
+            };
+            bytes.push(byte);
+            rest = &rest[len..];
+        }
+        bytes.extend_from_slice(rest);
+        Ok(OsString::from_vec(bytes))
+    }
+
+    fn from_text_escaped(bytes: &[u8]) -> io::Result<OsString> {
+        OsString::from_escaped_text(&encoding::to_utf8(bytes)?)
+    }
+}
+
+fn invalid_escape() -> io::Error {
+    io::const_io_error!(io::ErrorKind::InvalidData, "invalid escape in escaped text")
+}
+
+impl Sealed for CString {}
+impl Sealed for CStr {}
+
+/// Postgres-specific extensions to [`CString`].
+///
+/// This trait is sealed: it cannot be implemented outside the standard library.
+/// This is so that future additional methods are not breaking changes.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::ffi::CString;
+/// use std::io::ErrorKind;
+/// use std::os::postgres::ffi::CStringExt;
+///
+/// let name = CString::from_text("café")?;
+///
+/// let err = CString::from_text("caf\0é").unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::InvalidInput);
+/// assert_eq!(err.to_string(), "nul byte found in provided data at position: 3");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub trait CStringExt: Sealed {
+    /// Makes a C string of `bytes`, which are to be text in the server
+    /// encoding already.
+    ///
+    /// This fails with [`ErrorKind::InvalidInput`] if there is a nul in
+    /// `bytes`, with a [`NulError`] telling where, and with
+    /// [`ErrorKind::InvalidData`] if they are not valid in the server
+    /// encoding, with postgres's own error naming the offending bytes.
+    ///
+    /// [`ErrorKind::InvalidInput`]: crate::io::ErrorKind::InvalidInput
+    /// [`ErrorKind::InvalidData`]: crate::io::ErrorKind::InvalidData
+    /// [`NulError`]: crate::ffi::NulError
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn from_server_text<T: Into<Vec<u8>>>(bytes: T) -> io::Result<Self>
+    where
+        Self: Sized;
+
+    /// Makes a C string of `s`, converted from UTF-8 into the server
+    /// encoding, failing like [`from_server_text`] does, or with
+    /// [`ErrorKind::InvalidData`] if `s` has characters the server encoding
+    /// does not.
+    ///
+    /// [`from_server_text`]: CStringExt::from_server_text
+    /// [`ErrorKind::InvalidData`]: crate::io::ErrorKind::InvalidData
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn from_text(s: &str) -> io::Result<Self>
+    where
+        Self: Sized;
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl CStringExt for CString {
+    fn from_server_text<T: Into<Vec<u8>>>(bytes: T) -> io::Result<CString> {
+        let s = CString::new(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        s.check_server_encoding()?;
+        Ok(s)
+    }
+
+    fn from_text(s: &str) -> io::Result<CString> {
+        // Before converting, so that the position of a nul is where it is
+        // in `s`.
+        if let Err(e) = CString::new(s) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+        }
+        let bytes = encoding::from_utf8(s)?.into_owned();
+        // Converted text has no nul in it, as there was none to convert.
+        Ok(unsafe { CString::from_vec_unchecked(bytes) })
+    }
+}
+
+/// Postgres-specific extensions to [`CStr`].
+///
+/// This trait is sealed: it cannot be implemented outside the standard library.
+/// This is so that future additional methods are not breaking changes.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub trait CStrExt: Sealed {
+    /// Checks that this string is valid text in the server encoding, failing
+    /// with [`ErrorKind::InvalidData`] and postgres's own error, which names
+    /// the offending bytes, if it is not.
+    ///
+    /// [`ErrorKind::InvalidData`]: crate::io::ErrorKind::InvalidData
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn check_server_encoding(&self) -> io::Result<()>;
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl CStrExt for CStr {
+    fn check_server_encoding(&self) -> io::Result<()> {
+        encoding::verify(self.to_bytes())
+    }
+}
+
+/// A nul-terminated string palloc'd in a postgres memory context, to hand
+/// to postgres.
+///
+/// This is like a [`CString`], save for where its bytes are: postgres can
+/// keep the pointer for as long as the memory context is around, or
+/// `pfree` it, which it cannot do with a `CString`'s. Until it is handed
+/// over with [`into_raw`], the string is freed on drop, like a `CString`.
+///
+/// [`into_raw`]: CtxCString::into_raw
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::ffi::CtxCString;
+/// use std::os::postgres::memory::MemoryContext;
+///
+/// extern "C" {
+///     // Keeps `value` until the end of the transaction.
+///     fn remember_for_transaction(value: *mut std::ffi::c_char);
+/// }
+///
+/// let context = MemoryContext::top_transaction()?;
+/// // The transaction outlives the string, which is handed over right away.
+/// let value = unsafe { CtxCString::new_in(context, b"done")? };
+/// unsafe { remember_for_transaction(value.into_raw()) };
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct CtxCString {
+    ptr: NonNull<c_char>,
+    context: MemoryContext,
+}
+
+impl CtxCString {
+    /// Copies `bytes`, with a nul after them, into `context`.
+    ///
+    /// This fails with [`ErrorKind::InvalidInput`] if there is a nul in
+    /// `bytes`, with a [`NulError`] telling where, with
+    /// [`ErrorKind::OutOfMemory`] if `context` is out of memory, and with
+    /// [`ErrorKind::Unsupported`] outside of a backend.
+    ///
+    /// [`ErrorKind::InvalidInput`]: crate::io::ErrorKind::InvalidInput
+    /// [`ErrorKind::OutOfMemory`]: crate::io::ErrorKind::OutOfMemory
+    /// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+    /// [`NulError`]: crate::ffi::NulError
+    ///
+    /// # Safety
+    ///
+    /// `context` is not reset or deleted while the string is around, that
+    /// is, before it is dropped or handed over with
+    /// [`into_raw`](CtxCString::into_raw).
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn new_in(context: MemoryContext, bytes: &[u8]) -> io::Result<CtxCString> {
+        if bytes.contains(&0) {
+            let nul = CString::new(bytes).unwrap_err();
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, nul));
+        }
+        let ptr = unsafe { mcxt::alloc(context.as_ptr(), bytes.len() + 1)? };
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+            *ptr.add(bytes.len()) = 0;
+        }
+        Ok(CtxCString { ptr: unsafe { NonNull::new_unchecked(ptr.cast()) }, context })
+    }
+
+    /// Copies `s` into `context`, failing like [`new_in`] does, save for the
+    /// nul.
+    ///
+    /// [`new_in`]: CtxCString::new_in
+    ///
+    /// # Safety
+    ///
+    /// The same as for [`new_in`].
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn from_c_str_in(context: MemoryContext, s: &CStr) -> io::Result<CtxCString> {
+        unsafe { CtxCString::new_in(context, s.to_bytes()) }
+    }
+
+    /// Returns the memory context the string is in.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn context(&self) -> MemoryContext {
+        self.context
+    }
+
+    /// Hands the string over to postgres: it is not freed on drop, but with
+    /// its memory context, or by whatever calls `pfree` on it.
+    ///
+    /// The pointer is only good for as long as the memory context is
+    /// around, and not at all after a `pfree`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    #[must_use = "`self` will be dropped if the result is not used"]
+    pub fn into_raw(self) -> *mut c_char {
+        let ptr = self.ptr.as_ptr();
+        unsafe { mcxt::release(self.context.as_ptr(), ptr.cast(), self.to_bytes_with_nul().len()) };
+        crate::mem::forget(self);
+        ptr
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Deref for CtxCString {
+    type Target = CStr;
+
+    fn deref(&self) -> &CStr {
+        unsafe { CStr::from_ptr(self.ptr.as_ptr()) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl AsRef<CStr> for CtxCString {
+    fn as_ref(&self) -> &CStr {
+        self
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for CtxCString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Drop for CtxCString {
+    fn drop(&mut self) {
+        unsafe {
+            mcxt::free(
+                self.context.as_ptr(),
+                self.ptr.as_ptr().cast(),
+                self.to_bytes_with_nul().len(),
+            )
+        }
+    }
+}
+
+/// Formats `fmt` with `args` into a [`CString`], like `vsprintf` does, but
+/// with postgres's own `printf`, which knows `%m` too.
+///
+/// This is for implementing or wrapping variadic C functions, like a
+/// `printf`-style hook, which take their arguments as a [`VaList`]. It
+/// fails with [`ErrorKind::Unsupported`] outside of a backend.
+///
+/// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+///
+/// # Safety
+///
+/// `args` are what `fmt` calls for, as for `vsprintf`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(c_variadic, postgres_ext)]
+/// use std::ffi::{c_char, CStr, VaList};
+/// use std::os::postgres::ffi::format_va_list;
+///
+/// // A `va_list` taking variant of some logging function.
+/// #[no_mangle]
+/// pub unsafe extern "C" fn my_extension_vlog(fmt: *const c_char, args: VaList<'_, '_>) {
+///     let line = unsafe { format_va_list(CStr::from_ptr(fmt), args) }.unwrap();
+///     // ...
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub unsafe fn format_va_list(fmt: &CStr, args: VaList<'_, '_>) -> io::Result<CString> {
+    let info = string_info::make()?;
+    let formatted = unsafe { string_info::append_va(info, fmt, args) }.map(|()| {
+        // Formatted with `%s` and the like, which stop at a nul.
+        unsafe { CStr::from_ptr((*info).data) }.to_owned()
+    });
+    unsafe { string_info::free(info) };
+    formatted
+}
@@ -0,0 +1,81 @@
+use super::{escape, format_va_list, OsStrExt, OsStringExt};
+use crate::borrow::Cow;
+use crate::ffi::{c_char, c_int, CStr, CString, OsStr, OsString};
+use crate::io;
+use crate::os::unix::ffi::OsStrExt as _;
+
+#[test]
+fn escaped_text_round_trip() {
+    let cases: &[&[u8]] = &[
+        b"",
+        b"plain.txt",
+        "café/menü".as_bytes(),
+        b"caf\xe9\\menu.txt",
+        b"nul\0in the middle",
+        b"\\\\x41",
+        b"\xff\xfe\xfd",
+        b"trailing \xc3",
+        b"\\",
+    ];
+    for &bytes in cases {
+        let text = OsStr::from_bytes(bytes).to_escaped_text();
+        assert_eq!(OsString::from_escaped_text(&text).unwrap().as_bytes(), bytes, "{text:?}");
+    }
+}
+
+#[test]
+fn escaped_text() {
+    fn escaped(bytes: &[u8]) -> Cow<'_, str> {
+        OsStr::from_bytes(bytes).to_escaped_text()
+    }
+    assert!(matches!(escaped(b"nothing to escape"), Cow::Borrowed("nothing to escape")));
+    assert_eq!(escaped(b"caf\xe9\\menu.txt"), r"caf\xe9\\menu.txt");
+    assert_eq!(escaped(b"a\0b"), r"a\x00b");
+    assert_eq!(escaped("ünïcode".as_bytes()), "ünïcode");
+}
+
+#[test]
+fn escape_what_does_not_fit() {
+    let text = escape("caf\u{e9}-\u{20ac}5-".as_bytes(), |c| c != '\u{20ac}');
+    assert_eq!(text, "caf\u{e9}-\\xe2\\x82\\xac5-");
+    assert_eq!(OsString::from_escaped_text(&text).unwrap(), "caf\u{e9}-\u{20ac}5-");
+
+    let text = escape(b"ascii \xc3\xa9 \xff", |c| c.is_ascii());
+    assert_eq!(text, r"ascii \xc3\xa9 \xff");
+    assert_eq!(OsString::from_escaped_text(&text).unwrap().as_bytes(), b"ascii \xc3\xa9 \xff");
+}
+
+#[test]
+fn invalid_escapes() {
+    for text in [r"\", r"trailing \", r"\q", r"\x4", r"\xg0", r"\X41"] {
+        let err = OsString::from_escaped_text(text).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{text:?}");
+    }
+}
+
+/// A C-variadic function handing its arguments to `format_va_list`, the way
+/// a `printf`-style hook would.
+unsafe extern "C" fn format_variadic(
+    out: *mut io::Result<CString>,
+    fmt: *const c_char,
+    mut args: ...
+) {
+    let formatted = unsafe { format_va_list(CStr::from_ptr(fmt), args.as_va_list()) };
+    unsafe { out.write(formatted) };
+}
+
+fn format(fmt: &[u8], name: &CStr, n: c_int) -> io::Result<CString> {
+    let mut out = Ok(CString::default());
+    unsafe { format_variadic(&mut out, fmt.as_ptr().cast(), name.as_ptr(), n, n) };
+    out
+}
+
+#[test]
+fn format_through_va_list() {
+    let name = CStr::from_bytes_with_nul(b"answer\0").unwrap();
+    match format(b"%s = %d (%#06x), 100%%\0", name, 42) {
+        Ok(s) => assert_eq!(s.to_bytes(), b"answer = 42 (0x002a), 100%"),
+        // Only postgres's `printf` is used, which is only there in a backend.
+        Err(e) => assert_eq!(e.kind(), io::ErrorKind::Unsupported),
+    }
+}
@@ -0,0 +1,158 @@
+//! Keyed hashing, and hashing with reproducible iteration orders.
+//!
+//! [`SipHasher13`] and [`SipHasher24`] hash with SipHash keyed with two
+//! `u64`s of the caller's, for hashes that stay the same from one backend,
+//! server or version to the next, as partitioning or a bloom filter stored
+//! in a table need, yet cannot be predicted without the keys.
+//!
+//! Every [`HashMap`] normally gets its own random keys, so the order it
+//! iterates in changes from one run to the next, which is what keeps
+//! crafted input from degrading it. Regression tests comparing the output
+//! of functions that iterate over a map then need sorting all over the
+//! place: instead, the embedding runtime can fix the keys while running
+//! them, for instance after a setting of its own.
+//!
+//! [`HashMap`]: crate::collections::HashMap
+//!
+//! # Examples
+//!
+//! ```no_run
+//! #![feature(postgres_ext)]
+//! use std::collections::HashSet;
+//! use std::os::postgres::hash;
+//!
+//! hash::set_fixed_keys(Some((0, 0)));
+//! let a: Vec<_> = HashSet::<_>::from_iter(1..100).into_iter().collect();
+//! let b: Vec<_> = HashSet::<_>::from_iter(1..100).into_iter().collect();
+//! assert_eq!(a, b);
+//! hash::set_fixed_keys(None);
+//! ```
+
+#![allow(deprecated)]
+
+use crate::fmt;
+use crate::hash::{self, Hasher};
+use crate::sys::rand;
+
+/// SipHash-1-3 with keys of your own, the hash function of [`HashMap`].
+///
+/// The output is the same on every platform and version of Rust, for the
+/// same keys and input written with [`Hasher::write`]. The other methods of
+/// [`Hasher`] write integers in native endianness.
+///
+/// [`HashMap`]: crate::collections::HashMap
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::hash::Hasher;
+/// use std::os::postgres::hash::SipHasher13;
+///
+/// fn partition(key: &[u8], partitions: u64) -> u64 {
+///     let mut hasher = SipHasher13::new_with_keys(0x5eed, 0xcafe);
+///     hasher.write(key);
+///     hasher.finish() % partitions
+/// }
+/// ```
+#[derive(Clone, Default)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct SipHasher13(hash::SipHasher13);
+
+impl SipHasher13 {
+    /// Creates a hasher keyed with `key0` and `key1`.
+    #[must_use]
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new_with_keys(key0: u64, key1: u64) -> SipHasher13 {
+        SipHasher13(hash::SipHasher13::new_with_keys(key0, key1))
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Hasher for SipHasher13 {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes)
+    }
+
+    #[inline]
+    fn write_str(&mut self, s: &str) {
+        self.0.write_str(s)
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for SipHasher13 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SipHasher13").finish_non_exhaustive()
+    }
+}
+
+/// SipHash-2-4 with keys of your own, the original SipHash, as most other
+/// implementations have it.
+///
+/// The output is the same on every platform and version of Rust, for the
+/// same keys and input written with [`Hasher::write`]. The other methods of
+/// [`Hasher`] write integers in native endianness.
+#[derive(Clone, Default)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct SipHasher24(hash::SipHasher);
+
+impl SipHasher24 {
+    /// Creates a hasher keyed with `key0` and `key1`.
+    #[must_use]
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new_with_keys(key0: u64, key1: u64) -> SipHasher24 {
+        SipHasher24(hash::SipHasher::new_with_keys(key0, key1))
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Hasher for SipHasher24 {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes)
+    }
+
+    #[inline]
+    fn write_str(&mut self, s: &str) {
+        self.0.write_str(s)
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for SipHasher24 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SipHasher24").finish_non_exhaustive()
+    }
+}
+
+/// Makes every [`RandomState`] created from now on have the keys `keys`,
+/// rather than random ones, or with `None`, go back to random keys, which is
+/// the default.
+///
+/// Only use this for testing: with fixed keys, anyone who knows them can
+/// craft input that makes a [`HashMap`] slow to a crawl.
+///
+/// [`RandomState`]: crate::collections::hash_map::RandomState
+/// [`HashMap`]: crate::collections::HashMap
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn set_fixed_keys(keys: Option<(u64, u64)>) {
+    rand::set_fixed_hash_keys(keys)
+}
+
+/// The keys set with [`set_fixed_keys`], if any.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn fixed_keys() -> Option<(u64, u64)> {
+    rand::fixed_hash_keys()
+}
@@ -0,0 +1,55 @@
+//! The clock postgres times its own instrumentation with.
+//!
+//! `EXPLAIN ANALYZE`, `track_io_timing` and `pg_stat_statements` all read
+//! `INSTR_TIME_SET_CURRENT`. Timings taken with an [`InstrTime`] are on the
+//! same clock, to compare with theirs, and to benchmark code in a backend.
+
+use crate::fmt;
+use crate::sys::time;
+use crate::time::Duration;
+
+/// A reading of postgres's `instr_time` clock, which is monotonic.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::error::log;
+/// use std::os::postgres::instr_time::InstrTime;
+///
+/// let start = InstrTime::now();
+/// let sum: u64 = (0..1_000_000u64).sum();
+/// log(format_args!("{sum} in {:?}", start.elapsed()))?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct InstrTime(time::InstrTime);
+
+impl InstrTime {
+    /// Reads the clock, like `INSTR_TIME_SET_CURRENT`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn now() -> InstrTime {
+        InstrTime(time::InstrTime::now())
+    }
+
+    /// The time elapsed from `earlier` to `self`, or zero if `earlier` is
+    /// later, like `INSTR_TIME_SUBTRACT`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn duration_since(&self, earlier: InstrTime) -> Duration {
+        self.0.checked_sub(&earlier.0).unwrap_or_default()
+    }
+
+    /// The time elapsed since this reading.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn elapsed(&self) -> Duration {
+        InstrTime::now().duration_since(*self)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for InstrTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstrTime").finish_non_exhaustive()
+    }
+}
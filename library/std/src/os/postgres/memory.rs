@@ -0,0 +1,186 @@
+//! Postgres memory contexts.
+//!
+//! Postgres allocates nearly everything with `palloc`, in a memory context
+//! that frees all of it at once when it is reset or deleted: at the end of
+//! the function call, the statement, the transaction, and so on. Memory
+//! handed to postgres has to be in the context it expects it in, and must
+//! not be used, or freed, once that context is gone.
+//!
+//! A [`MemoryContext`] names one, for allocating in it, like with
+//! [`CtxCString`](super::ffi::CtxCString), or with a [`CtxAlloc`], for
+//! collections that take an allocator, like `HashMap::new_in`.
+
+use crate::alloc::{AllocError, Allocator, Layout};
+use crate::ffi::c_void;
+use crate::fmt;
+use crate::io;
+use crate::ptr::NonNull;
+use crate::sys::mcxt;
+
+/// A postgres memory context.
+///
+/// This is a handle to a context postgres owns, which it may reset or
+/// delete at any time, as far as the handle is concerned: it does not keep
+/// the context around.
+#[unstable(feature = "postgres_ext", issue = "none")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct MemoryContext {
+    ptr: NonNull<c_void>,
+}
+
+impl MemoryContext {
+    /// Returns `CurrentMemoryContext`, the context `palloc` allocates in.
+    ///
+    /// This, like the other contexts, fails with [`ErrorKind::Unsupported`]
+    /// outside of a backend.
+    ///
+    /// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn current() -> io::Result<MemoryContext> {
+        mcxt::current().map(MemoryContext::wrap)
+    }
+
+    /// Returns `TopMemoryContext`, which lasts as long as the backend.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn top() -> io::Result<MemoryContext> {
+        mcxt::top().map(MemoryContext::wrap)
+    }
+
+    /// Returns `TopTransactionContext`, which lasts until the end of the
+    /// current transaction, and fails outside of one.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn top_transaction() -> io::Result<MemoryContext> {
+        mcxt::top_transaction().map(MemoryContext::wrap)
+    }
+
+    /// Returns `CurTransactionContext`, which lasts until the end of the
+    /// current subtransaction, or until the end of the transaction if the
+    /// subtransaction commits, and fails outside of a transaction.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn cur_transaction() -> io::Result<MemoryContext> {
+        mcxt::cur_transaction().map(MemoryContext::wrap)
+    }
+
+    /// Wraps a `MemoryContext` pointer from postgres, or returns `None` if
+    /// it is null.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` is null or a valid memory context.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn from_ptr(ptr: *mut c_void) -> Option<MemoryContext> {
+        NonNull::new(ptr).map(|ptr| MemoryContext { ptr })
+    }
+
+    /// Returns the `MemoryContext` pointer.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn as_ptr(self) -> *mut c_void {
+        self.ptr.as_ptr()
+    }
+
+    fn wrap(ptr: *mut c_void) -> MemoryContext {
+        // `mcxt` checks for null.
+        MemoryContext { ptr: unsafe { NonNull::new_unchecked(ptr) } }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for MemoryContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MemoryContext").field(&self.ptr).finish()
+    }
+}
+
+/// An [`Allocator`] that pallocs in a memory context, for collections that
+/// should go away with it, like a hash table for one query.
+///
+/// What it allocates is freed when dropped, like with the global allocator,
+/// and all at once if the context is reset or deleted first, which nothing
+/// that allocated it may be used, or dropped, after: leak it, with
+/// [`mem::forget`](crate::mem::forget), once the context is about to go.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext, allocator_api)]
+/// use std::collections::HashMap;
+/// use std::os::postgres::memory::{CtxAlloc, MemoryContext};
+///
+/// let context = MemoryContext::current()?;
+/// // Dropped before the function returns, and the context with it.
+/// let mut seen = HashMap::new_in(unsafe { CtxAlloc::new(context) });
+/// seen.insert("key", 1);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CtxAlloc {
+    context: MemoryContext,
+}
+
+impl CtxAlloc {
+    /// Returns an allocator for `context`.
+    ///
+    /// # Safety
+    ///
+    /// `context` is not reset or deleted while what was allocated with this
+    /// allocator, or a copy of it, is in use, that is, before it is dropped
+    /// or leaked.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn new(context: MemoryContext) -> CtxAlloc {
+        CtxAlloc { context }
+    }
+
+    /// Returns the memory context it allocates in.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn context(&self) -> MemoryContext {
+        self.context
+    }
+}
+
+/// How much to palloc for `layout`. palloc only aligns to
+/// `MAXIMUM_ALIGNOF`, so more than that is got by allocating more, and
+/// keeping the pointer palloc returned right before the memory, where there
+/// is always room for it.
+fn palloc_size(layout: Layout) -> Option<usize> {
+    if layout.align() <= mcxt::MAXIMUM_ALIGNOF {
+        Some(layout.size())
+    } else {
+        layout.size().checked_add(layout.align())
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl Allocator for CtxAlloc {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(NonNull::slice_from_raw_parts(layout.dangling(), 0));
+        }
+        let size = palloc_size(layout).ok_or(AllocError)?;
+        let ptr = unsafe { mcxt::alloc(self.context.as_ptr(), size) }.map_err(|_| AllocError)?;
+        let ptr = if layout.align() <= mcxt::MAXIMUM_ALIGNOF {
+            ptr
+        } else {
+            // palloc's alignment leaves at least `MAXIMUM_ALIGNOF` bytes,
+            // which a pointer fits in, before the aligned memory.
+            let aligned = unsafe { ptr.add(layout.align() - ptr.addr() % layout.align()) };
+            unsafe { aligned.cast::<*mut u8>().sub(1).write_unaligned(ptr) };
+            aligned
+        };
+        Ok(NonNull::slice_from_raw_parts(unsafe { NonNull::new_unchecked(ptr) }, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() == 0 {
+            return;
+        }
+        let ptr = if layout.align() <= mcxt::MAXIMUM_ALIGNOF {
+            ptr.as_ptr()
+        } else {
+            unsafe { ptr.as_ptr().cast::<*mut u8>().sub(1).read_unaligned() }
+        };
+        // It was allocated, so its size did not overflow.
+        let size = palloc_size(layout).unwrap_or(0);
+        unsafe { mcxt::free(self.context.as_ptr(), ptr, size) }
+    }
+}
@@ -0,0 +1,194 @@
+//! Postgres-specific extensions to `std`.
+//!
+//! These are only available when building for one of the `postgrestd`
+//! targets, i.e. when `target_family = "postgres"`.
+
+#![unstable(feature = "postgres_ext", issue = "none")]
+#![doc(cfg(target_family = "postgres"))]
+
+pub mod abi;
+pub mod capability;
+pub mod collections;
+pub mod debug;
+pub mod error;
+pub mod ffi;
+pub mod hash;
+pub mod instr_time;
+pub mod memory;
+pub mod mpsc;
+pub mod net;
+pub mod nonpoison;
+pub mod panic;
+pub mod parallel;
+pub mod random;
+pub mod string_info;
+pub mod sync;
+pub mod sysinfo;
+pub mod varlena;
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub use self::error::{catch_pg_error, ensure_drop, guard, pg_error, subtransaction, PgError};
+
+/// Returns an error if the backend has been asked to cancel the running
+/// statement or to terminate, i.e. if postgres's `CHECK_FOR_INTERRUPTS()`
+/// would raise an error right now.
+///
+/// Call this regularly from long-running loops that never block or call
+/// into postgres, so that `pg_cancel_backend()` and `statement_timeout` can
+/// stop them. Blocking operations in `std` do this on their own, and so do
+/// [`thread::yield_now`] and [`thread::sleep`], which panic with the
+/// interrupt instead.
+///
+/// The interrupt stays pending, and is serviced (raising the actual `ERROR`
+/// or `FATAL`) at postgres's next `CHECK_FOR_INTERRUPTS()`: return the error
+/// to postgres as soon as possible. Nothing is reported while interrupts are
+/// held off, and outside of a backend this always succeeds.
+///
+/// [`thread::yield_now`]: crate::thread::yield_now
+/// [`thread::sleep`]: crate::thread::sleep
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::check_interrupt;
+///
+/// fn sum_of_squares(n: u64) -> std::io::Result<u64> {
+///     let mut sum = 0u64;
+///     for i in 0..n {
+///         if i % 65536 == 0 {
+///             check_interrupt()?;
+///         }
+///         sum = sum.wrapping_add(i * i);
+///     }
+///     Ok(sum)
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn check_interrupt() -> crate::io::Result<()> {
+    crate::sys::pg::check_interrupts()
+}
+
+/// Returns how many more bytes of stack this backend may use before
+/// exceeding the `max_stack_depth` setting, or `None` outside of a backend.
+///
+/// Postgres only checks the stack depth in its own recursive functions, and
+/// Rust code overflowing the stack crashes the whole server. Deeply
+/// recursive code should check this, or [`check_stack_depth`], to bail out
+/// with an error instead.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn remaining_stack() -> Option<usize> {
+    crate::sys::pg::remaining_stack()
+}
+
+/// Returns an error if the stack has grown beyond the `max_stack_depth`
+/// setting, like postgres's `check_stack_depth()` would raise one.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::check_stack_depth;
+///
+/// enum Expr {
+///     Leaf(i64),
+///     Add(Box<Expr>, Box<Expr>),
+/// }
+///
+/// fn eval(expr: &Expr) -> std::io::Result<i64> {
+///     check_stack_depth()?;
+///     Ok(match expr {
+///         Expr::Leaf(n) => *n,
+///         Expr::Add(a, b) => eval(a)? + eval(b)?,
+///     })
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn check_stack_depth() -> crate::io::Result<()> {
+    crate::sys::pg::check_stack_depth()
+}
+
+/// Returns the PID of this backend, i.e. postgres's `MyProcPid`, or `None`
+/// outside of a backend.
+///
+/// This is also part of the name of [`thread::current`], which reads
+/// `postgres:<database>:<pid>` inside a backend.
+///
+/// [`thread::current`]: crate::thread::current
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn backend_pid() -> Option<u32> {
+    crate::sys::pg::my_proc_pid()
+}
+
+/// Returns the `application_name` the client connected with, or `None`
+/// outside of a backend.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn application_name() -> Option<String> {
+    crate::sys::pg::config_option("application_name\0")
+}
+
+/// Runs `f` with `args` as the arguments [`env::args`] returns after the
+/// program name, and puts the previous ones back afterwards.
+///
+/// Inside a backend, [`env::args`] never returns the command line of the
+/// process, which is the postmaster's, with whatever options the server was
+/// started with. It is `postgres`, followed by the arguments set here: the
+/// embedding runtime can pass those of the function it is calling, for code
+/// written against [`env::args`] to pick up.
+///
+/// [`env::args`]: crate::env::args
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::env;
+/// use std::os::postgres::with_args;
+///
+/// let first = with_args(["--verbose"], || env::args().nth(1));
+/// assert_eq!(first.as_deref(), Some("--verbose"));
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn with_args<I, F, R>(args: I, f: F) -> R
+where
+    I: IntoIterator,
+    I::Item: Into<crate::ffi::OsString>,
+    F: FnOnce() -> R,
+{
+    struct Restore(Option<Vec<crate::ffi::OsString>>);
+
+    impl Drop for Restore {
+        // Also on unwind, for the arguments not to outlive the call.
+        fn drop(&mut self) {
+            if let Some(args) = self.0.take() {
+                crate::sys::args::replace(args);
+            }
+        }
+    }
+
+    let _restore =
+        Restore(Some(crate::sys::args::replace(args.into_iter().map(Into::into).collect())));
+    f()
+}
+
+/// Fills `buf` with cryptographically strong random bytes, for tokens,
+/// nonces, salts and keys, like `gen_random_bytes()` does.
+///
+/// This is postgres's `pg_strong_random()`, which reads from OpenSSL or the
+/// kernel, whichever postgres was built to use. Outside of a backend, the
+/// bytes come from the kernel.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::random_bytes;
+///
+/// let mut token = [0u8; 32];
+/// random_bytes(&mut token)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn random_bytes(buf: &mut [u8]) -> crate::io::Result<()> {
+    crate::sys::rand::strong_random(buf)
+}
@@ -0,0 +1,358 @@
+//! Channels between backends, over `shm_mq` message queues.
+//!
+//! These mirror [`std::sync::mpsc`], but connect two processes rather than
+//! two threads: a backend and a background worker it launched, for example.
+//! Messages are copied through dynamic shared memory, so they are encoded to
+//! bytes on the way in and decoded on the way out, through the [`Message`]
+//! trait.
+//!
+//! A channel is created by one backend, which keeps one end and passes the
+//! [`handle`](Sender::handle) to the other backend, typically as part of a
+//! worker's input. That backend then attaches to the opposite end. Each
+//! channel has exactly one sender and one receiver.
+//!
+//! For a stream of small, fixed-size values, [`Ring`] is a bounded channel
+//! laid out directly in shared memory the caller provides, with no encoding
+//! and no allocation per message.
+//!
+//! [`std::sync::mpsc`]: crate::sync::mpsc
+//!
+//! # Examples
+//!
+//! ```no_run
+//! #![feature(postgres_ext)]
+//! use std::os::postgres::mpsc::{Receiver, Sender};
+//! use std::os::postgres::parallel::{self, Worker, WorkerContext};
+//!
+//! // Leader: the worker streams results back as it goes.
+//! fn collect() -> std::io::Result<Vec<u64>> {
+//!     let rx = Receiver::<u64>::new()?;
+//!     parallel::scope(|s| {
+//!         let worker = Worker::new("my_extension", "produce").input(rx.handle().to_le_bytes());
+//!         let worker = s.launch(worker)?;
+//!         // Stop waiting if the worker dies before sending everything.
+//!         unsafe { rx.set_worker(&worker) };
+//!         Ok(rx.iter().collect())
+//!     })
+//! }
+//!
+//! // Worker.
+//! #[no_mangle]
+//! pub extern "C" fn produce(main_arg: usize) {
+//!     let (_ctx, input) = unsafe { WorkerContext::attach(main_arg) }.unwrap();
+//!     let handle = u32::from_le_bytes(input.try_into().unwrap());
+//!     let tx = unsafe { Sender::<u64>::attach(handle) }.unwrap();
+//!     for i in 0..100 {
+//!         tx.send(i * i).unwrap();
+//!     }
+//! }
+//! ```
+
+use crate::cell::RefCell;
+use crate::fmt;
+use crate::io;
+use crate::marker::PhantomData;
+use crate::os::postgres::parallel::WorkerHandle;
+use crate::ptr;
+use crate::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use crate::sys::channel as imp;
+use crate::sys::shm_mq::Role;
+use crate::time::{Duration, Instant};
+
+mod ring;
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub use self::ring::{Ring, RingReceiver, RingSender};
+
+/// A value that can be sent over a channel to another backend.
+///
+/// Implementations must round-trip: `decode` has to accept whatever
+/// `encode` produced, possibly in another process.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub trait Message: Sized {
+    /// Appends the encoding of `self` to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes a message, or returns `None` if `bytes` is malformed.
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Message for Vec<u8> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Message for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Message for bool {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(*self as u8);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0] => Some(false),
+            [1] => Some(true),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! int_message {
+    ($($t:ty)*) => {$(
+        #[unstable(feature = "postgres_ext", issue = "none")]
+        impl Message for $t {
+            fn encode(&self, buf: &mut Vec<u8>) {
+                buf.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(bytes: &[u8]) -> Option<Self> {
+                bytes.try_into().ok().map(<$t>::from_le_bytes)
+            }
+        }
+    )*};
+}
+
+int_message! { u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize }
+
+/// The sending half of a channel to another backend.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct Sender<T: Message> {
+    inner: RefCell<imp::End>,
+    _marker: PhantomData<fn(T)>,
+}
+
+/// The receiving half of a channel from another backend.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct Receiver<T: Message> {
+    inner: RefCell<imp::End>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Message> Sender<T> {
+    /// Creates a channel and returns its sending half. The receiving half is
+    /// attached to with [`Receiver::attach`], given [`handle`](Self::handle).
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new() -> io::Result<Sender<T>> {
+        let inner = imp::create(Role::Sender, ptr::null_mut())?;
+        Ok(Sender { inner: RefCell::new(inner), _marker: PhantomData })
+    }
+
+    /// Attaches to the sending half of the channel created by
+    /// [`Receiver::new`] with the given handle.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must come from [`Receiver::handle`], whose channel must
+    /// still be alive and must not have a sender attached yet.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn attach(handle: u32) -> io::Result<Sender<T>> {
+        let inner = imp::attach(handle, Role::Sender, ptr::null_mut())?;
+        Ok(Sender { inner: RefCell::new(inner), _marker: PhantomData })
+    }
+
+    /// The handle the other backend attaches to this channel with.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn handle(&self) -> u32 {
+        self.inner.borrow().handle()
+    }
+
+    /// Tells the channel that the other end belongs to `worker`, so that
+    /// blocking on it fails instead of hanging if the worker exits, or never
+    /// starts, before attaching.
+    ///
+    /// # Safety
+    ///
+    /// The channel keeps using the worker's `BackgroundWorkerHandle`, which
+    /// lives in the memory context that was current when the worker was
+    /// launched. That context must outlive this sender.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn set_worker(&self, worker: &WorkerHandle<'_>) {
+        self.inner.borrow_mut().set_worker(worker.as_ptr())
+    }
+
+    /// Sends a value to the receiving backend.
+    ///
+    /// This blocks while the queue is full, until the receiver has made room
+    /// for the whole message. Like [`mpsc::Sender::send`], it fails, giving
+    /// back `t`, if the receiver has hung up.
+    ///
+    /// [`mpsc::Sender::send`]: crate::sync::mpsc::Sender::send
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        let mut buf = Vec::new();
+        t.encode(&mut buf);
+        match self.inner.borrow_mut().send(&buf) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(SendError(t)),
+        }
+    }
+}
+
+impl<T: Message> Receiver<T> {
+    /// Creates a channel and returns its receiving half. The sending half is
+    /// attached to with [`Sender::attach`], given [`handle`](Self::handle).
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new() -> io::Result<Receiver<T>> {
+        let inner = imp::create(Role::Receiver, ptr::null_mut())?;
+        Ok(Receiver { inner: RefCell::new(inner), _marker: PhantomData })
+    }
+
+    /// Attaches to the receiving half of the channel created by
+    /// [`Sender::new`] with the given handle.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must come from [`Sender::handle`], whose channel must still
+    /// be alive and must not have a receiver attached yet.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn attach(handle: u32) -> io::Result<Receiver<T>> {
+        let inner = imp::attach(handle, Role::Receiver, ptr::null_mut())?;
+        Ok(Receiver { inner: RefCell::new(inner), _marker: PhantomData })
+    }
+
+    /// The handle the other backend attaches to this channel with.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn handle(&self) -> u32 {
+        self.inner.borrow().handle()
+    }
+
+    /// Tells the channel that the other end belongs to `worker`, so that
+    /// blocking on it fails instead of hanging if the worker exits, or never
+    /// starts, before attaching.
+    ///
+    /// # Safety
+    ///
+    /// The channel keeps using the worker's `BackgroundWorkerHandle`, which
+    /// lives in the memory context that was current when the worker was
+    /// launched. That context must outlive this receiver.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn set_worker(&self, worker: &WorkerHandle<'_>) {
+        self.inner.borrow_mut().set_worker(worker.as_ptr())
+    }
+
+    /// Blocks until a message arrives, or fails once the sender has hung up
+    /// and every message it sent has been received.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message cannot be decoded as a `T`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn recv(&self) -> Result<T, RecvError> {
+        match self.inner.borrow_mut().receive() {
+            Ok(msg) => Ok(decode(msg)),
+            Err(_) => Err(RecvError),
+        }
+    }
+
+    /// Returns a message if one is waiting, without blocking.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message cannot be decoded as a `T`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        match self.inner.borrow_mut().try_receive() {
+            Ok(Some(msg)) => Ok(decode(msg)),
+            Ok(None) => Err(TryRecvError::Empty),
+            Err(_) => Err(TryRecvError::Disconnected),
+        }
+    }
+
+    /// Like [`recv`](Self::recv), but gives up after `timeout`.
+    ///
+    /// The wait also ends, with [`RecvTimeoutError::Timeout`], when the
+    /// running statement times out or is cancelled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message cannot be decoded as a `T`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now().checked_add(timeout);
+        let result = match deadline {
+            Some(deadline) => self.inner.borrow_mut().receive_deadline(deadline),
+            None => self.inner.borrow_mut().receive().map(|msg| Some(msg.to_vec())),
+        };
+        match result {
+            Ok(Some(msg)) => Ok(decode(&msg)),
+            Ok(None) => Err(RecvTimeoutError::Timeout),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Err(RecvTimeoutError::Disconnected),
+            Err(_) => Err(RecvTimeoutError::Timeout),
+        }
+    }
+
+    /// Returns an iterator that blocks waiting for messages, and ends when
+    /// the sender hangs up.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { rx: self }
+    }
+}
+
+fn decode<T: Message>(msg: &[u8]) -> T {
+    T::decode(msg).expect("received a message that could not be decoded")
+}
+
+/// An iterator over messages on a [`Receiver`], created by
+/// [`Receiver::iter`].
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct Iter<'a, T: Message> {
+    rx: &'a Receiver<T>,
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<'a, T: Message> Iterator for Iter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<'a, T: Message> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Message> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Message> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Message> fmt::Debug for Iter<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Iter").finish_non_exhaustive()
+    }
+}
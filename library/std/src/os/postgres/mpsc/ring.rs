@@ -0,0 +1,307 @@
+use crate::cell::UnsafeCell;
+use crate::fmt;
+use crate::io;
+use crate::mem::MaybeUninit;
+use crate::ptr;
+use crate::sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst};
+use crate::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize};
+use crate::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+use crate::sys::pg;
+use crate::sys::spin::SpinLock;
+use crate::time::{Duration, Instant};
+use libc::c_void;
+
+/// Only the sender that started waiting last is woken up directly when room
+/// frees up; any others poll this often.
+const SENDER_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A bounded channel whose ring buffer of `N` messages lives in shared
+/// memory provided by the caller.
+///
+/// This is the shared-memory counterpart of [`sync_channel`]: senders block
+/// while the buffer is full, and the receiver blocks while it is empty, by
+/// sleeping on their latches. Messages are copied straight into the buffer,
+/// with no allocation per message, which makes this suited to streaming
+/// small, fixed-size values between a backend and a worker. For
+/// variable-size messages, see [`Sender`](super::Sender) and
+/// [`Receiver`](super::Receiver).
+///
+/// Any number of backends may send, through a [`RingSender`] each, and one
+/// backend receives, through the [`RingReceiver`].
+///
+/// Waiting is interruptible: if the statement is cancelled while waiting,
+/// the wait panics with the interrupt, to be turned into an `ERROR`.
+///
+/// [`sync_channel`]: crate::sync::mpsc::sync_channel
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::mpsc::Ring;
+///
+/// // In shared memory reachable from both backends, e.g. a DSM segment,
+/// // initialized with `ring.write(Ring::new())`.
+/// type Rows = Ring<(u64, f64), 1024>;
+///
+/// fn produce(ring: &Rows) -> std::io::Result<()> {
+///     let tx = ring.sender();
+///     for i in 0..10_000 {
+///         tx.send((i, i as f64 / 2.0)).unwrap();
+///     }
+///     Ok(())
+/// }
+///
+/// fn consume(ring: &Rows) -> std::io::Result<f64> {
+///     let rx = ring.receiver()?;
+///     Ok(rx.iter().map(|(_, x)| x).sum())
+/// }
+/// ```
+#[repr(C)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct Ring<T: Copy, const N: usize> {
+    /// Serializes senders.
+    send_lock: SpinLock,
+    /// Count of messages received; only the receiver moves it.
+    head: AtomicUsize,
+    /// Count of messages sent; only moved with `send_lock` held.
+    tail: AtomicUsize,
+    senders: AtomicUsize,
+    had_sender: AtomicBool,
+    receiver_gone: AtomicBool,
+    receiver_latch: AtomicPtr<c_void>,
+    sender_latch: AtomicPtr<c_void>,
+    slots: UnsafeCell<MaybeUninit<[T; N]>>,
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl<T: Copy + Send, const N: usize> Send for Ring<T, N> {}
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl<T: Copy + Send, const N: usize> Sync for Ring<T, N> {}
+
+/// The sending half of a [`Ring`].
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct RingSender<'a, T: Copy, const N: usize> {
+    ring: &'a Ring<T, N>,
+}
+
+/// The receiving half of a [`Ring`].
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct RingReceiver<'a, T: Copy, const N: usize> {
+    ring: &'a Ring<T, N>,
+}
+
+/// Sleeps on the latch until it is set or `deadline` passes.
+fn wait(deadline: Option<Instant>) {
+    if let Err(e) = pg::wait_latch(deadline) {
+        panic!("{e}")
+    }
+}
+
+impl<T: Copy, const N: usize> Ring<T, N> {
+    /// Creates an empty channel. To be shared, it has to be moved into shared
+    /// memory before any sender or receiver is created.
+    ///
+    /// `T` must be meaningful in every backend attached to that memory, so
+    /// it must not contain pointers or references.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub const fn new() -> Ring<T, N> {
+        assert!(N > 0, "a ring channel needs room for at least one message");
+        Ring {
+            send_lock: SpinLock::new(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            senders: AtomicUsize::new(0),
+            had_sender: AtomicBool::new(false),
+            receiver_gone: AtomicBool::new(false),
+            receiver_latch: AtomicPtr::new(ptr::null_mut()),
+            sender_latch: AtomicPtr::new(ptr::null_mut()),
+            slots: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Adds a sender to the channel.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn sender(&self) -> RingSender<'_, T, N> {
+        self.senders.fetch_add(1, Relaxed);
+        self.had_sender.store(true, Relaxed);
+        RingSender { ring: self }
+    }
+
+    /// Becomes the receiver of the channel.
+    ///
+    /// Fails with `AlreadyExists` if some backend has already done so, even
+    /// if it has since dropped its receiver, and with `Unsupported` outside
+    /// of a backend.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn receiver(&self) -> io::Result<RingReceiver<'_, T, N>> {
+        let latch = pg::my_latch()?;
+        match self.receiver_latch.compare_exchange(ptr::null_mut(), latch, SeqCst, Relaxed) {
+            Ok(_) => Ok(RingReceiver { ring: self }),
+            Err(_) => Err(io::const_io_error!(
+                io::ErrorKind::AlreadyExists,
+                "the channel already has a receiver",
+            )),
+        }
+    }
+
+    fn slot(&self, index: usize) -> *mut T {
+        unsafe { self.slots.get().cast::<T>().add(index % N) }
+    }
+
+    fn is_full(&self) -> bool {
+        self.tail.load(Relaxed).wrapping_sub(self.head.load(Acquire)) == N
+    }
+
+    fn wake(latch: &AtomicPtr<c_void>) {
+        let latch = latch.load(SeqCst);
+        if !latch.is_null() {
+            unsafe { pg::set_latch_of(latch) }
+        }
+    }
+}
+
+impl<T: Copy, const N: usize> RingSender<'_, T, N> {
+    /// Sends a message if there is room for it, without blocking.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        let ring = self.ring;
+        if ring.receiver_gone.load(Acquire) {
+            return Err(TrySendError::Disconnected(t));
+        }
+
+        ring.send_lock.lock();
+        let tail = ring.tail.load(Relaxed);
+        if tail.wrapping_sub(ring.head.load(Acquire)) == N {
+            unsafe { ring.send_lock.unlock() };
+            return Err(TrySendError::Full(t));
+        }
+        unsafe { ring.slot(tail).write(t) };
+        ring.tail.store(tail.wrapping_add(1), Release);
+        unsafe { ring.send_lock.unlock() };
+
+        Ring::<T, N>::wake(&ring.receiver_latch);
+        Ok(())
+    }
+
+    /// Sends a message, blocking while the buffer is full. Fails, giving back
+    /// `t`, if the receiver has hung up.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn send(&self, mut t: T) -> Result<(), SendError<T>> {
+        loop {
+            match self.try_send(t) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(t)) => return Err(SendError(t)),
+                Err(TrySendError::Full(back)) => t = back,
+            }
+            // Ask the receiver to wake us once it makes room, and check again
+            // in case it already did.
+            let latch = pg::my_latch().expect("cannot wait for a ring channel outside a backend");
+            self.ring.sender_latch.store(latch, SeqCst);
+            if self.ring.is_full() && !self.ring.receiver_gone.load(Acquire) {
+                wait(Some(Instant::now() + SENDER_POLL_INTERVAL));
+            }
+        }
+    }
+}
+
+impl<T: Copy, const N: usize> RingReceiver<'_, T, N> {
+    /// Receives a message if there is one, without blocking.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let ring = self.ring;
+        let head = ring.head.load(Relaxed);
+        if head == ring.tail.load(Acquire) {
+            if !ring.had_sender.load(Relaxed) || ring.senders.load(Acquire) != 0 {
+                return Err(TryRecvError::Empty);
+            }
+            // The last sender may have sent something right before leaving.
+            if head == ring.tail.load(Acquire) {
+                return Err(TryRecvError::Disconnected);
+            }
+        }
+        let t = unsafe { ring.slot(head).read() };
+        ring.head.store(head.wrapping_add(1), Release);
+        Ring::<T, N>::wake(&ring.sender_latch);
+        Ok(t)
+    }
+
+    fn recv_deadline(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        loop {
+            match self.try_recv() {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) => {}
+            }
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            wait(deadline);
+        }
+    }
+
+    /// Blocks until a message arrives, or fails once every sender has hung
+    /// up and every message has been received.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.recv_deadline(None).map_err(|_| RecvError)
+    }
+
+    /// Like [`recv`](Self::recv), but gives up after `timeout`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.recv_deadline(Instant::now().checked_add(timeout))
+    }
+
+    /// Returns an iterator that blocks waiting for messages, and ends when
+    /// every sender has hung up.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        crate::iter::from_fn(move || self.recv().ok())
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Copy, const N: usize> Clone for RingSender<'_, T, N> {
+    fn clone(&self) -> Self {
+        self.ring.sender()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Copy, const N: usize> Drop for RingSender<'_, T, N> {
+    fn drop(&mut self) {
+        if self.ring.senders.fetch_sub(1, Release) == 1 {
+            Ring::<T, N>::wake(&self.ring.receiver_latch);
+        }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Copy, const N: usize> Drop for RingReceiver<'_, T, N> {
+    fn drop(&mut self) {
+        self.ring.receiver_gone.store(true, Release);
+        Ring::<T, N>::wake(&self.ring.sender_latch);
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Copy, const N: usize> fmt::Debug for Ring<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ring").field("capacity", &N).finish_non_exhaustive()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Copy, const N: usize> fmt::Debug for RingSender<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RingSender").finish_non_exhaustive()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Copy, const N: usize> fmt::Debug for RingReceiver<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RingReceiver").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,496 @@
+//! Postgres-specific networking functionality.
+//!
+//! See also [`Capability::Net`](super::capability::Capability::Net), which
+//! must be enabled before any of [`std::net`](crate::net) is usable at all.
+
+use crate::fmt;
+use crate::io::{self, IoSliceMut};
+use crate::marker::PhantomData;
+use crate::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use crate::os::fd::{AsRawFd, BorrowedFd};
+use crate::os::unix::net::UnixStream;
+use crate::path::PathBuf;
+use crate::sealed::Sealed;
+use crate::sys::net as imp;
+use crate::sys::pg;
+use crate::sys_common::{AsInner, FromInner, IntoInner};
+use crate::time::{Duration, Instant};
+
+/// Registers a hook that is consulted before every outbound connection,
+/// replacing any that was previously registered.
+///
+/// The hook is called with the address a [`TcpStream`] is about to connect
+/// to (datagrams are covered by [`set_udp_policy`]). Returning an error aborts
+/// the connection attempt, and the error is handed back to the caller as-is,
+/// which makes the hook a single place to log, allowlist or deny outbound
+/// traffic.
+///
+/// The hook must not register or take a connect hook itself.
+///
+/// # Safety
+///
+/// This does not affect memory safety, but the hook usually enforces the
+/// backend's network policy. As with [`capability::enable`], only the
+/// embedding runtime should ever call this.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::io;
+/// use std::os::postgres::net;
+///
+/// unsafe {
+///     net::set_connect_hook(Box::new(|addr| {
+///         if addr.ip().is_loopback() {
+///             Ok(())
+///         } else {
+///             Err(io::Error::new(io::ErrorKind::PermissionDenied, "only loopback is allowed"))
+///         }
+///     }));
+/// }
+/// ```
+///
+/// [`TcpStream`]: crate::net::TcpStream
+/// [`capability::enable`]: super::capability::enable
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub unsafe fn set_connect_hook(hook: Box<dyn Fn(&SocketAddr) -> io::Result<()> + Sync + Send>) {
+    drop(imp::set_connect_hook(Some(hook)));
+}
+
+/// Unregisters the current connect hook and returns it, if there was one.
+///
+/// # Safety
+///
+/// See [`set_connect_hook`].
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub unsafe fn take_connect_hook() -> Option<Box<dyn Fn(&SocketAddr) -> io::Result<()> + Sync + Send>>
+{
+    imp::set_connect_hook(None)
+}
+
+/// Registers a per-destination policy for UDP, replacing any that was
+/// previously registered.
+///
+/// The policy is called with the destination of every datagram sent with
+/// [`UdpSocket::send_to`], and with the peer passed to
+/// [`UdpSocket::connect`]. Returning an error makes that call fail with it.
+/// UDP as a whole is switched on separately, with [`Capability::Udp`].
+///
+/// The policy must not register or take a UDP policy itself.
+///
+/// # Safety
+///
+/// See [`set_connect_hook`].
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::io;
+/// use std::net::SocketAddr;
+/// use std::os::postgres::net;
+///
+/// let statsd: SocketAddr = "127.0.0.1:8125".parse().unwrap();
+/// unsafe {
+///     net::set_udp_policy(Box::new(move |addr| {
+///         if *addr == statsd {
+///             Ok(())
+///         } else {
+///             Err(io::Error::new(io::ErrorKind::PermissionDenied, "not the metrics endpoint"))
+///         }
+///     }));
+/// }
+/// ```
+///
+/// [`UdpSocket::send_to`]: crate::net::UdpSocket::send_to
+/// [`UdpSocket::connect`]: crate::net::UdpSocket::connect
+/// [`Capability::Udp`]: super::capability::Capability::Udp
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub unsafe fn set_udp_policy(policy: Box<dyn Fn(&SocketAddr) -> io::Result<()> + Sync + Send>) {
+    drop(imp::set_udp_policy(Some(policy)));
+}
+
+/// Unregisters the current UDP policy and returns it, if there was one.
+///
+/// # Safety
+///
+/// See [`set_connect_hook`].
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub unsafe fn take_udp_policy() -> Option<Box<dyn Fn(&SocketAddr) -> io::Result<()> + Sync + Send>>
+{
+    imp::set_udp_policy(None)
+}
+
+/// Returns the paths of the unix sockets this server accepts local
+/// connections on, one for each entry of `unix_socket_directories`.
+///
+/// This fails with [`ErrorKind::Unsupported`] when not running inside a
+/// backend, since the settings are only known there.
+///
+/// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn local_socket_paths() -> io::Result<Vec<PathBuf>> {
+    imp::local_socket_paths()
+}
+
+/// Opens a connection to the server this backend belongs to, over its local
+/// unix socket.
+///
+/// Each of [`local_socket_paths`] is tried in turn and the first successful
+/// connection is returned; otherwise the last error is. Like any other
+/// connection this requires [`Capability::Net`]. The caller is responsible
+/// for the protocol, starting with the startup packet naming the database to
+/// connect to.
+///
+/// [`Capability::Net`]: super::capability::Capability::Net
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::net;
+///
+/// fn main() -> std::io::Result<()> {
+///     let stream = net::connect_local()?;
+///     // send a StartupMessage for the database of choice on `stream`...
+///     Ok(())
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn connect_local() -> io::Result<UnixStream> {
+    let mut last_err = io::const_io_error!(
+        io::ErrorKind::NotFound,
+        "`unix_socket_directories` does not name any usable directory",
+    );
+    for path in local_socket_paths()? {
+        match imp::Socket::connect_unix(&path) {
+            Ok(sock) => return Ok(UnixStream::from(sock.into_inner().into_inner())),
+            Err(e) => last_err = e,
+        }
+    }
+    Err(last_err)
+}
+
+/// The readiness a socket in a [`WaitSet`] is watched for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub enum Interest {
+    /// Data can be read (or a connection was closed).
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    Readable,
+    /// Data can be written.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    Writable,
+    /// Either of the above.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    Both,
+}
+
+impl Interest {
+    fn flags(self) -> (bool, bool) {
+        match self {
+            Interest::Readable => (true, false),
+            Interest::Writable => (false, true),
+            Interest::Both => (true, true),
+        }
+    }
+}
+
+/// Identifies a socket registered with a [`WaitSet`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct Token(usize);
+
+/// A readiness event reported by [`WaitSet::wait`].
+#[derive(Copy, Clone, Debug)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct Event {
+    token: Token,
+    readable: bool,
+    writable: bool,
+}
+
+impl Event {
+    /// The socket this event is about.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn token(&self) -> Token {
+        self.token
+    }
+
+    /// Whether the socket is readable.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn is_readable(&self) -> bool {
+        self.readable
+    }
+
+    /// Whether the socket is writable.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+}
+
+/// Waits for readiness of several sockets at once, through a postgres
+/// `WaitEventSet`.
+///
+/// The set always includes the process latch, so a wait is cut short (with
+/// an error) when the backend is asked to cancel the query or to terminate,
+/// and it never outlives `statement_timeout`. This makes it possible to
+/// multiplex network I/O without an async runtime.
+///
+/// Registered sockets should be in non-blocking mode (see
+/// [`TcpStream::set_nonblocking`]), since readiness reported here can be
+/// spurious.
+///
+/// [`TcpStream::set_nonblocking`]: crate::net::TcpStream::set_nonblocking
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::io::Read;
+/// use std::net::TcpStream;
+/// use std::os::fd::AsFd;
+/// use std::os::postgres::net::{Interest, WaitSet};
+///
+/// fn main() -> std::io::Result<()> {
+///     let a = TcpStream::connect("10.0.0.1:80")?;
+///     let b = TcpStream::connect("10.0.0.2:80")?;
+///     a.set_nonblocking(true)?;
+///     b.set_nonblocking(true)?;
+///
+///     let mut set = WaitSet::with_capacity(2)?;
+///     let token_a = set.add(a.as_fd(), Interest::Readable)?;
+///     set.add(b.as_fd(), Interest::Readable)?;
+///
+///     let mut events = Vec::new();
+///     set.wait(&mut events, None)?;
+///     for event in &events {
+///         let mut stream = if event.token() == token_a { &a } else { &b };
+///         let mut buf = [0; 1024];
+///         let _ = stream.read(&mut buf);
+///     }
+///     Ok(())
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct WaitSet<'a> {
+    inner: pg::WaitEventSet,
+    _sockets: PhantomData<BorrowedFd<'a>>,
+}
+
+impl<'a> WaitSet<'a> {
+    /// Creates a set with room for `sockets` sockets.
+    ///
+    /// This fails with [`ErrorKind::Unsupported`] outside of a backend.
+    ///
+    /// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn with_capacity(sockets: usize) -> io::Result<WaitSet<'a>> {
+        Ok(WaitSet { inner: pg::WaitEventSet::new(sockets)?, _sockets: PhantomData })
+    }
+
+    /// Starts watching `socket` for `interest`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn add(&mut self, socket: BorrowedFd<'a>, interest: Interest) -> io::Result<Token> {
+        let (readable, writable) = interest.flags();
+        self.inner.add_socket(socket.as_raw_fd(), readable, writable).map(Token)
+    }
+
+    /// Changes what the socket identified by `token` is watched for.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn modify(&mut self, token: Token, interest: Interest) {
+        let (readable, writable) = interest.flags();
+        self.inner.modify_socket(token.0, readable, writable)
+    }
+
+    /// Blocks until at least one socket is ready or `timeout` elapses, and
+    /// replaces the contents of `events` with what happened.
+    ///
+    /// `events` may be left empty, e.g. on timeout or after an unrelated
+    /// wakeup of the latch.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn wait(&mut self, events: &mut Vec<Event>, timeout: Option<Duration>) -> io::Result<()> {
+        events.clear();
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self.inner.wait(deadline, |pos, readable, writable| {
+            events.push(Event { token: Token(pos), readable, writable })
+        })
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for WaitSet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitSet").finish_non_exhaustive()
+    }
+}
+
+/// Keepalive and timeout options for [`TcpStream`], mirroring the
+/// `keepalives*` and `tcp_user_timeout` connection parameters of libpq.
+///
+/// Long-lived outbound connections should use these to notice a peer that
+/// went away without closing the connection.
+///
+/// [`TcpStream`]: crate::net::TcpStream
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::net::TcpStream;
+/// use std::os::postgres::net::TcpStreamExt;
+/// use std::time::Duration;
+///
+/// fn main() -> std::io::Result<()> {
+///     let stream = TcpStream::connect("10.0.0.1:5432")?;
+///     stream.set_keepalive(true)?;
+///     stream.set_keepalive_idle(Duration::from_secs(60))?;
+///     stream.set_keepalive_interval(Duration::from_secs(10))?;
+///     stream.set_keepalive_count(3)?;
+///     stream.set_user_timeout(Some(Duration::from_secs(30)))?;
+///     Ok(())
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub trait TcpStreamExt: Sealed {
+    /// Enables or disables `SO_KEEPALIVE` (libpq's `keepalives`).
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn set_keepalive(&self, keepalive: bool) -> io::Result<()>;
+
+    /// Gets the value of the `SO_KEEPALIVE` option on this socket.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn keepalive(&self) -> io::Result<bool>;
+
+    /// Sets how long the connection has to be idle before keepalive probes
+    /// are sent (libpq's `keepalives_idle`). Sub-second precision is ignored.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn set_keepalive_idle(&self, idle: Duration) -> io::Result<()>;
+
+    /// Gets the keepalive idle time of this socket.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn keepalive_idle(&self) -> io::Result<Duration>;
+
+    /// Sets the time between unacknowledged keepalive probes (libpq's
+    /// `keepalives_interval`). Sub-second precision is ignored.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn set_keepalive_interval(&self, interval: Duration) -> io::Result<()>;
+
+    /// Gets the keepalive interval of this socket.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn keepalive_interval(&self) -> io::Result<Duration>;
+
+    /// Sets how many unacknowledged keepalive probes are sent before the
+    /// connection is considered dead (libpq's `keepalives_count`).
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn set_keepalive_count(&self, count: u32) -> io::Result<()>;
+
+    /// Gets the keepalive probe count of this socket.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn keepalive_count(&self) -> io::Result<u32>;
+
+    /// Sets `TCP_USER_TIMEOUT` (libpq's `tcp_user_timeout`): how long
+    /// transmitted data may remain unacknowledged before the connection is
+    /// closed. `None` restores the system default.
+    ///
+    /// This is only supported on Linux, and fails with
+    /// [`ErrorKind::Unsupported`] elsewhere.
+    ///
+    /// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn set_user_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Gets the `TCP_USER_TIMEOUT` of this socket, `None` meaning the system
+    /// default.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn user_timeout(&self) -> io::Result<Option<Duration>>;
+
+    /// Like [`TcpStream::peek`], but scattering the data into several
+    /// buffers, which is handy for looking at a protocol header and the
+    /// start of the payload without copying.
+    ///
+    /// [`TcpStream::peek`]: crate::net::TcpStream::peek
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn peek_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize>;
+}
+
+// On Linux, `std::os::linux::net` already seals `TcpStream`.
+#[cfg(not(target_os = "linux"))]
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Sealed for TcpStream {}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl TcpStreamExt for TcpStream {
+    fn set_keepalive(&self, keepalive: bool) -> io::Result<()> {
+        self.as_inner().socket().set_keepalive(keepalive)
+    }
+
+    fn keepalive(&self) -> io::Result<bool> {
+        self.as_inner().socket().keepalive()
+    }
+
+    fn set_keepalive_idle(&self, idle: Duration) -> io::Result<()> {
+        self.as_inner().socket().set_keepalive_idle(idle)
+    }
+
+    fn keepalive_idle(&self) -> io::Result<Duration> {
+        self.as_inner().socket().keepalive_idle()
+    }
+
+    fn set_keepalive_interval(&self, interval: Duration) -> io::Result<()> {
+        self.as_inner().socket().set_keepalive_interval(interval)
+    }
+
+    fn keepalive_interval(&self) -> io::Result<Duration> {
+        self.as_inner().socket().keepalive_interval()
+    }
+
+    fn set_keepalive_count(&self, count: u32) -> io::Result<()> {
+        self.as_inner().socket().set_keepalive_count(count)
+    }
+
+    fn keepalive_count(&self) -> io::Result<u32> {
+        self.as_inner().socket().keepalive_count()
+    }
+
+    fn set_user_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.as_inner().socket().set_user_timeout(timeout)
+    }
+
+    fn user_timeout(&self) -> io::Result<Option<Duration>> {
+        self.as_inner().socket().user_timeout()
+    }
+
+    fn peek_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.as_inner().socket().peek_vectored(bufs)
+    }
+}
+
+/// Opens a TCP connection to one of the addresses `addr` resolves to, trying
+/// them concurrently ("happy eyeballs", RFC 8305).
+///
+/// Addresses are tried alternating between IPv6 and IPv4, and a new attempt
+/// is started every 250ms while earlier ones are still pending. The first
+/// connection to succeed is returned and the others are abandoned. Unlike
+/// [`TcpStream::connect_timeout`], `timeout` bounds the whole operation,
+/// across all addresses.
+///
+/// This is also what [`TcpStream::connect`] does inside a backend, without a
+/// timeout.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::net;
+/// use std::time::Duration;
+///
+/// let stream = net::connect_timeout("example.com:443", Duration::from_secs(5))
+///     .expect("couldn't connect to the server");
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn connect_timeout<A: ToSocketAddrs>(addr: A, timeout: Duration) -> io::Result<TcpStream> {
+    let deadline = Instant::now() + timeout;
+    imp::connect_any(addr.to_socket_addrs()?, Some(deadline)).map(TcpStream::from_inner)
+}
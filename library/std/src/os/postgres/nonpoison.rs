@@ -0,0 +1,199 @@
+//! Locks that ignore poisoning.
+//!
+//! A lock in [`std::sync`] is poisoned when a panic unwinds while it is
+//! held, on the assumption that another thread may find the data behind it
+//! half-updated. In a backend, that panic is usually a postgres `ERROR` on
+//! its way back to postgres: the transaction is aborted, the backend carries
+//! on, and no other thread was ever going to look at the data. Poisoning
+//! then only makes every later statement fail to take the lock.
+//!
+//! [`Mutex`] and [`RwLock`] wrap their [`std::sync`] counterparts, and
+//! simply hand out the guard whether the lock is poisoned or not. Code that
+//! does care can keep using the poisoning locks, and reset them with
+//! `clear_poison` (under `#![feature(mutex_unpoison)]`) once the data has
+//! been repaired.
+//!
+//! [`std::sync`]: crate::sync
+//!
+//! # Examples
+//!
+//! ```no_run
+//! #![feature(once_cell, postgres_ext)]
+//! use std::collections::HashMap;
+//! use std::os::postgres::nonpoison::Mutex;
+//! use std::sync::LazyLock;
+//!
+//! static PLAN_CACHE: LazyLock<Mutex<HashMap<String, u32>>> = LazyLock::new(Default::default);
+//!
+//! fn cached_plan(query: &str, plan: impl FnOnce() -> u32) -> u32 {
+//!     // Keeps working after a previous `plan()` raised an error.
+//!     *PLAN_CACHE.lock().entry(query.to_owned()).or_insert_with(plan)
+//! }
+//! ```
+
+use crate::fmt;
+use crate::sync::{self, MutexGuard, PoisonError, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+/// A mutual exclusion lock that is never poisoned. See the [module-level
+/// documentation](self) for more.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct Mutex<T: ?Sized> {
+    inner: sync::Mutex<T>,
+}
+
+/// A reader-writer lock that is never poisoned. See the [module-level
+/// documentation](self) for more.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct RwLock<T: ?Sized> {
+    inner: sync::RwLock<T>,
+}
+
+fn try_lock<G>(result: Result<G, TryLockError<G>>) -> Option<G> {
+    match result {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+impl<T> Mutex<T> {
+    /// Creates a new mutex in an unlocked state ready for use.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub const fn new(t: T) -> Mutex<T> {
+        Mutex { inner: sync::Mutex::new(t) }
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquires the mutex, blocking until it is available.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Acquires the mutex if it is not locked, without blocking.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        try_lock(self.inner.try_lock())
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the `Mutex` mutably, no actual locking needs to
+    /// take place.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> RwLock<T> {
+    /// Creates a new instance of an `RwLock<T>` which is unlocked.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub const fn new(t: T) -> RwLock<T> {
+        RwLock { inner: sync::RwLock::new(t) }
+    }
+
+    /// Consumes this `RwLock`, returning the underlying data.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Locks this `RwLock` with shared read access, blocking until it can be
+    /// acquired.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks this `RwLock` with shared read access, if that is possible
+    /// without blocking.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        try_lock(self.inner.try_read())
+    }
+
+    /// Locks this `RwLock` with exclusive write access, blocking until it can
+    /// be acquired.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks this `RwLock` with exclusive write access, if that is possible
+    /// without blocking.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        try_lock(self.inner.try_write())
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the `RwLock` mutably, no actual locking needs
+    /// to take place.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Mutex<T> {
+        Mutex::new(Default::default())
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> From<T> for Mutex<T> {
+    fn from(t: T) -> Self {
+        Mutex::new(t)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Mutex");
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> RwLock<T> {
+        RwLock::new(Default::default())
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> From<T> for RwLock<T> {
+    fn from(t: T) -> Self {
+        RwLock::new(t)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("RwLock");
+        match self.try_read() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,109 @@
+//! Panics turned into postgres errors.
+//!
+//! [`guard`](super::guard) reports a panic to postgres as an `ERROR`, which
+//! then longjmps past the Rust code for good. [`take_last_panic`] hands the
+//! embedding runtime what the report was made of, for its own error
+//! representation, after catching the `ERROR` with `PG_TRY()`.
+
+use crate::fmt;
+use crate::sys::elog;
+
+/// Takes the last panic turned into an `ERROR` on this backend, if any has
+/// been since the last call.
+///
+/// That is a panic out of [`guard`](super::guard), or one with no
+/// `catch_unwind` to unwind to. A panic caught with `catch_unwind` is not
+/// one, and neither is a panic raised while another was unwinding, which
+/// restarts the server instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::panic::take_last_panic;
+///
+/// struct RuntimeError {
+///     message: String,
+///     origin: Option<(String, u32)>,
+/// }
+///
+/// // Called once the `ERROR` out of a call into Rust has been caught.
+/// fn runtime_error(message: String) -> RuntimeError {
+///     match take_last_panic() {
+///         Some(panic) => RuntimeError {
+///             message: panic.message().to_owned(),
+///             origin: Some((panic.file().to_owned(), panic.line())),
+///         },
+///         None => RuntimeError { message, origin: None },
+///     }
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn take_last_panic() -> Option<PanicDetails> {
+    elog::take_last_panic().map(|inner| PanicDetails { inner })
+}
+
+/// A panic turned into an `ERROR`, as returned by [`take_last_panic`].
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct PanicDetails {
+    inner: elog::PanicOrigin,
+}
+
+impl PanicDetails {
+    /// The panic message, as reported: the message of a [`PgError`] raised
+    /// with [`PgError::raise`], like any other panic payload.
+    ///
+    /// [`PgError`]: super::PgError
+    /// [`PgError::raise`]: super::PgError::raise
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn message(&self) -> &str {
+        self.inner.message.as_deref().unwrap_or(elog::LOST_MESSAGE)
+    }
+
+    /// The source file the panic started in.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn file(&self) -> &str {
+        &self.inner.file
+    }
+
+    /// The line the panic started on.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn line(&self) -> u32 {
+        self.inner.line
+    }
+
+    /// The column the panic started at.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn column(&self) -> u32 {
+        self.inner.col
+    }
+
+    /// The backtrace of the panic, as reported in the `CONTEXT`, if those
+    /// are turned on with [`set_backtrace_style`].
+    ///
+    /// [`set_backtrace_style`]: crate::panic::set_backtrace_style
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn backtrace(&self) -> Option<&str> {
+        self.inner.backtrace.as_deref()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for PanicDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PanicDetails")
+            .field("message", &self.message())
+            .field("file", &self.file())
+            .field("line", &self.line())
+            .field("column", &self.column())
+            .field("backtrace", &self.backtrace())
+            .finish()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Display for PanicDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}:{}:{}", self.message(), self.file(), self.line(), self.column())
+    }
+}
@@ -0,0 +1,220 @@
+//! Structured parallelism over dynamic background workers.
+//!
+//! A backend cannot spawn threads (see [`thread::spawn`]), but it can start
+//! background worker processes. This module wraps that in an API shaped like
+//! [`thread::scope`]: every worker launched within a [`scope`] is waited for
+//! before `scope` returns.
+//!
+//! Since a worker is a separate process, it cannot run a closure from the
+//! leader. Instead it runs an exported `extern "C"` function from a shared
+//! library (normally the extension itself), which calls [`WorkerContext::attach`]
+//! to receive its input and send back a reply. Both are plain bytes; any
+//! serialization format will do.
+//!
+//! [`thread::spawn`]: crate::thread::spawn
+//! [`thread::scope`]: crate::thread::scope
+//!
+//! # Examples
+//!
+//! ```no_run
+//! #![feature(postgres_ext)]
+//! use std::os::postgres::parallel::{self, Worker, WorkerContext};
+//!
+//! // Leader.
+//! fn sum_in_parallel(chunks: &[Vec<u8>]) -> std::io::Result<u64> {
+//!     parallel::scope(|s| {
+//!         let handles = chunks
+//!             .iter()
+//!             .map(|chunk| s.launch(Worker::new("my_extension", "sum_worker").input(chunk.clone())))
+//!             .collect::<std::io::Result<Vec<_>>>()?;
+//!         let mut total = 0;
+//!         for handle in handles {
+//!             let reply = handle.join()?;
+//!             total += u64::from_le_bytes(reply.try_into().unwrap());
+//!         }
+//!         Ok(total)
+//!     })
+//! }
+//!
+//! // Worker, started by postgres as `sum_worker` in the `my_extension` library.
+//! #[no_mangle]
+//! pub extern "C" fn sum_worker(main_arg: usize) {
+//!     let (mut ctx, input) = unsafe { WorkerContext::attach(main_arg) }.unwrap();
+//!     let sum: u64 = input.iter().map(|&b| b as u64).sum();
+//!     ctx.reply(&sum.to_le_bytes()).unwrap();
+//! }
+//! ```
+
+use crate::cell::RefCell;
+use crate::fmt;
+use crate::io;
+use crate::marker::PhantomData;
+use crate::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use crate::rc::Rc;
+use crate::sys::parallel as imp;
+
+/// Describes a background worker to [`launch`](Scope::launch).
+#[derive(Clone, Debug)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct Worker {
+    name: Option<String>,
+    library: String,
+    function: String,
+    input: Vec<u8>,
+}
+
+impl Worker {
+    /// A worker running `function` from the shared library `library`, which
+    /// postgres resolves the same way as for `CREATE FUNCTION ... LANGUAGE C`
+    /// (e.g. `"$libdir/my_extension"` or just `"my_extension"`).
+    ///
+    /// The function is called with the `usize` to pass to
+    /// [`WorkerContext::attach`].
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new(library: impl Into<String>, function: impl Into<String>) -> Worker {
+        Worker { name: None, library: library.into(), function: function.into(), input: Vec::new() }
+    }
+
+    /// Names the worker, as shown in `pg_stat_activity`. Defaults to the
+    /// function name.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn name(mut self, name: impl Into<String>) -> Worker {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the bytes the worker receives from [`WorkerContext::attach`].
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn input(mut self, input: impl Into<Vec<u8>>) -> Worker {
+        self.input = input.into();
+        self
+    }
+}
+
+/// A scope to launch workers in. See [`scope`].
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct Scope<'scope> {
+    workers: RefCell<Vec<Rc<RefCell<imp::Launched>>>>,
+    // Like `thread::Scope`, invariant over 'scope.
+    scope: PhantomData<&'scope mut &'scope ()>,
+}
+
+/// An owned permission to join a worker launched in a [`Scope`].
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct WorkerHandle<'scope> {
+    worker: Rc<RefCell<imp::Launched>>,
+    scope: PhantomData<&'scope ()>,
+}
+
+/// Creates a scope for launching background workers.
+///
+/// All workers launched through the [`Scope`] and not joined explicitly are
+/// waited for before this function returns, even if `f` panics.
+///
+/// Launching needs free slots under `max_worker_processes`; when there are
+/// none, [`Scope::launch`] fails with [`ErrorKind::WouldBlock`].
+///
+/// [`ErrorKind::WouldBlock`]: crate::io::ErrorKind::WouldBlock
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn scope<F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&'scope Scope<'scope>) -> T,
+{
+    let scope = Scope { workers: RefCell::new(Vec::new()), scope: PhantomData };
+    let result = catch_unwind(AssertUnwindSafe(|| f(&scope)));
+
+    for worker in scope.workers.take() {
+        let _ = worker.borrow_mut().wait();
+    }
+
+    match result {
+        Ok(result) => result,
+        Err(e) => resume_unwind(e),
+    }
+}
+
+impl<'scope> Scope<'scope> {
+    /// Starts `worker` and hands it its input.
+    ///
+    /// This returns once the worker has been registered with the postmaster
+    /// and its input is in flight; it does not wait for the worker to finish.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn launch(&self, worker: Worker) -> io::Result<WorkerHandle<'scope>> {
+        let name = worker.name.as_deref().unwrap_or(&worker.function);
+        let launched = imp::launch(name, &worker.library, &worker.function, &worker.input)?;
+        let launched = Rc::new(RefCell::new(launched));
+        self.workers.borrow_mut().push(launched.clone());
+        Ok(WorkerHandle { worker: launched, scope: PhantomData })
+    }
+}
+
+impl WorkerHandle<'_> {
+    /// Waits for the worker to reply and exit, and returns the reply.
+    ///
+    /// Fails if the worker exits without calling [`WorkerContext::reply`],
+    /// e.g. because it raised an error.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn join(self) -> io::Result<Vec<u8>> {
+        self.worker.borrow_mut().join()
+    }
+
+    /// Asks the postmaster to terminate the worker.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn terminate(&self) {
+        self.worker.borrow().terminate()
+    }
+
+    /// The worker's `BackgroundWorkerHandle`.
+    pub(crate) fn as_ptr(&self) -> *mut libc::c_void {
+        self.worker.borrow().worker_ptr()
+    }
+}
+
+/// The worker side of a [`Worker`] launched from a [`scope`].
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct WorkerContext {
+    inner: imp::Attached,
+}
+
+impl WorkerContext {
+    /// Connects to the leader that launched this worker, and returns the
+    /// input it provided.
+    ///
+    /// # Safety
+    ///
+    /// `main_arg` must be the argument postgres passed to the worker's entry
+    /// point, and this may be called only once per worker.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn attach(main_arg: usize) -> io::Result<(WorkerContext, Vec<u8>)> {
+        let (inner, input) = imp::attach(main_arg)?;
+        Ok((WorkerContext { inner }, input))
+    }
+
+    /// Sends the result back to the leader, where it is returned from
+    /// [`WorkerHandle::join`].
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn reply(&mut self, output: &[u8]) -> io::Result<()> {
+        self.inner.reply(output)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for Scope<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scope").field("workers", &self.workers.borrow().len()).finish()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for WorkerHandle<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorkerHandle").finish_non_exhaustive()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for WorkerContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorkerContext").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,138 @@
+//! Fast, non-cryptographic random numbers, from the same generator as
+//! postgres's own `random()`.
+//!
+//! [`PgPrng`] is xoroshiro128\*\*, seeded with splitmix64, exactly like
+//! `pg_prng_state` in `common/pg_prng.h` on postgres 15 and later: for the
+//! same seed, it gives the same numbers as `pg_prng_seed()` followed by
+//! `pg_prng_uint64()` and friends do. It is no good for secrets; for those,
+//! see [`random_bytes`].
+//!
+//! [`random_bytes`]: super::random_bytes
+//!
+//! # Examples
+//!
+//! ```no_run
+//! #![feature(postgres_ext)]
+//! use std::os::postgres::random::{self, RandomSource};
+//!
+//! // A sample of about 1% of the rows.
+//! let sample = random::with_backend_prng(|rng| rng.next_f64() < 0.01);
+//!
+//! let mut rng = random::PgPrng::new(42);
+//! let mut salt = [0u8; 16];
+//! rng.fill_bytes(&mut salt);
+//! ```
+
+use crate::cell::RefCell;
+use crate::sys::rand;
+
+/// A source of random bytes.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub trait RandomSource {
+    /// Fills `bytes` with random bytes.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    fn fill_bytes(&mut self, bytes: &mut [u8]);
+}
+
+/// Postgres's pseudo-random number generator, `pg_prng_state`.
+#[derive(Clone, Debug)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct PgPrng {
+    s0: u64,
+    s1: u64,
+}
+
+impl PgPrng {
+    /// Creates a generator seeded with `seed`, like `pg_prng_seed`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new(mut seed: u64) -> PgPrng {
+        let s0 = splitmix64(&mut seed);
+        let s1 = splitmix64(&mut seed);
+        // The one state xoroshiro128** never leaves.
+        if s0 == 0 && s1 == 0 {
+            PgPrng { s0: 0x5851F42D4C957F2D, s1: 0x14057B7EF767814F }
+        } else {
+            PgPrng { s0, s1 }
+        }
+    }
+
+    /// Creates a generator seeded with randomness from the kernel.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn from_entropy() -> PgPrng {
+        PgPrng::new(rand::seed())
+    }
+
+    /// A random `u64`, like `pg_prng_uint64`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn next_u64(&mut self) -> u64 {
+        // xoroshiro128**
+        let s0 = self.s0;
+        let sx = self.s1 ^ s0;
+        let val = s0.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        self.s0 = s0.rotate_left(24) ^ sx ^ (sx << 16);
+        self.s1 = sx.rotate_left(37);
+        val
+    }
+
+    /// A random `u32`, like `pg_prng_uint32`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// A random `f64` in `[0, 1)`, like `pg_prng_double`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> (64 - 52)) as f64 * (1.0 / (1u64 << 52) as f64)
+    }
+
+    /// A random `u64` in `min..=max`, without bias, like
+    /// `pg_prng_uint64_range`. Returns `min` if `max` is not greater.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn range(&mut self, min: u64, max: u64) -> u64 {
+        if max <= min {
+            return min;
+        }
+        let len = max - min;
+        let mask = u64::MAX >> len.leading_zeros();
+        loop {
+            let val = self.next_u64() & mask;
+            if val <= len {
+                return min + val;
+            }
+        }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl RandomSource for PgPrng {
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let val = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&val[..chunk.len()]);
+        }
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut val = *state;
+    val = (val ^ (val >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    val = (val ^ (val >> 27)).wrapping_mul(0x94D049BB133111EB);
+    val ^ (val >> 31)
+}
+
+thread_local! {
+    static BACKEND_PRNG: RefCell<Option<PgPrng>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with the generator of this backend, seeded from the kernel the
+/// first time.
+///
+/// # Panics
+///
+/// Panics if `f` calls `with_backend_prng` again.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn with_backend_prng<F: FnOnce(&mut PgPrng) -> R, R>(f: F) -> R {
+    BACKEND_PRNG.with(|prng| f(prng.borrow_mut().get_or_insert_with(PgPrng::from_entropy)))
+}
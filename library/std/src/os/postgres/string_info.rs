@@ -0,0 +1,177 @@
+//! Formatting straight into postgres's string buffers.
+//!
+//! Messages, `EXPLAIN` output and the results of output functions are built
+//! in a `StringInfo`, in whichever memory context postgres expects them.
+//! Formatting them into a `String` first, only to copy it over after, takes
+//! twice the memory and a copy. A [`StringInfo`] implements [`fmt::Write`]
+//! and [`io::Write`] over the buffer itself, so that `write!` appends to it
+//! directly.
+//!
+//! [`fmt::Write`]: crate::fmt::Write
+//! [`io::Write`]: crate::io::Write
+
+use crate::ffi::{c_void, CStr, VaList};
+use crate::fmt;
+use crate::io;
+use crate::ptr::NonNull;
+use crate::slice;
+use crate::sys::string_info::{self, StringInfoData};
+
+/// A postgres `StringInfo`, to format into.
+///
+/// The buffer is palloc'd, and goes away with its memory context, not when
+/// this is dropped: hand it, or its contents, to postgres with
+/// [`as_ptr`](StringInfo::as_ptr) or [`as_c_str`](StringInfo::as_c_str).
+///
+/// Appending fails with the `ERROR` postgres raises once the buffer would be
+/// over 1GB, as an [`io::Error`] of kind [`OutOfMemory`], and as an
+/// [`fmt::Error`] through [`fmt::Write`].
+///
+/// [`OutOfMemory`]: crate::io::ErrorKind::OutOfMemory
+/// [`fmt::Write`]: crate::fmt::Write
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::fmt::Write;
+/// use std::os::postgres::string_info::StringInfo;
+///
+/// // The output function of a point type, which returns a `cstring`.
+/// fn point_out(x: f64, y: f64) -> std::io::Result<*const std::ffi::c_char> {
+///     let mut out = StringInfo::new()?;
+///     write!(out, "({x},{y})").unwrap();
+///     Ok(out.as_c_str().as_ptr())
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct StringInfo {
+    info: NonNull<StringInfoData>,
+}
+
+impl StringInfo {
+    /// Makes an empty buffer in the current memory context, like
+    /// `makeStringInfo()`.
+    ///
+    /// This fails with [`ErrorKind::Unsupported`] outside of a backend.
+    ///
+    /// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new() -> io::Result<StringInfo> {
+        let info = string_info::make()?;
+        Ok(StringInfo { info: unsafe { NonNull::new_unchecked(info) } })
+    }
+
+    /// Wraps a `StringInfo` postgres handed over, like the buffer of an
+    /// `EXPLAIN` hook, to append to it.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` is a valid `StringInfo` that nothing else appends to while this
+    /// is around, and its memory context outlives this.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn from_ptr(ptr: *mut c_void) -> StringInfo {
+        StringInfo { info: unsafe { NonNull::new_unchecked(ptr.cast()) } }
+    }
+
+    /// Returns the `StringInfo` itself.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.info.as_ptr().cast()
+    }
+
+    /// Returns the bytes appended so far.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn as_bytes(&self) -> &[u8] {
+        let info = unsafe { self.info.as_ref() };
+        unsafe { slice::from_raw_parts(info.data.cast(), info.len as usize) }
+    }
+
+    /// Returns the contents as a C string, which postgres keeps
+    /// nul-terminated.
+    ///
+    /// If a nul was appended, the string ends there.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn as_c_str(&self) -> &CStr {
+        unsafe { CStr::from_ptr(self.info.as_ref().data) }
+    }
+
+    /// Returns the number of bytes appended so far.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn len(&self) -> usize {
+        unsafe { self.info.as_ref().len as usize }
+    }
+
+    /// Returns whether nothing has been appended.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends `bytes`, like `appendBinaryStringInfo()`.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        unsafe { string_info::append(self.info.as_ptr(), bytes) }
+    }
+
+    /// Appends `fmt` formatted with `args`, like `appendStringInfoVA()`,
+    /// for a variadic C function or a `va_list` postgres hands over.
+    ///
+    /// This formats with postgres's own `printf`, which knows `%m` too.
+    ///
+    /// # Safety
+    ///
+    /// `args` are what `fmt` calls for, as for `vprintf`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(c_variadic, postgres_ext)]
+    /// use std::ffi::{c_char, CStr};
+    /// use std::os::postgres::string_info::StringInfo;
+    ///
+    /// // A `printf`-style function for C code to call.
+    /// #[no_mangle]
+    /// pub unsafe extern "C" fn my_extension_log(fmt: *const c_char, mut args: ...) {
+    ///     let mut line = StringInfo::new().unwrap();
+    ///     unsafe { line.push_va_list(CStr::from_ptr(fmt), args.as_va_list()).unwrap() };
+    ///     // ...
+    /// }
+    /// ```
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn push_va_list(&mut self, fmt: &CStr, args: VaList<'_, '_>) -> io::Result<()> {
+        unsafe { string_info::append_va(self.info.as_ptr(), fmt, args) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Write for StringInfo {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_bytes(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl io::Write for StringInfo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.push_bytes(buf)?;
+        Ok(buf.len())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.push_bytes(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for StringInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StringInfo")
+            .field("contents", &String::from_utf8_lossy(self.as_bytes()))
+            .finish()
+    }
+}
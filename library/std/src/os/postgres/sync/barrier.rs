@@ -0,0 +1,132 @@
+use crate::cell::UnsafeCell;
+use crate::fmt;
+use crate::ptr::addr_of_mut;
+use crate::sync::BarrierWaitResult;
+use crate::sys::barrier::Barrier;
+
+/// A phase barrier in shared memory, backed by the `Barrier` postgres's own
+/// parallel executor nodes use.
+///
+/// Unlike [`std::sync::Barrier`], the party can change over time: backends
+/// [`attach`](PgBarrier::attach) to join it and [`detach`](PgBarrier::detach)
+/// to leave, and the barrier counts the phases it has gone through. This is
+/// what allows a parallel worker that starts late to find out which phase
+/// the computation is in, and catch up.
+///
+/// Waiting is done on a postgres condition variable, and is interruptible:
+/// cancelling the query raises an error in every waiting backend.
+///
+/// [`std::sync::Barrier`]: crate::sync::Barrier
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::sync::PgBarrier;
+///
+/// // `barrier` lives in the DSM segment shared by the leader and workers,
+/// // and was initialized by the leader with `PgBarrier::initialize(ptr, 0)`.
+/// fn build_then_probe(barrier: &PgBarrier) {
+///     if barrier.attach() == 0 {
+///         // Phase 0: everyone builds their share of the hash table.
+///         barrier.arrive_and_wait();
+///     }
+///     // Phase 1: probe the hash table. Workers starting this late skip
+///     // straight to it.
+///     barrier.detach();
+/// }
+/// ```
+#[repr(C)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct PgBarrier {
+    inner: UnsafeCell<Barrier>,
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl Send for PgBarrier {}
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl Sync for PgBarrier {}
+
+impl PgBarrier {
+    /// Initializes a barrier at `this`.
+    ///
+    /// With a non-zero number of `participants`, the party is static: that
+    /// many backends take part without attaching, like [`std::sync::Barrier`],
+    /// and [`attach`](Self::attach) must not be used. With zero, backends
+    /// join and leave the party dynamically.
+    ///
+    /// [`std::sync::Barrier`]: crate::sync::Barrier
+    ///
+    /// # Safety
+    ///
+    /// `this` must be valid for writes and point into shared memory that
+    /// stays mapped for as long as any backend uses the barrier, and it must
+    /// only be initialized once.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn initialize(this: *mut PgBarrier, participants: usize) {
+        let participants =
+            i32::try_from(participants).expect("too many participants for a barrier");
+        unsafe {
+            Barrier::initialize(UnsafeCell::raw_get(addr_of_mut!((*this).inner)), participants)
+                .expect("barriers can only be initialized inside a postgres backend")
+        }
+    }
+
+    fn raw(&self) -> *mut Barrier {
+        self.inner.get()
+    }
+
+    /// Joins the party, and returns the current phase.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn attach(&self) -> u32 {
+        unsafe { Barrier::attach(self.raw()) as u32 }
+    }
+
+    /// Leaves the party without waiting for the others. Returns `true` if
+    /// this was the last participant.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn detach(&self) -> bool {
+        unsafe { Barrier::detach(self.raw()) }
+    }
+
+    /// Blocks until all participants have arrived, which ends the current
+    /// phase.
+    ///
+    /// Exactly one participant gets a [`BarrierWaitResult`] for which
+    /// [`is_leader`](BarrierWaitResult::is_leader) is `true`; postgres calls
+    /// it "elected", and it is typically the one to run any serial work
+    /// before the next phase.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn arrive_and_wait(&self) -> BarrierWaitResult {
+        BarrierWaitResult(unsafe { Barrier::arrive_and_wait(self.raw()) })
+    }
+
+    /// Arrives at the barrier and leaves the party without waiting, which
+    /// ends the phase if everyone else has already arrived. Returns `true` if
+    /// this was the last participant.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn arrive_and_detach(&self) -> bool {
+        unsafe { Barrier::arrive_and_detach(self.raw()) }
+    }
+
+    /// The current phase, counted from zero. This is only stable while
+    /// attached.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn phase(&self) -> u32 {
+        unsafe { Barrier::phase(self.raw()) as u32 }
+    }
+
+    /// The number of participants currently attached. This is only a
+    /// snapshot, and may be out of date by the time it is returned.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn participants(&self) -> usize {
+        unsafe { Barrier::participants(self.raw()) as usize }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for PgBarrier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PgBarrier").finish_non_exhaustive()
+    }
+}
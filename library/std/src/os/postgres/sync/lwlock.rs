@@ -0,0 +1,244 @@
+use crate::cell::UnsafeCell;
+use crate::ffi::CStr;
+use crate::fmt;
+use crate::io;
+use crate::marker::PhantomData;
+use crate::ops::{Deref, DerefMut};
+use crate::ptr::addr_of_mut;
+use crate::sys::lwlock::{self as imp, LWLock};
+
+/// A named group of lightweight locks, as shown in the `wait_event` column
+/// of `pg_stat_activity` while a backend waits for one of them.
+#[derive(Clone, Copy, Debug)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct Tranche {
+    id: i32,
+}
+
+impl Tranche {
+    /// Allocates a new tranche and names it in this backend.
+    ///
+    /// This must happen only once for the whole cluster, so that all
+    /// backends agree on the ID. Other backends call [`Tranche::from_id`]
+    /// with the [`id`](Tranche::id) stored in shared memory.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new(name: &'static CStr) -> io::Result<Tranche> {
+        Tranche::from_id(imp::new_tranche_id()?, name)
+    }
+
+    /// Names the existing tranche `id` in this backend.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn from_id(id: i32, name: &'static CStr) -> io::Result<Tranche> {
+        imp::register_tranche(id, name)?;
+        Ok(Tranche { id })
+    }
+
+    /// The ID of this tranche, the same in every backend.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+/// A reader-writer lock in shared memory, backed by a postgres `LWLock`.
+///
+/// This behaves like [`RwLock`], except that it coordinates backends rather
+/// than threads. Waiting for the lock is not interruptible, so it should
+/// only be held for short, bounded stretches of code.
+///
+/// The lock is never poisoned: when a backend raises an error while holding
+/// it, postgres releases it during error recovery, as it does for all
+/// `LWLock`s.
+///
+/// [`RwLock`]: crate::sync::RwLock
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::ffi::CStr;
+/// use std::os::postgres::sync::{LwRwLock, Tranche};
+///
+/// #[repr(C)]
+/// struct Shared {
+///     tranche: i32,
+///     hits: LwRwLock<u64>,
+/// }
+///
+/// // From the `shmem_startup_hook`, with `shared` just allocated by
+/// // `ShmemInitStruct`.
+/// unsafe fn init(shared: *mut Shared) -> std::io::Result<()> {
+///     let name = CStr::from_bytes_with_nul(b"my_extension\0").unwrap();
+///     let tranche = Tranche::new(name)?;
+///     (*shared).tranche = tranche.id();
+///     LwRwLock::initialize(std::ptr::addr_of_mut!((*shared).hits), &tranche, 0);
+///     Ok(())
+/// }
+///
+/// fn hit(shared: &Shared) {
+///     *shared.hits.write() += 1;
+/// }
+/// ```
+#[repr(C)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct LwRwLock<T> {
+    lock: UnsafeCell<LWLock>,
+    data: UnsafeCell<T>,
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl<T: Send> Send for LwRwLock<T> {}
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl<T: Send + Sync> Sync for LwRwLock<T> {}
+
+/// RAII structure used to release the shared read access of a [`LwRwLock`]
+/// when dropped.
+#[must_use = "if unused the LwRwLock will immediately unlock"]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct LwReadGuard<'a, T: 'a> {
+    lock: &'a LwRwLock<T>,
+    // Releasing an LWLock from another process is meaningless.
+    _not_send: PhantomData<*const ()>,
+}
+
+/// RAII structure used to release the exclusive write access of a
+/// [`LwRwLock`] when dropped.
+#[must_use = "if unused the LwRwLock will immediately unlock"]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct LwWriteGuard<'a, T: 'a> {
+    lock: &'a LwRwLock<T>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T> LwRwLock<T> {
+    /// Initializes a lock protecting `value` at `this`, in `tranche`.
+    ///
+    /// # Safety
+    ///
+    /// `this` must be valid for writes and point into shared memory that
+    /// stays mapped for as long as any backend uses the lock, and it must
+    /// only be initialized once. `T` must be meaningful in every backend
+    /// attached to that memory, so it must not contain pointers to
+    /// backend-local memory.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn initialize(this: *mut LwRwLock<T>, tranche: &Tranche, value: T) {
+        unsafe {
+            addr_of_mut!((*this).data).write(UnsafeCell::new(value));
+            LWLock::initialize(UnsafeCell::raw_get(addr_of_mut!((*this).lock)), tranche.id)
+                .expect("lightweight locks can only be initialized inside a postgres backend");
+        }
+    }
+
+    fn raw(&self) -> *mut LWLock {
+        self.lock.get()
+    }
+
+    /// Locks this lock with shared read access, blocking the current backend
+    /// until it can be acquired.
+    ///
+    /// Like with any `LWLock`, acquiring it again in the same backend while
+    /// it is held exclusively deadlocks.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn read(&self) -> LwReadGuard<'_, T> {
+        unsafe { LWLock::acquire(self.raw(), false) };
+        LwReadGuard { lock: self, _not_send: PhantomData }
+    }
+
+    /// Attempts to acquire this lock with shared read access, without
+    /// waiting.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn try_read(&self) -> Option<LwReadGuard<'_, T>> {
+        unsafe { LWLock::try_acquire(self.raw(), false) }
+            .then(|| LwReadGuard { lock: self, _not_send: PhantomData })
+    }
+
+    /// Locks this lock with exclusive write access, blocking the current
+    /// backend until it can be acquired.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn write(&self) -> LwWriteGuard<'_, T> {
+        unsafe { LWLock::acquire(self.raw(), true) };
+        LwWriteGuard { lock: self, _not_send: PhantomData }
+    }
+
+    /// Attempts to acquire this lock with exclusive write access, without
+    /// waiting.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn try_write(&self) -> Option<LwWriteGuard<'_, T>> {
+        unsafe { LWLock::try_acquire(self.raw(), true) }
+            .then(|| LwWriteGuard { lock: self, _not_send: PhantomData })
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the lock mutably, no actual locking needs to
+    /// take place.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: fmt::Debug> fmt::Debug for LwRwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("LwRwLock");
+        match self.try_read() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> Deref for LwReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> Deref for LwWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> DerefMut for LwWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> Drop for LwReadGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { LWLock::release(self.lock.raw()) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> Drop for LwWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { LWLock::release(self.lock.raw()) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: fmt::Debug> fmt::Debug for LwReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: fmt::Debug> fmt::Debug for LwWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
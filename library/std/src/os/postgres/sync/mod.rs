@@ -0,0 +1,30 @@
+//! Synchronization primitives for data in shared memory.
+//!
+//! The locks in [`std::sync`] only coordinate the threads of one process,
+//! and a backend has only one. These instead live in shared memory next to
+//! the data they protect, and coordinate all the backends attached to it.
+//!
+//! They are initialized in place, normally from a `shmem_startup_hook`, in
+//! memory obtained with `ShmemInitStruct` after reserving
+//! `size_of::<LwRwLock<T>>()` bytes with `RequestAddinShmemSpace`.
+//! [`SharedArc`] is the exception: it allocates a dynamic shared memory
+//! segment of its own, so it can be created at any time.
+//!
+//! [`std::sync`]: crate::sync
+
+#[cfg(test)]
+mod tests;
+
+mod barrier;
+mod lwlock;
+mod shared_arc;
+mod spin;
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub use self::barrier::PgBarrier;
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub use self::lwlock::{LwReadGuard, LwRwLock, LwWriteGuard, Tranche};
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub use self::shared_arc::SharedArc;
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub use self::spin::{SpinMutex, SpinMutexGuard};
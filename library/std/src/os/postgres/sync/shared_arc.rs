@@ -0,0 +1,203 @@
+use crate::cell::RefCell;
+use crate::fmt;
+use crate::io;
+use crate::mem;
+use crate::ops::Deref;
+use crate::ptr::{self, NonNull};
+use crate::rc::{Rc, Weak};
+use crate::sync::atomic::{self, AtomicUsize, Ordering};
+use crate::sys::shm_mq::Segment;
+
+/// A reference-counted pointer to a value in dynamic shared memory.
+///
+/// This is an [`Arc`] whose allocation is a DSM segment, so that clones can
+/// live in other backends: the leader of a parallel operation can build a
+/// large, immutable value once and hand its [`handle`](SharedArc::handle)
+/// to its workers, which [`attach`](SharedArc::attach) to it instead of each
+/// receiving a copy. The reference count is shared by all backends, and the
+/// value is dropped when the last reference anywhere goes away.
+///
+/// Each backend maps the segment once, however many references it holds
+/// (attaching to a value the backend already holds shares its mapping), and
+/// keeps it mapped across transactions until its last reference is dropped.
+///
+/// [`Arc`]: crate::sync::Arc
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::sync::SharedArc;
+///
+/// // Leader.
+/// fn share(table: [u32; 1024]) -> std::io::Result<(SharedArc<[u32; 1024]>, u32)> {
+///     let table = SharedArc::new(table)?;
+///     let handle = SharedArc::handle(&table);
+///     // `table` must stay alive until the workers have attached.
+///     Ok((table, handle))
+/// }
+///
+/// // Worker, given `handle` as part of its input.
+/// fn lookup(handle: u32, i: usize) -> std::io::Result<u32> {
+///     let table = unsafe { SharedArc::<[u32; 1024]>::attach(handle)? };
+///     Ok(table[i])
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct SharedArc<T> {
+    ptr: NonNull<Inner<T>>,
+    seg: Rc<Segment>,
+}
+
+#[repr(C)]
+struct Inner<T> {
+    strong: AtomicUsize,
+    data: T,
+}
+
+thread_local! {
+    /// The segments this backend has mapped for `SharedArc`s, by handle.
+    /// Postgres refuses to map a segment twice in the same backend.
+    static MAPPINGS: RefCell<Vec<(u32, Weak<Segment>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Remembers that `seg` is mapped, for [`map`] to find.
+fn remember(seg: Segment) -> Rc<Segment> {
+    seg.pin_mapping();
+    let seg = Rc::new(seg);
+    MAPPINGS.with(|mappings| {
+        let mut mappings = mappings.borrow_mut();
+        mappings.retain(|(_, seg)| seg.strong_count() > 0);
+        mappings.push((seg.handle(), Rc::downgrade(&seg)));
+    });
+    seg
+}
+
+/// Maps the segment `handle`, or returns the existing mapping of it.
+fn map(handle: u32) -> io::Result<Rc<Segment>> {
+    let existing = MAPPINGS.with(|mappings| {
+        mappings.borrow().iter().find(|(h, _)| *h == handle).and_then(|(_, seg)| seg.upgrade())
+    });
+    match existing {
+        Some(seg) => Ok(seg),
+        None => Ok(remember(Segment::attach(handle)?)),
+    }
+}
+
+impl<T> SharedArc<T> {
+    /// Moves `data` into a new DSM segment.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new(data: T) -> io::Result<SharedArc<T>> {
+        let seg = Segment::create(mem::size_of::<Inner<T>>())?;
+        let ptr = seg.address().cast::<Inner<T>>();
+        // DSM segments are page aligned.
+        assert!(
+            ptr.addr() % mem::align_of::<Inner<T>>() == 0,
+            "over-aligned types cannot be shared"
+        );
+        unsafe { ptr.write(Inner { strong: AtomicUsize::new(1), data }) };
+        Ok(SharedArc { ptr: unsafe { NonNull::new_unchecked(ptr) }, seg: remember(seg) })
+    }
+
+    /// Attaches to the value shared under `handle`, adding a reference to
+    /// it.
+    ///
+    /// Fails with `NotFound` if all references to the value are gone.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must come from [`SharedArc::handle`] on a `SharedArc<T>` of
+    /// the same `T`, built from the same code. `T` must mean the same in
+    /// every backend, so it must not contain pointers or references.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn attach(handle: u32) -> io::Result<SharedArc<T>> {
+        let seg = map(handle)?;
+        let ptr = seg.address().cast::<Inner<T>>();
+        // Like `Arc::upgrade`: the count may already have dropped to zero, in
+        // which case the value is (being) dropped, and must not come back.
+        let strong = unsafe { &(*ptr).strong };
+        let mut n = strong.load(Ordering::Relaxed);
+        loop {
+            if n == 0 {
+                return Err(io::const_io_error!(
+                    io::ErrorKind::NotFound,
+                    "the shared value has already been dropped",
+                ));
+            }
+            match strong.compare_exchange_weak(n, n + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(old) => n = old,
+            }
+        }
+        Ok(SharedArc { ptr: unsafe { NonNull::new_unchecked(ptr) }, seg })
+    }
+
+    /// The handle other backends [`attach`](SharedArc::attach) with.
+    ///
+    /// Like [`Arc::as_ptr`], this is an associated function, called as
+    /// `SharedArc::handle(&this)`.
+    ///
+    /// [`Arc::as_ptr`]: crate::sync::Arc::as_ptr
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn handle(this: &Self) -> u32 {
+        this.seg.handle()
+    }
+
+    /// The number of references to this value, across all backends.
+    ///
+    /// Other backends may change it at any time, so this is only a snapshot.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn strong_count(this: &Self) -> usize {
+        this.inner().strong.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if the two `SharedArc`s point to the same value in this
+    /// backend.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.ptr == other.ptr
+    }
+
+    fn inner(&self) -> &Inner<T> {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> Clone for SharedArc<T> {
+    fn clone(&self) -> SharedArc<T> {
+        // See `Arc::clone` for why this can be relaxed.
+        self.inner().strong.fetch_add(1, Ordering::Relaxed);
+        SharedArc { ptr: self.ptr, seg: self.seg.clone() }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> Deref for SharedArc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner().data
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> Drop for SharedArc<T> {
+    fn drop(&mut self) {
+        // Same protocol as `Arc::drop`.
+        if self.inner().strong.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        atomic::fence(Ordering::Acquire);
+        unsafe { ptr::drop_in_place(ptr::addr_of_mut!((*self.ptr.as_ptr()).data)) };
+        // The segment itself goes away once every backend has detached from
+        // it, i.e. once `seg` is dropped here and elsewhere.
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: fmt::Debug> fmt::Debug for SharedArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,168 @@
+use crate::cell::UnsafeCell;
+use crate::fmt;
+use crate::marker::PhantomData;
+use crate::ops::{Deref, DerefMut};
+use crate::sys::spin as imp;
+#[cfg(debug_assertions)]
+use crate::time::{Duration, Instant};
+
+/// How long a spinlock may be held before debug assertions complain. Postgres
+/// expects a spinlock to protect a few dozen instructions at most; anything
+/// taking this long belongs under an [`LwRwLock`](super::LwRwLock).
+#[cfg(debug_assertions)]
+const MAX_HOLD_TIME: Duration = Duration::from_millis(10);
+
+/// A mutual exclusion lock in shared memory, backed by a postgres spinlock.
+///
+/// This is meant for tiny critical sections, like bumping a counter or
+/// flipping a flag, following the same rules as spinlocks in postgres
+/// itself: while the lock is held, do not call into the backend, do not
+/// allocate, do not panic, and do not take another lock. Waiters burn CPU
+/// and are not interruptible, and one that waits too long makes postgres
+/// PANIC with "stuck spinlock". When `std` is built with debug assertions,
+/// holding the lock for more than a few milliseconds panics on release.
+///
+/// Like [`LwRwLock`](super::LwRwLock), the lock is never poisoned.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::os::postgres::sync::SpinMutex;
+///
+/// #[repr(C)]
+/// struct Shared {
+///     requests: SpinMutex<u64>,
+/// }
+///
+/// // From the `shmem_startup_hook`, with `shared` just allocated by
+/// // `ShmemInitStruct`.
+/// unsafe fn init(shared: *mut Shared) {
+///     shared.write(Shared { requests: SpinMutex::new(0) });
+/// }
+///
+/// fn count_request(shared: &Shared) {
+///     *shared.requests.lock() += 1;
+/// }
+/// ```
+#[repr(C)]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct SpinMutex<T> {
+    lock: imp::SpinLock,
+    data: UnsafeCell<T>,
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl<T: Send> Send for SpinMutex<T> {}
+#[unstable(feature = "postgres_ext", issue = "none")]
+unsafe impl<T: Send> Sync for SpinMutex<T> {}
+
+/// RAII structure used to release the lock of a [`SpinMutex`] when dropped.
+#[must_use = "if unused the SpinMutex will immediately unlock"]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct SpinMutexGuard<'a, T: 'a> {
+    lock: &'a SpinMutex<T>,
+    #[cfg(debug_assertions)]
+    acquired: Instant,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T> SpinMutex<T> {
+    /// Creates a new, unlocked spin mutex. To be shared, it has to be moved
+    /// into shared memory before it is first locked.
+    ///
+    /// `T` must be meaningful in every backend attached to that memory, so
+    /// it must not contain pointers to backend-local memory.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub const fn new(value: T) -> SpinMutex<T> {
+        SpinMutex { lock: imp::SpinLock::new(), data: UnsafeCell::new(value) }
+    }
+
+    fn guard(&self) -> SpinMutexGuard<'_, T> {
+        SpinMutexGuard {
+            lock: self,
+            #[cfg(debug_assertions)]
+            acquired: Instant::now(),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Acquires the lock, spinning until it is available.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn lock(&self) -> SpinMutexGuard<'_, T> {
+        self.lock.lock();
+        self.guard()
+    }
+
+    /// Acquires the lock if it is not held, without spinning.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn try_lock(&self) -> Option<SpinMutexGuard<'_, T>> {
+        self.lock.try_lock().then(|| self.guard())
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the lock mutably, no actual locking needs to
+    /// take place.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: fmt::Debug> fmt::Debug for SpinMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("SpinMutex");
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> Deref for SpinMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> DerefMut for SpinMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> Drop for SpinMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        let held = self.acquired.elapsed();
+        unsafe { self.lock.lock.unlock() };
+        // Checked after unlocking, so that other backends are not left
+        // spinning on the lock while this one panics.
+        #[cfg(debug_assertions)]
+        assert!(
+            held <= MAX_HOLD_TIME,
+            "spinlock held for {held:?}, use an LWLock for critical sections this long",
+        );
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: fmt::Debug> fmt::Debug for SpinMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
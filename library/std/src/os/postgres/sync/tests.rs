@@ -0,0 +1,22 @@
+// These need a backend to run in, through `test::postgres::run_tests`.
+
+use super::SharedArc;
+
+#[test]
+fn shared_arc_attach_twice() {
+    let arc = SharedArc::new([7u32; 16]).unwrap();
+    let handle = SharedArc::handle(&arc);
+
+    // The creating backend already maps the segment, and attaching again
+    // must share that mapping rather than map it a second time.
+    let a = unsafe { SharedArc::<[u32; 16]>::attach(handle) }.unwrap();
+    let b = unsafe { SharedArc::<[u32; 16]>::attach(handle) }.unwrap();
+    assert!(SharedArc::ptr_eq(&a, &arc));
+    assert!(SharedArc::ptr_eq(&b, &arc));
+    assert_eq!(SharedArc::strong_count(&arc), 3);
+
+    drop(arc);
+    drop(a);
+    assert_eq!(b[15], 7);
+    assert_eq!(SharedArc::strong_count(&b), 1);
+}
@@ -0,0 +1,67 @@
+//! How much memory there is, and how much postgres is configured to use.
+//!
+//! Postgres sizes its own sorts and hash tables after `work_mem`, and so
+//! should Rust code doing the same: hardcoded sizes are either too small for
+//! the server at hand, or many times `work_mem` once every backend runs the
+//! same query. Outside of a backend, only [`physical_memory`] is known.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! #![feature(postgres_ext)]
+//! use std::collections::HashMap;
+//! use std::os::postgres::sysinfo;
+//!
+//! // Leave room for the values, too.
+//! let budget = sysinfo::hash_mem().unwrap_or(4 << 20) / 2;
+//! let capacity = budget / std::mem::size_of::<(u64, u64)>();
+//! let counts: HashMap<u64, u64> = HashMap::with_capacity(capacity);
+//! ```
+
+use crate::sys::{os, pg};
+
+/// Reads the integer setting `name`, which is in units of `unit` bytes.
+fn setting(name: &str, unit: usize) -> Option<usize> {
+    pg::config_option(name)?.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+/// The physical memory of the machine, in bytes, if the OS says.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn physical_memory() -> Option<u64> {
+    os::physical_memory()
+}
+
+/// The `work_mem` setting, in bytes: how much memory each sort or hash
+/// table of a query may use before spilling to disk.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn work_mem() -> Option<usize> {
+    setting("work_mem\0", 1024)
+}
+
+/// How much memory a hash table of a query may use, in bytes: `work_mem`
+/// times `hash_mem_multiplier`.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn hash_mem() -> Option<usize> {
+    let multiplier = pg::config_option("hash_mem_multiplier\0")?.parse::<f64>().ok()?;
+    Some((work_mem()? as f64 * multiplier) as usize)
+}
+
+/// The `maintenance_work_mem` setting, in bytes: how much memory
+/// maintenance operations like `CREATE INDEX` may use.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn maintenance_work_mem() -> Option<usize> {
+    setting("maintenance_work_mem\0", 1024)
+}
+
+/// The size of postgres's shared buffer cache, in bytes, from the
+/// `shared_buffers` and `block_size` settings.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn shared_buffers() -> Option<usize> {
+    setting("shared_buffers\0", block_size()?)
+}
+
+/// The size of a postgres page, in bytes, usually 8192.
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub fn block_size() -> Option<usize> {
+    setting("block_size\0", 1)
+}
@@ -0,0 +1,280 @@
+//! Variable-length datums, without copying them.
+//!
+//! A `text` or `bytea` argument can be a gigabyte, and copying it into a
+//! `Vec<u8>` or `String` just to read it, or to return it after, doubles
+//! the memory a function needs. A [`VarlenaBuf`] is the datum itself, once
+//! detoasted, and hands out its bytes, and those of a new datum, in place.
+//!
+//! A result built up in a `Vec<u8>` need not be copied either: built in a
+//! memory context with [`VarlenaBuf::vec_in`], it turns into a datum where
+//! it is, with [`VarlenaBuf::from_vec`].
+
+use crate::io::{self, BufRead, IoSliceMut, Read};
+use crate::ops::Deref;
+use crate::os::postgres::memory::CtxAlloc;
+use crate::ptr::NonNull;
+use crate::str;
+use crate::sys::mcxt;
+use crate::sys::varlena::{self, VARHDRSZ};
+use crate::{cmp, fmt};
+
+/// The bytes of a `text`, `bytea` or other variable-length datum, in the
+/// memory postgres allocated for it.
+///
+/// This dereferences to the bytes after the varlena header, and reads them
+/// from the front like a [`Cursor`] does, with the bytes still there to
+/// dereference to. Postgres allocated the memory with `palloc`, in the
+/// memory context current when the buffer was made, and it goes away with
+/// that context, if not dropped before.
+///
+/// [`Cursor`]: crate::io::Cursor
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::io::{self, BufRead};
+/// use std::os::postgres::varlena::VarlenaBuf;
+///
+/// // `text` in and out, of a function returning the first line of its
+/// // argument.
+/// unsafe fn first_line(arg: usize) -> io::Result<usize> {
+///     let text = unsafe { VarlenaBuf::from_datum(arg)? };
+///     let line = text.lines().next().transpose()?.unwrap_or_default();
+///     Ok(VarlenaBuf::new(line.as_bytes())?.into_datum())
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct VarlenaBuf {
+    ptr: NonNull<u8>,
+    // Whether `ptr` is ours to free, rather than the datum handed in.
+    owned: bool,
+    pos: usize,
+}
+
+impl VarlenaBuf {
+    /// Makes a new varlena of `bytes`, in the current memory context.
+    ///
+    /// This fails with [`ErrorKind::InvalidInput`] if `bytes` are over the
+    /// 1GB limit of a varlena, and with [`ErrorKind::Unsupported`] outside
+    /// of a backend.
+    ///
+    /// [`ErrorKind::InvalidInput`]: crate::io::ErrorKind::InvalidInput
+    /// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn new(bytes: &[u8]) -> io::Result<VarlenaBuf> {
+        let ptr = varlena::alloc(bytes)?;
+        Ok(VarlenaBuf { ptr: unsafe { NonNull::new_unchecked(ptr) }, owned: true, pos: 0 })
+    }
+
+    /// Makes an empty vector in the memory context of `alloc`, for
+    /// [`from_vec`](VarlenaBuf::from_vec) to turn into a varlena without
+    /// copying it.
+    ///
+    /// The vector starts with [`HEADER_LEN`](VarlenaBuf::HEADER_LEN) bytes
+    /// of room for the varlena header, which are only there to be
+    /// overwritten: push the bytes of the varlena after them. It has room
+    /// for at least `capacity` of those.
+    ///
+    /// This fails with [`ErrorKind::OutOfMemory`] if the memory context
+    /// cannot allocate that much.
+    ///
+    /// [`ErrorKind::OutOfMemory`]: crate::io::ErrorKind::OutOfMemory
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(postgres_ext, allocator_api)]
+    /// use std::io;
+    /// use std::os::postgres::memory::{CtxAlloc, MemoryContext};
+    /// use std::os::postgres::varlena::VarlenaBuf;
+    ///
+    /// // `bytea` out, of a function returning `n` bytes counting up.
+    /// fn counting(n: usize) -> io::Result<usize> {
+    ///     let alloc = unsafe { CtxAlloc::new(MemoryContext::current()?) };
+    ///     let mut bytes = VarlenaBuf::vec_in(n, alloc)?;
+    ///     bytes.extend((0..n).map(|i| i as u8));
+    ///     Ok(VarlenaBuf::from_vec(bytes)?.into_datum())
+    /// }
+    /// ```
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn vec_in(capacity: usize, alloc: CtxAlloc) -> io::Result<Vec<u8, CtxAlloc>> {
+        let capacity = capacity.checked_add(VARHDRSZ).ok_or_else(out_of_memory)?;
+        let mut vec = Vec::try_with_capacity_in(capacity, alloc).map_err(|_| out_of_memory())?;
+        vec.extend_from_slice(&[0; VARHDRSZ]);
+        Ok(vec)
+    }
+
+    /// Turns `vec` into a varlena, in place: its first
+    /// [`HEADER_LEN`](VarlenaBuf::HEADER_LEN) bytes are overwritten with the
+    /// varlena header, and the rest are the bytes of the varlena.
+    ///
+    /// The varlena is in the memory context of the vector's allocator, in
+    /// the very memory the vector had, so a vector from
+    /// [`vec_in`](VarlenaBuf::vec_in) is not copied at all. A
+    /// `Box<[u8], CtxAlloc>` turns into one with [`Vec::from`].
+    ///
+    /// This fails with [`ErrorKind::InvalidInput`] if `vec` is shorter than
+    /// the header, or over the 1GB limit of a varlena.
+    ///
+    /// [`ErrorKind::InvalidInput`]: crate::io::ErrorKind::InvalidInput
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn from_vec(mut vec: Vec<u8, CtxAlloc>) -> io::Result<VarlenaBuf> {
+        if vec.len() < VARHDRSZ {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "the vector has no room for a varlena header",
+            ));
+        }
+        unsafe { varlena::init(vec.as_mut_ptr(), vec.len())? };
+        let (ptr, _, capacity, alloc) = vec.into_raw_parts_with_alloc();
+        // `u8`s are not over-aligned, so `ptr` is what palloc returned, which
+        // postgres can `pfree` once it has it.
+        unsafe { mcxt::release(alloc.context().as_ptr(), ptr, capacity) };
+        Ok(VarlenaBuf { ptr: unsafe { NonNull::new_unchecked(ptr) }, owned: true, pos: 0 })
+    }
+
+    /// The size of the varlena header, which a vector for
+    /// [`from_vec`](VarlenaBuf::from_vec) starts with room for.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub const HEADER_LEN: usize = VARHDRSZ;
+
+    /// Takes the varlena `datum`, detoasting it like `PG_DETOAST_DATUM_PACKED()`
+    /// does.
+    ///
+    /// If `datum` had to be fetched or decompressed, the buffer is a copy of
+    /// it in the current memory context, which is freed on drop. Otherwise
+    /// it is `datum` itself, which is not.
+    ///
+    /// This fails with [`ErrorKind::Unsupported`] outside of a backend.
+    ///
+    /// [`ErrorKind::Unsupported`]: crate::io::ErrorKind::Unsupported
+    ///
+    /// # Safety
+    ///
+    /// `datum` is a pointer to a varlena, like a `text` or `bytea` argument,
+    /// which stays valid for as long as the buffer is around.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub unsafe fn from_datum(datum: usize) -> io::Result<VarlenaBuf> {
+        let datum = datum as *mut u8;
+        let ptr = unsafe { varlena::detoast(datum)? };
+        Ok(VarlenaBuf { ptr: unsafe { NonNull::new_unchecked(ptr) }, owned: ptr != datum, pos: 0 })
+    }
+
+    /// Turns the buffer into a datum, to return from a function, without
+    /// copying it.
+    ///
+    /// The datum is the detoasted copy or the very datum the buffer was made
+    /// from, or the one made by [`new`](VarlenaBuf::new) or
+    /// [`from_vec`](VarlenaBuf::from_vec). Either way, it lives
+    /// in its memory context from here on.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn into_datum(self) -> usize {
+        let ptr = self.ptr.as_ptr();
+        crate::mem::forget(self);
+        ptr as usize
+    }
+
+    /// Returns the bytes as a string slice, if they are valid UTF-8.
+    ///
+    /// That is what a `text` datum is in a database whose `server_encoding`
+    /// is `UTF8`: see [`ffi`](super::ffi) for the others.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn to_str(&self) -> Result<&str, str::Utf8Error> {
+        str::from_utf8(self)
+    }
+
+    /// Returns the number of bytes read so far.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Reads from the start again.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn rewind(&mut self) {
+        self.pos = 0;
+    }
+
+    fn remaining(&self) -> &[u8] {
+        &self[self.pos..]
+    }
+}
+
+fn out_of_memory() -> io::Error {
+    io::const_io_error!(io::ErrorKind::OutOfMemory, "out of memory")
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Deref for VarlenaBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { varlena::payload(self.ptr.as_ptr()) }
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl AsRef<[u8]> for VarlenaBuf {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Read for VarlenaBuf {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.remaining().read(buf)?;
+        self.pos += n;
+        Ok(n)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let n = self.remaining().read_vectored(bufs)?;
+        self.pos += n;
+        Ok(n)
+    }
+
+    fn is_read_vectored(&self) -> bool {
+        true
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.remaining().read_exact(buf)?;
+        self.pos += buf.len();
+        Ok(())
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let n = self.remaining().read_to_end(buf)?;
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl BufRead for VarlenaBuf {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.remaining())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.len());
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl fmt::Debug for VarlenaBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VarlenaBuf").field("len", &self.len()).field("pos", &self.pos).finish()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl Drop for VarlenaBuf {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { varlena::free(self.ptr.as_ptr()) }
+        }
+    }
+}
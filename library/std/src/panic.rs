@@ -73,11 +73,12 @@ impl<T: ?Sized> RefUnwindSafe for RwLock<T> {}
 
 // https://github.com/rust-lang/rust/issues/62301
 #[stable(feature = "hashbrown", since = "1.36.0")]
-impl<K, V, S> UnwindSafe for collections::HashMap<K, V, S>
+impl<K, V, S, A> UnwindSafe for collections::HashMap<K, V, S, A>
 where
     K: UnwindSafe,
     V: UnwindSafe,
     S: UnwindSafe,
+    A: crate::alloc::Allocator + Clone + UnwindSafe,
 {
 }
 
@@ -260,7 +261,7 @@ static SHOULD_CAPTURE: AtomicUsize = AtomicUsize::new(0);
 /// environment variable; see the details in [`get_backtrace_style`].
 #[unstable(feature = "panic_backtrace_config", issue = "93346")]
 pub fn set_backtrace_style(style: BacktraceStyle) {
-    if !cfg!(feature = "backtrace") {
+    if !cfg!(feature = "backtrace") && !cfg!(target_family = "postgres") {
         // If the `backtrace` feature of this crate isn't enabled, skip setting.
         return;
     }
@@ -290,7 +291,9 @@ pub fn set_backtrace_style(style: BacktraceStyle) {
 /// Returns `None` if backtraces aren't currently supported.
 #[unstable(feature = "panic_backtrace_config", issue = "93346")]
 pub fn get_backtrace_style() -> Option<BacktraceStyle> {
-    if !cfg!(feature = "backtrace") {
+    // Postgres backtraces do without the `backtrace` feature, see
+    // `sys::postgres::backtrace`.
+    if !cfg!(feature = "backtrace") && !cfg!(target_family = "postgres") {
         // If the `backtrace` feature of this crate isn't enabled quickly return
         // `Unsupported` so this can be constant propagated all over the place
         // to optimize away callers.
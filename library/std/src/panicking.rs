@@ -292,6 +292,23 @@ fn default_hook(info: &PanicInfo<'_>) {
 #[cfg(target_family = "postgres")]
 fn default_hook(_: &PanicInfo<'_>) {}
 
+/// Logs the panic as a `WARNING`, which goes wherever postgres sends those
+/// (`log_min_messages`, `client_min_messages`). The panic may well end up an
+/// `ERROR` as well, but only if nothing catches it first.
+///
+/// This runs before the panic hook, whichever it is, so that the runtime and
+/// extensions setting hooks of their own cannot lose it. A `std` built with
+/// `pg_minimal` leaves it to the `ERROR`.
+#[cfg(target_family = "postgres")]
+fn log_panic(info: &PanicInfo<'_>) {
+    if cfg!(feature = "pg_minimal") {
+        return;
+    }
+    let thread = thread_info::current_thread();
+    let name = thread.as_ref().and_then(|t| t.name()).unwrap_or("<unnamed>");
+    crate::sys::elog::log_panic(name, info);
+}
+
 #[cfg(not(test))]
 #[doc(hidden)]
 #[unstable(feature = "update_panic_count", issue = "none")]
@@ -362,6 +379,14 @@ pub mod panic_count {
         });
     }
 
+    // Forgets about the panics in flight, when a backend abandons them by
+    // longjmp-ing out to postgres's error handling.
+    #[cfg(target_family = "postgres")]
+    pub fn reset() {
+        let count = LOCAL_PANIC_COUNT.with(|c| c.replace(0));
+        GLOBAL_PANIC_COUNT.fetch_sub(count, Ordering::Relaxed);
+    }
+
     pub fn set_always_abort() {
         GLOBAL_PANIC_COUNT.fetch_or(ALWAYS_ABORT_FLAG, Ordering::Relaxed);
     }
@@ -438,6 +463,9 @@ pub unsafe fn r#try<R, F: FnOnce() -> R>(f: F) -> Result<R, Box<dyn Any + Send>>
     // method of calling a catch panic whilst juggling ownership.
     let mut data = Data { f: ManuallyDrop::new(f) };
 
+    #[cfg(target_family = "postgres")]
+    let _frame = crate::sys::elog::CatchFrame::enter();
+
     let data_ptr = &mut data as *mut _ as *mut u8;
     // SAFETY:
     //
@@ -674,10 +702,28 @@ fn rust_panic_with_hook(
             // a `Backtrace` will allocate, which we must to avoid here.
             let panicinfo = PanicInfo::internal_constructor(message, location, can_unwind);
             rtprintpanic!("{panicinfo}\npanicked after panic::always_abort(), aborting.\n");
+            // A backend has its own way out, which leaves shared memory in
+            // order.
+            #[cfg(target_family = "postgres")]
+            crate::sys::elog::exit_fatal(&panicinfo, "Panicked after panic::always_abort().");
         }
         crate::sys::abort_internal();
     }
 
+    // Postgres hears of the panic before the hook runs. Formatting a
+    // `panic!` message into the payload allocates, and the panic may be about
+    // running out of memory, so the message goes straight into postgres's
+    // memory instead.
+    #[cfg(target_family = "postgres")]
+    {
+        let mut info = PanicInfo::internal_constructor(message, location, can_unwind);
+        if message.is_none() {
+            info.set_payload(payload.get());
+        }
+        crate::sys::elog::record_panic(&info, panics > 1);
+        log_panic(&info);
+    }
+
     let mut info = PanicInfo::internal_constructor(message, location, can_unwind);
     let hook = HOOK.read().unwrap_or_else(PoisonError::into_inner);
     match *hook {
@@ -699,6 +745,40 @@ fn rust_panic_with_hook(
     };
     drop(hook);
 
+    // A backend need not die for a panic that cannot unwind: an `ERROR`
+    // longjmps straight back to postgres, leaving the unwinding behind, and
+    // only aborts the transaction. The same goes for a panic with no
+    // `catch_unwind` to go to before postgres's C frames, which the unwinder
+    // would run through without a care for `PG_TRY()`, as out of an
+    // `extern "C-unwind"` function the function manager calls directly. A
+    // panic while unwinding, though, interrupted cleanup halfway, so that
+    // becomes a `PANIC` naming both, and the postmaster restarts the server
+    // rather than a bare SIGABRT.
+    #[cfg(target_family = "postgres")]
+    if panics > 1 || !can_unwind || !crate::sys::elog::can_catch() {
+        let payload = if message.is_none() { Some(payload.get()) } else { None };
+        let report = if panics > 1 {
+            crate::sys::elog::nested_panic_report(payload)
+        } else {
+            crate::sys::elog::panic_report(payload)
+        };
+        if let Some(report) = report {
+            panic_count::reset();
+            report.throw();
+        }
+    }
+
+    // Built with `panic=abort`, `std` cannot unwind into a `catch_unwind`
+    // either, and the backend exits rather than aborting.
+    #[cfg(all(target_family = "postgres", panic = "abort"))]
+    {
+        let payload = if message.is_none() { Some(payload.get()) } else { None };
+        if let Some(report) = crate::sys::elog::fatal_panic_report(payload) {
+            panic_count::reset();
+            report.throw();
+        }
+    }
+
     if panics > 1 || !can_unwind {
         // If a thread panics while it's already unwinding then we
         // have limited options. Currently our preference is to
@@ -55,7 +55,7 @@ struct BarrierState {
 /// let barrier_wait_result = barrier.wait();
 /// ```
 #[stable(feature = "rust1", since = "1.0.0")]
-pub struct BarrierWaitResult(bool);
+pub struct BarrierWaitResult(pub(crate) bool);
 
 #[stable(feature = "std_debug", since = "1.16.0")]
 impl fmt::Debug for Barrier {
@@ -176,6 +176,9 @@ pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 pub use self::lazy_lock::LazyLock;
 #[unstable(feature = "once_cell", issue = "74465")]
 pub use self::once_lock::OnceLock;
+#[cfg(target_family = "postgres")]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub use self::reentrant_lock::{ReentrantLock, ReentrantLockGuard};
 
 pub mod mpsc;
 
@@ -187,4 +190,6 @@ mod mutex;
 mod once;
 mod once_lock;
 mod poison;
+#[cfg(target_family = "postgres")]
+mod reentrant_lock;
 mod rwlock;
@@ -111,6 +111,11 @@ impl Backoff {
     ///     before falling back to parking the thread (see `Backoff::is_completed`).
     #[inline]
     pub fn spin_light(&self) {
+        // A backend has no other thread to wait for, only a cancelled
+        // statement to notice.
+        #[cfg(target_family = "postgres")]
+        crate::sys::pg::panic_on_interrupt();
+
         let step = self.step.get().min(SPIN_LIMIT);
         for _ in 0..step.pow(2) {
             crate::hint::spin_loop();
@@ -125,6 +130,8 @@ impl Backoff {
     #[inline]
     pub fn spin_heavy(&self) {
         if self.step.get() <= SPIN_LIMIT {
+            #[cfg(target_family = "postgres")]
+            crate::sys::pg::panic_on_interrupt();
             for _ in 0..self.step.get().pow(2) {
                 crate::hint::spin_loop()
             }
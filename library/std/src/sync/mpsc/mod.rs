@@ -153,6 +153,17 @@ use crate::fmt;
 use crate::sync::mpmc;
 use crate::time::{Duration, Instant};
 
+/// Channels to other backends, such as background workers, through dynamic
+/// shared memory. The same as [`std::os::postgres::mpsc`].
+///
+/// [`std::os::postgres::mpsc`]: crate::os::postgres::mpsc
+#[cfg(target_family = "postgres")]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub mod backend {
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub use crate::os::postgres::mpsc::*;
+}
+
 /// The receiving half of Rust's [`channel`] (or [`sync_channel`]) type.
 /// This half can only be owned by one thread.
 ///
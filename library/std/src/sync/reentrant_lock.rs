@@ -0,0 +1,125 @@
+use crate::fmt;
+use crate::ops::Deref;
+use crate::panic::{RefUnwindSafe, UnwindSafe};
+use crate::sys_common::remutex::{ReentrantMutex, ReentrantMutexGuard};
+
+/// A mutual exclusion lock that the thread holding it can lock again.
+///
+/// This is the lock behind `stdout().lock()`. Where a [`Mutex`] deadlocks
+/// (or panics) when the thread holding it locks it again, a `ReentrantLock`
+/// just hands out another guard, and is only released when the last guard is
+/// dropped. In a backend, that is what extension code calling into postgres
+/// needs around state that postgres may call back into: a Rust function
+/// holding the lock can run a query through SPI, whose execution calls
+/// another Rust function of the same extension, which takes the lock again.
+///
+/// Because several guards may be alive at once, the guard only derefs to
+/// `&T`; put a [`RefCell`] inside for mutable access. Like the rest of the
+/// postgres-specific locks, a `ReentrantLock` is never poisoned.
+///
+/// [`Mutex`]: crate::sync::Mutex
+/// [`RefCell`]: crate::cell::RefCell
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(postgres_ext)]
+/// use std::cell::RefCell;
+/// use std::collections::HashMap;
+/// use std::sync::ReentrantLock;
+///
+/// static TYPE_CACHE: ReentrantLock<RefCell<Option<HashMap<u32, String>>>> =
+///     ReentrantLock::new(RefCell::new(None));
+///
+/// fn type_name(oid: u32, lookup: impl FnOnce(u32) -> String) -> String {
+///     let cache = TYPE_CACHE.lock();
+///     if let Some(name) = cache.borrow().as_ref().and_then(|c| c.get(&oid)) {
+///         return name.clone();
+///     }
+///     // `lookup` may end up calling `type_name` again: no deadlock, as long
+///     // as no `RefCell` borrow is held across the call.
+///     let name = lookup(oid);
+///     cache.borrow_mut().get_or_insert_with(HashMap::new).insert(oid, name.clone());
+///     name
+/// }
+/// ```
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct ReentrantLock<T> {
+    inner: ReentrantMutex<T>,
+}
+
+/// RAII structure used to release a [`ReentrantLock`] when dropped.
+#[must_use = "if unused the ReentrantLock will immediately unlock"]
+#[unstable(feature = "postgres_ext", issue = "none")]
+pub struct ReentrantLockGuard<'a, T: 'a> {
+    inner: ReentrantMutexGuard<'a, T>,
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> UnwindSafe for ReentrantLock<T> {}
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> RefUnwindSafe for ReentrantLock<T> {}
+
+impl<T> ReentrantLock<T> {
+    /// Creates a new reentrant lock in an unlocked state.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub const fn new(t: T) -> ReentrantLock<T> {
+        ReentrantLock { inner: ReentrantMutex::new(t) }
+    }
+
+    /// Acquires the lock, blocking until it is available. If the current
+    /// thread already holds the lock, this succeeds without blocking.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn lock(&self) -> ReentrantLockGuard<'_, T> {
+        ReentrantLockGuard { inner: self.inner.lock() }
+    }
+
+    /// Acquires the lock if it is available or already held by the current
+    /// thread, without blocking.
+    #[unstable(feature = "postgres_ext", issue = "none")]
+    pub fn try_lock(&self) -> Option<ReentrantLockGuard<'_, T>> {
+        self.inner.try_lock().map(|inner| ReentrantLockGuard { inner })
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> Deref for ReentrantLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: Default> Default for ReentrantLock<T> {
+    fn default() -> ReentrantLock<T> {
+        ReentrantLock::new(Default::default())
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T> From<T> for ReentrantLock<T> {
+    fn from(t: T) -> Self {
+        ReentrantLock::new(t)
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: fmt::Debug> fmt::Debug for ReentrantLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("ReentrantLock");
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+#[unstable(feature = "postgres_ext", issue = "none")]
+impl<T: fmt::Debug> fmt::Debug for ReentrantLockGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
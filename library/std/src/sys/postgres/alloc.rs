@@ -7,11 +7,13 @@ use crate::sys::common::alloc::{realloc_fallback, MIN_ALIGN};
 unsafe impl GlobalAlloc for System {
     #[inline]
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        #[cfg(feature = "pg_debug")]
+        no_alloc::note(layout.size());
         // jemalloc provides alignment less than MIN_ALIGN for small allocations.
         // So only rely on MIN_ALIGN if size >= align.
         // Also see <https://github.com/rust-lang/rust/issues/45955> and
         // <https://github.com/rust-lang/rust/issues/62251#issuecomment-507580914>.
-        if layout.align() <= MIN_ALIGN && layout.align() <= layout.size() {
+        let ptr = if layout.align() <= MIN_ALIGN && layout.align() <= layout.size() {
             libc::malloc(layout.size()) as *mut u8
         } else {
             #[cfg(target_os = "macos")]
@@ -21,14 +23,22 @@ unsafe impl GlobalAlloc for System {
                 }
             }
             aligned_malloc(&layout)
-        }
+        };
+        #[cfg(feature = "pg_debug")]
+        leaks::track(ptr, layout.size());
+        ptr
     }
 
     #[inline]
     unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
         // See the comment above in `alloc` for why this check looks the way it does.
         if layout.align() <= MIN_ALIGN && layout.align() <= layout.size() {
-            libc::calloc(layout.size(), 1) as *mut u8
+            #[cfg(feature = "pg_debug")]
+            no_alloc::note(layout.size());
+            let ptr = libc::calloc(layout.size(), 1) as *mut u8;
+            #[cfg(feature = "pg_debug")]
+            leaks::track(ptr, layout.size());
+            ptr
         } else {
             let ptr = self.alloc(layout);
             if !ptr.is_null() {
@@ -40,19 +50,247 @@ unsafe impl GlobalAlloc for System {
 
     #[inline]
     unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        #[cfg(feature = "pg_debug")]
+        leaks::untrack(ptr);
         libc::free(ptr as *mut libc::c_void)
     }
 
     #[inline]
     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
         if layout.align() <= MIN_ALIGN && layout.align() <= new_size {
-            libc::realloc(ptr as *mut libc::c_void, new_size) as *mut u8
+            #[cfg(feature = "pg_debug")]
+            no_alloc::note(new_size);
+            let new = libc::realloc(ptr as *mut libc::c_void, new_size) as *mut u8;
+            #[cfg(feature = "pg_debug")]
+            if !new.is_null() {
+                leaks::untrack(ptr);
+                leaks::track(new, new_size);
+            }
+            new
         } else {
             realloc_fallback(self, ptr, layout, new_size)
         }
     }
 }
 
+/// Allocation-free scopes, for `os::postgres::debug::assert_no_alloc`.
+///
+/// A global allocator must not unwind, so an allocation in a scope is only
+/// counted, with a backtrace of the first one, for the scope to panic with
+/// when it ends.
+#[cfg(feature = "pg_debug")]
+pub mod no_alloc {
+    use crate::backtrace::Backtrace;
+    use crate::cell::{Cell, RefCell};
+
+    /// What was allocated in the innermost scope.
+    pub struct Found {
+        pub count: usize,
+        pub bytes: usize,
+        pub first: Backtrace,
+    }
+
+    #[thread_local]
+    static DEPTH: Cell<usize> = Cell::new(0);
+    #[thread_local]
+    static FOUND: RefCell<Option<Found>> = RefCell::new(None);
+
+    /// Enters a scope, and returns what was allocated so far in the one
+    /// around it, for [`exit`] to put back.
+    pub fn enter() -> Option<Found> {
+        DEPTH.set(DEPTH.get() + 1);
+        FOUND.take()
+    }
+
+    /// Leaves the innermost scope, and returns what was allocated in it.
+    /// `outer` is what [`enter`] returned.
+    pub fn exit(outer: Option<Found>) -> Option<Found> {
+        DEPTH.set(DEPTH.get().saturating_sub(1));
+        FOUND.replace(outer)
+    }
+
+    #[inline]
+    pub fn note(size: usize) {
+        if DEPTH.get() != 0 {
+            note_slow(size);
+        }
+    }
+
+    #[cold]
+    fn note_slow(size: usize) {
+        // Capturing the backtrace allocates too.
+        let depth = DEPTH.replace(0);
+        let mut found = FOUND.borrow_mut();
+        match &mut *found {
+            Some(found) => {
+                found.count += 1;
+                found.bytes += size;
+            }
+            None => {
+                *found = Some(Found { count: 1, bytes: size, first: Backtrace::force_capture() })
+            }
+        }
+        drop(found);
+        DEPTH.set(depth);
+    }
+}
+
+/// What is still allocated at backend exit, and where it was allocated.
+///
+/// Each allocation is recorded with the innermost frames of the stack that
+/// made it, and forgotten about when it is freed. At exit, what is left is
+/// grouped by the first of those frames outside of std, and logged, the
+/// biggest first: a cache that is filled once and kept shows up as a few
+/// allocations from one place, a leak as many.
+#[cfg(feature = "pg_debug")]
+mod leaks {
+    use crate::cell::{Cell, RefCell};
+    use crate::collections::HashMap;
+    use crate::fmt::Write;
+    use crate::hash::{BuildHasherDefault, Hasher};
+    use crate::sync::atomic::{AtomicBool, Ordering};
+    use crate::sys::backtrace;
+    use crate::sys::{elog, pg};
+    use libc::c_int;
+
+    backend! {
+        fn on_proc_exit(function: extern "C" fn(c_int, usize), arg: usize);
+    }
+
+    /// How many frames are recorded with each allocation: enough to get
+    /// past `Vec`, `String` and friends.
+    const FRAMES: usize = 16;
+    /// How many call sites are logged at most.
+    const SITES: usize = 50;
+
+    struct Live {
+        size: usize,
+        frames: [usize; FRAMES],
+    }
+
+    /// The addresses are unique already.
+    #[derive(Default)]
+    struct AddrHasher(u64);
+
+    impl Hasher for AddrHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+        fn write(&mut self, _: &[u8]) {
+            unreachable!()
+        }
+        fn write_usize(&mut self, n: usize) {
+            // Allocations are at least 8-aligned, and mostly 16-aligned.
+            self.0 = (n as u64 >> 4).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        }
+    }
+
+    /// Whether allocations are recorded: only in a backend, which has an
+    /// exit to report them at.
+    static TRACKING: AtomicBool = AtomicBool::new(false);
+
+    // Set while recording, for the table's own allocations not to be.
+    #[thread_local]
+    static BUSY: Cell<bool> = Cell::new(false);
+    #[thread_local]
+    static LIVE: RefCell<Option<HashMap<usize, Live, BuildHasherDefault<AddrHasher>>>> =
+        RefCell::new(None);
+
+    // Registers the report once, as the library is loaded into a backend:
+    // the allocator itself has no business calling into postgres.
+    #[cfg_attr(target_os = "linux", link_section = ".init_array.00099")]
+    #[cfg_attr(target_os = "macos", link_section = "__DATA,__mod_init_func")]
+    #[used]
+    static REGISTER_AT_LOAD: extern "C" fn() = {
+        extern "C" fn register() {
+            if let (Some(on_proc_exit), Some(_)) = (on_proc_exit.get(), pg::stack_base()) {
+                unsafe { on_proc_exit(report, 0) };
+                TRACKING.store(true, Ordering::Relaxed);
+            }
+        }
+        register
+    };
+
+    #[inline]
+    pub fn track(ptr: *mut u8, size: usize) {
+        if !ptr.is_null() && !BUSY.get() && TRACKING.load(Ordering::Relaxed) {
+            let mut frames = [0; FRAMES];
+            backtrace::trace_into(&mut frames);
+            BUSY.set(true);
+            LIVE.borrow_mut()
+                .get_or_insert_with(Default::default)
+                .insert(ptr.addr(), Live { size, frames });
+            BUSY.set(false);
+        }
+    }
+
+    #[inline]
+    pub fn untrack(ptr: *mut u8) {
+        if !BUSY.get() && TRACKING.load(Ordering::Relaxed) {
+            BUSY.set(true);
+            if let Some(live) = &mut *LIVE.borrow_mut() {
+                live.remove(&ptr.addr());
+            }
+            BUSY.set(false);
+        }
+    }
+
+    /// Whether the frame at `ip` is std's own, or the allocator's: the call
+    /// site is the first frame after those.
+    fn is_std(ip: usize) -> bool {
+        let Some(symbol) = backtrace::resolve(ip) else { return false };
+        let Some((name, _)) = symbol.name else { return false };
+        let name = name.trim_start_matches('<');
+        ["alloc::", "core::", "std::", "__rust", "__rdl", "__rg"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    }
+
+    extern "C" fn report(_code: c_int, _arg: usize) {
+        BUSY.set(true);
+        let Some(live) = LIVE.borrow_mut().take() else { return };
+
+        // Bytes and allocations, by call site.
+        let mut sites: HashMap<usize, (usize, usize)> = HashMap::new();
+        let mut std_frames: HashMap<usize, bool> = HashMap::new();
+        let (mut bytes, mut count) = (0, 0);
+        for live in live.values() {
+            let site = live
+                .frames
+                .iter()
+                .copied()
+                .find(|&ip| ip != 0 && !*std_frames.entry(ip).or_insert_with(|| is_std(ip)));
+            let site = sites.entry(site.unwrap_or(0)).or_default();
+            site.0 += live.size;
+            site.1 += 1;
+            bytes += live.size;
+            count += 1;
+        }
+        if count == 0 {
+            return;
+        }
+        let mut sites: Vec<_> = sites.into_iter().collect();
+        sites.sort_by(|a, b| b.1.cmp(&a.1));
+
+        let mut out = format!("{bytes} bytes in {count} Rust allocations still live at exit");
+        for &(ip, (bytes, count)) in sites.iter().take(SITES) {
+            let _ = write!(out, "\n{bytes:>12} bytes in {count:>8} allocations from ");
+            let _ = match backtrace::resolve(ip).filter(|_| ip != 0) {
+                Some(symbol) => write!(out, "{symbol}"),
+                None if ip == 0 => write!(out, "an unknown call site"),
+                None => write!(out, "{ip:#x}"),
+            };
+        }
+        if sites.len() > SITES {
+            let _ = write!(out, "\n... and {} more call sites", sites.len() - SITES);
+        }
+        if let Ok(Some(mut report)) = elog::Report::start(elog::Level::Log) {
+            report.message(out);
+            report.finish();
+        }
+    }
+}
+
 cfg_if::cfg_if! {
     if #[cfg(any(
         target_os = "android",
@@ -1,36 +1,60 @@
+//! Arguments of a backend.
+//!
+//! The command line of a backend is the postmaster's, options and all,
+//! which is no business of the code running in it. `env::args` is the
+//! program name, `postgres`, followed by whatever the embedding runtime set
+//! for the function being called.
+
+use crate::cell::RefCell;
 use crate::ffi::OsString;
 use crate::fmt;
+use crate::mem;
+use crate::vec;
+
+thread_local! {
+    static ARGS: RefCell<Vec<OsString>> = const { RefCell::new(Vec::new()) };
+}
 
-pub struct Args {}
+/// Replaces the arguments after the program name, returning the previous
+/// ones.
+pub fn replace(args: Vec<OsString>) -> Vec<OsString> {
+    ARGS.with(|a| mem::replace(&mut *a.borrow_mut(), args))
+}
+
+pub struct Args {
+    iter: vec::IntoIter<OsString>,
+}
 
 pub fn args() -> Args {
-    Args {}
+    let mut args = vec![OsString::from("postgres")];
+    ARGS.with(|a| args.extend(a.borrow().iter().cloned()));
+    Args { iter: args.into_iter() }
 }
 
 impl fmt::Debug for Args {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_list().finish()
+        f.debug_list().entries(self.iter.as_slice()).finish()
     }
 }
 
 impl Iterator for Args {
     type Item = OsString;
     fn next(&mut self) -> Option<OsString> {
-        None
+        self.iter.next()
     }
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (0, Some(0))
+        self.iter.size_hint()
     }
 }
 
 impl ExactSizeIterator for Args {
     fn len(&self) -> usize {
-        0
+        self.iter.len()
     }
 }
 
 impl DoubleEndedIterator for Args {
     fn next_back(&mut self) -> Option<OsString> {
-        None
+        self.iter.next_back()
     }
 }
@@ -1,3 +1,11 @@
+use crate::ffi::CStr;
+use crate::fmt::{self, Write};
+use crate::mem;
+use crate::panic::{self, BacktraceStyle};
+use crate::path::Path;
+use crate::ptr;
+use crate::sys::pg;
+
 #[inline(never)]
 pub fn __rust_begin_short_backtrace<F, T>(f: F) -> T
 where
@@ -23,3 +31,194 @@ where
 
     result
 }
+
+/// With [`BacktraceStyle::Short`], how many frames to show at most.
+const SHORT_FRAMES: usize = 32;
+
+/// How many frames to walk at most, in case the frame pointer chain loops.
+const MAX_FRAMES: usize = 1024;
+
+/// Formats the stack of the current thread for the `CONTEXT` of an error
+/// report, if backtraces have been turned on with
+/// `std::panic::set_backtrace_style`.
+///
+/// A `std` built with `pg_minimal` has no backtraces at all: neither these
+/// nor [`Backtrace`](crate::backtrace::Backtrace)s, which are always
+/// unsupported.
+pub fn capture() -> Option<String> {
+    if cfg!(feature = "pg_minimal") {
+        return None;
+    }
+    let limit = match panic::get_backtrace_style()? {
+        BacktraceStyle::Off => return None,
+        BacktraceStyle::Short => SHORT_FRAMES,
+        BacktraceStyle::Full => usize::MAX,
+    };
+
+    let ips = trace();
+    let mut out = String::from("Rust backtrace:");
+    for (i, &ip) in ips.iter().take(limit).enumerate() {
+        let _ = write!(out, "\n{i:4}: ");
+        let _ = match resolve(ip) {
+            Some(symbol) => write!(out, "{symbol}"),
+            None => write!(out, "{ip:#x}"),
+        };
+    }
+    if ips.len() > limit {
+        let _ = write!(out, "\n      ... {} more frames", ips.len() - limit);
+    }
+    Some(out)
+}
+
+/// The return addresses of the frames calling this function, innermost
+/// first, found by following the chain of frame pointers.
+///
+/// This needs no unwind tables, only frame pointers, which the postgres
+/// targets always keep. The walk stops at the first frame of code compiled
+/// without them (postgres itself, on most builds), since its frame pointer
+/// register holds anything but a link up the stack.
+#[inline(never)]
+pub fn trace() -> Vec<usize> {
+    let mut ips = Vec::new();
+    walk(|ip| {
+        ips.push(ip);
+        ips.len() < MAX_FRAMES
+    });
+    ips
+}
+
+/// Like [`trace`], but only the innermost frames that fit into `ips`,
+/// without allocating. Returns how many there were.
+#[inline(never)]
+pub fn trace_into(ips: &mut [usize]) -> usize {
+    let mut n = 0;
+    walk(|ip| {
+        if n < ips.len() {
+            ips[n] = ip;
+            n += 1;
+        }
+        n < ips.len()
+    });
+    n
+}
+
+/// Calls `f` with each return address [`trace`] finds, for as long as it
+/// returns `true`. Inlined, for the walk to start at its caller's frame.
+#[inline(always)]
+fn walk(mut f: impl FnMut(usize) -> bool) {
+    if cfg!(feature = "pg_minimal") {
+        return;
+    }
+    let Some(top) = stack_top() else { return };
+    let mut fp = frame_pointer();
+    loop {
+        // A frame record is the frame pointer of the caller, followed by the
+        // return address into it. Anything outside of the stack, or not
+        // above the previous record, is not one.
+        if fp == 0
+            || fp % mem::align_of::<usize>() != 0
+            || top.saturating_sub(fp) < 2 * mem::size_of::<usize>()
+        {
+            break;
+        }
+        let record = ptr::from_exposed_addr::<usize>(fp);
+        let (next, ip) = unsafe { (*record, *record.add(1)) };
+        if ip == 0 || !f(ip) || next <= fp {
+            break;
+        }
+        fp = next;
+    }
+}
+
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let fp: usize;
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        crate::arch::asm!("mov {}, rbp", out(reg) fp, options(nomem, nostack, preserves_flags))
+    };
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        crate::arch::asm!("mov {}, x29", out(reg) fp, options(nomem, nostack, preserves_flags))
+    };
+    #[cfg(target_arch = "x86")]
+    unsafe {
+        crate::arch::asm!("mov {}, ebp", out(reg) fp, options(nomem, nostack, preserves_flags))
+    };
+    // In ARM (rather than Thumb) code, which is what the target is built as,
+    // with the same frame records as on AArch64.
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        crate::arch::asm!("mov {}, r11", out(reg) fp, options(nomem, nostack, preserves_flags))
+    };
+    fp
+}
+
+/// The upper end of the stack of the current thread, which frame records
+/// must be below.
+fn stack_top() -> Option<usize> {
+    pg::stack_base().or_else(thread_stack_top)
+}
+
+#[cfg(target_os = "linux")]
+fn thread_stack_top() -> Option<usize> {
+    unsafe {
+        let mut attr: libc::pthread_attr_t = mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            return None;
+        }
+        let mut addr = ptr::null_mut();
+        let mut size = 0;
+        let ret = libc::pthread_attr_getstack(&attr, &mut addr, &mut size);
+        libc::pthread_attr_destroy(&mut attr);
+        (ret == 0).then(|| addr.addr() + size)
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn thread_stack_top() -> Option<usize> {
+    Some(unsafe { libc::pthread_get_stackaddr_np(libc::pthread_self()) }.addr())
+}
+
+/// What the dynamic linker knows about a code address.
+///
+/// There is no debug info to symbolize with here, so frames are named after
+/// the nearest symbol the dynamic linker knows, and otherwise shown as an
+/// offset into their shared object, for `addr2line` to resolve later.
+pub struct Symbol {
+    /// The demangled name of the nearest exported symbol, and how far past
+    /// its start the address is.
+    pub name: Option<(String, usize)>,
+    /// The file name of the shared object, and the offset into it.
+    pub object: (String, usize),
+}
+
+/// Looks up the return address `ip` with `dladdr`.
+pub fn resolve(ip: usize) -> Option<Symbol> {
+    if cfg!(feature = "pg_minimal") {
+        return None;
+    }
+    let mut info: libc::Dl_info = unsafe { mem::zeroed() };
+    // The return address points after the call.
+    if unsafe { libc::dladdr(ptr::invalid(ip.wrapping_sub(1)), &mut info) } == 0
+        || info.dli_fname.is_null()
+    {
+        return None;
+    }
+    let name = (!info.dli_sname.is_null()).then(|| {
+        let name = unsafe { CStr::from_ptr(info.dli_sname) }.to_string_lossy();
+        (format!("{:#}", rustc_demangle::demangle(&name)), ip.wrapping_sub(info.dli_saddr.addr()))
+    });
+    let object = unsafe { CStr::from_ptr(info.dli_fname) }.to_string_lossy();
+    let object = Path::new(&*object).file_name().map_or(&*object, |f| f.to_str().unwrap_or("?"));
+    Some(Symbol { name, object: (object.to_owned(), ip.wrapping_sub(info.dli_fbase.addr())) })
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((name, offset)) = &self.name {
+            write!(f, "{name}+{offset:#x} ")?;
+        }
+        write!(f, "({}+{:#x})", self.object.0, self.object.1)
+    }
+}
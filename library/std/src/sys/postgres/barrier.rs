@@ -0,0 +1,73 @@
+//! Postgres's `Barrier` (storage/barrier.h), the phase barrier parallel
+//! executor nodes use to coordinate the leader and its workers.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use crate::io;
+use crate::sys::pg::{require, PG_WAIT_EXTENSION};
+use crate::sys::spin::SpinLock;
+use libc::c_int;
+
+/// `ConditionVariable` from storage/condition_variable.h.
+#[repr(C)]
+struct ConditionVariable {
+    mutex: SpinLock,
+    wakeup_head: c_int,
+    wakeup_tail: c_int,
+}
+
+/// `Barrier` from storage/barrier.h. Only ever touched by the backend.
+#[repr(C)]
+pub struct Barrier {
+    mutex: SpinLock,
+    phase: c_int,
+    participants: c_int,
+    arrived: c_int,
+    elected: c_int,
+    static_party: bool,
+    condition_variable: ConditionVariable,
+}
+
+backend! {
+    fn BarrierInit(barrier: *mut Barrier, num_workers: c_int);
+    fn BarrierArriveAndWait(barrier: *mut Barrier, wait_event_info: u32) -> bool;
+    fn BarrierArriveAndDetach(barrier: *mut Barrier) -> bool;
+    fn BarrierAttach(barrier: *mut Barrier) -> c_int;
+    fn BarrierDetach(barrier: *mut Barrier) -> bool;
+    fn BarrierPhase(barrier: *mut Barrier) -> c_int;
+    fn BarrierParticipants(barrier: *mut Barrier) -> c_int;
+}
+
+impl Barrier {
+    /// Initializes the barrier at `barrier`, which must be in shared memory,
+    /// for a static party of `participants`, or for a dynamic one if zero.
+    pub unsafe fn initialize(barrier: *mut Barrier, participants: c_int) -> io::Result<()> {
+        unsafe { require(&BarrierInit)?(barrier, participants) };
+        Ok(())
+    }
+
+    /// Returns whether this backend was elected to run the serial part of the
+    /// phase that just ended.
+    pub unsafe fn arrive_and_wait(barrier: *mut Barrier) -> bool {
+        unsafe { BarrierArriveAndWait.get().unwrap()(barrier, PG_WAIT_EXTENSION) }
+    }
+
+    pub unsafe fn arrive_and_detach(barrier: *mut Barrier) -> bool {
+        unsafe { BarrierArriveAndDetach.get().unwrap()(barrier) }
+    }
+
+    pub unsafe fn attach(barrier: *mut Barrier) -> c_int {
+        unsafe { BarrierAttach.get().unwrap()(barrier) }
+    }
+
+    pub unsafe fn detach(barrier: *mut Barrier) -> bool {
+        unsafe { BarrierDetach.get().unwrap()(barrier) }
+    }
+
+    pub unsafe fn phase(barrier: *mut Barrier) -> c_int {
+        unsafe { BarrierPhase.get().unwrap()(barrier) }
+    }
+
+    pub unsafe fn participants(barrier: *mut Barrier) -> c_int {
+        unsafe { BarrierParticipants.get().unwrap()(barrier) }
+    }
+}
@@ -0,0 +1,132 @@
+//! Registering and supervising dynamic background workers.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use crate::io;
+use crate::ptr;
+use crate::sys::pg::{self, require};
+use libc::{c_char, c_int, c_void};
+
+// postmaster/bgworker.h
+const BGW_MAXLEN: usize = 96;
+const BGW_EXTRALEN: usize = 128;
+const BGWORKER_SHMEM_ACCESS: c_int = 0x0001;
+const BGWORKER_BACKEND_DATABASE_CONNECTION: c_int = 0x0002;
+const BgWorkerStart_ConsistentState: c_int = 1;
+const BGW_NEVER_RESTART: c_int = -1;
+const BGWH_STARTED: c_int = 0;
+const BGWH_STOPPED: c_int = 2;
+
+#[repr(C)]
+struct BackgroundWorker {
+    bgw_name: [c_char; BGW_MAXLEN],
+    bgw_type: [c_char; BGW_MAXLEN],
+    bgw_flags: c_int,
+    bgw_start_time: c_int,
+    bgw_restart_time: c_int,
+    bgw_library_name: [c_char; BGW_MAXLEN],
+    bgw_function_name: [c_char; BGW_MAXLEN],
+    bgw_main_arg: usize,
+    bgw_extra: [c_char; BGW_EXTRALEN],
+    bgw_notify_pid: libc::pid_t,
+}
+
+backend! {
+    fn RegisterDynamicBackgroundWorker(
+        worker: *mut BackgroundWorker,
+        handle: *mut *mut c_void,
+    ) -> bool;
+    fn WaitForBackgroundWorkerStartup(handle: *mut c_void, pidp: *mut libc::pid_t) -> c_int;
+    fn WaitForBackgroundWorkerShutdown(handle: *mut c_void) -> c_int;
+    fn TerminateBackgroundWorker(handle: *mut c_void);
+}
+
+fn copy_name(dst: &mut [c_char; BGW_MAXLEN], src: &str, what: &'static str) -> io::Result<()> {
+    let src = src.as_bytes();
+    if src.len() >= BGW_MAXLEN || src.contains(&0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "background worker {what} must be shorter than {BGW_MAXLEN} bytes and not contain nul bytes"
+            ),
+        ));
+    }
+    for (d, &s) in dst.iter_mut().zip(src) {
+        *d = s as c_char;
+    }
+    Ok(())
+}
+
+/// What to run in a new background worker.
+pub struct Spec<'a> {
+    pub name: &'a str,
+    pub library: &'a str,
+    pub function: &'a str,
+    pub main_arg: usize,
+}
+
+/// A registered dynamic background worker.
+///
+/// The underlying `BackgroundWorkerHandle` is palloc'd by postgres in the
+/// current memory context, and goes away with it.
+pub struct Handle(*mut c_void);
+
+impl Handle {
+    pub fn register(spec: &Spec<'_>) -> io::Result<Handle> {
+        let register = require(&RegisterDynamicBackgroundWorker)?;
+
+        let mut worker: BackgroundWorker = unsafe { crate::mem::zeroed() };
+        copy_name(&mut worker.bgw_name, spec.name, "name")?;
+        copy_name(&mut worker.bgw_type, spec.name, "name")?;
+        copy_name(&mut worker.bgw_library_name, spec.library, "library name")?;
+        copy_name(&mut worker.bgw_function_name, spec.function, "function name")?;
+        worker.bgw_flags = BGWORKER_SHMEM_ACCESS | BGWORKER_BACKEND_DATABASE_CONNECTION;
+        worker.bgw_start_time = BgWorkerStart_ConsistentState;
+        worker.bgw_restart_time = BGW_NEVER_RESTART;
+        worker.bgw_main_arg = spec.main_arg;
+        // Have the postmaster signal us when the worker starts and stops, so
+        // the waits below don't have to poll.
+        worker.bgw_notify_pid = pg::my_proc_pid().unwrap_or(0) as libc::pid_t;
+
+        let mut handle = ptr::null_mut();
+        if unsafe { register(&mut worker, &mut handle) } {
+            Ok(Handle(handle))
+        } else {
+            Err(io::const_io_error!(
+                io::ErrorKind::WouldBlock,
+                "no free background worker slots; consider increasing max_worker_processes",
+            ))
+        }
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+
+    /// Waits until the worker has started, and returns its PID.
+    pub fn wait_for_startup(&self) -> io::Result<u32> {
+        let mut pid = 0;
+        match unsafe { WaitForBackgroundWorkerStartup.get().unwrap()(self.0, &mut pid) } {
+            BGWH_STARTED => Ok(pid as u32),
+            BGWH_STOPPED => Err(io::const_io_error!(
+                io::ErrorKind::Other,
+                "background worker exited before it could be waited for",
+            )),
+            _ => Err(io::const_io_error!(
+                io::ErrorKind::Other,
+                "background worker could not be started",
+            )),
+        }
+    }
+
+    /// Waits until the worker has exited.
+    pub fn wait_for_shutdown(&self) -> io::Result<()> {
+        match unsafe { WaitForBackgroundWorkerShutdown.get().unwrap()(self.0) } {
+            BGWH_STOPPED => Ok(()),
+            _ => Err(io::const_io_error!(io::ErrorKind::Other, "the postmaster died")),
+        }
+    }
+
+    pub fn terminate(&self) {
+        unsafe { TerminateBackgroundWorker.get().unwrap()(self.0) }
+    }
+}
@@ -0,0 +1,43 @@
+//! Finding blocking calls that interrupts cannot cut short.
+//!
+//! Waiting on the process latch is what lets `pg_cancel_backend()`,
+//! `statement_timeout` and the death of the postmaster stop a backend. A few
+//! calls in std block in the kernel instead: resolving host names, accepting
+//! connections on a blocking listener, and sleeping or polling where there
+//! is no latch to wait on. In a std built with the `pg_debug` feature, the
+//! first of each that a backend makes logs a `WARNING` with a backtrace, to
+//! find the code paths that cannot be cancelled.
+
+/// Notes that `call` is about to block without watching for interrupts.
+#[inline]
+pub fn uninterruptible(call: &'static str) {
+    #[cfg(feature = "pg_debug")]
+    warn(call);
+}
+
+#[cfg(feature = "pg_debug")]
+#[cold]
+fn warn(call: &'static str) {
+    use crate::backtrace::{Backtrace, BacktraceStatus};
+    use crate::cell::RefCell;
+    use crate::sys::{elog, pg};
+
+    #[thread_local]
+    static WARNED: RefCell<Vec<&'static str>> = RefCell::new(Vec::new());
+
+    // Outside of a backend, there are no interrupts to miss.
+    if pg::my_proc_pid().is_none() || WARNED.borrow().contains(&call) {
+        return;
+    }
+    WARNED.borrow_mut().push(call);
+
+    let backtrace = Backtrace::force_capture();
+    if let Ok(Some(mut report)) = elog::Report::start(elog::Level::Warning) {
+        report.message(format_args!("{call} blocks without checking for interrupts"));
+        if backtrace.status() == BacktraceStatus::Captured {
+            report.detail(format_args!("Called from:\n{backtrace}"));
+        }
+        report.hint("The statement cannot be cancelled while it waits.");
+        report.finish();
+    }
+}
@@ -0,0 +1,98 @@
+//! Storage for the runtime capability switches exposed through
+//! `std::os::postgres::capability`.
+//!
+//! Everything starts out disabled; sys code calls `check` before doing
+//! anything that reaches outside the backend.
+//!
+//! Whole subsystems are compiled in or out on top of that, by the
+//! `pg_allow_*` features of `std`, so that distributors can ship builds that
+//! are capable of less from the same tree. What was left out fails with an
+//! error saying so, and no capability turns it back on.
+//!
+//! The permissive build, with `pg_untrusted`, has them all, and every
+//! capability enabled for good: the checks are constant, so they compile out.
+
+use crate::io;
+use crate::os::postgres::capability::Capability;
+use crate::sync::atomic::{AtomicBool, Ordering};
+
+static NET: AtomicBool = AtomicBool::new(false);
+static UDP: AtomicBool = AtomicBool::new(false);
+
+fn flag(cap: Capability) -> &'static AtomicBool {
+    match cap {
+        Capability::Net => &NET,
+        Capability::Udp => &UDP,
+    }
+}
+
+/// Whether `cap` was compiled in, by the feature of the subsystem it
+/// belongs to.
+pub const fn is_built_in(cap: Capability) -> bool {
+    match cap {
+        Capability::Net | Capability::Udp => cfg!(feature = "pg_allow_net"),
+    }
+}
+
+/// Whether this is the permissive build, for untrusted languages.
+pub const fn is_untrusted() -> bool {
+    cfg!(feature = "pg_untrusted")
+}
+
+pub fn set_enabled(cap: Capability, enabled: bool) {
+    flag(cap).store(enabled && is_built_in(cap), Ordering::Relaxed);
+}
+
+#[inline]
+pub fn is_enabled(cap: Capability) -> bool {
+    if is_untrusted() {
+        return true;
+    }
+    is_built_in(cap) && flag(cap).load(Ordering::Relaxed)
+}
+
+/// Returns `Ok(())` if `cap` is enabled, and a policy error otherwise.
+#[inline]
+pub fn check(cap: Capability) -> io::Result<()> {
+    if is_enabled(cap) {
+        return Ok(());
+    }
+    if !is_built_in(cap) {
+        return Err(io::const_io_error!(
+            io::ErrorKind::Unsupported,
+            "networking is not built into this std: it was compiled without the `pg_allow_net` feature",
+        ));
+    }
+    Err(match cap {
+        Capability::Net => io::const_io_error!(
+            io::ErrorKind::Unsupported,
+            "networking is disabled in this backend: the `Net` capability has not been enabled",
+        ),
+        Capability::Udp => io::const_io_error!(
+            io::ErrorKind::Unsupported,
+            "UDP is disabled in this backend: the `Udp` capability has not been enabled",
+        ),
+    })
+}
+
+/// Fails unless the filesystem was compiled in, with `pg_allow_fs`.
+pub fn check_fs() -> io::Result<()> {
+    if cfg!(feature = "pg_allow_fs") {
+        return Ok(());
+    }
+    Err(io::const_io_error!(
+        io::ErrorKind::Unsupported,
+        "the filesystem is not built into this std: it was compiled without the `pg_allow_fs` feature",
+    ))
+}
+
+/// Fails unless spawning processes was compiled in, with `pg_allow_process`.
+pub fn check_process() -> io::Result<()> {
+    if cfg!(feature = "pg_allow_process") {
+        return Ok(());
+    }
+    Err(io::const_io_error!(
+        io::ErrorKind::Unsupported,
+        "processes are not built into this std: it was compiled without the `pg_allow_process` feature",
+    ))
+}
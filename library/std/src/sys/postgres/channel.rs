@@ -0,0 +1,78 @@
+//! One-way byte channels between backends, each a single `shm_mq` in its own
+//! dynamic shared memory segment.
+//!
+//! The backend creating a channel keeps one end and hands the segment's
+//! handle to the other backend, which attaches to the opposite end.
+
+use crate::io;
+use crate::sys::pg;
+use crate::sys::shm_mq::{self, Queue, Role, Segment};
+use crate::time::Instant;
+use libc::c_void;
+
+/// Size of the queue. Larger messages are streamed through it in pieces.
+const QUEUE_SIZE: usize = 64 * 1024;
+
+pub struct End {
+    // The queue must be detached before the segment it lives in.
+    queue: Queue,
+    seg: Segment,
+}
+
+/// Creates a channel, keeping the `role` end of it.
+///
+/// `worker` is the `BackgroundWorkerHandle` of the other end, or null if it
+/// isn't a background worker or hasn't been registered yet.
+pub fn create(role: Role, worker: *mut c_void) -> io::Result<End> {
+    let seg = Segment::create(QUEUE_SIZE)?;
+    let queue = unsafe {
+        let mq = shm_mq::create(seg.address(), QUEUE_SIZE)?;
+        Queue::attach(mq, &seg, role, worker)?
+    };
+    Ok(End { queue, seg })
+}
+
+/// Attaches to the `role` end of the channel created with handle `handle`.
+/// `worker` is as for [`create`].
+pub fn attach(handle: u32, role: Role, worker: *mut c_void) -> io::Result<End> {
+    let seg = Segment::attach(handle)?;
+    let queue = unsafe { Queue::attach(seg.address().cast(), &seg, role, worker)? };
+    Ok(End { queue, seg })
+}
+
+impl End {
+    pub fn handle(&self) -> u32 {
+        self.seg.handle()
+    }
+
+    /// Sets the `BackgroundWorkerHandle` of the other end, for workers
+    /// registered after the channel was created.
+    pub fn set_worker(&mut self, worker: *mut c_void) {
+        self.queue.set_worker(worker)
+    }
+
+    pub fn send(&mut self, msg: &[u8]) -> io::Result<()> {
+        self.queue.send(msg)
+    }
+
+    pub fn receive(&mut self) -> io::Result<&[u8]> {
+        self.queue.receive()
+    }
+
+    pub fn try_receive(&mut self) -> io::Result<Option<&[u8]>> {
+        self.queue.try_receive()
+    }
+
+    /// Receives one message, waiting on the latch until `deadline`. Returns
+    /// `Ok(None)` if it passes first.
+    pub fn receive_deadline(&mut self, deadline: Instant) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(msg) = self.queue.try_receive()? {
+                return Ok(Some(msg.to_vec()));
+            }
+            if !pg::wait_latch(Some(deadline))? {
+                return Ok(None);
+            }
+        }
+    }
+}
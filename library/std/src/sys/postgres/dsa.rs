@@ -0,0 +1,146 @@
+//! Dynamic shared memory areas, from utils/dsa.h: heaps spread over as many
+//! DSM segments as they need, which any backend can attach to.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use super::elog;
+use crate::io;
+use crate::sys::pg::require;
+use libc::{c_int, c_void, size_t};
+
+/// `dsa_pointer`: an address in an area, the same in every backend. It is
+/// 64 bits wide wherever postgres has 64-bit atomics, which is everywhere
+/// this std runs.
+pub type DsaPointer = u64;
+
+pub const InvalidDsaPointer: DsaPointer = 0;
+
+// utils/dsa.h
+const DSA_ALLOC_HUGE: c_int = 0x01;
+const DSA_ALLOC_NO_OOM: c_int = 0x02;
+const DSA_ALLOC_ZERO: c_int = 0x04;
+const DSA_DEFAULT_INIT_SEGMENT_SIZE: size_t = 1024 * 1024;
+const DSA_MAX_SEGMENT_SIZE: size_t = 1 << 40;
+
+backend! {
+    // Gone as of postgres 17, which turned it into a macro over
+    // `dsa_create_ext`.
+    weak fn dsa_create(tranche_id: c_int) -> *mut c_void;
+    weak fn dsa_create_ext(
+        tranche_id: c_int,
+        init_segment_size: size_t,
+        max_segment_size: size_t,
+    ) -> *mut c_void;
+    fn dsa_attach(handle: u32) -> *mut c_void;
+    fn dsa_get_handle(area: *mut c_void) -> u32;
+    fn dsa_pin_mapping(area: *mut c_void);
+    fn dsa_pin(area: *mut c_void);
+    fn dsa_detach(area: *mut c_void);
+    fn dsa_allocate_extended(area: *mut c_void, size: size_t, flags: c_int) -> DsaPointer;
+    fn dsa_free(area: *mut c_void, dp: DsaPointer);
+    fn dsa_get_address(area: *mut c_void, dp: DsaPointer) -> *mut c_void;
+}
+
+fn error(inner: elog::ErrorData) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, crate::os::postgres::PgError { inner })
+}
+
+/// An attached area, detached on drop.
+///
+/// Each backend keeps it mapped until then, instead of until the end of the
+/// current resource owner (usually the transaction).
+pub struct Area(*mut c_void);
+
+impl Area {
+    /// Creates an area, whose locks are in `tranche`.
+    pub fn create(tranche: c_int) -> io::Result<Area> {
+        let pin = require(&dsa_pin_mapping)?;
+        // The mapping is pinned inside the subtransaction, which would
+        // otherwise detach it when it ends.
+        let area = match dsa_create.get() {
+            Some(create) => elog::subtransaction(|| unsafe {
+                let area = create(tranche);
+                pin(area);
+                area
+            }),
+            None => {
+                let create = require(&dsa_create_ext)?;
+                elog::subtransaction(|| unsafe {
+                    let area = create(tranche, DSA_DEFAULT_INIT_SEGMENT_SIZE, DSA_MAX_SEGMENT_SIZE);
+                    pin(area);
+                    area
+                })
+            }
+        }
+        .map_err(error)?;
+        Ok(Area(area))
+    }
+
+    /// Attaches to the area `handle`, failing with the `ERROR` postgres
+    /// raises if it is gone.
+    pub fn attach(handle: u32) -> io::Result<Area> {
+        let attach = require(&dsa_attach)?;
+        let pin = require(&dsa_pin_mapping)?;
+        let area = elog::subtransaction(|| unsafe {
+            let area = attach(handle);
+            pin(area);
+            area
+        })
+        .map_err(error)?;
+        Ok(Area(area))
+    }
+
+    pub fn handle(&self) -> u32 {
+        unsafe { dsa_get_handle.get().unwrap()(self.0) }
+    }
+
+    /// Keeps the area until the server shuts down, even once no backend is
+    /// attached to it.
+    pub fn pin(&self) {
+        unsafe { dsa_pin.get().unwrap()(self.0) }
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+
+    /// Allocates `size` bytes in the area, zeroed if `zeroed`, failing
+    /// rather than raising an `ERROR` when out of memory.
+    pub fn allocate(&self, size: usize, zeroed: bool) -> io::Result<DsaPointer> {
+        let allocate = require(&dsa_allocate_extended)?;
+        let flags = DSA_ALLOC_HUGE | DSA_ALLOC_NO_OOM | if zeroed { DSA_ALLOC_ZERO } else { 0 };
+        let dp = unsafe { allocate(self.0, size, flags) };
+        if dp == InvalidDsaPointer {
+            return Err(io::const_io_error!(
+                io::ErrorKind::OutOfMemory,
+                "out of dynamic shared memory",
+            ));
+        }
+        Ok(dp)
+    }
+
+    /// Frees the allocation at `dp`.
+    ///
+    /// # Safety
+    ///
+    /// `dp` was allocated in this area, and nothing uses it anymore, in any
+    /// backend.
+    pub unsafe fn free(&self, dp: DsaPointer) {
+        unsafe { dsa_free.get().unwrap()(self.0, dp) }
+    }
+
+    /// Where the allocation at `dp` is mapped in this backend, mapping the
+    /// segment it is in if need be.
+    ///
+    /// # Safety
+    ///
+    /// `dp` was allocated in this area, and is not freed yet.
+    pub unsafe fn address(&self, dp: DsaPointer) -> *mut u8 {
+        unsafe { dsa_get_address.get().unwrap()(self.0, dp).cast() }
+    }
+}
+
+impl Drop for Area {
+    fn drop(&mut self) {
+        unsafe { dsa_detach.get().unwrap()(self.0) }
+    }
+}
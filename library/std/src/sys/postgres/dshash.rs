@@ -0,0 +1,203 @@
+//! Hash tables in a dynamic shared memory area, from lib/dshash.h, locked
+//! by partition.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use super::dsa::{Area, DsaPointer};
+use super::elog;
+use crate::io;
+use crate::sys::pg::require;
+use libc::{c_int, c_void, size_t};
+
+type CompareFn = unsafe extern "C" fn(*const c_void, *const c_void, size_t, *mut c_void) -> c_int;
+type HashFn = unsafe extern "C" fn(*const c_void, size_t, *mut c_void) -> u32;
+type CopyFn = unsafe extern "C" fn(*mut c_void, *const c_void, size_t, *mut c_void);
+
+// lib/dshash.h, before postgres 17.
+#[repr(C)]
+struct Parameters {
+    key_size: size_t,
+    entry_size: size_t,
+    compare_function: CompareFn,
+    hash_function: HashFn,
+    tranche_id: c_int,
+}
+
+// lib/dshash.h, as of postgres 17, which added `copy_function`.
+#[repr(C)]
+struct Parameters17 {
+    key_size: size_t,
+    entry_size: size_t,
+    compare_function: CompareFn,
+    hash_function: HashFn,
+    copy_function: CopyFn,
+    tranche_id: c_int,
+}
+
+backend! {
+    fn dshash_memcmp(a: *const c_void, b: *const c_void, size: size_t, arg: *mut c_void) -> c_int;
+    fn dshash_memhash(v: *const c_void, size: size_t, arg: *mut c_void) -> u32;
+    // Only there as of postgres 17, which then needs it in the parameters.
+    weak fn dshash_memcpy(dest: *mut c_void, src: *const c_void, size: size_t, arg: *mut c_void);
+    fn dshash_create(area: *mut c_void, params: *const c_void, arg: *mut c_void) -> *mut c_void;
+    fn dshash_attach(
+        area: *mut c_void,
+        params: *const c_void,
+        handle: DsaPointer,
+        arg: *mut c_void,
+    ) -> *mut c_void;
+    fn dshash_detach(hash_table: *mut c_void);
+    fn dshash_get_hash_table_handle(hash_table: *mut c_void) -> DsaPointer;
+    fn dshash_find(hash_table: *mut c_void, key: *const c_void, exclusive: bool) -> *mut c_void;
+    fn dshash_find_or_insert(
+        hash_table: *mut c_void,
+        key: *const c_void,
+        found: *mut bool,
+    ) -> *mut c_void;
+    fn dshash_delete_key(hash_table: *mut c_void, key: *const c_void) -> bool;
+    fn dshash_delete_entry(hash_table: *mut c_void, entry: *mut c_void);
+    fn dshash_release_lock(hash_table: *mut c_void, entry: *mut c_void);
+}
+
+fn error(inner: elog::ErrorData) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, crate::os::postgres::PgError { inner })
+}
+
+/// Calls `f` with the parameters of a table of `entry_size`-byte entries,
+/// each starting with a `key_size`-byte key that is hashed and compared as
+/// plain bytes.
+fn with_parameters<R>(
+    key_size: usize,
+    entry_size: usize,
+    tranche: c_int,
+    f: impl FnOnce(*const c_void) -> R,
+) -> io::Result<R> {
+    let compare_function = require(&dshash_memcmp)?;
+    let hash_function = require(&dshash_memhash)?;
+    Ok(match dshash_memcpy.get() {
+        Some(copy_function) => f((&Parameters17 {
+            key_size,
+            entry_size,
+            compare_function,
+            hash_function,
+            copy_function,
+            tranche_id: tranche,
+        } as *const Parameters17)
+            .cast()),
+        None => f((&Parameters {
+            key_size,
+            entry_size,
+            compare_function,
+            hash_function,
+            tranche_id: tranche,
+        } as *const Parameters)
+            .cast()),
+    })
+}
+
+/// An attached table, detached on drop. The table itself stays in its
+/// area.
+pub struct Table(*mut c_void);
+
+impl Table {
+    /// Creates a table in `area`, whose partition locks are in `tranche`.
+    ///
+    /// Entries are `entry_size` bytes, and start with their `key_size`-byte
+    /// key.
+    pub fn create(
+        area: &Area,
+        key_size: usize,
+        entry_size: usize,
+        tranche: c_int,
+    ) -> io::Result<Table> {
+        let create = require(&dshash_create)?;
+        let table = with_parameters(key_size, entry_size, tranche, |params| {
+            elog::subtransaction(|| unsafe {
+                create(area.as_ptr(), params, crate::ptr::null_mut())
+            })
+        })?
+        .map_err(error)?;
+        Ok(Table(table))
+    }
+
+    /// Attaches to the table `handle` in `area`.
+    ///
+    /// # Safety
+    ///
+    /// `handle` is a table of `area`, created with the same sizes.
+    pub unsafe fn attach(
+        area: &Area,
+        handle: DsaPointer,
+        key_size: usize,
+        entry_size: usize,
+        tranche: c_int,
+    ) -> io::Result<Table> {
+        let attach = require(&dshash_attach)?;
+        let table = with_parameters(key_size, entry_size, tranche, |params| {
+            elog::subtransaction(|| unsafe {
+                attach(area.as_ptr(), params, handle, crate::ptr::null_mut())
+            })
+        })?
+        .map_err(error)?;
+        Ok(Table(table))
+    }
+
+    pub fn handle(&self) -> DsaPointer {
+        unsafe { dshash_get_hash_table_handle.get().unwrap()(self.0) }
+    }
+
+    /// Finds the entry for `key`, and locks its partition, shared or
+    /// exclusively, until [`release`](Table::release).
+    ///
+    /// # Safety
+    ///
+    /// `key` is `key_size` bytes, and no entry of this table is locked by
+    /// this backend.
+    pub unsafe fn find(&self, key: *const u8, exclusive: bool) -> Option<*mut u8> {
+        let entry = unsafe { dshash_find.get().unwrap()(self.0, key.cast(), exclusive) };
+        (!entry.is_null()).then_some(entry.cast())
+    }
+
+    /// Finds the entry for `key`, or inserts one with only its key set, and
+    /// locks its partition exclusively until [`release`](Table::release).
+    /// Returns whether the entry was found.
+    ///
+    /// When the area cannot grow, postgres raises an `ERROR` with the
+    /// partition still locked, which only aborting the transaction releases,
+    /// so it is left to propagate.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`find`](Table::find).
+    pub unsafe fn find_or_insert(&self, key: *const u8) -> (*mut u8, bool) {
+        let mut found = false;
+        let entry = unsafe { dshash_find_or_insert.get().unwrap()(self.0, key.cast(), &mut found) };
+        (entry.cast(), found)
+    }
+
+    /// Deletes the entry for `key`, if there is one.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`find`](Table::find).
+    pub unsafe fn delete_key(&self, key: *const u8) -> bool {
+        unsafe { dshash_delete_key.get().unwrap()(self.0, key.cast()) }
+    }
+
+    /// Deletes `entry`, which this backend has locked exclusively, and
+    /// releases the lock.
+    pub unsafe fn delete_entry(&self, entry: *mut u8) {
+        unsafe { dshash_delete_entry.get().unwrap()(self.0, entry.cast()) }
+    }
+
+    /// Releases the lock on `entry`, from [`find`](Table::find) or
+    /// [`find_or_insert`](Table::find_or_insert).
+    pub unsafe fn release(&self, entry: *mut u8) {
+        unsafe { dshash_release_lock.get().unwrap()(self.0, entry.cast()) }
+    }
+}
+
+impl Drop for Table {
+    fn drop(&mut self) {
+        unsafe { dshash_detach.get().unwrap()(self.0) }
+    }
+}
@@ -0,0 +1,931 @@
+//! Reporting errors to postgres, like `ereport` does.
+//!
+//! An `ERROR` does not return: `errfinish` longjmps to the innermost
+//! `PG_TRY` (or to the backend's main loop), skipping every Rust frame in
+//! between without running their destructors. Whatever those frames own is
+//! leaked, so a [`Report`] copies whatever goes into it to postgres's memory
+//! right away, and callers drop what they own before [`Report::throw`].
+//!
+//! The text of a report is formatted straight into `ErrorContext`, which
+//! postgres keeps some memory in even when it has run out otherwise, for
+//! this very purpose. That way, a panic about running out of memory still
+//! gets its message across.
+#![allow(non_upper_case_globals)]
+
+use super::pg;
+use crate::any::Any;
+use crate::cell::{Cell, RefCell};
+use crate::ffi::CStr;
+use crate::fmt::{self, Write};
+use crate::io;
+use crate::marker::PhantomData;
+use crate::mem;
+use crate::panic::{Location, PanicInfo};
+use crate::ptr;
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::sys::symbol::Backend;
+use libc::{c_char, c_int, c_void};
+
+// utils/elog.h, as of postgres 14, which added `WARNING_CLIENT_ONLY` right
+// before `ERROR`.
+const DEBUG1: c_int = 14;
+const LOG: c_int = 15;
+const NOTICE: c_int = 18;
+const WARNING: c_int = 19;
+const ERROR: c_int = 21;
+const FATAL: c_int = 22;
+const PANIC: c_int = 23;
+
+backend! {
+    fn errstart(elevel: c_int, domain: *const c_char) -> bool;
+    fn errfinish(filename: *const c_char, lineno: c_int, funcname: *const c_char);
+    fn errcode(sqlerrcode: c_int) -> c_int;
+    fn errmsg_internal(fmt: *const c_char, ...) -> c_int;
+    fn errdetail_internal(fmt: *const c_char, ...) -> c_int;
+    fn errhint(fmt: *const c_char, ...) -> c_int;
+    fn errposition(cursorpos: c_int) -> c_int;
+    fn errcontext_msg(fmt: *const c_char, ...) -> c_int;
+    fn MemoryContextAllocExtended(context: *mut c_void, size: usize, flags: c_int) -> *mut c_void;
+    fn pfree(pointer: *mut c_void);
+    static ErrorContext: AtomicPtr<c_void>;
+    static CurrentMemoryContext: AtomicPtr<c_void>;
+    static PG_exception_stack: AtomicPtr<c_void>;
+    static error_context_stack: AtomicPtr<c_void>;
+    static debug_query_string: AtomicPtr<c_char>;
+    fn CopyErrorData() -> *mut c_void;
+    fn FreeErrorData(edata: *mut c_void);
+    fn FlushErrorState();
+    fn pg_re_throw() -> !;
+    fn before_shmem_exit(function: OnExitCallback, arg: usize);
+    fn cancel_before_shmem_exit(function: OnExitCallback, arg: usize);
+    static CurrentResourceOwner: AtomicPtr<c_void>;
+    fn IsTransactionState() -> bool;
+    fn BeginInternalSubTransaction(name: *const c_char);
+    fn ReleaseCurrentSubTransaction();
+    fn RollbackAndReleaseCurrentSubTransaction();
+}
+
+/// storage/ipc.h's `pg_on_exit_callback`.
+type OnExitCallback = extern "C" fn(c_int, usize);
+
+/// Big enough and aligned enough for a `sigjmp_buf` on every supported
+/// target.
+#[repr(C, align(16))]
+struct SigJmpBuf([u64; 64]);
+
+extern "C" {
+    #[cfg_attr(all(target_os = "linux", target_env = "gnu"), link_name = "__sigsetjmp")]
+    #[ffi_returns_twice]
+    fn sigsetjmp(env: *mut SigJmpBuf, savemask: c_int) -> c_int;
+}
+
+/// Encodes a five-character SQLSTATE like postgres's `MAKE_SQLSTATE`.
+pub const fn sqlstate(code: &[u8; 5]) -> c_int {
+    let mut packed = 0;
+    let mut i = 0;
+    while i < 5 {
+        packed |= ((code[i].wrapping_sub(b'0') & 0x3F) as c_int) << (6 * i);
+        i += 1;
+    }
+    packed
+}
+
+pub const ERRCODE_INTERNAL_ERROR: c_int = sqlstate(b"XX000");
+const ERRCODE_OUT_OF_MEMORY: c_int = sqlstate(b"53200");
+
+/// Whether the server is older than postgres 14, where some of the error
+/// reporting ABI changed.
+fn before_14() -> bool {
+    pg::server_version().map_or(false, |v| v < 140000)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// What `dbg!` reports at, which goes nowhere by default.
+    Debug1,
+    /// Only goes to the server log, by default.
+    Log,
+    /// Only goes to the client, by default.
+    Notice,
+    Warning,
+    Error,
+    /// Exits the backend.
+    Fatal,
+    /// Takes the whole server down for crash recovery.
+    Panic,
+}
+
+impl Level {
+    fn elevel(self) -> c_int {
+        let elevel = match self {
+            Level::Debug1 => DEBUG1,
+            Level::Log => LOG,
+            Level::Notice => NOTICE,
+            Level::Warning => WARNING,
+            Level::Error => ERROR,
+            Level::Fatal => FATAL,
+            Level::Panic => PANIC,
+        };
+        if elevel > WARNING && before_14() { elevel - 1 } else { elevel }
+    }
+}
+
+/// Decodes a SQLSTATE encoded by [`sqlstate`].
+pub fn unpack_sqlstate(packed: c_int) -> [u8; 5] {
+    let mut code = [0; 5];
+    for (i, c) in code.iter_mut().enumerate() {
+        *c = ((packed >> (6 * i)) & 0x3F) as u8 + b'0';
+    }
+    code
+}
+
+// utils/palloc.h
+const MCXT_ALLOC_NO_OOM: c_int = 0x02;
+
+/// A nul-terminated string being formatted into `ErrorContext`, and freed
+/// again on drop. Whatever does not fit is cut off.
+struct ErrorBuf {
+    ptr: *mut u8,
+    len: usize,
+    cap: usize,
+}
+
+impl ErrorBuf {
+    fn format(text: impl fmt::Display) -> ErrorBuf {
+        let mut buf = ErrorBuf { ptr: ptr::null_mut(), len: 0, cap: 0 };
+        let _ = write!(buf, "{text}");
+        buf
+    }
+
+    fn as_ptr(&self) -> *const c_char {
+        if self.ptr.is_null() { b"\0".as_ptr().cast() } else { self.ptr.cast() }
+    }
+
+    /// Leaves the string to `ErrorContext`, for postgres to free, or returns
+    /// null if there was no memory for it.
+    fn into_raw(self) -> *const c_char {
+        let ptr = self.ptr;
+        mem::forget(self);
+        ptr.cast()
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> fmt::Result {
+        let needed = self.len + bytes.len() + 1;
+        if needed > self.cap {
+            let (Some(alloc), Some(context)) =
+                (MemoryContextAllocExtended.get(), ErrorContext.get())
+            else {
+                return Err(fmt::Error);
+            };
+            let cap = needed.max(self.cap * 2).max(128);
+            // Not `repalloc`, which raises an `ERROR` when out of memory.
+            let new = unsafe { alloc(context.load(Ordering::Relaxed), cap, MCXT_ALLOC_NO_OOM) };
+            if new.is_null() {
+                return Err(fmt::Error);
+            }
+            let old = mem::replace(&mut self.ptr, new.cast());
+            if !old.is_null() {
+                unsafe { ptr::copy_nonoverlapping(old, self.ptr, self.len) };
+                drop(ErrorBuf { ptr: old, len: 0, cap: 0 });
+            }
+            self.cap = cap;
+        }
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.add(self.len), bytes.len());
+            self.len += bytes.len();
+            *self.ptr.add(self.len) = 0;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Write for ErrorBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Postgres only sees up to the first nul.
+        let mut parts = s.split('\0');
+        self.push(parts.next().unwrap_or("").as_bytes())?;
+        for part in parts {
+            self.push(b"\\0")?;
+            self.push(part.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ErrorBuf {
+    fn drop(&mut self) {
+        if let (false, Some(pfree)) = (self.ptr.is_null(), pfree.get()) {
+            unsafe { pfree(self.ptr.cast()) };
+        }
+    }
+}
+
+/// A report being built, between `errstart` and `errfinish`.
+pub struct Report {
+    level: Level,
+    file: *const c_char,
+    line: c_int,
+    // Postgres keeps a single stack of reports being built.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Report {
+    /// Starts a report, or returns `None` if postgres is not going to output
+    /// anything at this `level`. Fails with `Unsupported` outside a backend.
+    pub fn start(level: Level) -> io::Result<Option<Report>> {
+        let start = pg::require(&errstart)?;
+        let report = Report { level, file: ptr::null(), line: 0, _not_send: PhantomData };
+        Ok(unsafe { start(level.elevel(), ptr::null()) }.then_some(report))
+    }
+
+    /// Sets the SQLSTATE, as encoded by [`sqlstate`].
+    pub fn code(&mut self, code: c_int) {
+        if let Some(errcode) = errcode.get() {
+            unsafe { errcode(code) };
+        }
+    }
+
+    pub fn message(&mut self, message: impl fmt::Display) {
+        if let Some(errmsg) = errmsg_internal.get() {
+            let message = ErrorBuf::format(message);
+            unsafe { errmsg(b"%s\0".as_ptr().cast(), message.as_ptr()) };
+        }
+    }
+
+    pub fn detail(&mut self, detail: impl fmt::Display) {
+        if let Some(errdetail) = errdetail_internal.get() {
+            let detail = ErrorBuf::format(detail);
+            unsafe { errdetail(b"%s\0".as_ptr().cast(), detail.as_ptr()) };
+        }
+    }
+
+    pub fn hint(&mut self, hint: impl fmt::Display) {
+        if let Some(errhint) = errhint.get() {
+            let hint = ErrorBuf::format(hint);
+            unsafe { errhint(b"%s\0".as_ptr().cast(), hint.as_ptr()) };
+        }
+    }
+
+    /// Points at the character `position` (counted from 1) of the query.
+    pub fn position(&mut self, position: u32) {
+        if let Some(errposition) = errposition.get() {
+            unsafe { errposition(position.try_into().unwrap_or(c_int::MAX)) };
+        }
+    }
+
+    /// Adds a line to the `CONTEXT` of the report.
+    pub fn context(&mut self, context: impl fmt::Display) {
+        if let Some(errcontext) = errcontext_msg.get() {
+            let context = ErrorBuf::format(context);
+            unsafe { errcontext(b"%s\0".as_ptr().cast(), context.as_ptr()) };
+        }
+    }
+
+    /// Adds the statement the backend is running to the `CONTEXT`, if any.
+    ///
+    /// Postgres logs it with an `ERROR` (as `STATEMENT`), but not with a
+    /// `WARNING`, nor does it send it to the client, and a panic, like an
+    /// `assert!` failing, is not much use to anyone without it.
+    pub fn statement(&mut self) {
+        let query = debug_query_string.get().map_or(ptr::null_mut(), |q| q.load(Ordering::Relaxed));
+        if !query.is_null() {
+            let query = unsafe { CStr::from_ptr(query) };
+            self.context(format_args!("statement: {}", query.to_string_lossy()));
+        }
+    }
+
+    /// Fills in everything `error` has.
+    pub fn error_data(&mut self, error: &ErrorData) {
+        self.code(sqlstate(&error.code));
+        self.message(&error.message);
+        if let Some(detail) = &error.detail {
+            self.detail(detail);
+        }
+        if let Some(hint) = &error.hint {
+            self.hint(hint);
+        }
+        if let Some(context) = &error.context {
+            self.context(context);
+        }
+        if let Some(position) = error.position {
+            self.position(position);
+        }
+    }
+
+    /// Attributes the report to `location`, which the server log shows with
+    /// `log_error_verbosity = verbose`.
+    pub fn location(&mut self, location: &Location<'_>) {
+        self.at(location.file(), location.line());
+    }
+
+    /// Attributes the report to `line` of `file`, like [`Report::location`].
+    pub fn at(&mut self, file: &str, line: u32) {
+        // `errfinish` keeps the file name pointer as is, so it has to live
+        // until the error has been dealt with, like `ErrorContext`.
+        self.file = ErrorBuf::format(file).into_raw();
+        self.line = line as c_int;
+    }
+
+    /// Outputs the report with `errfinish`. For an `ERROR`, this does not
+    /// return.
+    pub fn finish(self) {
+        if let Some(finish) = errfinish.get() {
+            unsafe { finish(self.file, self.line, ptr::null()) }
+        }
+    }
+
+    /// Finishes an `ERROR` report, which longjmps back to postgres, or a
+    /// `FATAL` or `PANIC` one, which exits the process.
+    pub fn throw(self) -> ! {
+        assert!(matches!(self.level, Level::Error | Level::Fatal | Level::Panic));
+        // The longjmp skips every `CatchFrame` up to the innermost
+        // `PG_TRY()`: back in C, nothing is left to catch a panic, and
+        // `try_catch` restores its own count.
+        swap_catch_depth(0);
+        self.finish();
+        crate::sys::abort_internal()
+    }
+}
+
+/// The message of a panic payload, like the default panic hook prints it.
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&'static str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        &s[..]
+    } else if let Some(e) = payload.downcast_ref::<crate::os::postgres::PgError>() {
+        e.message()
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+/// The message of a panic, formatted from the `panic!` arguments if there
+/// are any, rather than from the payload, which allocates.
+pub struct PanicMessage<'a>(pub &'a PanicInfo<'a>);
+
+impl fmt::Display for PanicMessage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.message() {
+            Some(message) => f.write_fmt(*message),
+            None => f.write_str(panic_message(self.0.payload())),
+        }
+    }
+}
+
+/// The message when there is no payload to take it from, and it could not
+/// be recorded either.
+pub const LOST_MESSAGE: &str = "Rust panic (out of memory for its message)";
+
+/// Formats `text` into a `String`, or returns `None` if out of memory.
+fn try_format(text: impl fmt::Display) -> Option<String> {
+    struct TryString(String);
+
+    impl fmt::Write for TryString {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0.try_reserve(s.len()).map_err(|_| fmt::Error)?;
+            self.0.push_str(s);
+            Ok(())
+        }
+    }
+
+    let mut s = TryString(String::new());
+    write!(s, "{text}").ok()?;
+    Some(s.0)
+}
+
+/// Reports a panic of the thread named `thread` as a `WARNING`, doing
+/// nothing outside a backend.
+pub fn log_panic(thread: &str, info: &PanicInfo<'_>) {
+    if let Ok(Some(mut report)) = Report::start(Level::Warning) {
+        // The current implementation always returns `Some`.
+        let location = info.location().unwrap();
+        report.code(ERRCODE_INTERNAL_ERROR);
+        report.message(format_args!(
+            "thread '{thread}' panicked at '{}', {location}",
+            PanicMessage(info)
+        ));
+        report.statement();
+        report.finish();
+    }
+}
+
+/// Splits a panic message into the primary message and the detail, like the
+/// `left` and `right` lines of a failed `assert_eq!`, since postgres
+/// messages are one line.
+fn split_message(message: &str) -> (&str, Option<&str>) {
+    match message.split_once('\n') {
+        Some((message, detail)) if !detail.trim().is_empty() => (message, Some(detail)),
+        _ => (message.trim_end(), None),
+    }
+}
+
+/// Exits the backend with a `FATAL` report, instead of aborting, or returns
+/// outside a backend.
+///
+/// That goes through `proc_exit`, which releases whatever the backend holds
+/// in shared memory. Inside a critical section, postgres turns it into a
+/// `PANIC` for the whole server to restart.
+pub fn exit_fatal(message: impl fmt::Display, detail: &str) {
+    if let Ok(Some(mut report)) = Report::start(Level::Fatal) {
+        report.code(ERRCODE_INTERNAL_ERROR);
+        report.message(message);
+        report.detail(detail);
+        report.throw();
+    }
+}
+
+/// Raises postgres's own `ERROR: out of memory` for a failed allocation of
+/// `size` bytes, or returns outside a backend.
+pub fn out_of_memory(size: usize) {
+    if let Ok(Some(mut report)) = Report::start(Level::Error) {
+        report.code(ERRCODE_OUT_OF_MEMORY);
+        report.message("out of memory");
+        report.detail(format_args!("Failed on request of size {size}."));
+        report.throw();
+    }
+}
+
+/// Starts the `ERROR` report for a panic, or returns `None` outside a
+/// backend.
+///
+/// A panic carrying a [`PgError`] is reported as that error, and any other
+/// as an `internal_error` with the panic message. Either way, the report is
+/// attributed to where the panic started, as recorded by [`record_panic`].
+/// Without a `payload`, the panic is one of a `panic!` message, which
+/// [`record_panic`] kept too.
+///
+/// The panic is then kept for [`take_last_panic`], with the message it is
+/// reported with.
+///
+/// [`PgError`]: crate::os::postgres::PgError
+pub fn panic_report(payload: Option<&(dyn Any + Send)>) -> Option<Report> {
+    panic_report_at(Level::Error, payload)
+}
+
+/// Starts the `FATAL` report for a panic in a `std` built with
+/// `panic=abort`, which no `catch_unwind` can catch, or returns `None`
+/// outside a backend. It is reported like [`panic_report`] does.
+///
+/// An `ERROR` would go back past the `catch_unwind`, to code written to
+/// deal with the panic right there, so the backend exits instead. That goes
+/// through `proc_exit`, unlike the SIGABRT `panic=abort` would be otherwise,
+/// for which the postmaster restarts the whole server.
+pub fn fatal_panic_report(payload: Option<&(dyn Any + Send)>) -> Option<Report> {
+    panic_report_at(Level::Fatal, payload)
+}
+
+fn panic_report_at(level: Level, payload: Option<&(dyn Any + Send)>) -> Option<Report> {
+    let mut report = Report::start(level).ok()??;
+    let mut origin = PANIC_ORIGIN.try_with(|o| o.borrow_mut().take()).ok().flatten();
+    let location = origin.as_ref().map(|o| Location::internal_constructor(&o.file, o.line, o.col));
+    match payload.and_then(|p| p.downcast_ref::<crate::os::postgres::PgError>()) {
+        Some(error) => report.error_data(&error.inner),
+        None => {
+            let (message, detail) = split_message(message_of(payload, origin.as_ref()));
+            report.code(ERRCODE_INTERNAL_ERROR);
+            report.message(message);
+            if let Some(detail) = detail {
+                report.detail(detail);
+            }
+            if let Some(location) = &location {
+                report.context(format_args!("Rust panic at {location}"));
+            }
+            report.statement();
+        }
+    }
+    if let Some(backtrace) = origin.as_ref().and_then(|o| o.backtrace.as_deref()) {
+        report.context(backtrace);
+    }
+    if let Some(location) = &location {
+        report.location(location);
+    }
+    if let (Some(origin), Some(payload)) = (&mut origin, payload) {
+        origin.message = try_format(panic_message(payload));
+    }
+    let _ = LAST_PANIC.try_with(|l| *l.borrow_mut() = origin);
+    Some(report)
+}
+
+/// Takes the last panic [`panic_report`] reported, if any.
+pub fn take_last_panic() -> Option<PanicOrigin> {
+    LAST_PANIC.try_with(|l| l.borrow_mut().take()).ok().flatten()
+}
+
+/// Starts the `PANIC` report for a panic raised while another one was
+/// unwinding, such as from a destructor, or returns `None` outside a backend.
+///
+/// Unwinding cannot go on, and the state the first panic left behind is
+/// anyone's guess, shared memory included, so the whole server restarts. The
+/// report names both panics, for the server log to tell what happened.
+pub fn nested_panic_report(payload: Option<&(dyn Any + Send)>) -> Option<Report> {
+    let mut report = Report::start(Level::Panic).ok()??;
+    let origin = PANIC_ORIGIN.try_with(|o| o.borrow_mut().take()).ok().flatten();
+    let outer = UNWINDING.try_with(|o| o.borrow_mut().take()).ok().flatten();
+    let location = origin.as_ref().map(|o| Location::internal_constructor(&o.file, o.line, o.col));
+    report.code(ERRCODE_INTERNAL_ERROR);
+    report.message(format_args!(
+        "Rust panic while panicking: {}",
+        message_of(payload, origin.as_ref())
+    ));
+    if let Some(outer) = &outer {
+        let outer_location = Location::internal_constructor(&outer.file, outer.line, outer.col);
+        report.detail(format_args!(
+            "The panic being unwound was \"{}\" at {outer_location}.",
+            message_of(None, Some(outer))
+        ));
+    }
+    if let Some(location) = &location {
+        report.context(format_args!("Rust panic at {location}"));
+    }
+    report.statement();
+    if let Some(backtrace) = origin.as_ref().and_then(|o| o.backtrace.as_deref()) {
+        report.context(backtrace);
+    }
+    if let Some(location) = &location {
+        report.location(location);
+    }
+    Some(report)
+}
+
+fn message_of<'a>(
+    payload: Option<&'a (dyn Any + Send)>,
+    origin: Option<&'a PanicOrigin>,
+) -> &'a str {
+    match (payload, origin.and_then(|o| o.message.as_deref())) {
+        (Some(payload), _) => panic_message(payload),
+        (None, Some(message)) => message,
+        (None, None) => LOST_MESSAGE,
+    }
+}
+
+/// Where a panic started.
+pub struct PanicOrigin {
+    pub file: String,
+    pub line: u32,
+    pub col: u32,
+    /// `None` if there was no memory left to format it.
+    pub message: Option<String>,
+    pub backtrace: Option<String>,
+}
+
+thread_local! {
+    /// Where the last panic started, for when it is reported after being
+    /// caught, by which point the stack and location are gone.
+    static PANIC_ORIGIN: RefCell<Option<PanicOrigin>> = const { RefCell::new(None) };
+    /// The panic that was unwinding when a nested one started.
+    static UNWINDING: RefCell<Option<PanicOrigin>> = const { RefCell::new(None) };
+    /// The last panic turned into an `ERROR`.
+    static LAST_PANIC: RefCell<Option<PanicOrigin>> = const { RefCell::new(None) };
+}
+
+/// Records where a panic is starting, for [`panic_report`], with a backtrace
+/// if those are turned on. For a panic raised while `nested` in the
+/// unwinding of another, that one is kept for [`nested_panic_report`].
+///
+/// The panic may be about running out of memory, so this makes do with
+/// what it can allocate.
+pub fn record_panic(info: &PanicInfo<'_>, nested: bool) {
+    // The current implementation always returns `Some`.
+    let location = info.location().unwrap();
+    let origin = try_format(location.file()).map(|file| PanicOrigin {
+        file,
+        line: location.line(),
+        col: location.column(),
+        message: try_format(PanicMessage(info)),
+        backtrace: crate::sys_common::backtrace::capture(),
+    });
+    let _ = PANIC_ORIGIN.try_with(|o| {
+        let outer = mem::replace(&mut *o.borrow_mut(), origin);
+        if nested {
+            let _ = UNWINDING.try_with(|u| *u.borrow_mut() = outer);
+        }
+    });
+}
+
+thread_local! {
+    /// How many `catch_unwind` frames a panic could unwind into without
+    /// leaving Rust, that is, since postgres last called into Rust.
+    static CATCH_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Whether a panic starting now has a `catch_unwind` to unwind to, rather
+/// than running into postgres's C frames first.
+pub fn can_catch() -> bool {
+    CATCH_DEPTH.try_with(|d| d.get() > 0).unwrap_or(true)
+}
+
+fn swap_catch_depth(depth: usize) -> usize {
+    CATCH_DEPTH.try_with(|d| d.replace(depth)).unwrap_or(0)
+}
+
+/// A live `catch_unwind` frame, for [`can_catch`].
+///
+/// Postgres's C code pops whatever it pushes on its exception and error
+/// context stacks before returning, and `PG_CATCH()` does after an `ERROR`,
+/// so those are the same when the frame goes away as when it was entered.
+/// Unless an unwind went right through C frames, out of a function declared
+/// `extern "C-unwind"` that postgres did not call directly. The stacks are put
+/// back so that postgres does not follow them into dead frames, and debug
+/// builds assert.
+pub struct CatchFrame {
+    exception_stack: *mut c_void,
+    context_stack: *mut c_void,
+}
+
+impl CatchFrame {
+    pub fn enter() -> CatchFrame {
+        let load = |s: &Backend<&'static AtomicPtr<c_void>>| {
+            s.get().map_or(ptr::null_mut(), |s| s.load(Ordering::Relaxed))
+        };
+        let frame = CatchFrame {
+            exception_stack: load(&PG_exception_stack),
+            context_stack: load(&error_context_stack),
+        };
+        let _ = CATCH_DEPTH.try_with(|d| d.set(d.get() + 1));
+        frame
+    }
+}
+
+impl Drop for CatchFrame {
+    fn drop(&mut self) {
+        let _ = CATCH_DEPTH.try_with(|d| d.set(d.get().saturating_sub(1)));
+        let mut intact = true;
+        for (stack, saved) in [
+            (&PG_exception_stack, self.exception_stack),
+            (&error_context_stack, self.context_stack),
+        ] {
+            if let Some(stack) = stack.get() {
+                intact &= stack.swap(saved, Ordering::Relaxed) == saved;
+            }
+        }
+        debug_assert!(
+            intact,
+            "a panic unwound through C frames; run Rust functions called by postgres under \
+             `std::os::postgres::guard`"
+        );
+    }
+}
+
+/// The part of postgres's `ErrorData` that stayed the same since postgres 13,
+/// which had one more flag before it.
+#[repr(C)]
+struct RawErrorData {
+    filename: *const c_char,
+    lineno: c_int,
+    funcname: *const c_char,
+    domain: *const c_char,
+    context_domain: *const c_char,
+    sqlerrcode: c_int,
+    message: *mut c_char,
+    detail: *mut c_char,
+    detail_log: *mut c_char,
+    hint: *mut c_char,
+    context: *mut c_char,
+    backtrace: *mut c_char,
+    message_id: *const c_char,
+    schema_name: *mut c_char,
+    table_name: *mut c_char,
+    column_name: *mut c_char,
+    datatype_name: *mut c_char,
+    constraint_name: *mut c_char,
+    cursorpos: c_int,
+}
+
+/// A whole `ErrorData`, as far as we read it: `elevel`, then `FLAGS` `bool`
+/// flags, five before postgres 14 and four since, and then the rest, at
+/// whatever offset that makes for on the target.
+#[repr(C)]
+struct VersionedErrorData<const FLAGS: usize> {
+    elevel: c_int,
+    flags: [bool; FLAGS],
+    rest: RawErrorData,
+}
+
+/// An error raised by postgres, copied out of its memory, or one to raise.
+#[derive(Clone)]
+pub struct ErrorData {
+    pub code: [u8; 5],
+    pub message: String,
+    pub detail: Option<String>,
+    pub hint: Option<String>,
+    pub context: Option<String>,
+    pub position: Option<u32>,
+}
+
+impl ErrorData {
+    unsafe fn copy_from(raw: *const c_void) -> ErrorData {
+        let raw = unsafe {
+            if before_14() {
+                &(*raw.cast::<VersionedErrorData<5>>()).rest
+            } else {
+                &(*raw.cast::<VersionedErrorData<4>>()).rest
+            }
+        };
+        let text = |s: *const c_char| {
+            (!s.is_null()).then(|| unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned())
+        };
+        ErrorData {
+            code: unpack_sqlstate(raw.sqlerrcode),
+            message: text(raw.message).unwrap_or_default(),
+            detail: text(raw.detail),
+            hint: text(raw.hint),
+            context: text(raw.context),
+            position: u32::try_from(raw.cursorpos).ok().filter(|&p| p > 0),
+        }
+    }
+}
+
+/// Runs `f` like inside `PG_TRY()`, catching an `ERROR` raised by postgres
+/// instead of letting it longjmp further. Outside a backend, nothing can be
+/// raised, and this just calls `f`.
+///
+/// The longjmp skips the Rust frames between `f` and where the error was
+/// raised, so those must not own anything.
+pub fn try_catch<R>(f: impl FnOnce() -> R) -> Result<R, ErrorData> {
+    let (
+        Some(exception_stack),
+        Some(context_stack),
+        Some(memory_context),
+        Some(copy),
+        Some(free),
+        Some(flush),
+    ) = (
+        PG_exception_stack.get(),
+        error_context_stack.get(),
+        CurrentMemoryContext.get(),
+        CopyErrorData.get(),
+        FreeErrorData.get(),
+        FlushErrorState.get(),
+    )
+    else {
+        return Ok(f());
+    };
+
+    let saved_exception_stack = exception_stack.load(Ordering::Relaxed);
+    let saved_context_stack = context_stack.load(Ordering::Relaxed);
+    let saved_memory_context = memory_context.load(Ordering::Relaxed);
+    // `f` calls into postgres, and a panic in a Rust function it calls back
+    // cannot unwind through postgres to a `catch_unwind` out here.
+    let saved_depth = swap_catch_depth(0);
+    let mut buf = SigJmpBuf([0; 64]);
+    if unsafe { sigsetjmp(&mut buf, 0) } == 0 {
+        return Ok(run_protected(&mut buf, saved_exception_stack, saved_depth, f));
+    }
+
+    // PG_CATCH()
+    exception_stack.store(saved_exception_stack, Ordering::Relaxed);
+    context_stack.store(saved_context_stack, Ordering::Relaxed);
+    swap_catch_depth(saved_depth);
+    // `ErrorContext` is current by now, and `CopyErrorData` refuses to copy
+    // into it.
+    memory_context.store(saved_memory_context, Ordering::Relaxed);
+    unsafe {
+        let raw = copy();
+        flush();
+        let error = ErrorData::copy_from(raw);
+        free(raw);
+        Err(error)
+    }
+}
+
+/// Runs `f` in an internal subtransaction, like PL/pgSQL runs a block with
+/// an `EXCEPTION` clause. An `ERROR` raised inside `f` rolls back the
+/// subtransaction, undoing whatever `f` did in the database and releasing
+/// what it held (locks, buffer pins, ...), and is returned. Otherwise the
+/// subtransaction is committed into the enclosing one.
+///
+/// `f` runs in the memory context that is current when this is called, so
+/// what it pallocs outlives the subtransaction. Resources it acquires belong
+/// to the subtransaction, though, so pin whatever must outlive it (DSM
+/// mappings, ...) inside `f`.
+///
+/// Outside of a transaction there is nothing to roll back to, and an `ERROR`
+/// raised inside `f` propagates as usual. Outside a backend, this just calls
+/// `f`.
+pub fn subtransaction<R>(f: impl FnOnce() -> R) -> Result<R, ErrorData> {
+    let (
+        Some(memory_context),
+        Some(resource_owner),
+        Some(in_transaction),
+        Some(begin),
+        Some(release),
+        Some(rollback),
+    ) = (
+        CurrentMemoryContext.get(),
+        CurrentResourceOwner.get(),
+        IsTransactionState.get(),
+        BeginInternalSubTransaction.get(),
+        ReleaseCurrentSubTransaction.get(),
+        RollbackAndReleaseCurrentSubTransaction.get(),
+    )
+    else {
+        return Ok(f());
+    };
+    if !unsafe { in_transaction() } {
+        return Ok(f());
+    }
+
+    let saved_memory_context = memory_context.load(Ordering::Relaxed);
+    let saved_resource_owner = resource_owner.load(Ordering::Relaxed);
+    unsafe { begin(ptr::null()) };
+    memory_context.store(saved_memory_context, Ordering::Relaxed);
+
+    let result = try_catch(f);
+    unsafe {
+        match result {
+            Ok(_) => release(),
+            Err(_) => rollback(),
+        }
+    }
+    memory_context.store(saved_memory_context, Ordering::Relaxed);
+    resource_owner.store(saved_resource_owner, Ordering::Relaxed);
+    result
+}
+
+/// Runs `f`, and `cleanup` as well if an `ERROR` or `FATAL` raised inside
+/// `f` skips past it, like `PG_ENSURE_ERROR_CLEANUP()` does in C.
+///
+/// On an `ERROR`, `cleanup` runs in between catching the error and raising
+/// it again, towards whichever `PG_TRY()` would have caught it otherwise. A
+/// `FATAL` does not longjmp at all, but exits the backend from inside `f`:
+/// `cleanup` then runs from `before_shmem_exit`, while the frames of `f` are
+/// still there. A panic out of `cleanup` is swallowed, since nothing can
+/// unwind to a `catch_unwind` from either.
+///
+/// Outside a backend, this just calls `f`.
+pub fn ensure_cleanup<R>(cleanup: &mut dyn FnMut(), f: impl FnOnce() -> R) -> R {
+    let (Some(exception_stack), Some(context_stack), Some(rethrow), Some(register), Some(cancel)) = (
+        PG_exception_stack.get(),
+        error_context_stack.get(),
+        pg_re_throw.get(),
+        before_shmem_exit.get(),
+        cancel_before_shmem_exit.get(),
+    ) else {
+        return f();
+    };
+
+    extern "C" fn on_exit(_code: c_int, arg: usize) {
+        let cleanup = unsafe { &mut *ptr::from_exposed_addr_mut::<&mut dyn FnMut()>(arg) };
+        let _ = crate::panic::catch_unwind(crate::panic::AssertUnwindSafe(cleanup));
+    }
+
+    struct Cancel(unsafe extern "C" fn(OnExitCallback, usize), usize);
+
+    impl Drop for Cancel {
+        // Also on unwind, since `arg` points into the frame.
+        fn drop(&mut self) {
+            unsafe { (self.0)(on_exit, self.1) };
+        }
+    }
+
+    let mut cleanup = cleanup;
+    let arg = (&mut cleanup as *mut &mut dyn FnMut()).expose_addr();
+    unsafe { register(on_exit, arg) };
+    let cancel = Cancel(cancel, arg);
+
+    let saved_exception_stack = exception_stack.load(Ordering::Relaxed);
+    let saved_context_stack = context_stack.load(Ordering::Relaxed);
+    // `f` is Rust code, which a panic unwinds out of as usual.
+    let depth = CATCH_DEPTH.try_with(|d| d.get()).unwrap_or(0);
+    let mut buf = SigJmpBuf([0; 64]);
+    if unsafe { sigsetjmp(&mut buf, 0) } == 0 {
+        return run_protected(&mut buf, saved_exception_stack, depth, f);
+    }
+
+    // PG_CATCH()
+    exception_stack.store(saved_exception_stack, Ordering::Relaxed);
+    context_stack.store(saved_context_stack, Ordering::Relaxed);
+    swap_catch_depth(depth);
+    drop(cancel);
+    on_exit(0, arg);
+    unsafe { rethrow() }
+}
+
+/// The `PG_TRY()` block of [`try_catch`], in a frame of its own so that
+/// nothing in the frame calling `sigsetjmp` changes in between.
+#[inline(never)]
+fn run_protected<R>(
+    buf: &mut SigJmpBuf,
+    saved: *mut c_void,
+    saved_depth: usize,
+    f: impl FnOnce() -> R,
+) -> R {
+    struct Restore(*mut c_void, usize);
+
+    impl Drop for Restore {
+        // Also on unwind, since the jump buffer goes away with the frame.
+        fn drop(&mut self) {
+            if let Some(stack) = PG_exception_stack.get() {
+                stack.store(self.0, Ordering::Relaxed);
+            }
+            swap_catch_depth(self.1);
+        }
+    }
+
+    let _restore = Restore(saved, saved_depth);
+    if let Some(stack) = PG_exception_stack.get() {
+        stack.store((buf as *mut SigJmpBuf).cast(), Ordering::Relaxed);
+    }
+    f()
+}
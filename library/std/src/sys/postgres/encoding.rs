@@ -0,0 +1,87 @@
+//! The `server_encoding` of the database, which every text datum is in.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use super::{elog, pg};
+use crate::borrow::Cow;
+use crate::ffi::CStr;
+use crate::io;
+use libc::{c_char, c_int, c_void};
+
+backend! {
+    fn GetDatabaseEncoding() -> c_int;
+    fn pg_verify_mbstr(encoding: c_int, mbstr: *const c_char, len: c_int, noError: bool) -> bool;
+    fn pg_any_to_server(s: *const c_char, len: c_int, encoding: c_int) -> *mut c_char;
+    fn pg_server_to_any(s: *const c_char, len: c_int, encoding: c_int) -> *mut c_char;
+    fn pfree(pointer: *mut c_void);
+}
+
+// mb/pg_wchar.h
+const PG_UTF8: c_int = 6;
+
+fn len(bytes: &[u8]) -> io::Result<c_int> {
+    c_int::try_from(bytes.len())
+        .map_err(|_| io::const_io_error!(io::ErrorKind::InvalidInput, "text too long for postgres"))
+}
+
+fn invalid(inner: elog::ErrorData) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, crate::os::postgres::PgError { inner })
+}
+
+/// Whether the server encoding is UTF-8, which a Rust string is in already.
+pub fn server_is_utf8() -> io::Result<bool> {
+    Ok(unsafe { pg::require(&GetDatabaseEncoding)?() } == PG_UTF8)
+}
+
+/// Checks that `bytes` are valid text in the server encoding, without any
+/// nul in them.
+///
+/// The error is postgres's own, which names the encoding and the offending
+/// bytes.
+pub fn verify(bytes: &[u8]) -> io::Result<()> {
+    let encoding = unsafe { pg::require(&GetDatabaseEncoding)?() };
+    let verify = pg::require(&pg_verify_mbstr)?;
+    let len = len(bytes)?;
+    elog::try_catch(|| unsafe { verify(encoding, bytes.as_ptr().cast(), len, false) })
+        .map(drop)
+        .map_err(invalid)
+}
+
+/// Converts `bytes` with `convert`, which hands back either `bytes` itself
+/// or a conversion of them it allocated, and raises an `ERROR` for anything
+/// it cannot convert.
+fn convert(
+    convert: unsafe extern "C" fn(*const c_char, c_int, c_int) -> *mut c_char,
+    bytes: &[u8],
+) -> io::Result<Cow<'_, [u8]>> {
+    let len = len(bytes)?;
+    let converted = elog::try_catch(|| unsafe { convert(bytes.as_ptr().cast(), len, PG_UTF8) })
+        .map_err(invalid)?;
+    if converted.cast_const().cast::<u8>() == bytes.as_ptr() {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    let owned = unsafe { CStr::from_ptr(converted) }.to_bytes().to_vec();
+    if let Some(pfree) = pfree.get() {
+        unsafe { pfree(converted.cast()) }
+    }
+    Ok(Cow::Owned(owned))
+}
+
+/// Converts `s` into the server encoding.
+pub fn from_utf8(s: &str) -> io::Result<Cow<'_, [u8]>> {
+    convert(pg::require(&pg_any_to_server)?, s.as_bytes())
+}
+
+/// Converts `bytes` from the server encoding into UTF-8.
+pub fn to_utf8(bytes: &[u8]) -> io::Result<Cow<'_, str>> {
+    match convert(pg::require(&pg_server_to_any)?, bytes)? {
+        // The conversion does not check text already in UTF-8.
+        Cow::Borrowed(bytes) => crate::str::from_utf8(bytes).map(Cow::Borrowed),
+        Cow::Owned(bytes) => String::from_utf8(bytes).map(Cow::Owned).map_err(|e| e.utf8_error()),
+    }
+    .map_err(|_| {
+        io::const_io_error!(
+            io::ErrorKind::InvalidData,
+            "invalid byte sequence for encoding \"UTF8\""
+        )
+    })
+}
@@ -1,9 +1,17 @@
+// `target_os` stays what the postgres server runs on, for `libc` and the
+// like, but to code asking at runtime, this is its own OS.
 pub mod os {
-    pub const FAMILY: &str = "";
-    pub const OS: &str = "";
-    pub const DLL_PREFIX: &str = "";
-    pub const DLL_SUFFIX: &str = "";
-    pub const DLL_EXTENSION: &str = "";
+    pub const FAMILY: &str = "unix";
+    pub const OS: &str = "postgres";
+    pub const DLL_PREFIX: &str = "lib";
+    #[cfg(target_os = "linux")]
+    pub const DLL_SUFFIX: &str = ".so";
+    #[cfg(target_os = "linux")]
+    pub const DLL_EXTENSION: &str = "so";
+    #[cfg(target_os = "macos")]
+    pub const DLL_SUFFIX: &str = ".dylib";
+    #[cfg(target_os = "macos")]
+    pub const DLL_EXTENSION: &str = "dylib";
     pub const EXE_SUFFIX: &str = "";
     pub const EXE_EXTENSION: &str = "";
 }
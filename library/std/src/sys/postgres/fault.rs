@@ -0,0 +1,70 @@
+//! Faults injected into socket I/O, for testing how extensions handle them.
+//!
+//! [`inject_fault`](crate::os::postgres::debug::inject_fault) queues faults
+//! on the calling thread. Each read or write on a socket takes the first one
+//! queued for its kind of operation, before it goes to the kernel, and fails
+//! with it, or transfers fewer bytes than it could. Without the `pg_debug`
+//! feature, nothing is ever queued, and this costs nothing.
+
+use crate::io;
+use crate::os::postgres::debug::{Fault, IoOp};
+
+/// Takes the next fault queued for `op`: the error to fail with, or, for a
+/// short transfer, the most bytes to transfer.
+#[inline]
+pub fn take(op: IoOp) -> io::Result<Option<usize>> {
+    #[cfg(feature = "pg_debug")]
+    return queued::take(op);
+    #[cfg(not(feature = "pg_debug"))]
+    {
+        let _ = op;
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "pg_debug")]
+mod queued {
+    use super::{Fault, IoOp};
+    use crate::cell::{Cell, RefCell};
+    use crate::collections::VecDeque;
+    use crate::io;
+
+    #[thread_local]
+    static QUEUED: RefCell<VecDeque<(IoOp, Fault)>> = RefCell::new(VecDeque::new());
+    // Whether anything is queued, for I/O to check without a borrow.
+    #[thread_local]
+    static ANY: Cell<bool> = Cell::new(false);
+
+    pub fn push(op: IoOp, fault: Fault) {
+        QUEUED.borrow_mut().push_back((op, fault));
+        ANY.set(true);
+    }
+
+    pub fn clear() {
+        QUEUED.borrow_mut().clear();
+        ANY.set(false);
+    }
+
+    #[inline]
+    pub fn take(op: IoOp) -> io::Result<Option<usize>> {
+        if !ANY.get() { Ok(None) } else { take_slow(op) }
+    }
+
+    #[cold]
+    fn take_slow(op: IoOp) -> io::Result<Option<usize>> {
+        let mut queued = QUEUED.borrow_mut();
+        let Some(i) = queued.iter().position(|&(o, _)| o == op) else { return Ok(None) };
+        let (_, fault) = queued.remove(i).unwrap();
+        ANY.set(!queued.is_empty());
+        match fault {
+            Fault::Interrupted => Err(io::Error::from_raw_os_error(libc::EINTR)),
+            Fault::Short(n) => Ok(Some(n)),
+            Fault::NoSpace => Err(io::Error::from_raw_os_error(libc::ENOSPC)),
+            // What a socket whose timeout expires fails with.
+            Fault::TimedOut => Err(io::Error::from_raw_os_error(libc::EAGAIN)),
+        }
+    }
+}
+
+#[cfg(feature = "pg_debug")]
+pub use queued::{clear, push};
@@ -3,11 +3,16 @@
 use crate::cmp;
 use crate::io::{self, BorrowedCursor, IoSlice, IoSliceMut, Read};
 use crate::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
-use crate::sys::unsupported;
+use crate::sys::{cvt_os, unsupported};
 use crate::sys_common::{AsInner, FromInner, IntoInner};
 
+/// An owned file descriptor.
+///
+/// Reading and writing through a `FileDesc` stays unsupported: the only
+/// descriptors that can exist on this target are sockets opened under the
+/// `net` capability, and those do their I/O through `sys::net::Socket`.
 #[derive(Debug)]
-pub struct FileDesc(!);
+pub struct FileDesc(OwnedFd);
 
 impl FileDesc {
     pub fn read(&self, _: &mut [u8]) -> io::Result<usize> {
@@ -51,19 +56,39 @@ impl FileDesc {
     }
 
     pub fn get_cloexec(&self) -> io::Result<bool> {
-        unsupported()
+        unsafe {
+            Ok((cvt_os(libc::fcntl(self.as_raw_fd(), libc::F_GETFD))? & libc::FD_CLOEXEC) != 0)
+        }
     }
 
     pub fn set_cloexec(&self) -> io::Result<()> {
-        unsupported()
+        unsafe {
+            let previous = cvt_os(libc::fcntl(self.as_raw_fd(), libc::F_GETFD))?;
+            let new = previous | libc::FD_CLOEXEC;
+            if new != previous {
+                cvt_os(libc::fcntl(self.as_raw_fd(), libc::F_SETFD, new))?;
+            }
+            Ok(())
+        }
     }
 
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
-        unsupported()
+        unsafe {
+            let previous = cvt_os(libc::fcntl(self.as_raw_fd(), libc::F_GETFL))?;
+            let new = if nonblocking {
+                previous | libc::O_NONBLOCK
+            } else {
+                previous & !libc::O_NONBLOCK
+            };
+            if new != previous {
+                cvt_os(libc::fcntl(self.as_raw_fd(), libc::F_SETFL, new))?;
+            }
+            Ok(())
+        }
     }
 
     pub fn duplicate(&self) -> io::Result<FileDesc> {
-        unsupported()
+        Ok(Self(self.0.try_clone()?))
     }
 }
 
@@ -75,42 +100,42 @@ impl<'a> Read for &'a FileDesc {
 
 impl AsInner<OwnedFd> for FileDesc {
     fn as_inner(&self) -> &OwnedFd {
-        unimplemented!()
+        &self.0
     }
 }
 
 impl IntoInner<OwnedFd> for FileDesc {
     fn into_inner(self) -> OwnedFd {
-        unimplemented!()
+        self.0
     }
 }
 
 impl FromInner<OwnedFd> for FileDesc {
     fn from_inner(owned_fd: OwnedFd) -> Self {
-        unimplemented!()
+        Self(owned_fd)
     }
 }
 
 impl AsFd for FileDesc {
     fn as_fd(&self) -> BorrowedFd<'_> {
-        unimplemented!()
+        self.0.as_fd()
     }
 }
 
 impl AsRawFd for FileDesc {
     fn as_raw_fd(&self) -> RawFd {
-        unimplemented!()
+        self.0.as_raw_fd()
     }
 }
 
 impl IntoRawFd for FileDesc {
     fn into_raw_fd(self) -> RawFd {
-        unimplemented!()
+        self.0.into_raw_fd()
     }
 }
 
 impl FromRawFd for FileDesc {
     unsafe fn from_raw_fd(raw_fd: RawFd) -> Self {
-        unimplemented!()
+        Self(FromRawFd::from_raw_fd(raw_fd))
     }
 }
@@ -2,7 +2,7 @@ use crate::fmt;
 use crate::hash::{Hash, Hasher};
 use crate::path::{Path, PathBuf};
 use crate::sys::time::SystemTime;
-use crate::sys::unsupported;
+use crate::sys::{capability, unsupported};
 
 use crate::default::Default;
 use crate::ffi::{CStr, CString, OsStr, OsString};
@@ -16,6 +16,14 @@ use crate::sync::Arc;
 use crate::sys::fd::FileDesc;
 use crate::sys_common::{AsInner, AsInnerMut, FromInner, IntoInner};
 
+/// Where the filesystem is entered. This tells apart a build that left it
+/// out, without `pg_allow_fs`, but there is no filesystem implementation
+/// for it to reach on this target yet either way.
+fn unsupported_fs<T>() -> io::Result<T> {
+    capability::check_fs()?;
+    unsupported()
+}
+
 impl AsInner<stat64> for FileAttr {
     fn as_inner(&self) -> &stat64 {
         // Should be impossible, but things transmute the return value of this
@@ -233,7 +241,7 @@ impl OpenOptions {
 
 impl File {
     pub fn open(_path: &Path, _opts: &OpenOptions) -> io::Result<File> {
-        unsupported()
+        unsupported_fs()
     }
 
     pub fn file_attr(&self) -> io::Result<FileAttr> {
@@ -315,7 +323,7 @@ impl DirBuilder {
     }
 
     pub fn mkdir(&self, _p: &Path) -> io::Result<()> {
-        unsupported()
+        unsupported_fs()
     }
 
     pub fn set_mode(&mut self, _mode: u32) {
@@ -330,59 +338,59 @@ impl fmt::Debug for File {
 }
 
 pub fn readdir(_p: &Path) -> io::Result<ReadDir> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn unlink(_p: &Path) -> io::Result<()> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn rename(_old: &Path, _new: &Path) -> io::Result<()> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn set_perm(_p: &Path, perm: FilePermissions) -> io::Result<()> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn rmdir(_p: &Path) -> io::Result<()> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn remove_dir_all(_path: &Path) -> io::Result<()> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn try_exists(_path: &Path) -> io::Result<bool> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn readlink(_p: &Path) -> io::Result<PathBuf> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn symlink(_original: &Path, _link: &Path) -> io::Result<()> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn link(_src: &Path, _dst: &Path) -> io::Result<()> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn stat(_p: &Path) -> io::Result<FileAttr> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn lstat(_p: &Path) -> io::Result<FileAttr> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn canonicalize(_p: &Path) -> io::Result<PathBuf> {
-    unsupported()
+    unsupported_fs()
 }
 
 pub fn copy(_from: &Path, _to: &Path) -> io::Result<u64> {
-    unsupported()
+    unsupported_fs()
 }
 
 #[cfg(any(
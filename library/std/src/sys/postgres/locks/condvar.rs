@@ -1,28 +1,85 @@
+//! With a single thread, nothing but a signal handler can notify a condvar
+//! while we wait on it, so waiting means sleeping on the backend latch. Any
+//! latch set wakes the waiter up (spurious wakeups are allowed), and pending
+//! interrupts are checked for at least every [`INTERRUPT_CHECK_INTERVAL`], so
+//! that a library sleeping on a condvar can still be cancelled.
+
+use crate::cmp;
+use crate::io;
+use crate::sync::atomic::AtomicBool;
+use crate::sync::atomic::Ordering::{Acquire, Release};
 use crate::sys::locks::Mutex;
-use crate::time::Duration;
+use crate::sys::pg;
+use crate::sys::thread::Thread;
+use crate::time::{Duration, Instant};
+
+const INTERRUPT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
-pub struct Condvar {}
+pub struct Condvar {
+    notified: AtomicBool,
+}
 
 pub type MovableCondvar = Condvar;
 
 impl Condvar {
     pub const fn new() -> Condvar {
-        Condvar {}
+        Condvar { notified: AtomicBool::new(false) }
     }
 
-    // No-ops can be safe, it's fine.
     #[inline]
-    pub fn notify_one(&self) {}
+    pub fn notify_one(&self) {
+        self.notified.store(true, Release);
+        pg::set_latch();
+    }
 
-    // No-ops can be safe, it's fine.
     #[inline]
-    pub fn notify_all(&self) {}
+    pub fn notify_all(&self) {
+        self.notify_one()
+    }
+
+    pub unsafe fn wait(&self, mutex: &Mutex) {
+        mutex.unlock();
+        let result = self.sleep(None);
+        mutex.lock();
+        match result {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                panic!("condvar wait not supported outside of a postgres backend")
+            }
+            Err(e) => panic!("{e}"),
+        }
+    }
 
-    pub unsafe fn wait(&self, _mutex: &Mutex) {
-        panic!("condvar wait not supported")
+    pub unsafe fn wait_timeout(&self, mutex: &Mutex, dur: Duration) -> bool {
+        mutex.unlock();
+        let result = self.sleep(Instant::now().checked_add(dur));
+        mutex.lock();
+        match result {
+            Ok(woken) => woken,
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                Thread::sleep(dur);
+                false
+            }
+            Err(e) => panic!("{e}"),
+        }
     }
 
-    pub unsafe fn wait_timeout(&self, _mutex: &Mutex, _dur: Duration) -> bool {
-        panic!("condvar wait not supported");
+    /// Sleeps until notified, the latch is set, or `deadline` passes, in
+    /// which case this returns `Ok(false)`. Fails if an interrupt is pending.
+    fn sleep(&self, deadline: Option<Instant>) -> io::Result<bool> {
+        loop {
+            if self.notified.swap(false, Acquire) {
+                return Ok(true);
+            }
+            let now = Instant::now();
+            if deadline.map_or(false, |deadline| deadline <= now) {
+                return Ok(false);
+            }
+            let slice = now + INTERRUPT_CHECK_INTERVAL;
+            if pg::wait_latch(Some(deadline.map_or(slice, |deadline| cmp::min(deadline, slice))))? {
+                self.notified.store(false, Release);
+                return Ok(true);
+            }
+        }
     }
 }
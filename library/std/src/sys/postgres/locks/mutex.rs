@@ -1,3 +1,8 @@
+//! A backend runs exactly one thread, so a mutex can never be contended:
+//! locking it again before unlocking is a re-entrancy bug, not something to
+//! wait out. That makes a plain flag enough, with no atomics or futex calls
+//! on the fast path.
+
 use crate::cell::Cell;
 
 pub struct Mutex {
@@ -10,7 +15,14 @@ pub type MovableMutex = Mutex;
 unsafe impl Send for Mutex {}
 unsafe impl Sync for Mutex {} // no threads on this platform
 
+#[cold]
+#[inline(never)]
+fn recursive_lock() -> ! {
+    panic!("cannot recursively acquire mutex")
+}
+
 impl Mutex {
+    #[inline]
     pub const fn new() -> Mutex {
         Mutex { locked: Cell::new(false) }
     }
@@ -20,16 +32,19 @@ impl Mutex {
 
     #[inline]
     pub unsafe fn lock(&self) {
-        assert_eq!(self.locked.replace(true), false, "cannot recursively acquire mutex");
+        if self.locked.replace(true) {
+            recursive_lock()
+        }
     }
 
     #[inline]
     pub unsafe fn unlock(&self) {
+        debug_assert!(self.locked.get());
         self.locked.set(false);
     }
 
     #[inline]
     pub unsafe fn try_lock(&self) -> bool {
-        self.locked.replace(true) == false
+        !self.locked.replace(true)
     }
 }
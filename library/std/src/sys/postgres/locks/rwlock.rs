@@ -1,7 +1,11 @@
+//! See `mutex.rs`: with a single thread, a conflicting lock request can only
+//! come from the thread already holding the lock, so it fails immediately.
+
 use crate::cell::Cell;
 
 pub struct RwLock {
     // This platform has no threads, so we can use a Cell here.
+    // 0: unlocked, -1: locked for writing, n > 0: n readers.
     mode: Cell<isize>,
 }
 
@@ -10,18 +14,22 @@ pub type MovableRwLock = RwLock;
 unsafe impl Send for RwLock {}
 unsafe impl Sync for RwLock {} // no threads on this platform
 
+#[cold]
+#[inline(never)]
+fn conflict(msg: &'static str) -> ! {
+    panic!("{msg}")
+}
+
 impl RwLock {
+    #[inline]
     pub const fn new() -> RwLock {
         RwLock { mode: Cell::new(0) }
     }
 
     #[inline]
     pub unsafe fn read(&self) {
-        let m = self.mode.get();
-        if m >= 0 {
-            self.mode.set(m + 1);
-        } else {
-            rtabort!("rwlock locked for writing");
+        if !self.try_read() {
+            conflict("rwlock locked for writing")
         }
     }
 
@@ -38,8 +46,8 @@ impl RwLock {
 
     #[inline]
     pub unsafe fn write(&self) {
-        if self.mode.replace(-1) != 0 {
-            rtabort!("rwlock locked for reading")
+        if !self.try_write() {
+            conflict("rwlock locked for reading or writing")
         }
     }
 
@@ -55,11 +63,13 @@ impl RwLock {
 
     #[inline]
     pub unsafe fn read_unlock(&self) {
+        debug_assert!(self.mode.get() > 0);
         self.mode.set(self.mode.get() - 1);
     }
 
     #[inline]
     pub unsafe fn write_unlock(&self) {
-        assert_eq!(self.mode.replace(0), -1);
+        debug_assert_eq!(self.mode.get(), -1);
+        self.mode.set(0);
     }
 }
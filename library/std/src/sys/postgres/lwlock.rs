@@ -0,0 +1,66 @@
+//! Postgres lightweight locks, for data in shared memory.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use crate::ffi::CStr;
+use crate::io;
+use crate::sys::pg::require;
+use libc::{c_char, c_int};
+
+// storage/lwlock.h
+const LW_EXCLUSIVE: c_int = 0;
+const LW_SHARED: c_int = 1;
+
+/// `LWLock` from storage/lwlock.h. Only ever touched by the backend.
+#[repr(C)]
+pub struct LWLock {
+    tranche: u16,
+    state: u32,
+    waiters_head: c_int,
+    waiters_tail: c_int,
+}
+
+backend! {
+    fn LWLockNewTrancheId() -> c_int;
+    fn LWLockRegisterTranche(tranche_id: c_int, tranche_name: *const c_char);
+    fn LWLockInitialize(lock: *mut LWLock, tranche_id: c_int);
+    fn LWLockAcquire(lock: *mut LWLock, mode: c_int) -> bool;
+    fn LWLockConditionalAcquire(lock: *mut LWLock, mode: c_int) -> bool;
+    fn LWLockRelease(lock: *mut LWLock);
+}
+
+/// Allocates a new tranche ID, shared by all backends.
+pub fn new_tranche_id() -> io::Result<c_int> {
+    Ok(unsafe { require(&LWLockNewTrancheId)?() })
+}
+
+/// Names tranche `id` in this backend, as reported in `pg_stat_activity`.
+pub fn register_tranche(id: c_int, name: &'static CStr) -> io::Result<()> {
+    unsafe { require(&LWLockRegisterTranche)?(id, name.as_ptr()) };
+    Ok(())
+}
+
+impl LWLock {
+    /// Initializes the lock at `lock`, which must be in shared memory.
+    pub unsafe fn initialize(lock: *mut LWLock, tranche: c_int) -> io::Result<()> {
+        unsafe { require(&LWLockInitialize)?(lock, tranche) };
+        Ok(())
+    }
+
+    fn mode(exclusive: bool) -> c_int {
+        if exclusive { LW_EXCLUSIVE } else { LW_SHARED }
+    }
+
+    /// Blocks until the lock is held in the given mode.
+    pub unsafe fn acquire(lock: *mut LWLock, exclusive: bool) {
+        unsafe { LWLockAcquire.get().unwrap()(lock, Self::mode(exclusive)) };
+    }
+
+    /// Takes the lock in the given mode if that can be done without waiting.
+    pub unsafe fn try_acquire(lock: *mut LWLock, exclusive: bool) -> bool {
+        unsafe { LWLockConditionalAcquire.get().unwrap()(lock, Self::mode(exclusive)) }
+    }
+
+    pub unsafe fn release(lock: *mut LWLock) {
+        unsafe { LWLockRelease.get().unwrap()(lock) }
+    }
+}
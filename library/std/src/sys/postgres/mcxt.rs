@@ -0,0 +1,306 @@
+//! Memory contexts, from utils/memutils.h and utils/palloc.h.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use crate::ffi::CStr;
+use crate::io;
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::sys::symbol::Backend;
+use crate::sys::{pg, sanitizer};
+use libc::{c_char, c_int, c_void};
+
+backend! {
+    static CurrentMemoryContext: AtomicPtr<c_void>;
+    static TopMemoryContext: AtomicPtr<c_void>;
+    static TopTransactionContext: AtomicPtr<c_void>;
+    static CurTransactionContext: AtomicPtr<c_void>;
+    fn MemoryContextAllocExtended(context: *mut c_void, size: usize, flags: c_int) -> *mut c_void;
+    fn pfree(pointer: *mut c_void);
+    fn MemoryContextRegisterResetCallback(context: *mut c_void, cb: *mut MemoryContextCallback);
+}
+
+// utils/palloc.h
+#[repr(C)]
+struct MemoryContextCallback {
+    func: unsafe extern "C" fn(*mut c_void),
+    arg: *mut c_void,
+    next: *mut MemoryContextCallback,
+}
+
+// nodes/memnodes.h, as of postgres 13, which added `mem_allocated`.
+#[repr(C)]
+struct MemoryContextData {
+    type_: c_int,
+    isReset: bool,
+    allowInCritSection: bool,
+    mem_allocated: usize,
+    methods: *const c_void,
+    parent: *mut MemoryContextData,
+    firstchild: *mut MemoryContextData,
+    prevchild: *mut MemoryContextData,
+    nextchild: *mut MemoryContextData,
+    name: *const c_char,
+    ident: *const c_char,
+    reset_cbs: *mut MemoryContextCallback,
+}
+
+// utils/palloc.h
+const MCXT_ALLOC_NO_OOM: c_int = 0x02;
+// utils/memutils.h
+const MAX_ALLOC_SIZE: usize = 0x3fff_ffff;
+
+/// `MAXIMUM_ALIGNOF`, what `palloc` aligns to: the alignment of a `double`
+/// or `int64`, whichever is larger, which is only 4 on 32-bit x86.
+#[cfg(target_arch = "x86")]
+pub const MAXIMUM_ALIGNOF: usize = 4;
+#[cfg(not(target_arch = "x86"))]
+pub const MAXIMUM_ALIGNOF: usize = 8;
+
+fn load(context: &Backend<&'static AtomicPtr<c_void>>) -> io::Result<*mut c_void> {
+    let context = pg::require(context)?.load(Ordering::Relaxed);
+    if context.is_null() {
+        Err(io::const_io_error!(io::ErrorKind::Other, "memory context does not exist right now"))
+    } else {
+        Ok(context)
+    }
+}
+
+pub fn current() -> io::Result<*mut c_void> {
+    load(&CurrentMemoryContext)
+}
+
+pub fn top() -> io::Result<*mut c_void> {
+    load(&TopMemoryContext)
+}
+
+/// The context of the current top-level transaction, which is null outside
+/// of one.
+pub fn top_transaction() -> io::Result<*mut c_void> {
+    load(&TopTransactionContext)
+}
+
+/// The context of the current (sub)transaction, which is null outside of
+/// one.
+pub fn cur_transaction() -> io::Result<*mut c_void> {
+    load(&CurTransactionContext)
+}
+
+/// Allocates `size` bytes in `context`, failing rather than raising an
+/// `ERROR` when out of memory.
+///
+/// The memory is only aligned to [`MAXIMUM_ALIGNOF`], which is less than
+/// the alignment of some Rust types, like `u128`.
+///
+/// Under AddressSanitizer, a poisoned redzone follows the memory, until it
+/// is freed, released, or its context is reset. Under MemorySanitizer, the
+/// memory is uninitialized, as it is in truth.
+///
+/// # Safety
+///
+/// `context` is a valid memory context.
+pub unsafe fn alloc(context: *mut c_void, size: usize) -> io::Result<*mut u8> {
+    if size > MAX_ALLOC_SIZE - sanitizer::REDZONE {
+        return Err(io::const_io_error!(
+            io::ErrorKind::OutOfMemory,
+            "allocation too large for a memory context",
+        ));
+    }
+    let ptr = unsafe { raw_alloc(context, size + sanitizer::REDZONE)? };
+    debug_assert_eq!(ptr.addr() % MAXIMUM_ALIGNOF, 0);
+    #[cfg(feature = "pg_debug")]
+    if let Err(e) = unsafe { own(context, size) } {
+        unsafe { raw_free(ptr) };
+        return Err(e);
+    }
+    if sanitizer::REDZONE > 0 {
+        // postgres reuses the chunk after a reset without telling anyone, so
+        // the redzone has to go then at the latest.
+        if let Err(e) = unsafe { unpoison_on_reset(context, ptr.add(size)) } {
+            #[cfg(feature = "pg_debug")]
+            disown(context, size);
+            unsafe { raw_free(ptr) };
+            return Err(e);
+        }
+        unsafe { sanitizer::poison(ptr.add(size), sanitizer::REDZONE) };
+    }
+    unsafe { sanitizer::mark_uninit(ptr, size) };
+    Ok(ptr)
+}
+
+unsafe fn raw_alloc(context: *mut c_void, size: usize) -> io::Result<*mut u8> {
+    let alloc = pg::require(&MemoryContextAllocExtended)?;
+    let ptr = unsafe { alloc(context, size, MCXT_ALLOC_NO_OOM) };
+    if ptr.is_null() {
+        Err(io::const_io_error!(io::ErrorKind::OutOfMemory, "out of memory"))
+    } else {
+        Ok(ptr.cast())
+    }
+}
+
+unsafe fn raw_free(ptr: *mut u8) {
+    if let Some(pfree) = pfree.get() {
+        unsafe { pfree(ptr.cast()) }
+    }
+}
+
+/// Unpoisons the redzone at `redzone` when `context` is reset or deleted.
+unsafe fn unpoison_on_reset(context: *mut c_void, redzone: *mut u8) -> io::Result<()> {
+    unsafe extern "C" fn unpoison(redzone: *mut c_void) {
+        unsafe { sanitizer::unpoison(redzone.cast(), sanitizer::REDZONE) }
+    }
+
+    unsafe { on_reset(context, unpoison, redzone.cast()) }
+}
+
+/// Calls `func` with `arg` when `context` is reset or deleted.
+unsafe fn on_reset(
+    context: *mut c_void,
+    func: unsafe extern "C" fn(*mut c_void),
+    arg: *mut c_void,
+) -> io::Result<()> {
+    let register = pg::require(&MemoryContextRegisterResetCallback)?;
+    let size = crate::mem::size_of::<MemoryContextCallback>();
+    let callback = unsafe { raw_alloc(context, size)? }.cast::<MemoryContextCallback>();
+    unsafe {
+        callback.write(MemoryContextCallback { func, arg, next: crate::ptr::null_mut() });
+        register(context, callback);
+    }
+    Ok(())
+}
+
+/// Frees memory from [`alloc`], which was `size` bytes in `context`.
+///
+/// # Safety
+///
+/// `ptr` was palloc'd, and its memory context is still there.
+pub unsafe fn free(context: *mut c_void, ptr: *mut u8, size: usize) {
+    unsafe {
+        release(context, ptr, size);
+        raw_free(ptr);
+    }
+}
+
+/// Gets memory from [`alloc`], which was `size` bytes in `context`, ready
+/// to hand over to postgres, which may `pfree` it and reuse the chunk: there
+/// is no redzone after it from here on.
+///
+/// # Safety
+///
+/// `ptr` was palloc'd, and its memory context is still there.
+pub unsafe fn release(context: *mut c_void, ptr: *mut u8, size: usize) {
+    #[cfg(feature = "pg_debug")]
+    disown(context, size);
+    #[cfg(not(feature = "pg_debug"))]
+    let _ = context;
+    unsafe { sanitizer::unpoison(ptr.add(size), sanitizer::REDZONE) }
+}
+
+/// What std has palloc'd in a context, and not freed or handed over.
+///
+/// Only kept track of with the `pg_debug` feature: the lookups would cost
+/// every palloc and pfree otherwise.
+#[cfg(feature = "pg_debug")]
+struct Owned {
+    context: *mut c_void,
+    bytes: usize,
+    chunks: usize,
+}
+
+// Only ever the backend's own thread pallocs, and until the context is reset
+// or deleted.
+#[cfg(feature = "pg_debug")]
+#[thread_local]
+static OWNED: crate::cell::RefCell<Vec<Owned>> = crate::cell::RefCell::new(Vec::new());
+
+/// Counts `size` bytes more as owned in `context`, which is forgotten about
+/// when it is reset or deleted.
+#[cfg(feature = "pg_debug")]
+unsafe fn own(context: *mut c_void, size: usize) -> io::Result<()> {
+    unsafe extern "C" fn forget(context: *mut c_void) {
+        OWNED.borrow_mut().retain(|owned| owned.context != context);
+    }
+
+    if let Some(owned) = OWNED.borrow_mut().iter_mut().find(|owned| owned.context == context) {
+        owned.bytes += size;
+        owned.chunks += 1;
+        return Ok(());
+    }
+    unsafe { on_reset(context, forget, context)? };
+    OWNED.borrow_mut().push(Owned { context, bytes: size, chunks: 1 });
+    Ok(())
+}
+
+#[cfg(feature = "pg_debug")]
+fn disown(context: *mut c_void, size: usize) {
+    if let Some(owned) = OWNED.borrow_mut().iter_mut().find(|owned| owned.context == context) {
+        owned.bytes -= size;
+        owned.chunks -= 1;
+    }
+}
+
+/// A memory context, as [`walk`] finds it.
+pub struct ContextInfo<'a> {
+    pub context: *mut c_void,
+    /// How many levels below the root of the walk it is.
+    pub depth: usize,
+    pub name: &'a CStr,
+    pub ident: Option<&'a CStr>,
+    /// The memory postgres got for it, in blocks.
+    pub allocated: usize,
+    /// What std has palloc'd in it and still owns, in bytes and chunks,
+    /// with the `pg_debug` feature.
+    pub owned: Option<(usize, usize)>,
+}
+
+/// Calls `f` with `root` and every context under it, each one before its
+/// children.
+///
+/// # Safety
+///
+/// `root` is a valid memory context, and `f` does not create or delete any.
+pub unsafe fn walk(root: *mut c_void, f: &mut dyn FnMut(&ContextInfo<'_>)) {
+    #[cfg(feature = "pg_debug")]
+    let owned = OWNED.borrow();
+    let root = root.cast::<MemoryContextData>();
+    let mut node = root;
+    let mut depth = 0;
+    loop {
+        let data = unsafe { &*node };
+        let context = node.cast::<c_void>();
+        #[cfg(feature = "pg_debug")]
+        let owned = Some(
+            owned
+                .iter()
+                .find(|owned| owned.context == context)
+                .map_or((0, 0), |owned| (owned.bytes, owned.chunks)),
+        );
+        #[cfg(not(feature = "pg_debug"))]
+        let owned = None;
+        f(&ContextInfo {
+            context,
+            depth,
+            name: unsafe { CStr::from_ptr(data.name) },
+            ident: (!data.ident.is_null()).then(|| unsafe { CStr::from_ptr(data.ident) }),
+            allocated: data.mem_allocated,
+            owned,
+        });
+
+        if !data.firstchild.is_null() {
+            node = data.firstchild;
+            depth += 1;
+            continue;
+        }
+        // Up to the next sibling of this context or of one of its parents.
+        loop {
+            if node == root {
+                return;
+            }
+            let data = unsafe { &*node };
+            if !data.nextchild.is_null() {
+                node = data.nextchild;
+                break;
+            }
+            node = data.parent;
+            depth -= 1;
+        }
+    }
+}
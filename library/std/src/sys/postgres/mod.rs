@@ -2,32 +2,57 @@
 #[path = "../unix/weak.rs"]
 #[macro_use]
 pub mod weak;
+#[macro_use]
+pub mod symbol;
 
 pub mod alloc;
 pub mod args;
+pub mod barrier;
+pub mod bgworker;
+pub mod blocking;
+pub mod capability;
+pub mod channel;
 #[path = "../unix/cmath.rs"]
 pub mod cmath;
+pub mod dsa;
+pub mod dshash;
+pub mod elog;
+pub mod encoding;
 pub mod env;
+pub mod fault;
 pub mod fs;
 pub mod io;
 pub mod locks;
+pub mod lwlock;
+pub mod mcxt;
 pub mod net;
+pub mod once;
 pub mod os;
 #[path = "../unix/os_str.rs"]
 pub mod os_str;
+pub mod parallel;
 #[path = "../unix/path.rs"]
 pub mod path;
+pub mod pg;
 pub mod pipe;
 pub mod process;
+pub mod rand;
+pub mod sanitizer;
+pub mod shm_mq;
+pub mod spin;
 pub mod stdio;
+pub mod string_info;
 pub mod thread;
 #[cfg(target_thread_local)]
 pub mod thread_local_dtor;
 pub mod thread_local_key;
+pub mod thread_parker;
 pub mod time;
+pub mod varlena;
 
 mod common;
 pub use common::*;
+pub use rand::hashmap_random_keys;
 
 pub mod fd;
 
@@ -78,6 +103,25 @@ where
     }
 }
 
+/// Like `cvt_unsup`, but reports the real OS error. Only for code paths that
+/// are allowed to touch the OS at all, i.e. those behind a `capability`.
+pub fn cvt_os<T: IsMinusOne>(t: T) -> crate::io::Result<T> {
+    if t.is_minus_one() { Err(real_last_os_error_use_carefully()) } else { Ok(t) }
+}
+
+pub fn cvt_os_r<T, F>(mut f: F) -> crate::io::Result<T>
+where
+    T: IsMinusOne,
+    F: FnMut() -> T,
+{
+    loop {
+        match cvt_os(f()) {
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            other => return other,
+        }
+    }
+}
+
 // #[allow(dead_code)] // Not used on all platforms.
 // pub fn cvt_nz(error: libc::c_int) -> crate::io::Result<()> {
 //     if error == 0 {
@@ -1,425 +1,1117 @@
 #![allow(non_camel_case_types)]
-use crate::fmt;
-use crate::io::{self, IoSlice, IoSliceMut};
-use crate::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
-use crate::sys::unsupported;
-
-use crate::os::unix::prelude::*;
-
+//! Sockets for the postgres target.
+//!
+//! This is a trimmed-down merge of `sys_common::net` and `sys::unix::net`.
+//! Every way of obtaining a new socket (connecting, binding, resolving a host)
+//! goes through `capability::check` first (`Capability::Udp` for datagram
+//! sockets, `Capability::Net` for everything else), so nothing here touches
+//! the network until the embedding runtime has opted in.
 use crate::cmp;
-use crate::ffi::CStr;
+use crate::convert::{TryFrom, TryInto};
+use crate::fmt;
+use crate::io::{self, ErrorKind, IoSlice, IoSliceMut};
 use crate::mem;
+use crate::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use crate::os::postgres::capability::Capability;
+use crate::os::postgres::debug::IoOp;
+use crate::os::unix::ffi::OsStrExt;
 use crate::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
+use crate::path::{Path, PathBuf};
+use crate::ptr;
 use crate::str;
+use crate::sync::{PoisonError, RwLock};
+use crate::sys::blocking;
+use crate::sys::capability;
+use crate::sys::common::small_c_string::run_with_cstr;
+use crate::sys::fault;
 use crate::sys::fd::FileDesc;
+use crate::sys::pg::{self, Interest};
+use crate::sys::{cvt_os as cvt, cvt_os_r as cvt_r};
 use crate::sys_common::{AsInner, FromInner, IntoInner};
 use crate::time::{Duration, Instant};
 
+use crate::ffi::CStr;
 use core::ffi::c_void;
 pub(crate) use libc as netc;
-use libc::{c_int, sockaddr, socklen_t};
+use libc::{c_int, size_t, sockaddr, socklen_t, MSG_PEEK};
+
+cfg_if::cfg_if! {
+    if #[cfg(target_vendor = "apple")] {
+        use libc::SO_LINGER_SEC as SO_LINGER;
+        use libc::IPV6_JOIN_GROUP as IPV6_ADD_MEMBERSHIP;
+        use libc::IPV6_LEAVE_GROUP as IPV6_DROP_MEMBERSHIP;
+        use libc::TCP_KEEPALIVE as TCP_KEEPIDLE;
+        const MSG_NOSIGNAL: c_int = 0x0;
+    } else {
+        use libc::{SO_LINGER, IPV6_ADD_MEMBERSHIP, IPV6_DROP_MEMBERSHIP, MSG_NOSIGNAL, TCP_KEEPIDLE};
+    }
+}
+
+pub type wrlen_t = size_t;
+
+// Both Linux and macOS define `IOV_MAX` as 1024.
+const fn max_iov() -> usize {
+    1024
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Outbound connection hook
+////////////////////////////////////////////////////////////////////////////////
+
+type AddrHook = Box<dyn Fn(&SocketAddr) -> io::Result<()> + Sync + Send>;
+
+/// Consulted before every stream connection.
+static CONNECT_HOOK: RwLock<Option<AddrHook>> = RwLock::new(None);
+/// Consulted for every datagram destination.
+static UDP_POLICY: RwLock<Option<AddrHook>> = RwLock::new(None);
+
+fn replace_hook(slot: &RwLock<Option<AddrHook>>, hook: Option<AddrHook>) -> Option<AddrHook> {
+    let mut slot = slot.write().unwrap_or_else(PoisonError::into_inner);
+    mem::replace(&mut *slot, hook)
+}
+
+fn run_hook(slot: &RwLock<Option<AddrHook>>, addr: &SocketAddr) -> io::Result<()> {
+    match &*slot.read().unwrap_or_else(PoisonError::into_inner) {
+        Some(hook) => hook(addr),
+        None => Ok(()),
+    }
+}
+
+pub fn set_connect_hook(hook: Option<AddrHook>) -> Option<AddrHook> {
+    replace_hook(&CONNECT_HOOK, hook)
+}
+
+pub fn set_udp_policy(policy: Option<AddrHook>) -> Option<AddrHook> {
+    replace_hook(&UDP_POLICY, policy)
+}
+
+/// Gives the registered hook a chance to veto a connection to `addr`.
+fn audit_connect(addr: &SocketAddr) -> io::Result<()> {
+    run_hook(&CONNECT_HOOK, addr)
+}
+
+/// Gives the registered policy a chance to veto datagrams to `addr`.
+fn audit_datagram(addr: &SocketAddr) -> io::Result<()> {
+    run_hook(&UDP_POLICY, addr)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Multi-address connect
+////////////////////////////////////////////////////////////////////////////////
+
+/// How long to give one connection attempt before also trying the next
+/// address, as recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Orders `addrs` the way RFC 8305 ("happy eyeballs") asks for: alternating
+/// between address families, starting with the family of the first address.
+fn interleave_families(addrs: impl Iterator<Item = SocketAddr>) -> Vec<SocketAddr> {
+    let (mut first, mut second) = (Vec::new(), Vec::new());
+    let mut first_is_v6 = None;
+    for addr in addrs {
+        if *first_is_v6.get_or_insert(addr.is_ipv6()) == addr.is_ipv6() {
+            first.push(addr);
+        } else {
+            second.push(addr);
+        }
+    }
+
+    let mut out = Vec::with_capacity(first.len() + second.len());
+    let (mut first, mut second) = (first.into_iter(), second.into_iter());
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return out,
+            (a, b) => out.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Connects to one of `addrs`, starting a new attempt every
+/// `CONNECTION_ATTEMPT_DELAY` while earlier ones are still pending, and
+/// returning the first that succeeds. `deadline` bounds the whole operation.
+pub fn connect_any(
+    addrs: impl Iterator<Item = SocketAddr>,
+    deadline: Option<Instant>,
+) -> io::Result<TcpStream> {
+    let mut addrs = interleave_families(addrs).into_iter();
+    let mut pending: Vec<Socket> = Vec::new();
+    let mut last_err = None;
+
+    loop {
+        if let Some(addr) = addrs.next() {
+            match Socket::start_connect(&addr) {
+                Ok((sock, true)) => {
+                    sock.set_nonblocking(false)?;
+                    return Ok(TcpStream { inner: sock });
+                }
+                Ok((sock, false)) => pending.push(sock),
+                Err(e) => {
+                    // Move on to the next address right away.
+                    last_err = Some(e);
+                    continue;
+                }
+            }
+        }
+
+        if pending.is_empty() {
+            return Err(last_err.unwrap_or_else(|| {
+                io::const_io_error!(ErrorKind::InvalidInput, "could not resolve to any addresses")
+            }));
+        }
+
+        let now = Instant::now();
+        if deadline.map_or(false, |d| now >= d) {
+            return Err(io::const_io_error!(io::ErrorKind::TimedOut, "connection timed out"));
+        }
+        // Only wait for the next attempt if there is an address left for it.
+        let wait_until = match (deadline, addrs.len()) {
+            (deadline, 0) => deadline,
+            (Some(deadline), _) => Some(cmp::min(deadline, now + CONNECTION_ATTEMPT_DELAY)),
+            (None, _) => Some(now + CONNECTION_ATTEMPT_DELAY),
+        };
+
+        let fds: Vec<c_int> = pending.iter().map(|s| s.as_raw_fd()).collect();
+        let ready = pg::wait_sockets(&fds, Interest::Write, wait_until)?;
+        // Remove from the back so that earlier indices stay valid.
+        for i in ready.into_iter().rev() {
+            let sock = pending.swap_remove(i);
+            match sock.take_error()? {
+                None => {
+                    sock.set_nonblocking(false)?;
+                    return Ok(TcpStream { inner: sock });
+                }
+                Some(e) => last_err = Some(e),
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// sockaddr and misc bindings
+////////////////////////////////////////////////////////////////////////////////
+
+pub fn init() {}
+
+pub fn cvt_gai(err: c_int) -> io::Result<()> {
+    if err == 0 {
+        return Ok(());
+    }
+
+    if err == libc::EAI_SYSTEM {
+        return Err(crate::sys::real_last_os_error_use_carefully());
+    }
+
+    let detail = unsafe {
+        str::from_utf8(CStr::from_ptr(libc::gai_strerror(err)).to_bytes()).unwrap().to_owned()
+    };
+
+    Err(io::Error::new(
+        io::ErrorKind::Uncategorized,
+        &format!("failed to lookup address information: {detail}")[..],
+    ))
+}
+
+pub fn setsockopt<T>(
+    sock: &Socket,
+    level: c_int,
+    option_name: c_int,
+    option_value: T,
+) -> io::Result<()> {
+    unsafe {
+        cvt(libc::setsockopt(
+            sock.as_raw(),
+            level,
+            option_name,
+            &option_value as *const T as *const _,
+            mem::size_of::<T>() as socklen_t,
+        ))?;
+        Ok(())
+    }
+}
+
+pub fn getsockopt<T: Copy>(sock: &Socket, level: c_int, option_name: c_int) -> io::Result<T> {
+    unsafe {
+        let mut option_value: T = mem::zeroed();
+        let mut option_len = mem::size_of::<T>() as socklen_t;
+        cvt(libc::getsockopt(
+            sock.as_raw(),
+            level,
+            option_name,
+            &mut option_value as *mut T as *mut _,
+            &mut option_len,
+        ))?;
+        Ok(option_value)
+    }
+}
+
+fn sockname<F>(f: F) -> io::Result<SocketAddr>
+where
+    F: FnOnce(*mut sockaddr, *mut socklen_t) -> c_int,
+{
+    unsafe {
+        let mut storage: libc::sockaddr_storage = mem::zeroed();
+        let mut len = mem::size_of_val(&storage) as socklen_t;
+        cvt(f(&mut storage as *mut _ as *mut _, &mut len))?;
+        sockaddr_to_addr(&storage, len as usize)
+    }
+}
+
+pub fn sockaddr_to_addr(storage: &libc::sockaddr_storage, len: usize) -> io::Result<SocketAddr> {
+    match storage.ss_family as c_int {
+        libc::AF_INET => {
+            assert!(len as usize >= mem::size_of::<libc::sockaddr_in>());
+            Ok(SocketAddr::V4(FromInner::from_inner(unsafe {
+                *(storage as *const _ as *const libc::sockaddr_in)
+            })))
+        }
+        libc::AF_INET6 => {
+            assert!(len as usize >= mem::size_of::<libc::sockaddr_in6>());
+            Ok(SocketAddr::V6(FromInner::from_inner(unsafe {
+                *(storage as *const _ as *const libc::sockaddr_in6)
+            })))
+        }
+        _ => Err(io::const_io_error!(ErrorKind::InvalidInput, "invalid argument")),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Local server socket
+////////////////////////////////////////////////////////////////////////////////
+
+/// The sockets the server accepts local connections on, one for each entry
+/// of `unix_socket_directories`.
+pub fn local_socket_paths() -> io::Result<Vec<PathBuf>> {
+    let (dirs, port) = match (
+        pg::config_option("unix_socket_directories\0"),
+        pg::config_option("port\0").and_then(|port| port.parse::<u16>().ok()),
+    ) {
+        (Some(dirs), Some(port)) => (dirs, port),
+        _ => {
+            return Err(io::const_io_error!(
+                io::ErrorKind::Unsupported,
+                "the server's local socket is only known inside a backend",
+            ));
+        }
+    };
+
+    Ok(dirs
+        .split(',')
+        .map(|dir| dir.trim().trim_matches('"'))
+        // Entries starting with `@` live in the abstract namespace, which
+        // `connect_unix` cannot address.
+        .filter(|dir| !dir.is_empty() && !dir.starts_with('@'))
+        .map(|dir| Path::new(dir).join(format!(".s.PGSQL.{port}")))
+        .collect())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// get_host_addresses
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct LookupHost {
+    original: *mut libc::addrinfo,
+    cur: *mut libc::addrinfo,
+    port: u16,
+}
+
+impl LookupHost {
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Iterator for LookupHost {
+    type Item = SocketAddr;
+    fn next(&mut self) -> Option<SocketAddr> {
+        loop {
+            unsafe {
+                let cur = self.cur.as_ref()?;
+                self.cur = cur.ai_next;
+                match sockaddr_to_addr(mem::transmute(cur.ai_addr), cur.ai_addrlen as usize) {
+                    Ok(addr) => return Some(addr),
+                    Err(_) => continue,
+                }
+            }
+        }
+    }
+}
+
+unsafe impl Sync for LookupHost {}
+unsafe impl Send for LookupHost {}
+
+impl Drop for LookupHost {
+    fn drop(&mut self) {
+        unsafe { libc::freeaddrinfo(self.original) }
+    }
+}
+
+impl TryFrom<&str> for LookupHost {
+    type Error = io::Error;
+
+    fn try_from(s: &str) -> io::Result<LookupHost> {
+        macro_rules! try_opt {
+            ($e:expr, $msg:expr) => {
+                match $e {
+                    Some(r) => r,
+                    None => return Err(io::const_io_error!(io::ErrorKind::InvalidInput, $msg)),
+                }
+            };
+        }
 
-pub struct TcpStream(!);
+        // split the string by ':' and convert the second part to u16
+        let (host, port_str) = try_opt!(s.rsplit_once(':'), "invalid socket address");
+        let port: u16 = try_opt!(port_str.parse().ok(), "invalid port value");
+        (host, port).try_into()
+    }
+}
+
+impl<'a> TryFrom<(&'a str, u16)> for LookupHost {
+    type Error = io::Error;
+
+    fn try_from((host, port): (&'a str, u16)) -> io::Result<LookupHost> {
+        // Resolving a name can reach out to DNS servers, so it needs one of
+        // the networking capabilities.
+        if !capability::is_enabled(Capability::Udp) {
+            capability::check(Capability::Net)?;
+        }
+
+        blocking::uninterruptible("getaddrinfo");
+        run_with_cstr(host.as_bytes(), |c_host| {
+            let mut hints: libc::addrinfo = unsafe { mem::zeroed() };
+            hints.ai_socktype = libc::SOCK_STREAM;
+            let mut res = ptr::null_mut();
+            unsafe {
+                cvt_gai(libc::getaddrinfo(c_host.as_ptr(), ptr::null(), &hints, &mut res))
+                    .map(|_| LookupHost { original: res, cur: res, port })
+            }
+        })
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TCP streams
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct TcpStream {
+    inner: Socket,
+}
 
 impl TcpStream {
-    pub fn connect(_: io::Result<&SocketAddr>) -> io::Result<TcpStream> {
-        unsupported()
+    pub fn connect(addr: io::Result<&SocketAddr>) -> io::Result<TcpStream> {
+        let addr = addr?;
+
+        let sock = Socket::new(addr, libc::SOCK_STREAM)?;
+        sock.connect_deadline(addr, None)?;
+        Ok(TcpStream { inner: sock })
     }
 
-    pub fn connect_timeout(_: &SocketAddr, _: Duration) -> io::Result<TcpStream> {
-        unsupported()
+    pub fn connect_timeout(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+        let sock = Socket::new(addr, libc::SOCK_STREAM)?;
+        sock.connect_timeout(addr, timeout)?;
+        Ok(TcpStream { inner: sock })
     }
 
     pub fn socket(&self) -> &Socket {
-        self.0
+        &self.inner
     }
 
     pub fn into_socket(self) -> Socket {
-        self.0
+        self.inner
     }
 
-    pub fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> {
-        self.0
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.inner.set_timeout(dur, libc::SO_RCVTIMEO)
     }
 
-    pub fn set_write_timeout(&self, _: Option<Duration>) -> io::Result<()> {
-        self.0
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.inner.set_timeout(dur, libc::SO_SNDTIMEO)
     }
 
     pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
-        self.0
+        self.inner.timeout(libc::SO_RCVTIMEO)
     }
 
     pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
-        self.0
+        self.inner.timeout(libc::SO_SNDTIMEO)
     }
 
-    pub fn peek(&self, _: &mut [u8]) -> io::Result<usize> {
-        self.0
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.peek(buf)
     }
 
-    pub fn read(&self, _: &mut [u8]) -> io::Result<usize> {
-        self.0
+    pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
     }
 
-    pub fn read_vectored(&self, _: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
-        self.0
+    pub fn read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.inner.read_vectored(bufs)
     }
 
+    #[inline]
     pub fn is_read_vectored(&self) -> bool {
-        self.0
+        self.inner.is_read_vectored()
     }
 
-    pub fn write(&self, _: &[u8]) -> io::Result<usize> {
-        self.0
+    pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
     }
 
-    pub fn write_vectored(&self, _: &[IoSlice<'_>]) -> io::Result<usize> {
-        self.0
+    pub fn write_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.inner.write_vectored(bufs)
     }
 
+    #[inline]
     pub fn is_write_vectored(&self) -> bool {
-        self.0
+        self.inner.is_write_vectored()
     }
 
     pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-        self.0
+        sockname(|buf, len| unsafe { libc::getpeername(self.inner.as_raw(), buf, len) })
     }
 
     pub fn socket_addr(&self) -> io::Result<SocketAddr> {
-        self.0
+        sockname(|buf, len| unsafe { libc::getsockname(self.inner.as_raw(), buf, len) })
     }
 
-    pub fn shutdown(&self, _: Shutdown) -> io::Result<()> {
-        self.0
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
     }
 
     pub fn duplicate(&self) -> io::Result<TcpStream> {
-        self.0
+        self.inner.duplicate().map(|s| TcpStream { inner: s })
     }
 
-    pub fn set_linger(&self, _: Option<Duration>) -> io::Result<()> {
-        self.0
+    pub fn set_linger(&self, linger: Option<Duration>) -> io::Result<()> {
+        self.inner.set_linger(linger)
     }
 
     pub fn linger(&self) -> io::Result<Option<Duration>> {
-        self.0
+        self.inner.linger()
     }
 
-    pub fn set_nodelay(&self, _: bool) -> io::Result<()> {
-        self.0
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.inner.set_nodelay(nodelay)
     }
 
     pub fn nodelay(&self) -> io::Result<bool> {
-        self.0
+        self.inner.nodelay()
     }
 
-    pub fn set_ttl(&self, _: u32) -> io::Result<()> {
-        self.0
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        setsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_TTL, ttl as c_int)
     }
 
     pub fn ttl(&self) -> io::Result<u32> {
-        self.0
+        let raw: c_int = getsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_TTL)?;
+        Ok(raw as u32)
     }
 
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
-        self.0
+        self.inner.take_error()
     }
 
-    pub fn set_nonblocking(&self, _: bool) -> io::Result<()> {
-        self.0
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+}
+
+impl AsInner<Socket> for TcpStream {
+    fn as_inner(&self) -> &Socket {
+        &self.inner
+    }
+}
+
+impl FromInner<Socket> for TcpStream {
+    fn from_inner(socket: Socket) -> TcpStream {
+        TcpStream { inner: socket }
     }
 }
 
 impl fmt::Debug for TcpStream {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TcpStream").finish_non_exhaustive()
+        let mut res = f.debug_struct("TcpStream");
+
+        if let Ok(addr) = self.socket_addr() {
+            res.field("addr", &addr);
+        }
+
+        if let Ok(peer) = self.peer_addr() {
+            res.field("peer", &peer);
+        }
+
+        res.field("fd", &self.inner.as_raw()).finish()
     }
 }
 
-pub struct TcpListener(!);
+////////////////////////////////////////////////////////////////////////////////
+// TCP listeners
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct TcpListener {
+    inner: Socket,
+}
 
 impl TcpListener {
-    pub fn bind(_: io::Result<&SocketAddr>) -> io::Result<TcpListener> {
-        unsupported()
+    pub fn bind(addr: io::Result<&SocketAddr>) -> io::Result<TcpListener> {
+        // Connections are accepted by the postmaster, never by a backend,
+        // regardless of the `Net` capability.
+        let _ = addr?;
+        Err(io::const_io_error!(
+            io::ErrorKind::Unsupported,
+            "a backend must not accept connections: `TcpListener::bind` is not supported",
+        ))
     }
 
     pub fn socket(&self) -> &Socket {
-        self.0
+        &self.inner
     }
 
     pub fn into_socket(self) -> Socket {
-        self.0
+        self.inner
     }
 
     pub fn socket_addr(&self) -> io::Result<SocketAddr> {
-        self.0
+        sockname(|buf, len| unsafe { libc::getsockname(self.inner.as_raw(), buf, len) })
     }
 
     pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
-        self.0
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut len = mem::size_of_val(&storage) as socklen_t;
+        let sock = self.inner.accept(&mut storage as *mut _ as *mut _, &mut len)?;
+        let addr = sockaddr_to_addr(&storage, len as usize)?;
+        Ok((TcpStream { inner: sock }, addr))
     }
 
     pub fn duplicate(&self) -> io::Result<TcpListener> {
-        self.0
+        self.inner.duplicate().map(|s| TcpListener { inner: s })
     }
 
-    pub fn set_ttl(&self, _: u32) -> io::Result<()> {
-        self.0
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        setsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_TTL, ttl as c_int)
     }
 
     pub fn ttl(&self) -> io::Result<u32> {
-        self.0
+        let raw: c_int = getsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_TTL)?;
+        Ok(raw as u32)
     }
 
-    pub fn set_only_v6(&self, _: bool) -> io::Result<()> {
-        self.0
+    pub fn set_only_v6(&self, only_v6: bool) -> io::Result<()> {
+        setsockopt(&self.inner, libc::IPPROTO_IPV6, libc::IPV6_V6ONLY, only_v6 as c_int)
     }
 
     pub fn only_v6(&self) -> io::Result<bool> {
-        self.0
+        let raw: c_int = getsockopt(&self.inner, libc::IPPROTO_IPV6, libc::IPV6_V6ONLY)?;
+        Ok(raw != 0)
     }
 
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
-        self.0
+        self.inner.take_error()
     }
 
-    pub fn set_nonblocking(&self, _: bool) -> io::Result<()> {
-        self.0
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+}
+
+impl FromInner<Socket> for TcpListener {
+    fn from_inner(socket: Socket) -> TcpListener {
+        TcpListener { inner: socket }
     }
 }
 
 impl fmt::Debug for TcpListener {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TcpListener").finish_non_exhaustive()
+        let mut res = f.debug_struct("TcpListener");
+
+        if let Ok(addr) = self.socket_addr() {
+            res.field("addr", &addr);
+        }
+
+        res.field("fd", &self.inner.as_raw()).finish()
     }
 }
 
-pub struct UdpSocket(!);
+////////////////////////////////////////////////////////////////////////////////
+// UDP
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct UdpSocket {
+    inner: Socket,
+}
 
 impl UdpSocket {
-    pub fn bind(_: io::Result<&SocketAddr>) -> io::Result<UdpSocket> {
-        unsupported()
+    pub fn bind(addr: io::Result<&SocketAddr>) -> io::Result<UdpSocket> {
+        let addr = addr?;
+
+        // Binding an unspecified address to an ephemeral port is how a client
+        // socket for outbound datagrams is created; anything else would have
+        // the backend receive traffic of its own.
+        if !addr.ip().is_unspecified() || addr.port() != 0 {
+            return Err(io::const_io_error!(
+                io::ErrorKind::Unsupported,
+                "a backend must not accept connections: `UdpSocket::bind` only supports \
+                 unspecified addresses with port 0",
+            ));
+        }
+
+        let sock = Socket::new(addr, libc::SOCK_DGRAM)?;
+        let (addr, len) = addr.into_inner();
+        cvt(unsafe { libc::bind(sock.as_raw(), addr.as_ptr(), len as _) })?;
+        Ok(UdpSocket { inner: sock })
     }
 
     pub fn socket(&self) -> &Socket {
-        self.0
+        &self.inner
     }
 
     pub fn into_socket(self) -> Socket {
-        self.0
+        self.inner
     }
 
     pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-        self.0
+        sockname(|buf, len| unsafe { libc::getpeername(self.inner.as_raw(), buf, len) })
     }
 
     pub fn socket_addr(&self) -> io::Result<SocketAddr> {
-        self.0
+        sockname(|buf, len| unsafe { libc::getsockname(self.inner.as_raw(), buf, len) })
     }
 
-    pub fn recv_from(&self, _: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
-        self.0
-    }
-
-    pub fn peek_from(&self, _: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
-        self.0
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.inner.recv_from(buf)
     }
 
-    pub fn send_to(&self, _: &[u8], _: &SocketAddr) -> io::Result<usize> {
-        self.0
+    pub fn peek_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.inner.peek_from(buf)
+    }
+
+    pub fn send_to(&self, buf: &[u8], dst: &SocketAddr) -> io::Result<usize> {
+        audit_datagram(dst)?;
+        let buf = match fault::take(IoOp::Write)? {
+            Some(n) => &buf[..cmp::min(n, buf.len())],
+            None => buf,
+        };
+        let len = cmp::min(buf.len(), <wrlen_t>::MAX as usize) as wrlen_t;
+        let (dst, dstlen) = dst.into_inner();
+        self.inner.wait_io(Interest::Write, libc::SO_SNDTIMEO, || unsafe {
+            libc::sendto(
+                self.inner.as_raw(),
+                buf.as_ptr() as *const c_void,
+                len,
+                MSG_NOSIGNAL | libc::MSG_DONTWAIT,
+                dst.as_ptr(),
+                dstlen,
+            )
+        })
     }
 
     pub fn duplicate(&self) -> io::Result<UdpSocket> {
-        self.0
+        self.inner.duplicate().map(|s| UdpSocket { inner: s })
     }
 
-    pub fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> {
-        self.0
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.inner.set_timeout(dur, libc::SO_RCVTIMEO)
     }
 
-    pub fn set_write_timeout(&self, _: Option<Duration>) -> io::Result<()> {
-        self.0
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.inner.set_timeout(dur, libc::SO_SNDTIMEO)
     }
 
     pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
-        self.0
+        self.inner.timeout(libc::SO_RCVTIMEO)
     }
 
     pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
-        self.0
+        self.inner.timeout(libc::SO_SNDTIMEO)
     }
 
-    pub fn set_broadcast(&self, _: bool) -> io::Result<()> {
-        self.0
+    pub fn set_broadcast(&self, broadcast: bool) -> io::Result<()> {
+        setsockopt(&self.inner, libc::SOL_SOCKET, libc::SO_BROADCAST, broadcast as c_int)
     }
 
     pub fn broadcast(&self) -> io::Result<bool> {
-        self.0
+        let raw: c_int = getsockopt(&self.inner, libc::SOL_SOCKET, libc::SO_BROADCAST)?;
+        Ok(raw != 0)
     }
 
-    pub fn set_multicast_loop_v4(&self, _: bool) -> io::Result<()> {
-        self.0
+    pub fn set_multicast_loop_v4(&self, multicast_loop_v4: bool) -> io::Result<()> {
+        setsockopt(
+            &self.inner,
+            libc::IPPROTO_IP,
+            libc::IP_MULTICAST_LOOP,
+            multicast_loop_v4 as c_int,
+        )
     }
 
     pub fn multicast_loop_v4(&self) -> io::Result<bool> {
-        self.0
+        let raw: c_int = getsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP)?;
+        Ok(raw != 0)
     }
 
-    pub fn set_multicast_ttl_v4(&self, _: u32) -> io::Result<()> {
-        self.0
+    pub fn set_multicast_ttl_v4(&self, multicast_ttl_v4: u32) -> io::Result<()> {
+        setsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL, multicast_ttl_v4 as c_int)
     }
 
     pub fn multicast_ttl_v4(&self) -> io::Result<u32> {
-        self.0
+        let raw: c_int = getsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL)?;
+        Ok(raw as u32)
     }
 
-    pub fn set_multicast_loop_v6(&self, _: bool) -> io::Result<()> {
-        self.0
+    pub fn set_multicast_loop_v6(&self, multicast_loop_v6: bool) -> io::Result<()> {
+        setsockopt(
+            &self.inner,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MULTICAST_LOOP,
+            multicast_loop_v6 as c_int,
+        )
     }
 
     pub fn multicast_loop_v6(&self) -> io::Result<bool> {
-        self.0
+        let raw: c_int = getsockopt(&self.inner, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_LOOP)?;
+        Ok(raw != 0)
     }
 
-    pub fn join_multicast_v4(&self, _: &Ipv4Addr, _: &Ipv4Addr) -> io::Result<()> {
-        self.0
+    pub fn join_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        let mreq = libc::ip_mreq {
+            imr_multiaddr: multiaddr.into_inner(),
+            imr_interface: interface.into_inner(),
+        };
+        setsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP, mreq)
     }
 
-    pub fn join_multicast_v6(&self, _: &Ipv6Addr, _: u32) -> io::Result<()> {
-        self.0
+    pub fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        let mreq = libc::ipv6_mreq {
+            ipv6mr_multiaddr: multiaddr.into_inner(),
+            ipv6mr_interface: interface as libc::c_uint,
+        };
+        setsockopt(&self.inner, libc::IPPROTO_IPV6, IPV6_ADD_MEMBERSHIP, mreq)
     }
 
-    pub fn leave_multicast_v4(&self, _: &Ipv4Addr, _: &Ipv4Addr) -> io::Result<()> {
-        self.0
+    pub fn leave_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        let mreq = libc::ip_mreq {
+            imr_multiaddr: multiaddr.into_inner(),
+            imr_interface: interface.into_inner(),
+        };
+        setsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_DROP_MEMBERSHIP, mreq)
     }
 
-    pub fn leave_multicast_v6(&self, _: &Ipv6Addr, _: u32) -> io::Result<()> {
-        self.0
+    pub fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        let mreq = libc::ipv6_mreq {
+            ipv6mr_multiaddr: multiaddr.into_inner(),
+            ipv6mr_interface: interface as libc::c_uint,
+        };
+        setsockopt(&self.inner, libc::IPPROTO_IPV6, IPV6_DROP_MEMBERSHIP, mreq)
     }
 
-    pub fn set_ttl(&self, _: u32) -> io::Result<()> {
-        self.0
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        setsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_TTL, ttl as c_int)
     }
 
     pub fn ttl(&self) -> io::Result<u32> {
-        self.0
+        let raw: c_int = getsockopt(&self.inner, libc::IPPROTO_IP, libc::IP_TTL)?;
+        Ok(raw as u32)
     }
 
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
-        self.0
+        self.inner.take_error()
     }
 
-    pub fn set_nonblocking(&self, _: bool) -> io::Result<()> {
-        self.0
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
     }
 
-    pub fn recv(&self, _: &mut [u8]) -> io::Result<usize> {
-        self.0
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
     }
 
-    pub fn peek(&self, _: &mut [u8]) -> io::Result<usize> {
-        self.0
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.peek(buf)
     }
 
-    pub fn send(&self, _: &[u8]) -> io::Result<usize> {
-        self.0
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
     }
 
-    pub fn connect(&self, _: io::Result<&SocketAddr>) -> io::Result<()> {
-        self.0
+    pub fn connect(&self, addr: io::Result<&SocketAddr>) -> io::Result<()> {
+        let addr = addr?;
+        audit_datagram(addr)?;
+        let (addr, len) = addr.into_inner();
+        cvt_r(|| unsafe { libc::connect(self.inner.as_raw(), addr.as_ptr(), len) }).map(drop)
     }
 }
 
-impl fmt::Debug for UdpSocket {
-    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0
+impl FromInner<Socket> for UdpSocket {
+    fn from_inner(socket: Socket) -> UdpSocket {
+        UdpSocket { inner: socket }
     }
 }
 
-pub struct LookupHost(!);
+impl fmt::Debug for UdpSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut res = f.debug_struct("UdpSocket");
 
-impl LookupHost {
-    pub fn port(&self) -> u16 {
-        self.0
-    }
-}
+        if let Ok(addr) = self.socket_addr() {
+            res.field("addr", &addr);
+        }
 
-impl Iterator for LookupHost {
-    type Item = SocketAddr;
-    fn next(&mut self) -> Option<SocketAddr> {
-        self.0
+        res.field("fd", &self.inner.as_raw()).finish()
     }
 }
 
-impl TryFrom<&str> for LookupHost {
-    type Error = io::Error;
+////////////////////////////////////////////////////////////////////////////////
+// Raw sockets
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct Socket(FileDesc);
 
-    fn try_from(_v: &str) -> io::Result<LookupHost> {
-        unsupported()
+impl Socket {
+    pub fn new(addr: &SocketAddr, ty: c_int) -> io::Result<Socket> {
+        let fam = match *addr {
+            SocketAddr::V4(..) => libc::AF_INET,
+            SocketAddr::V6(..) => libc::AF_INET6,
+        };
+        Socket::new_raw(fam, ty)
     }
-}
 
-impl<'a> TryFrom<(&'a str, u16)> for LookupHost {
-    type Error = io::Error;
+    pub fn new_raw(fam: c_int, ty: c_int) -> io::Result<Socket> {
+        if ty == libc::SOCK_DGRAM && fam != libc::AF_UNIX {
+            capability::check(Capability::Udp)?;
+        } else {
+            capability::check(Capability::Net)?;
+        }
 
-    fn try_from(_v: (&'a str, u16)) -> io::Result<LookupHost> {
-        unsupported()
+        unsafe {
+            cfg_if::cfg_if! {
+                if #[cfg(target_os = "linux")] {
+                    // Atomically create the socket and set it as CLOEXEC, so
+                    // it can't leak into anything the postmaster launches.
+                    let fd = cvt(libc::socket(fam, ty | libc::SOCK_CLOEXEC, 0))?;
+                    Ok(Socket(FileDesc::from_raw_fd(fd)))
+                } else {
+                    let fd = cvt(libc::socket(fam, ty, 0))?;
+                    let fd = FileDesc::from_raw_fd(fd);
+                    fd.set_cloexec()?;
+                    let socket = Socket(fd);
+
+                    // macOS and iOS use `SO_NOSIGPIPE` as a `setsockopt`
+                    // flag to disable `SIGPIPE` emission on socket.
+                    #[cfg(target_vendor = "apple")]
+                    setsockopt(&socket, libc::SOL_SOCKET, libc::SO_NOSIGPIPE, 1)?;
+
+                    Ok(socket)
+                }
+            }
+        }
     }
-}
 
-pub struct Socket(!);
+    pub fn new_pair(_fam: c_int, _ty: c_int) -> io::Result<(Socket, Socket)> {
+        // Only used by `std::os::unix::net`, which is not available here.
+        crate::sys::unsupported()
+    }
 
-pub fn init() {}
+    pub fn connect_timeout(&self, addr: &SocketAddr, timeout: Duration) -> io::Result<()> {
+        if timeout.as_secs() == 0 && timeout.subsec_nanos() == 0 {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "cannot set a 0 duration timeout",
+            ));
+        }
+        self.connect_deadline(addr, Some(Instant::now() + timeout))
+    }
+
+    /// Connects without ever blocking in `connect(2)` itself: the socket is
+    /// put into non-blocking mode and the handshake is awaited through
+    /// `pg::wait_socket`, so that a cancel request interrupts it.
+    pub fn connect_deadline(&self, addr: &SocketAddr, deadline: Option<Instant>) -> io::Result<()> {
+        audit_connect(addr)?;
+        let (addr, len) = addr.into_inner();
+        self.connect_raw(addr.as_ptr(), len, deadline)
+    }
+
+    /// Starts connecting a new stream socket to `addr` without waiting for
+    /// the handshake. The socket is left in non-blocking mode; the flag says
+    /// whether it is already connected.
+    fn start_connect(addr: &SocketAddr) -> io::Result<(Socket, bool)> {
+        let sock = Socket::new(addr, libc::SOCK_STREAM)?;
+        audit_connect(addr)?;
+        sock.set_nonblocking(true)?;
+        let (raw, len) = addr.into_inner();
+        match cvt(unsafe { libc::connect(sock.as_raw_fd(), raw.as_ptr(), len) }) {
+            Ok(_) => Ok((sock, true)),
+            Err(ref e) if e.raw_os_error() == Some(libc::EINPROGRESS) => Ok((sock, false)),
+            Err(e) => Err(e),
+        }
+    }
 
-pub fn cvt_gai(_: c_int) -> io::Result<()> {
-    unsupported()
-}
+    fn connect_raw(
+        &self,
+        addr: *const sockaddr,
+        len: socklen_t,
+        deadline: Option<Instant>,
+    ) -> io::Result<()> {
+        self.set_nonblocking(true)?;
+        let r = cvt(unsafe { libc::connect(self.as_raw_fd(), addr, len) });
+        self.set_nonblocking(false)?;
+
+        match r {
+            Ok(_) => return Ok(()),
+            // there's no ErrorKind for EINPROGRESS :(
+            Err(ref e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {}
+            Err(e) => return Err(e),
+        }
 
-impl Socket {
-    pub fn new(addr: &SocketAddr, ty: c_int) -> io::Result<Socket> {
-        unsupported()
+        if !pg::wait_socket(self.as_raw_fd(), Interest::Write, deadline)? {
+            return Err(io::const_io_error!(io::ErrorKind::TimedOut, "connection timed out"));
+        }
+        match self.take_error()? {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
     }
 
-    pub fn new_raw(fam: c_int, ty: c_int) -> io::Result<Socket> {
-        unsupported()
-    }
+    /// Connects a new `AF_UNIX` stream socket to `path`.
+    pub fn connect_unix(path: &Path) -> io::Result<Socket> {
+        let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
 
-    pub fn new_pair(fam: c_int, ty: c_int) -> io::Result<(Socket, Socket)> {
-        unsupported()
-    }
+        let bytes = path.as_os_str().as_bytes();
+        if bytes.contains(&0) {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "paths must not contain interior null bytes",
+            ));
+        }
+        if bytes.len() >= addr.sun_path.len() {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "path must be shorter than SUN_LEN",
+            ));
+        }
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), addr.sun_path.as_mut_ptr().cast(), bytes.len())
+        };
 
-    pub fn connect_timeout(&self, addr: &SocketAddr, timeout: Duration) -> io::Result<()> {
-        unsupported()
+        let sock = Socket::new_raw(libc::AF_UNIX, libc::SOCK_STREAM)?;
+        let len = mem::size_of::<libc::sockaddr_un>() as socklen_t;
+        sock.connect_raw(&addr as *const _ as *const sockaddr, len, None)?;
+        Ok(sock)
     }
 
     pub fn accept(&self, storage: *mut sockaddr, len: *mut socklen_t) -> io::Result<Socket> {
-        unsupported()
+        if cfg!(feature = "pg_debug") && !self.is_nonblocking()? {
+            blocking::uninterruptible("accept");
+        }
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                unsafe {
+                    let fd = cvt_r(|| libc::accept4(self.as_raw_fd(), storage, len, libc::SOCK_CLOEXEC))?;
+                    Ok(Socket(FileDesc::from_raw_fd(fd)))
+                }
+            } else {
+                unsafe {
+                    let fd = cvt_r(|| libc::accept(self.as_raw_fd(), storage, len))?;
+                    let fd = FileDesc::from_raw_fd(fd);
+                    fd.set_cloexec()?;
+                    Ok(Socket(fd))
+                }
+            }
+        }
     }
 
     pub fn duplicate(&self) -> io::Result<Socket> {
-        unsupported()
+        self.0.duplicate().map(Socket)
+    }
+
+    fn is_nonblocking(&self) -> io::Result<bool> {
+        let flags = cvt(unsafe { libc::fcntl(self.as_raw_fd(), libc::F_GETFL) })?;
+        Ok(flags & libc::O_NONBLOCK != 0)
+    }
+
+    /// Retries the non-blocking socket call `f` until it stops reporting
+    /// `WouldBlock`, waiting for readiness in between. Waits honour the
+    /// `timeout_kind` socket timeout and are cut short by backend interrupts.
+    /// Sockets put into non-blocking mode by the user get the error back.
+    fn wait_io<F>(&self, interest: Interest, timeout_kind: c_int, mut f: F) -> io::Result<usize>
+    where
+        F: FnMut() -> isize,
+    {
+        let mut deadline = None;
+        loop {
+            match cvt(f()) {
+                Ok(n) => return Ok(n as usize),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if self.is_nonblocking()? {
+                        return Err(e);
+                    }
+                    let deadline = match deadline {
+                        Some(deadline) => deadline,
+                        None => *deadline
+                            .insert(self.timeout(timeout_kind)?.map(|t| Instant::now() + t)),
+                    };
+                    if !pg::wait_socket(self.as_raw_fd(), interest, deadline)? {
+                        return Err(e);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
     }
 
     fn recv_with_flags(&self, buf: &mut [u8], flags: c_int) -> io::Result<usize> {
-        unsupported()
+        self.wait_io(Interest::Read, libc::SO_RCVTIMEO, || unsafe {
+            libc::recv(
+                self.as_raw_fd(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                flags | libc::MSG_DONTWAIT,
+            )
+        })
     }
 
     pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
-        unsupported()
+        let buf = match fault::take(IoOp::Read)? {
+            Some(n) => &mut buf[..cmp::min(n, buf.len())],
+            None => buf,
+        };
+        self.recv_with_flags(buf, 0)
     }
 
     pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
-        unsupported()
+        self.recv_with_flags(buf, MSG_PEEK)
+    }
+
+    fn recv_vectored_with_flags(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        flags: c_int,
+    ) -> io::Result<usize> {
+        // `IoSliceMut` is guaranteed to be ABI compatible with `iovec`.
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = bufs.as_mut_ptr().cast();
+        msg.msg_iovlen = cmp::min(bufs.len(), max_iov()) as _;
+        self.wait_io(Interest::Read, libc::SO_RCVTIMEO, || unsafe {
+            libc::recvmsg(self.as_raw_fd(), &mut msg, flags | libc::MSG_DONTWAIT)
+        })
     }
 
     pub fn read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
-        unsupported()
+        match fault::take(IoOp::Read)? {
+            // A short read fills some of the first buffer only.
+            Some(n) => match bufs.iter_mut().find(|buf| !buf.is_empty()) {
+                Some(buf) => self.recv_with_flags(&mut buf[..cmp::min(n, buf.len())], 0),
+                None => Ok(0),
+            },
+            None => self.recv_vectored_with_flags(bufs, 0),
+        }
+    }
+
+    pub fn peek_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.recv_vectored_with_flags(bufs, MSG_PEEK)
     }
 
     #[inline]
     pub fn is_read_vectored(&self) -> bool {
-        false
+        true
     }
 
     fn recv_from_with_flags(
@@ -427,178 +1119,315 @@ impl Socket {
         buf: &mut [u8],
         flags: c_int,
     ) -> io::Result<(usize, SocketAddr)> {
-        unsupported()
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut addrlen = mem::size_of_val(&storage) as libc::socklen_t;
+
+        let n = self.wait_io(Interest::Read, libc::SO_RCVTIMEO, || unsafe {
+            libc::recvfrom(
+                self.as_raw_fd(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                flags | libc::MSG_DONTWAIT,
+                &mut storage as *mut _ as *mut _,
+                &mut addrlen,
+            )
+        })?;
+        Ok((n, sockaddr_to_addr(&storage, addrlen as usize)?))
     }
 
     pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
-        unsupported()
+        // A short datagram read is truncated, like into a buffer too small.
+        let buf = match fault::take(IoOp::Read)? {
+            Some(n) => &mut buf[..cmp::min(n, buf.len())],
+            None => buf,
+        };
+        self.recv_from_with_flags(buf, 0)
     }
 
     #[cfg(any(target_os = "android", target_os = "linux"))]
-    pub fn recv_msg(&self, msg: &mut libc::msghdr) -> io::Result<usize> {
-        unsupported()
+    pub fn recv_msg(&self, _msg: &mut libc::msghdr) -> io::Result<usize> {
+        crate::sys::unsupported()
     }
 
     pub fn peek_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
-        unsupported()
+        self.recv_from_with_flags(buf, MSG_PEEK)
     }
 
     pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
-        unsupported()
+        let buf = match fault::take(IoOp::Write)? {
+            Some(n) => &buf[..cmp::min(n, buf.len())],
+            None => buf,
+        };
+        self.send(buf)
+    }
+
+    fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        let len = cmp::min(buf.len(), <wrlen_t>::MAX as usize) as wrlen_t;
+        self.wait_io(Interest::Write, libc::SO_SNDTIMEO, || unsafe {
+            libc::send(
+                self.as_raw_fd(),
+                buf.as_ptr() as *const c_void,
+                len,
+                MSG_NOSIGNAL | libc::MSG_DONTWAIT,
+            )
+        })
     }
 
     pub fn write_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
-        unsupported()
+        // A short write sends some of the first buffer only.
+        if let Some(n) = fault::take(IoOp::Write)? {
+            let buf = bufs.iter().find(|buf| !buf.is_empty()).map_or(&[][..], |buf| &**buf);
+            return self.send(&buf[..cmp::min(n, buf.len())]);
+        }
+        // `IoSlice` is guaranteed to be ABI compatible with `iovec`.
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = bufs.as_ptr() as *mut libc::iovec;
+        msg.msg_iovlen = cmp::min(bufs.len(), max_iov()) as _;
+        self.wait_io(Interest::Write, libc::SO_SNDTIMEO, || unsafe {
+            libc::sendmsg(self.as_raw_fd(), &msg, MSG_NOSIGNAL | libc::MSG_DONTWAIT)
+        })
     }
 
     #[inline]
     pub fn is_write_vectored(&self) -> bool {
-        false
+        true
     }
 
     #[cfg(any(target_os = "android", target_os = "linux"))]
-    pub fn send_msg(&self, msg: &mut libc::msghdr) -> io::Result<usize> {
-        unsupported()
-    }
-
-    pub fn set_timeout(&self, dur: Option<Duration>, kind: c_int) -> io::Result<()> {
-        unsupported()
+    pub fn send_msg(&self, _msg: &mut libc::msghdr) -> io::Result<usize> {
+        crate::sys::unsupported()
+    }
+
+    pub fn set_timeout(&self, dur: Option<Duration>, kind: libc::c_int) -> io::Result<()> {
+        let timeout = match dur {
+            Some(dur) => {
+                if dur.as_secs() == 0 && dur.subsec_nanos() == 0 {
+                    return Err(io::const_io_error!(
+                        io::ErrorKind::InvalidInput,
+                        "cannot set a 0 duration timeout",
+                    ));
+                }
+
+                let secs = if dur.as_secs() > libc::time_t::MAX as u64 {
+                    libc::time_t::MAX
+                } else {
+                    dur.as_secs() as libc::time_t
+                };
+                let mut timeout = libc::timeval {
+                    tv_sec: secs,
+                    tv_usec: dur.subsec_micros() as libc::suseconds_t,
+                };
+                if timeout.tv_sec == 0 && timeout.tv_usec == 0 {
+                    timeout.tv_usec = 1;
+                }
+                timeout
+            }
+            None => libc::timeval { tv_sec: 0, tv_usec: 0 },
+        };
+        setsockopt(self, libc::SOL_SOCKET, kind, timeout)
     }
 
     pub fn timeout(&self, kind: libc::c_int) -> io::Result<Option<Duration>> {
-        unsupported()
+        let raw: libc::timeval = getsockopt(self, libc::SOL_SOCKET, kind)?;
+        if raw.tv_sec == 0 && raw.tv_usec == 0 {
+            Ok(None)
+        } else {
+            let sec = raw.tv_sec as u64;
+            let nsec = (raw.tv_usec as u32) * 1000;
+            Ok(Some(Duration::new(sec, nsec)))
+        }
     }
 
     pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
-        unsupported()
+        let how = match how {
+            Shutdown::Write => libc::SHUT_WR,
+            Shutdown::Read => libc::SHUT_RD,
+            Shutdown::Both => libc::SHUT_RDWR,
+        };
+        cvt(unsafe { libc::shutdown(self.as_raw_fd(), how) })?;
+        Ok(())
     }
 
     pub fn set_linger(&self, linger: Option<Duration>) -> io::Result<()> {
-        unsupported()
+        let linger = libc::linger {
+            l_onoff: linger.is_some() as libc::c_int,
+            l_linger: linger.unwrap_or_default().as_secs() as libc::c_int,
+        };
+
+        setsockopt(self, libc::SOL_SOCKET, SO_LINGER, linger)
     }
 
     pub fn linger(&self) -> io::Result<Option<Duration>> {
-        unsupported()
+        let val: libc::linger = getsockopt(self, libc::SOL_SOCKET, SO_LINGER)?;
+
+        Ok((val.l_onoff != 0).then(|| Duration::from_secs(val.l_linger as u64)))
     }
 
     pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
-        unsupported()
+        setsockopt(self, libc::IPPROTO_TCP, libc::TCP_NODELAY, nodelay as c_int)
     }
 
     pub fn nodelay(&self) -> io::Result<bool> {
-        unsupported()
+        let raw: c_int = getsockopt(self, libc::IPPROTO_TCP, libc::TCP_NODELAY)?;
+        Ok(raw != 0)
+    }
+
+    pub fn set_keepalive(&self, keepalive: bool) -> io::Result<()> {
+        setsockopt(self, libc::SOL_SOCKET, libc::SO_KEEPALIVE, keepalive as c_int)
+    }
+
+    pub fn keepalive(&self) -> io::Result<bool> {
+        let raw: c_int = getsockopt(self, libc::SOL_SOCKET, libc::SO_KEEPALIVE)?;
+        Ok(raw != 0)
+    }
+
+    fn set_secs(&self, level: c_int, name: c_int, dur: Duration) -> io::Result<()> {
+        let secs = c_int::try_from(dur.as_secs()).unwrap_or(c_int::MAX);
+        setsockopt(self, level, name, secs)
+    }
+
+    fn secs(&self, level: c_int, name: c_int) -> io::Result<Duration> {
+        let raw: c_int = getsockopt(self, level, name)?;
+        Ok(Duration::from_secs(raw as u64))
+    }
+
+    pub fn set_keepalive_idle(&self, idle: Duration) -> io::Result<()> {
+        self.set_secs(libc::IPPROTO_TCP, TCP_KEEPIDLE, idle)
+    }
+
+    pub fn keepalive_idle(&self) -> io::Result<Duration> {
+        self.secs(libc::IPPROTO_TCP, TCP_KEEPIDLE)
+    }
+
+    pub fn set_keepalive_interval(&self, interval: Duration) -> io::Result<()> {
+        self.set_secs(libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, interval)
+    }
+
+    pub fn keepalive_interval(&self) -> io::Result<Duration> {
+        self.secs(libc::IPPROTO_TCP, libc::TCP_KEEPINTVL)
+    }
+
+    pub fn set_keepalive_count(&self, count: u32) -> io::Result<()> {
+        let count = c_int::try_from(count).unwrap_or(c_int::MAX);
+        setsockopt(self, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, count)
+    }
+
+    pub fn keepalive_count(&self) -> io::Result<u32> {
+        let raw: c_int = getsockopt(self, libc::IPPROTO_TCP, libc::TCP_KEEPCNT)?;
+        Ok(raw as u32)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn set_user_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        // Zero means "use the system default".
+        let ms = timeout.map_or(0, |t| cmp::min(t.as_millis(), libc::c_uint::MAX as u128));
+        setsockopt(self, libc::IPPROTO_TCP, libc::TCP_USER_TIMEOUT, ms as libc::c_uint)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn user_timeout(&self) -> io::Result<Option<Duration>> {
+        let raw: libc::c_uint = getsockopt(self, libc::IPPROTO_TCP, libc::TCP_USER_TIMEOUT)?;
+        Ok((raw != 0).then(|| Duration::from_millis(raw as u64)))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn set_user_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        crate::sys::unsupported()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn user_timeout(&self) -> io::Result<Option<Duration>> {
+        crate::sys::unsupported()
     }
 
     #[cfg(any(target_os = "android", target_os = "linux",))]
     pub fn set_quickack(&self, quickack: bool) -> io::Result<()> {
-        unsupported()
+        setsockopt(self, libc::IPPROTO_TCP, libc::TCP_QUICKACK, quickack as c_int)
     }
 
     #[cfg(any(target_os = "android", target_os = "linux",))]
     pub fn quickack(&self) -> io::Result<bool> {
-        unsupported()
+        let raw: c_int = getsockopt(self, libc::IPPROTO_TCP, libc::TCP_QUICKACK)?;
+        Ok(raw != 0)
     }
 
     #[cfg(any(target_os = "android", target_os = "linux"))]
-    pub fn set_passcred(&self, passcred: bool) -> io::Result<()> {
-        unsupported()
+    pub fn set_passcred(&self, _passcred: bool) -> io::Result<()> {
+        crate::sys::unsupported()
     }
 
     #[cfg(any(target_os = "android", target_os = "linux"))]
     pub fn passcred(&self) -> io::Result<bool> {
-        unsupported()
-    }
-
-    #[cfg(target_os = "netbsd")]
-    pub fn set_passcred(&self, passcred: bool) -> io::Result<()> {
-        unsupported()
-    }
-
-    #[cfg(target_os = "netbsd")]
-    pub fn passcred(&self) -> io::Result<bool> {
-        unsupported()
-    }
-
-    #[cfg(not(any(target_os = "solaris", target_os = "illumos")))]
-    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
-        unsupported()
+        crate::sys::unsupported()
     }
 
-    #[cfg(any(target_os = "solaris", target_os = "illumos"))]
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
-        unsupported()
+        let mut nonblocking = nonblocking as libc::c_int;
+        cvt(unsafe { libc::ioctl(self.as_raw_fd(), libc::FIONBIO, &mut nonblocking) }).map(drop)
     }
 
-    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
-    pub fn set_mark(&self, mark: u32) -> io::Result<()> {
-        unsupported()
+    #[cfg(target_os = "linux")]
+    pub fn set_mark(&self, _mark: u32) -> io::Result<()> {
+        // Requires CAP_NET_ADMIN, which a backend should never have.
+        crate::sys::unsupported()
     }
 
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
-        unsupported()
+        let raw: c_int = getsockopt(self, libc::SOL_SOCKET, libc::SO_ERROR)?;
+        if raw == 0 { Ok(None) } else { Ok(Some(io::Error::from_raw_os_error(raw as i32))) }
     }
 
     // This is used by sys_common code to abstract over Windows and Unix.
     pub fn as_raw(&self) -> RawFd {
-        unimplemented!()
+        self.as_raw_fd()
     }
 }
 
 impl AsInner<FileDesc> for Socket {
     fn as_inner(&self) -> &FileDesc {
-        unimplemented!()
+        &self.0
     }
 }
 
 impl IntoInner<FileDesc> for Socket {
     fn into_inner(self) -> FileDesc {
-        unimplemented!()
+        self.0
     }
 }
 
 impl FromInner<FileDesc> for Socket {
     fn from_inner(file_desc: FileDesc) -> Self {
-        unimplemented!()
+        Self(file_desc)
     }
 }
 
 impl AsFd for Socket {
     fn as_fd(&self) -> BorrowedFd<'_> {
-        unimplemented!()
+        self.0.as_fd()
     }
 }
 
 impl AsRawFd for Socket {
     fn as_raw_fd(&self) -> RawFd {
-        unimplemented!()
+        self.0.as_raw_fd()
     }
 }
 
 impl IntoRawFd for Socket {
     fn into_raw_fd(self) -> RawFd {
-        unimplemented!()
+        self.0.into_raw_fd()
     }
 }
 
 impl FromRawFd for Socket {
     unsafe fn from_raw_fd(raw_fd: RawFd) -> Self {
-        unimplemented!()
-    }
-}
-
-impl FromInner<Socket> for TcpListener {
-    fn from_inner(socket: Socket) -> TcpListener {
-        unimplemented!()
-    }
-}
-
-impl FromInner<Socket> for UdpSocket {
-    fn from_inner(socket: Socket) -> UdpSocket {
-        unimplemented!()
+        Self(FromRawFd::from_raw_fd(raw_fd))
     }
 }
 
-fn on_resolver_failure() {}
+////////////////////////////////////////////////////////////////////////////////
+// Converting SocketAddr to libc representation
+////////////////////////////////////////////////////////////////////////////////
 
 #[repr(C)]
 pub(crate) union SocketAddrCRepr {
@@ -626,15 +1455,3 @@ impl<'a> IntoInner<(SocketAddrCRepr, socklen_t)> for &'a SocketAddr {
         }
     }
 }
-
-impl AsInner<Socket> for TcpStream {
-    fn as_inner(&self) -> &Socket {
-        self.0
-    }
-}
-
-impl FromInner<Socket> for TcpStream {
-    fn from_inner(socket: Socket) -> TcpStream {
-        socket.0
-    }
-}
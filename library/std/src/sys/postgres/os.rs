@@ -1,11 +1,15 @@
-use super::unsupported;
+use super::{cvt_os, pg, unsupported};
 use crate::error::Error as StdError;
 use crate::ffi::{c_char, c_int, c_void};
-use crate::ffi::{OsStr, OsString};
+use crate::ffi::{CStr, OsStr, OsString};
 use crate::fmt;
 use crate::io;
 use crate::marker::PhantomData;
+use crate::os::unix::ffi::{OsStrExt, OsStringExt};
 use crate::path::{self, PathBuf};
+use crate::sync::OnceLock;
+use crate::sys::common::small_c_string::run_with_cstr;
+use crate::vec;
 use libc;
 // snarfed from sys/unix/os.rs, which is largely not something we can use
 extern "C" {
@@ -56,12 +60,17 @@ pub fn error_string(_errno: i32) -> String {
     "operation successful".to_string()
 }
 
+/// The data directory, which is where postgres keeps every backend: the
+/// relative paths in its configuration and its own code count on that.
 pub fn getcwd() -> io::Result<PathBuf> {
-    unsupported()
+    pg::data_dir().map_or_else(unsupported, Ok)
 }
 
 pub fn chdir(_: &path::Path) -> io::Result<()> {
-    unsupported()
+    Err(io::const_io_error!(
+        io::ErrorKind::Unsupported,
+        "cannot change the working directory of a postgres backend",
+    ))
 }
 
 pub struct SplitPaths<'a>(!, PhantomData<&'a ()>);
@@ -105,35 +114,180 @@ pub fn current_exe() -> io::Result<PathBuf> {
     unsupported()
 }
 
-pub struct Env(!);
+pub struct Env {
+    iter: vec::IntoIter<(OsString, OsString)>,
+}
 
 impl Iterator for Env {
     type Item = (OsString, OsString);
     fn next(&mut self) -> Option<(OsString, OsString)> {
-        self.0
+        self.iter.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
     }
 }
 
 pub fn env() -> Env {
-    panic!("not supported on this platform")
+    #[cfg(feature = "pg_allow_env_mutation")]
+    return Env { iter: unsafe { read_environ() }.into_iter() };
+    #[cfg(not(feature = "pg_allow_env_mutation"))]
+    Env { iter: snapshot().to_vec().into_iter() }
 }
 
-pub fn getenv(_: &OsStr) -> Option<OsString> {
-    None
+#[cfg(target_os = "macos")]
+unsafe fn environ() -> *const *const c_char {
+    unsafe { *libc::_NSGetEnviron() as *const *const c_char }
+}
+
+#[cfg(not(target_os = "macos"))]
+unsafe fn environ() -> *const *const c_char {
+    extern "C" {
+        static environ: *const *const c_char;
+    }
+    unsafe { environ }
+}
+
+static SNAPSHOT: OnceLock<Vec<(OsString, OsString)>> = OnceLock::new();
+
+/// The environment as it was when this library was loaded, which is all
+/// `env::vars` and `env::var` ever see of it.
+///
+/// The backend and the C libraries it loaded may change `environ` while
+/// Rust code reads it, and anything they put there later is theirs. Builds
+/// with `pg_allow_env_mutation` read it live instead, since `env::set_var`
+/// changes it there.
+fn snapshot() -> &'static [(OsString, OsString)] {
+    SNAPSHOT.get_or_init(|| unsafe { read_environ() })
+}
+
+unsafe fn read_environ() -> Vec<(OsString, OsString)> {
+    let mut result = Vec::new();
+    unsafe {
+        let mut entry = environ();
+        while !entry.is_null() && !(*entry).is_null() {
+            if let Some(pair) = parse(CStr::from_ptr(*entry).to_bytes()) {
+                result.push(pair);
+            }
+            entry = entry.add(1);
+        }
+    }
+    result
+}
+
+// Takes the snapshot as the library is loaded, before the backend gets to
+// run anything else.
+#[cfg_attr(target_os = "linux", link_section = ".init_array.00099")]
+#[cfg_attr(target_os = "macos", link_section = "__DATA,__mod_init_func")]
+#[used]
+static SNAPSHOT_AT_LOAD: extern "C" fn() = {
+    extern "C" fn take_snapshot() {
+        snapshot();
+    }
+    take_snapshot
+};
+
+fn parse(input: &[u8]) -> Option<(OsString, OsString)> {
+    // Strategy (copied from glibc): Variable name and value are separated
+    // by an ASCII equals sign '='. Since a variable name must not be
+    // empty, allow variable names starting with an equals sign. Skip all
+    // malformed lines.
+    if input.is_empty() {
+        return None;
+    }
+    let pos = input[1..].iter().position(|&b| b == b'=').map(|p| p + 1);
+    pos.map(|p| {
+        (OsStringExt::from_vec(input[..p].to_vec()), OsStringExt::from_vec(input[p + 1..].to_vec()))
+    })
+}
+
+/// The settings `env::var` looks in first: `env::var("FOO")` is the value
+/// of `plrust.env.FOO`, if that is set, so that it can be configured per
+/// database, role or session like any other setting.
+const ENV_NAMESPACE: &str = "plrust.env";
+
+pub fn getenv(key: &OsStr) -> Option<OsString> {
+    config_var(key).or_else(|| process_var(key))
+}
+
+fn config_var(key: &OsStr) -> Option<OsString> {
+    let key = key.to_str().filter(|k| !k.is_empty())?;
+    // `config_option` gives up on a name with a nul in it.
+    pg::config_option(&format!("{ENV_NAMESPACE}.{key}\0")).map(OsString::from)
+}
+
+#[cfg(not(feature = "pg_allow_env_mutation"))]
+fn process_var(key: &OsStr) -> Option<OsString> {
+    snapshot().iter().find(|(k, _)| k == key).map(|(_, v)| v.clone())
 }
 
-pub fn setenv(_: &OsStr, _: &OsStr) -> io::Result<()> {
-    Err(io::const_io_error!(io::ErrorKind::Unsupported, "cannot set env vars on this platform"))
+#[cfg(feature = "pg_allow_env_mutation")]
+fn process_var(key: &OsStr) -> Option<OsString> {
+    run_with_cstr(key.as_bytes(), |key| {
+        let value = unsafe { libc::getenv(key.as_ptr()) };
+        Ok((!value.is_null())
+            .then(|| OsStringExt::from_vec(unsafe { CStr::from_ptr(value) }.to_bytes().to_vec())))
+    })
+    .ok()
+    .flatten()
 }
 
+/// Sets `plrust.env.<key>` for the rest of the transaction, rather than
+/// the variable in the environment, which the backend and the libraries it
+/// loaded (libpq, the locale machinery) read as well.
+#[cfg(not(feature = "pg_allow_env_mutation"))]
+pub fn setenv(key: &OsStr, value: &OsStr) -> io::Result<()> {
+    let Some(key) = key.to_str().filter(|k| !k.is_empty()) else {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "environment variable names must be non-empty and valid unicode",
+        ));
+    };
+    run_with_cstr(format!("{ENV_NAMESPACE}.{key}").as_bytes(), |name| {
+        run_with_cstr(value.as_bytes(), |value| pg::set_config_local(name, value))
+    })
+}
+
+#[cfg(not(feature = "pg_allow_env_mutation"))]
 pub fn unsetenv(_: &OsStr) -> io::Result<()> {
-    Err(io::const_io_error!(io::ErrorKind::Unsupported, "cannot unset env vars on this platform"))
+    Err(io::const_io_error!(
+        io::ErrorKind::Unsupported,
+        "cannot unset env vars: this std was compiled without the `pg_allow_env_mutation` feature",
+    ))
+}
+
+/// Sets the variable in the backend's environment itself, for whatever
+/// reads it next, including the backend: builds with
+/// `pg_allow_env_mutation` trust their code with that. A `plrust.env`
+/// setting of the same name still takes precedence in `env::var`.
+#[cfg(feature = "pg_allow_env_mutation")]
+pub fn setenv(key: &OsStr, value: &OsStr) -> io::Result<()> {
+    run_with_cstr(key.as_bytes(), |key| {
+        run_with_cstr(value.as_bytes(), |value| {
+            cvt_os(unsafe { libc::setenv(key.as_ptr(), value.as_ptr(), 1) }).map(drop)
+        })
+    })
+}
+
+#[cfg(feature = "pg_allow_env_mutation")]
+pub fn unsetenv(key: &OsStr) -> io::Result<()> {
+    run_with_cstr(key.as_bytes(), |key| cvt_os(unsafe { libc::unsetenv(key.as_ptr()) }).map(drop))
 }
 
 pub fn temp_dir() -> PathBuf {
-    panic!("no filesystem on this platform")
+    match pg::temp_dir() {
+        Ok(Some(dir)) => dir,
+        Ok(None) => PathBuf::from("/tmp"),
+        // Falling back to a directory postgres knows nothing about would
+        // leave files behind for good.
+        Err(e) => panic!("could not create a temporary directory: {e}"),
+    }
 }
 
+/// There is no home directory to speak of, and no looking one up: `HOME` is
+/// that of whoever started the server, and `getpwuid_r` may block on NSS
+/// (LDAP, say) for as long as it likes, with the backend unable to service
+/// a cancel meanwhile.
 pub fn home_dir() -> Option<PathBuf> {
     None
 }
@@ -153,3 +307,8 @@ pub fn getppid() -> u32 {
 pub fn page_size() -> usize {
     unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
 }
+
+pub fn physical_memory() -> Option<u64> {
+    let pages = unsafe { libc::sysconf(libc::_SC_PHYS_PAGES) };
+    u64::try_from(pages).ok()?.checked_mul(page_size() as u64)
+}
@@ -0,0 +1,108 @@
+//! The leader/worker protocol behind `std::os::postgres::parallel`.
+//!
+//! The leader creates a dynamic shared memory segment holding two `shm_mq`s,
+//! one carrying the input to the worker and one carrying its output back,
+//! and passes the segment's handle as the worker's `bgw_main_arg`.
+
+use crate::io;
+use crate::ptr;
+use crate::sys::bgworker::{self, Spec};
+use crate::sys::shm_mq::{self, Queue, Role, Segment};
+
+/// Size of each of the two queues. Messages larger than this are fine, they
+/// are just streamed through the queue in pieces.
+const QUEUE_SIZE: usize = 64 * 1024;
+
+pub struct Launched {
+    // Queues must be detached before the segment they live in.
+    input: Option<Queue>,
+    output: Option<Queue>,
+    worker: bgworker::Handle,
+    _seg: Segment,
+}
+
+pub fn launch(name: &str, library: &str, function: &str, input: &[u8]) -> io::Result<Launched> {
+    let seg = Segment::create(2 * QUEUE_SIZE)?;
+    let (mut to_worker, mut from_worker) = unsafe {
+        let base = seg.address();
+        let mq_in = shm_mq::create(base, QUEUE_SIZE)?;
+        let mq_out = shm_mq::create(base.add(QUEUE_SIZE), QUEUE_SIZE)?;
+        (
+            Queue::attach(mq_in, &seg, Role::Sender, ptr::null_mut())?,
+            Queue::attach(mq_out, &seg, Role::Receiver, ptr::null_mut())?,
+        )
+    };
+
+    let spec = Spec { name, library, function, main_arg: seg.handle() as usize };
+    let worker = bgworker::Handle::register(&spec)?;
+    to_worker.set_worker(worker.as_ptr());
+    from_worker.set_worker(worker.as_ptr());
+
+    // Blocks until the worker picked up whatever didn't fit in the queue,
+    // or fails if it died first.
+    to_worker.send(input)?;
+
+    Ok(Launched { input: Some(to_worker), output: Some(from_worker), worker, _seg: seg })
+}
+
+impl Launched {
+    /// Waits for the worker's reply, and then for it to exit.
+    pub fn join(&mut self) -> io::Result<Vec<u8>> {
+        drop(self.input.take());
+        let reply = match self.output.as_mut() {
+            Some(output) => output.receive().map(<[u8]>::to_vec).map_err(|_| {
+                io::const_io_error!(io::ErrorKind::Other, "background worker exited without replying")
+            }),
+            None => Err(io::const_io_error!(io::ErrorKind::Other, "reply was already received")),
+        };
+        // Detach so a worker still sending doesn't wait on us forever.
+        drop(self.output.take());
+        self.worker.wait_for_shutdown()?;
+        reply
+    }
+
+    /// Waits for the worker to exit, ignoring any reply.
+    pub fn wait(&mut self) -> io::Result<()> {
+        // Detaching the output queue makes the worker's pending `send` fail
+        // instead of blocking, so it can get to exiting.
+        drop(self.input.take());
+        drop(self.output.take());
+        self.worker.wait_for_shutdown()
+    }
+
+    pub fn terminate(&self) {
+        self.worker.terminate()
+    }
+
+    pub fn worker_ptr(&self) -> *mut libc::c_void {
+        self.worker.as_ptr()
+    }
+}
+
+/// The worker's end of the protocol.
+pub struct Attached {
+    output: Queue,
+    _seg: Segment,
+}
+
+/// Attaches to the leader that launched this worker, returning the input it
+/// sent along.
+pub fn attach(main_arg: usize) -> io::Result<(Attached, Vec<u8>)> {
+    let seg = Segment::attach(main_arg as u32)?;
+    let (mut from_leader, to_leader) = unsafe {
+        let base = seg.address();
+        (
+            Queue::attach(base.cast(), &seg, Role::Receiver, ptr::null_mut())?,
+            Queue::attach(base.add(QUEUE_SIZE).cast(), &seg, Role::Sender, ptr::null_mut())?,
+        )
+    };
+    let input = from_leader.receive()?.to_vec();
+    drop(from_leader);
+    Ok((Attached { output: to_leader, _seg: seg }, input))
+}
+
+impl Attached {
+    pub fn reply(&mut self, output: &[u8]) -> io::Result<()> {
+        self.output.send(output)
+    }
+}
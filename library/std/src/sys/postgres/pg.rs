@@ -0,0 +1,772 @@
+//! Hooks into the postgres backend hosting this process.
+//!
+//! Backend symbols are looked up with `dlsym` rather than linked directly, so
+//! a binary built against this std still runs outside of a backend (unit
+//! tests, build scripts). There, waits fall back to plain `poll(2)` and no
+//! interrupt is ever reported. They are linked directly in builds with
+//! `pg_link_backend`; see `sys::symbol`.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use crate::cmp;
+use crate::ffi::{CStr, OsStr};
+use crate::io;
+use crate::os::unix::ffi::OsStrExt;
+use crate::path::PathBuf;
+use crate::ptr;
+use crate::sync::atomic::{AtomicI32, AtomicPtr, AtomicU32, Ordering};
+use crate::sys::blocking;
+use crate::sys::elog;
+use crate::sys::symbol::{Backend, Symbol};
+use crate::time::{Duration, Instant};
+use libc::{c_char, c_int, c_long, c_void};
+
+// storage/latch.h
+const WL_LATCH_SET: c_int = 1 << 0;
+const WL_SOCKET_READABLE: c_int = 1 << 1;
+const WL_SOCKET_WRITEABLE: c_int = 1 << 2;
+const WL_TIMEOUT: c_int = 1 << 3;
+const WL_EXIT_ON_PM_DEATH: c_int = 1 << 5;
+const PGINVALID_SOCKET: c_int = -1;
+
+// utils/wait_event.h
+pub const PG_WAIT_EXTENSION: u32 = 0x07000000;
+
+// utils/timeout.h
+const LOCK_TIMEOUT: c_int = 2;
+const STATEMENT_TIMEOUT: c_int = 3;
+
+backend! {
+    fn WaitLatchOrSocket(
+        latch: *mut c_void,
+        wake_events: c_int,
+        sock: c_int,
+        timeout: c_long,
+        wait_event_info: u32,
+    ) -> c_int;
+    fn ResetLatch(latch: *mut c_void);
+    fn SetLatch(latch: *mut c_void);
+    fn get_timeout_active(id: c_int) -> bool;
+    fn get_timeout_finish_time(id: c_int) -> i64;
+    // The first argument is a `MemoryContext` before postgres 17, and a
+    // `ResourceOwner` as of 17.
+    fn CreateWaitEventSet(context_or_owner: *mut c_void, nevents: c_int) -> *mut c_void;
+    fn AddWaitEventToSet(
+        set: *mut c_void,
+        events: u32,
+        fd: c_int,
+        latch: *mut c_void,
+        user_data: *mut c_void,
+    ) -> c_int;
+    fn ModifyWaitEvent(set: *mut c_void, pos: c_int, events: u32, latch: *mut c_void);
+    fn WaitEventSetWait(
+        set: *mut c_void,
+        timeout: c_long,
+        occurred_events: *mut WaitEvent,
+        nevents: c_int,
+        wait_event_info: u32,
+    ) -> c_int;
+    fn FreeWaitEventSet(set: *mut c_void);
+    fn GetConfigOption(
+        name: *const c_char,
+        missing_ok: bool,
+        restrict_privileged: bool,
+    ) -> *const c_char;
+    fn set_config_option(
+        name: *const c_char,
+        value: *const c_char,
+        context: c_int,
+        source: c_int,
+        action: c_int,
+        change_val: bool,
+        elevel: c_int,
+        is_reload: bool,
+    ) -> c_int;
+    fn GetCurrentTimestamp() -> i64;
+
+    // `volatile sig_atomic_t` globals in miscadmin.h, and `struct Latch *MyLatch`.
+    static QueryCancelPending: AtomicI32;
+    static ProcDiePending: AtomicI32;
+    static InterruptHoldoffCount: AtomicU32;
+    static QueryCancelHoldoffCount: AtomicU32;
+    static CritSectionCount: AtomicU32;
+    static MyLatch: AtomicPtr<c_void>;
+    static TopMemoryContext: AtomicPtr<c_void>;
+    static MyProc: AtomicPtr<c_void>;
+    static MyProcPid: AtomicI32;
+    static MyProcPort: AtomicPtr<c_void>;
+    fn IsTransactionState() -> bool;
+    static stack_base_ptr: AtomicPtr<c_char>;
+    // The `max_stack_depth` setting, in kilobytes.
+    static max_stack_depth: AtomicI32;
+    static DataDir: AtomicPtr<c_char>;
+    static MyDatabaseTableSpace: AtomicU32;
+    fn PrepareTempTablespaces();
+    fn GetNextTempTableSpace() -> u32;
+    fn TempTablespacePath(path: *mut c_char, tablespace: u32);
+    fn PathNameCreateTemporaryDir(basedir: *const c_char, directory: *const c_char);
+    fn stack_is_too_deep() -> bool;
+}
+
+/// Resolves a backend symbol, failing with `Unsupported` when we are not
+/// running inside a backend.
+pub(crate) fn require<F>(sym: &impl Symbol<F>) -> io::Result<F> {
+    sym.get().ok_or(io::const_io_error!(
+        io::ErrorKind::Unsupported,
+        "this operation is only available inside a postgres backend",
+    ))
+}
+
+/// This backend's `PGPROC`.
+pub fn my_proc() -> io::Result<*mut c_void> {
+    Ok(require(&MyProc)?.load(Ordering::Relaxed))
+}
+
+/// The PID of this backend, if we are running inside one.
+pub fn my_proc_pid() -> Option<u32> {
+    MyProcPid.get().map(|pid| pid.load(Ordering::Relaxed) as u32)
+}
+
+// libpq/libpq-be.h
+#[repr(C)]
+struct SockAddr {
+    addr: libc::sockaddr_storage,
+    salen: libc::socklen_t,
+}
+
+/// The start of `struct Port`, up to `database_name`. `Tail` is the part
+/// that differs between versions.
+#[repr(C)]
+struct Port<Tail> {
+    sock: c_int,
+    noblock: bool,
+    proto: u32,
+    laddr: SockAddr,
+    raddr: SockAddr,
+    remote_host: *mut c_char,
+    remote_hostname: *mut c_char,
+    remote_hostname_resolv: c_int,
+    remote_hostname_errcode: c_int,
+    remote_port: *mut c_char,
+    tail: Tail,
+}
+
+#[repr(C)]
+struct PortTailBefore17 {
+    canAcceptConnections: c_int,
+    database_name: *mut c_char,
+}
+
+#[repr(C)]
+struct PortTail {
+    database_name: *mut c_char,
+}
+
+/// The name of the database the client asked to connect to, from
+/// `MyProcPort`, so that no catalog access is needed. `None` outside of a
+/// backend, and in processes without a client connection, like background
+/// workers.
+pub fn database_name() -> Option<String> {
+    let port = MyProcPort.get()?.load(Ordering::Relaxed);
+    if port.is_null() {
+        return None;
+    }
+    // Postgres 17 dropped `canAcceptConnections`.
+    let version = server_version()?;
+    let name = unsafe {
+        if version < 170000 {
+            (*port.cast::<Port<PortTailBefore17>>()).tail.database_name
+        } else {
+            (*port.cast::<Port<PortTail>>()).tail.database_name
+        }
+    };
+    if name.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned())
+}
+
+/// The data directory of the server, which backends never leave, or `None`
+/// outside of a backend.
+pub fn data_dir() -> Option<PathBuf> {
+    let data_dir = DataDir.get()?.load(Ordering::Relaxed);
+    if data_dir.is_null() {
+        return None;
+    }
+    let data_dir = OsStr::from_bytes(unsafe { CStr::from_ptr(data_dir) }.to_bytes());
+    Some(PathBuf::from(data_dir))
+}
+
+// pg_config_manual.h, catalog/pg_tablespace.dat
+const MAXPGPATH: usize = 1024;
+const DEFAULTTABLESPACE_OID: u32 = 1663;
+
+/// The directory for the temporary files of this backend, created if need
+/// be: `pgsql_tmp<pid>.rust`, in the `pgsql_tmp` directory of the tablespace
+/// postgres would put its own next temporary file in (see
+/// `temp_tablespaces`).
+///
+/// Postgres removes whatever is named `pgsql_tmp*` in there when it
+/// restarts, like temporary files of its own a crash left behind.
+///
+/// `Ok(None)` outside of a backend. Fails with the `ERROR` postgres raises
+/// if `temp_tablespaces` names a tablespace that is not there, or the
+/// directory cannot be created.
+pub fn temp_dir() -> io::Result<Option<PathBuf>> {
+    let (Some(data_dir), Some(pid)) = (data_dir(), my_proc_pid()) else {
+        return Ok(None);
+    };
+    let prepare = require(&PrepareTempTablespaces)?;
+    let next = require(&GetNextTempTableSpace)?;
+    let tablespace_path = require(&TempTablespacePath)?;
+    let create = require(&PathNameCreateTemporaryDir)?;
+    let error =
+        |inner| io::Error::new(io::ErrorKind::Other, crate::os::postgres::PgError { inner });
+
+    // This looks up `temp_tablespaces` once per transaction, and does
+    // nothing outside of one.
+    elog::try_catch(|| unsafe { prepare() }).map_err(error)?;
+    let tablespace = match unsafe { next() } {
+        0 => match MyDatabaseTableSpace.get().map_or(0, |t| t.load(Ordering::Relaxed)) {
+            0 => DEFAULTTABLESPACE_OID,
+            tablespace => tablespace,
+        },
+        tablespace => tablespace,
+    };
+    let mut base = [0; MAXPGPATH];
+    unsafe { tablespace_path(base.as_mut_ptr(), tablespace) };
+    let base = unsafe { CStr::from_ptr(base.as_ptr()) };
+    let mut dir = base.to_bytes().to_vec();
+    dir.extend_from_slice(format!("/pgsql_tmp{pid}.rust\0").as_bytes());
+    // Relative to the data directory, which is the working directory of
+    // the backend.
+    elog::try_catch(|| unsafe { create(base.as_ptr(), dir.as_ptr().cast()) }).map_err(error)?;
+    Ok(Some(data_dir.join(OsStr::from_bytes(&dir[..dir.len() - 1]))))
+}
+
+/// What a socket is being waited on for.
+#[derive(Clone, Copy)]
+pub enum Interest {
+    Read,
+    Write,
+}
+
+fn pending(flag: &Backend<&'static AtomicI32>) -> bool {
+    flag.get().map_or(false, |f| f.load(Ordering::Relaxed) != 0)
+}
+
+fn held_off(count: &Backend<&'static AtomicU32>) -> bool {
+    count.get().map_or(false, |c| c.load(Ordering::Relaxed) != 0)
+}
+
+/// Fails if the backend has been asked to cancel the current statement or to
+/// terminate.
+///
+/// Like `ProcessInterrupts`, this reports nothing while interrupts are held
+/// off (`HOLD_INTERRUPTS`, `HOLD_CANCEL_INTERRUPTS`) or inside a critical
+/// section. The interrupt itself is left pending: it is serviced by postgres
+/// at its next `CHECK_FOR_INTERRUPTS()`, once control is back in the backend.
+pub fn check_interrupts() -> io::Result<()> {
+    if held_off(&InterruptHoldoffCount) || held_off(&CritSectionCount) {
+        return Ok(());
+    }
+    if pending(&ProcDiePending) {
+        Err(io::const_io_error!(
+            io::ErrorKind::Other,
+            "terminating connection due to administrator command",
+        ))
+    } else if pending(&QueryCancelPending) && !held_off(&QueryCancelHoldoffCount) {
+        Err(io::const_io_error!(io::ErrorKind::Other, "canceling statement due to user request"))
+    } else {
+        Ok(())
+    }
+}
+
+/// [`check_interrupts`] for callers that have no way to report an error:
+/// panics with the pending interrupt, to be turned into an `ERROR` where the
+/// panic is caught.
+#[track_caller]
+pub fn panic_on_interrupt() {
+    if let Err(e) = check_interrupts() {
+        panic!("{e}")
+    }
+}
+
+/// The address postgres measures stack depth from, close to the outermost
+/// frame of the process, or `None` outside a backend.
+pub fn stack_base() -> Option<usize> {
+    let base = stack_base_ptr.get()?.load(Ordering::Relaxed);
+    (!base.is_null()).then(|| base.addr())
+}
+
+/// The `max_stack_depth` setting in bytes, or `None` outside a backend.
+///
+/// This reads the variable behind the setting rather than going through
+/// `GetConfigOption`, which shows it with a unit (`2MB`).
+pub fn max_stack_size() -> Option<usize> {
+    let kb = max_stack_depth.get()?.load(Ordering::Relaxed);
+    Some(usize::try_from(kb).ok()?.saturating_mul(1024))
+}
+
+/// How many more bytes of stack can be used before `max_stack_depth` is
+/// exceeded, or `None` outside a backend.
+pub fn remaining_stack() -> Option<usize> {
+    let base = stack_base()?;
+    let limit = max_stack_size()?;
+    // The address of a local is as good an estimate of the stack pointer as
+    // the one postgres uses.
+    let here = ptr::addr_of!(base).addr();
+    Some(limit.saturating_sub(base.abs_diff(here)))
+}
+
+/// Fails like postgres's `check_stack_depth()`, but with an error instead of
+/// an `ERROR`, if the stack has grown beyond `max_stack_depth`.
+pub fn check_stack_depth() -> io::Result<()> {
+    match stack_is_too_deep.get() {
+        Some(too_deep) if unsafe { too_deep() } => Err(io::const_io_error!(
+            io::ErrorKind::Other,
+            "stack depth limit exceeded; increase the configuration parameter \"max_stack_depth\"",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the current value of the setting `name` (which must be
+/// nul-terminated), or `None` if there is no such setting or we are not
+/// running inside a backend.
+pub fn config_option(name: &str) -> Option<String> {
+    let name = CStr::from_bytes_with_nul(name.as_bytes()).ok()?;
+    let get = GetConfigOption.get()?;
+    let value = unsafe { get(name.as_ptr(), true, false) };
+    if value.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(value) }.to_string_lossy().into_owned())
+}
+
+/// `server_version_num`, e.g. 160002, or `None` outside of a backend.
+///
+/// It is looked up once, and without allocating, so that reporting an
+/// error, even that an allocation failed, can branch on it.
+pub fn server_version() -> Option<u32> {
+    // 0 until it is looked up.
+    static VERSION: AtomicU32 = AtomicU32::new(0);
+
+    let version = VERSION.load(Ordering::Relaxed);
+    if version != 0 {
+        return Some(version);
+    }
+    let get = GetConfigOption.get()?;
+    let value = unsafe { get(b"server_version_num\0".as_ptr().cast(), true, false) };
+    if value.is_null() {
+        return None;
+    }
+    let digits = unsafe { CStr::from_ptr(value) }.to_bytes();
+    let version = digits.iter().try_fold(0u32, |n, &d| {
+        let d = (d as char).to_digit(10)?;
+        n.checked_mul(10)?.checked_add(d)
+    })?;
+    if version == 0 {
+        return None;
+    }
+    VERSION.store(version, Ordering::Relaxed);
+    Some(version)
+}
+
+// utils/guc.h
+const PGC_USERSET: c_int = 6;
+const PGC_S_SESSION: c_int = 13;
+const GUC_ACTION_LOCAL: c_int = 1;
+
+/// Sets the setting `name` to `value` until the end of the transaction, like
+/// `SET LOCAL` does, so that it is undone if the transaction aborts.
+pub fn set_config_local(name: &CStr, value: &CStr) -> io::Result<()> {
+    let set = require(&set_config_option)?;
+    if !unsafe { require(&IsTransactionState)?() } {
+        return Err(io::const_io_error!(
+            io::ErrorKind::Unsupported,
+            "settings can only be changed inside a transaction",
+        ));
+    }
+    // An elevel of 0 means `ERROR`, for a session setting. In a
+    // subtransaction of its own, so that a rejected value is undone with it.
+    let applied = elog::subtransaction(|| unsafe {
+        set(
+            name.as_ptr(),
+            value.as_ptr(),
+            PGC_USERSET,
+            PGC_S_SESSION,
+            GUC_ACTION_LOCAL,
+            true,
+            0,
+            false,
+        )
+    })
+    .map_err(|inner| {
+        io::Error::new(io::ErrorKind::InvalidInput, crate::os::postgres::PgError { inner })
+    })?;
+    if applied <= 0 {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "the setting was not applied"
+        ));
+    }
+    Ok(())
+}
+
+/// The point at which the running statement gets cancelled by
+/// `statement_timeout` or `lock_timeout`, whichever comes first.
+pub fn statement_deadline() -> Option<Instant> {
+    let (active, finish_time, current) = match (
+        get_timeout_active.get(),
+        get_timeout_finish_time.get(),
+        GetCurrentTimestamp.get(),
+    ) {
+        (Some(active), Some(finish_time), Some(current)) => (active, finish_time, current),
+        _ => return None,
+    };
+
+    // Both clocks are sampled once, so the remaining budgets are comparable.
+    let pg_now = unsafe { current() };
+    let now = Instant::now();
+    [STATEMENT_TIMEOUT, LOCK_TIMEOUT]
+        .into_iter()
+        .filter(|&id| unsafe { active(id) })
+        .map(|id| {
+            // `TimestampTz` is in microseconds.
+            let left = unsafe { finish_time(id) }.saturating_sub(pg_now);
+            now + Duration::from_micros(cmp::max(left, 0) as u64)
+        })
+        .min()
+}
+
+fn clamp_to_statement(deadline: Option<Instant>) -> Option<Instant> {
+    match (deadline, statement_deadline()) {
+        (Some(deadline), Some(statement)) => Some(cmp::min(deadline, statement)),
+        (deadline, statement) => deadline.or(statement),
+    }
+}
+
+fn timeout_ms(deadline: Option<Instant>) -> c_long {
+    match deadline {
+        None => -1,
+        Some(deadline) => {
+            let left = deadline.saturating_duration_since(Instant::now());
+            // Round up, so that a sub-millisecond remainder doesn't spin.
+            let ms = left.as_millis() + (left.subsec_nanos() % 1_000_000 != 0) as u128;
+            cmp::min(ms, c_int::MAX as u128) as c_long
+        }
+    }
+}
+
+/// Blocks until `fd` is ready for `interest` or `deadline` passes, returning
+/// `Ok(false)` in the latter case. The deadline is capped at the
+/// [`statement_deadline`], so a wait never outlives the query that issued it.
+///
+/// Inside a backend this waits on the process latch as well, so that
+/// `pg_cancel_backend` and `pg_terminate_backend` cut the wait short with an
+/// error from [`check_interrupts`].
+pub fn wait_socket(fd: c_int, interest: Interest, deadline: Option<Instant>) -> io::Result<bool> {
+    let events = match interest {
+        Interest::Read => WL_SOCKET_READABLE,
+        Interest::Write => WL_SOCKET_WRITEABLE,
+    };
+    let deadline = clamp_to_statement(deadline);
+
+    let (wait, reset, latch) = match (WaitLatchOrSocket.get(), ResetLatch.get(), MyLatch.get()) {
+        (Some(wait), Some(reset), Some(latch)) => (wait, reset, latch),
+        _ => return poll_socket(fd, interest, deadline),
+    };
+
+    loop {
+        check_interrupts()?;
+
+        let timeout = timeout_ms(deadline);
+        let mut flags = events | WL_LATCH_SET | WL_EXIT_ON_PM_DEATH;
+        if timeout >= 0 {
+            flags |= WL_TIMEOUT;
+        }
+
+        let latch = latch.load(Ordering::Relaxed);
+        let rc = unsafe { wait(latch, flags, fd, timeout, PG_WAIT_EXTENSION) };
+
+        if rc & events != 0 {
+            return Ok(true);
+        }
+        if rc & WL_LATCH_SET != 0 {
+            unsafe { reset(latch) };
+        }
+        if rc & WL_TIMEOUT != 0 {
+            return Ok(false);
+        }
+    }
+}
+
+/// Blocks until the process latch is set or `deadline` passes, returning
+/// `Ok(false)` in the latter case. Other backends set our latch to tell us
+/// that something we may be waiting for, like a message, has arrived.
+///
+/// Outside of a backend there is no latch to wait on, so this fails with
+/// `Unsupported`.
+pub fn wait_latch(deadline: Option<Instant>) -> io::Result<bool> {
+    let wait = require(&WaitLatchOrSocket)?;
+    let reset = require(&ResetLatch)?;
+    let latch = require(&MyLatch)?.load(Ordering::Relaxed);
+    let deadline = clamp_to_statement(deadline);
+
+    check_interrupts()?;
+    let timeout = timeout_ms(deadline);
+    let mut flags = WL_LATCH_SET | WL_EXIT_ON_PM_DEATH;
+    if timeout >= 0 {
+        flags |= WL_TIMEOUT;
+    }
+    let rc = unsafe { wait(latch, flags, PGINVALID_SOCKET, timeout, PG_WAIT_EXTENSION) };
+    if rc & WL_LATCH_SET != 0 {
+        unsafe { reset(latch) };
+    }
+    check_interrupts()?;
+    Ok(rc & WL_TIMEOUT == 0)
+}
+
+/// Sets the process latch, waking up [`wait_latch`]. This is async-signal
+/// safe, like `SetLatch` itself.
+pub fn set_latch() {
+    if let (Some(set), Some(latch)) = (SetLatch.get(), MyLatch.get()) {
+        unsafe { set(latch.load(Ordering::Relaxed)) }
+    }
+}
+
+/// This backend's latch, which other backends can set with
+/// [`set_latch_of`] to wake it up, once it has a `PGPROC`.
+pub fn my_latch() -> io::Result<*mut c_void> {
+    Ok(require(&MyLatch)?.load(Ordering::Relaxed))
+}
+
+/// Sets the latch of another backend, as returned by its [`my_latch`].
+pub unsafe fn set_latch_of(latch: *mut c_void) {
+    if let Some(set) = SetLatch.get() {
+        unsafe { set(latch) }
+    }
+}
+
+/// Like [`wait_socket`], but for several sockets at once. Returns the indices
+/// into `fds` of the sockets that are ready, which is empty on timeout.
+pub fn wait_sockets(
+    fds: &[c_int],
+    interest: Interest,
+    deadline: Option<Instant>,
+) -> io::Result<Vec<usize>> {
+    let (readable, writable) = match interest {
+        Interest::Read => (true, false),
+        Interest::Write => (false, true),
+    };
+
+    let mut ready = Vec::new();
+    match WaitEventSet::new(fds.len()) {
+        Ok(mut set) => {
+            let mut positions = Vec::with_capacity(fds.len());
+            for &fd in fds {
+                positions.push(set.add_socket(fd, readable, writable)?);
+            }
+            set.wait(deadline, |pos, _, _| {
+                ready.extend(positions.iter().position(|&p| p == pos));
+            })?;
+        }
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+            let events = if readable { libc::POLLIN } else { libc::POLLOUT };
+            blocking::uninterruptible("poll");
+            let mut pollfds: Vec<_> =
+                fds.iter().map(|&fd| libc::pollfd { fd, events, revents: 0 }).collect();
+            loop {
+                let timeout = timeout_ms(deadline) as c_int;
+                let n = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as _, timeout) };
+                if n == -1 {
+                    let err = crate::sys::real_last_os_error_use_carefully();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                    continue;
+                }
+                ready.extend(
+                    pollfds.iter().enumerate().filter(|(_, p)| p.revents != 0).map(|(i, _)| i),
+                );
+                break;
+            }
+        }
+        Err(e) => return Err(e),
+    }
+    Ok(ready)
+}
+
+fn poll_socket(fd: c_int, interest: Interest, deadline: Option<Instant>) -> io::Result<bool> {
+    let events = match interest {
+        Interest::Read => libc::POLLIN,
+        Interest::Write => libc::POLLOUT,
+    };
+    let mut pollfd = libc::pollfd { fd, events, revents: 0 };
+    blocking::uninterruptible("poll");
+
+    loop {
+        let timeout = timeout_ms(deadline) as c_int;
+        match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+            -1 => {
+                let err = crate::sys::real_last_os_error_use_carefully();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => return Ok(false),
+            // Errors and hangups are reported as readiness; the caller finds
+            // out what happened from the next socket call.
+            _ => return Ok(true),
+        }
+    }
+}
+
+/// `WaitEvent` from storage/latch.h.
+#[repr(C)]
+struct WaitEvent {
+    pos: c_int,
+    events: u32,
+    fd: c_int,
+    user_data: *mut c_void,
+}
+
+/// A postgres `WaitEventSet` watching the process latch plus a fixed number
+/// of sockets.
+pub struct WaitEventSet {
+    set: *mut c_void,
+    latch: *mut c_void,
+    /// Number of socket slots still free.
+    free: usize,
+    occurred: Vec<WaitEvent>,
+}
+
+fn socket_events(readable: bool, writable: bool) -> u32 {
+    let mut events = 0;
+    if readable {
+        events |= WL_SOCKET_READABLE;
+    }
+    if writable {
+        events |= WL_SOCKET_WRITEABLE;
+    }
+    events as u32
+}
+
+impl WaitEventSet {
+    pub fn new(sockets: usize) -> io::Result<WaitEventSet> {
+        let (create, add, context, latch) = match (
+            CreateWaitEventSet.get(),
+            AddWaitEventToSet.get(),
+            TopMemoryContext.get(),
+            MyLatch.get(),
+        ) {
+            (Some(create), Some(add), Some(context), Some(latch)) => (create, add, context, latch),
+            _ => {
+                return Err(io::const_io_error!(
+                    io::ErrorKind::Unsupported,
+                    "wait event sets are only available inside a backend",
+                ));
+            }
+        };
+        let nevents = sockets
+            .checked_add(2)
+            .and_then(|n| c_int::try_from(n).ok())
+            .ok_or(io::const_io_error!(io::ErrorKind::InvalidInput, "too many sockets"))?;
+
+        // The set lives exactly as long as this value, which frees it on
+        // drop, so it goes in TopMemoryContext. As of postgres 17, the
+        // argument is a resource owner instead, and with none, the set goes
+        // there as well.
+        let version = server_version().ok_or(io::const_io_error!(
+            io::ErrorKind::Unsupported,
+            "could not tell the server version",
+        ))?;
+        let context_or_owner =
+            if version < 170000 { context.load(Ordering::Relaxed) } else { ptr::null_mut() };
+        let latch = latch.load(Ordering::Relaxed);
+        unsafe {
+            let set = create(context_or_owner, nevents);
+            add(set, WL_LATCH_SET as u32, PGINVALID_SOCKET, latch, ptr::null_mut());
+            add(
+                set,
+                WL_EXIT_ON_PM_DEATH as u32,
+                PGINVALID_SOCKET,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+            let occurred = Vec::with_capacity(nevents as usize);
+            Ok(WaitEventSet { set, latch, free: sockets, occurred })
+        }
+    }
+
+    /// Starts watching `fd`, returning the position identifying it in
+    /// [`WaitEventSet::wait`] results.
+    pub fn add_socket(&mut self, fd: c_int, readable: bool, writable: bool) -> io::Result<usize> {
+        if self.free == 0 {
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "the wait event set has no room for another socket",
+            ));
+        }
+        let add = AddWaitEventToSet.get().unwrap();
+        let events = socket_events(readable, writable);
+        let pos = unsafe { add(self.set, events, fd, ptr::null_mut(), ptr::null_mut()) };
+        self.free -= 1;
+        Ok(pos as usize)
+    }
+
+    pub fn modify_socket(&mut self, pos: usize, readable: bool, writable: bool) {
+        let modify = ModifyWaitEvent.get().unwrap();
+        unsafe {
+            modify(self.set, pos as c_int, socket_events(readable, writable), ptr::null_mut())
+        }
+    }
+
+    /// Waits for socket readiness until `deadline`, calling `ready` with the
+    /// position, readability and writability of each ready socket.
+    ///
+    /// Returns without reporting anything on timeout or when the latch was
+    /// set, and fails if that happened because of an interrupt.
+    pub fn wait<F>(&mut self, deadline: Option<Instant>, mut ready: F) -> io::Result<()>
+    where
+        F: FnMut(usize, bool, bool),
+    {
+        let wait = WaitEventSetWait.get().unwrap();
+        let reset = ResetLatch.get().unwrap();
+
+        check_interrupts()?;
+        let timeout = timeout_ms(clamp_to_statement(deadline));
+        let n = unsafe {
+            wait(
+                self.set,
+                timeout,
+                self.occurred.as_mut_ptr(),
+                self.occurred.capacity() as c_int,
+                PG_WAIT_EXTENSION,
+            )
+        };
+        unsafe { self.occurred.set_len(n as usize) };
+
+        for event in self.occurred.drain(..) {
+            let events = event.events as c_int;
+            if events & WL_LATCH_SET != 0 {
+                unsafe { reset(self.latch) };
+            } else if events & (WL_SOCKET_READABLE | WL_SOCKET_WRITEABLE) != 0 {
+                ready(
+                    event.pos as usize,
+                    events & WL_SOCKET_READABLE != 0,
+                    events & WL_SOCKET_WRITEABLE != 0,
+                );
+            }
+        }
+        check_interrupts()
+    }
+}
+
+impl Drop for WaitEventSet {
+    fn drop(&mut self) {
+        if let Some(free) = FreeWaitEventSet.get() {
+            unsafe { free(self.set) }
+        }
+    }
+}
@@ -11,7 +11,7 @@ use crate::path::Path;
 use crate::sys::fs::File;
 use crate::sys::fd::FileDesc;
 use crate::sys::pipe::AnonPipe;
-use crate::sys::{unsupported, unsupported_err};
+use crate::sys::{capability, unsupported, unsupported_err};
 use crate::sys_common::process::{CommandEnv, CommandEnvs};
 #[cfg(target_os = "linux")]
 use crate::os::linux::process::PidFd;
@@ -175,7 +175,10 @@ impl Command {
     }
 
     pub fn exec(&mut self, default: Stdio) -> io::Error {
-        unsupported_err()
+        match capability::check_process() {
+            Ok(()) => unsupported_err(),
+            Err(e) => e,
+        }
     }
 
     pub fn spawn(
@@ -183,6 +186,9 @@ impl Command {
         _default: Stdio,
         _needs_stdin: bool,
     ) -> io::Result<(Process, StdioPipes)> {
+        // Only whether it was left out of this build is told apart: built
+        // in, there is no process implementation on this target yet.
+        capability::check_process()?;
         unsupported()
     }
 
@@ -0,0 +1,146 @@
+//! Random keys for `HashMap`.
+//!
+//! These come straight from the kernel, with `getrandom(2)` on Linux and
+//! `getentropy(2)` on macOS, rather than from `/dev/urandom`: a backend may
+//! well be in a seccomp profile or chroot that has no business opening it,
+//! and there is no file descriptor to leak either way.
+
+use crate::io;
+use crate::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::sync::OnceLock;
+use crate::sys::os::real_errno_use_carefully;
+use crate::sys::sanitizer;
+
+backend! {
+    #[allow(non_upper_case_globals)]
+    fn pg_strong_random(buf: *mut libc::c_void, len: usize) -> bool;
+}
+
+/// Keys for `RandomState`.
+///
+/// `RandomState` asks once per thread, that is, once per backend, and varies
+/// the keys for each map from there. The kernel is only asked once per
+/// process all the same.
+pub fn hashmap_random_keys() -> (u64, u64) {
+    static KEYS: OnceLock<(u64, u64)> = OnceLock::new();
+
+    *KEYS.get_or_init(|| {
+        const KEY_LEN: usize = core::mem::size_of::<u64>();
+
+        let mut v = [0u8; KEY_LEN * 2];
+        if fill_bytes(&mut v).is_err() {
+            v = fallback_bytes();
+        }
+
+        let key1 = v[0..KEY_LEN].try_into().unwrap();
+        let key2 = v[KEY_LEN..].try_into().unwrap();
+
+        (u64::from_ne_bytes(key1), u64::from_ne_bytes(key2))
+    })
+}
+
+/// Fills `buf` with cryptographically strong random bytes, from whatever
+/// postgres was built to use, or from the kernel outside of a backend.
+pub fn strong_random(buf: &mut [u8]) -> io::Result<()> {
+    match pg_strong_random.get() {
+        Some(random) => match unsafe { random(buf.as_mut_ptr().cast(), buf.len()) } {
+            true => {
+                unsafe { sanitizer::mark_init(buf.as_ptr(), buf.len()) };
+                Ok(())
+            }
+            false => {
+                Err(io::const_io_error!(io::ErrorKind::Other, "could not generate random values"))
+            }
+        },
+        None => fill_bytes(buf).map_err(io::Error::from_raw_os_error),
+    }
+}
+
+/// A fresh random seed, from the kernel if it allows.
+pub fn seed() -> u64 {
+    let mut v = [0u8; 8];
+    match fill_bytes(&mut v) {
+        Ok(()) => u64::from_ne_bytes(v),
+        Err(_) => u64::from_ne_bytes(fallback_bytes()[..8].try_into().unwrap()),
+    }
+}
+
+// Whether every `RandomState` gets the same keys, and which.
+static FIXED: AtomicBool = AtomicBool::new(false);
+// Every postgres target, 32-bit ones included, has 64-bit atomics.
+static FIXED_K0: AtomicU64 = AtomicU64::new(0);
+static FIXED_K1: AtomicU64 = AtomicU64::new(0);
+
+/// The keys every `RandomState` gets instead of random ones, if any.
+pub fn fixed_hash_keys() -> Option<(u64, u64)> {
+    FIXED
+        .load(Ordering::Acquire)
+        .then(|| (FIXED_K0.load(Ordering::Relaxed), FIXED_K1.load(Ordering::Relaxed)))
+}
+
+pub fn set_fixed_hash_keys(keys: Option<(u64, u64)>) {
+    FIXED.store(false, Ordering::Release);
+    if let Some((k0, k1)) = keys {
+        FIXED_K0.store(k0, Ordering::Relaxed);
+        FIXED_K1.store(k1, Ordering::Relaxed);
+        FIXED.store(true, Ordering::Release);
+    }
+}
+
+/// Fills `buf` with random bytes from the kernel, or fails with the errno.
+#[cfg(target_os = "linux")]
+pub fn fill_bytes(buf: &mut [u8]) -> Result<(), i32> {
+    use crate::sys::weak::syscall;
+
+    syscall! {
+        fn getrandom(
+            buffer: *mut libc::c_void,
+            length: libc::size_t,
+            flags: libc::c_uint
+        ) -> libc::ssize_t
+    }
+
+    let mut read = 0;
+    while read < buf.len() {
+        let rest = &mut buf[read..];
+        let ret = unsafe { getrandom(rest.as_mut_ptr().cast(), rest.len(), 0) };
+        if ret == -1 {
+            match real_errno_use_carefully() {
+                libc::EINTR => continue,
+                errno => return Err(errno),
+            }
+        }
+        // A raw system call, which MemorySanitizer does not intercept.
+        unsafe { sanitizer::mark_init(rest.as_ptr(), ret as usize) };
+        read += ret as usize;
+    }
+    Ok(())
+}
+
+/// Fills `buf` with random bytes from the kernel, or fails with the errno.
+#[cfg(target_os = "macos")]
+pub fn fill_bytes(buf: &mut [u8]) -> Result<(), i32> {
+    // `getentropy` takes at most 256 bytes at a time.
+    for chunk in buf.chunks_mut(256) {
+        if unsafe { libc::getentropy(chunk.as_mut_ptr().cast(), chunk.len()) } == -1 {
+            return Err(real_errno_use_carefully());
+        }
+    }
+    Ok(())
+}
+
+/// Something to go on if the kernel refuses, like a seccomp profile that
+/// predates `getrandom(2)`: the time, the PID, and where the stack is.
+/// Guessable, but unlike fixed keys, not the same for every backend.
+fn fallback_bytes() -> [u8; 16] {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut ts) };
+    let pid = unsafe { libc::getpid() } as u64;
+    let stack = crate::ptr::addr_of!(ts).addr() as u64;
+    let k0 = (ts.tv_sec as u64).rotate_left(32) ^ ts.tv_nsec as u64 ^ pid.rotate_left(48);
+    let k1 = stack ^ (ts.tv_nsec as u64).rotate_left(17) ^ pid;
+    let mut v = [0; 16];
+    v[..8].copy_from_slice(&k0.to_ne_bytes());
+    v[8..].copy_from_slice(&k1.to_ne_bytes());
+    v
+}
@@ -0,0 +1,68 @@
+//! Hooks into AddressSanitizer and MemorySanitizer, for test backends whose
+//! extension is built with `-Zsanitizer=address` or `-Zsanitizer=memory`.
+//! Without either, these do nothing, and compile to nothing.
+//!
+//! Both sanitizers intercept `malloc` and friends, which is all the `System`
+//! allocator calls, so Rust's own heap is covered as is. What they do not
+//! see into is palloc'd memory: postgres's allocators hand out chunks of
+//! blocks they got from `malloc` themselves, so an overflow from one chunk
+//! into the next goes unnoticed. And to MemorySanitizer, whatever code that
+//! is not instrumented writes, like the backend or a raw system call, still
+//! looks uninitialized.
+
+use libc::c_void;
+
+#[cfg(sanitize = "address")]
+extern "C" {
+    fn __asan_poison_memory_region(addr: *const c_void, size: usize);
+    fn __asan_unpoison_memory_region(addr: *const c_void, size: usize);
+}
+
+#[cfg(sanitize = "memory")]
+extern "C" {
+    fn __msan_poison(addr: *const c_void, size: usize);
+    fn __msan_unpoison(addr: *const c_void, size: usize);
+}
+
+/// The bytes to leave after palloc'd memory Rust allocates, as a redzone
+/// for AddressSanitizer to catch overflows in. A multiple of every
+/// `MAXIMUM_ALIGNOF`, so that memory stays aligned.
+pub const REDZONE: usize = if cfg!(sanitize = "address") { 16 } else { 0 };
+
+/// Makes `size` bytes at `addr` off limits to AddressSanitizer.
+#[inline(always)]
+pub unsafe fn poison(addr: *const u8, size: usize) {
+    #[cfg(sanitize = "address")]
+    unsafe {
+        __asan_poison_memory_region(addr.cast(), size)
+    }
+}
+
+/// Undoes [`poison`].
+#[inline(always)]
+pub unsafe fn unpoison(addr: *const u8, size: usize) {
+    #[cfg(sanitize = "address")]
+    unsafe {
+        __asan_unpoison_memory_region(addr.cast(), size)
+    }
+}
+
+/// Tells MemorySanitizer that `size` bytes at `addr` are not initialized,
+/// like memory fresh from `malloc`.
+#[inline(always)]
+pub unsafe fn mark_uninit(addr: *const u8, size: usize) {
+    #[cfg(sanitize = "memory")]
+    unsafe {
+        __msan_poison(addr.cast(), size)
+    }
+}
+
+/// Tells MemorySanitizer that `size` bytes at `addr` were initialized, by
+/// code it cannot see into.
+#[inline(always)]
+pub unsafe fn mark_init(addr: *const u8, size: usize) {
+    #[cfg(sanitize = "memory")]
+    unsafe {
+        __msan_unpoison(addr.cast(), size)
+    }
+}
@@ -0,0 +1,201 @@
+//! Dynamic shared memory segments and the `shm_mq` message queues living in
+//! them, as used to talk to background workers.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use crate::io;
+use crate::ptr;
+use crate::slice;
+use crate::sys::pg::{self, require};
+use libc::{c_int, c_void, size_t};
+
+// storage/shm_mq.h
+const SHM_MQ_SUCCESS: c_int = 0;
+const SHM_MQ_WOULD_BLOCK: c_int = 1;
+
+backend! {
+    fn dsm_create(size: size_t, flags: c_int) -> *mut c_void;
+    fn dsm_attach(h: u32) -> *mut c_void;
+    fn dsm_find_mapping(h: u32) -> *mut c_void;
+    fn dsm_detach(seg: *mut c_void);
+    fn dsm_segment_handle(seg: *mut c_void) -> u32;
+    fn dsm_segment_address(seg: *mut c_void) -> *mut c_void;
+    fn dsm_pin_mapping(seg: *mut c_void);
+    fn shm_mq_create(address: *mut c_void, size: size_t) -> *mut c_void;
+    fn shm_mq_set_sender(mq: *mut c_void, proc_: *mut c_void);
+    fn shm_mq_set_receiver(mq: *mut c_void, proc_: *mut c_void);
+    fn shm_mq_attach(mq: *mut c_void, seg: *mut c_void, handle: *mut c_void) -> *mut c_void;
+    fn shm_mq_set_handle(mqh: *mut c_void, handle: *mut c_void);
+    fn shm_mq_detach(mqh: *mut c_void);
+    // postgres 15 added the trailing `force_flush` argument. Passing it to
+    // older versions is harmless: it ends up in a register the callee never
+    // reads.
+    fn shm_mq_send(
+        mqh: *mut c_void,
+        nbytes: size_t,
+        data: *const c_void,
+        nowait: bool,
+        force_flush: bool,
+    ) -> c_int;
+    fn shm_mq_receive(
+        mqh: *mut c_void,
+        nbytesp: *mut size_t,
+        datap: *mut *mut c_void,
+        nowait: bool,
+    ) -> c_int;
+}
+
+fn detached() -> io::Error {
+    io::const_io_error!(io::ErrorKind::BrokenPipe, "the other end of the queue has detached")
+}
+
+/// A mapping of a dynamic shared memory segment, detached on drop.
+pub struct Segment(*mut c_void);
+
+impl Segment {
+    pub fn create(size: usize) -> io::Result<Segment> {
+        let seg = unsafe { require(&dsm_create)?(size, 0) };
+        Ok(Segment(seg))
+    }
+
+    /// Maps the segment `handle`, which must not be mapped in this backend
+    /// already: postgres allows only one mapping of a segment per backend.
+    pub fn attach(handle: u32) -> io::Result<Segment> {
+        if !unsafe { require(&dsm_find_mapping)?(handle) }.is_null() {
+            return Err(io::const_io_error!(
+                io::ErrorKind::AlreadyExists,
+                "dynamic shared memory segment is already mapped in this backend",
+            ));
+        }
+        let seg = unsafe { require(&dsm_attach)?(handle) };
+        if seg.is_null() {
+            return Err(io::const_io_error!(
+                io::ErrorKind::NotFound,
+                "dynamic shared memory segment does not exist",
+            ));
+        }
+        Ok(Segment(seg))
+    }
+
+    pub fn handle(&self) -> u32 {
+        unsafe { dsm_segment_handle.get().unwrap()(self.0) }
+    }
+
+    pub fn address(&self) -> *mut u8 {
+        unsafe { dsm_segment_address.get().unwrap()(self.0).cast() }
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+
+    /// Keeps the mapping until it is dropped, instead of until the end of
+    /// the current resource owner (usually the transaction).
+    pub fn pin_mapping(&self) {
+        unsafe { dsm_pin_mapping.get().unwrap()(self.0) }
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        unsafe { dsm_detach.get().unwrap()(self.0) }
+    }
+}
+
+/// Which end of a queue this backend is.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Sender,
+    Receiver,
+}
+
+/// Initializes a queue of `size` bytes at `address`.
+pub unsafe fn create(address: *mut u8, size: usize) -> io::Result<*mut c_void> {
+    Ok(unsafe { require(&shm_mq_create)?(address.cast(), size) })
+}
+
+/// An attached end of a `shm_mq`.
+pub struct Queue {
+    handle: *mut c_void,
+    role: Role,
+}
+
+impl Queue {
+    /// Attaches to the queue at `mq` as `role`.
+    ///
+    /// `worker` is the `BackgroundWorkerHandle` of the counterparty, if
+    /// known, so that waits fail instead of hanging if it dies before
+    /// attaching.
+    pub unsafe fn attach(
+        mq: *mut c_void,
+        seg: &Segment,
+        role: Role,
+        worker: *mut c_void,
+    ) -> io::Result<Queue> {
+        let me = pg::my_proc()?;
+        unsafe {
+            match role {
+                Role::Sender => require(&shm_mq_set_sender)?(mq, me),
+                Role::Receiver => require(&shm_mq_set_receiver)?(mq, me),
+            }
+            let handle = require(&shm_mq_attach)?(mq, seg.as_ptr(), worker);
+            Ok(Queue { handle, role })
+        }
+    }
+
+    /// Tells the queue which background worker the counterparty is, for
+    /// queues attached before that worker was registered.
+    pub fn set_worker(&mut self, worker: *mut c_void) {
+        unsafe { shm_mq_set_handle.get().unwrap()(self.handle, worker) }
+    }
+
+    fn raw_send(&mut self, data: &[u8], nowait: bool) -> io::Result<bool> {
+        debug_assert!(self.role == Role::Sender);
+        let send = shm_mq_send.get().unwrap();
+        match unsafe { send(self.handle, data.len(), data.as_ptr().cast(), nowait, true) } {
+            SHM_MQ_SUCCESS => Ok(true),
+            SHM_MQ_WOULD_BLOCK => Ok(false),
+            _ => Err(detached()),
+        }
+    }
+
+    /// Sends one message, blocking (on the latch) while the queue is full.
+    pub fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        self.raw_send(data, false).map(drop)
+    }
+
+    /// Sends one message if there is room for it, returning whether it was.
+    pub fn try_send(&mut self, data: &[u8]) -> io::Result<bool> {
+        self.raw_send(data, true)
+    }
+
+    fn raw_receive(&mut self, nowait: bool) -> io::Result<Option<&[u8]>> {
+        debug_assert!(self.role == Role::Receiver);
+        let receive = shm_mq_receive.get().unwrap();
+        let mut len = 0;
+        let mut data = ptr::null_mut();
+        match unsafe { receive(self.handle, &mut len, &mut data, nowait) } {
+            SHM_MQ_SUCCESS => Ok(Some(unsafe { slice::from_raw_parts(data.cast(), len) })),
+            SHM_MQ_WOULD_BLOCK => Ok(None),
+            _ => Err(detached()),
+        }
+    }
+
+    /// Receives one message, blocking (on the latch) until there is one.
+    ///
+    /// The returned slice may point into the queue itself, and is only valid
+    /// until the next operation on it.
+    pub fn receive(&mut self) -> io::Result<&[u8]> {
+        self.raw_receive(false).map(|msg| msg.unwrap())
+    }
+
+    /// Receives one message if there is one.
+    pub fn try_receive(&mut self) -> io::Result<Option<&[u8]>> {
+        self.raw_receive(true)
+    }
+}
+
+impl Drop for Queue {
+    fn drop(&mut self) {
+        unsafe { shm_mq_detach.get().unwrap()(self.handle) }
+    }
+}
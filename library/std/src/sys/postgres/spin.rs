@@ -0,0 +1,80 @@
+//! Postgres spinlocks (`slock_t`), for tiny critical sections in shared
+//! memory.
+//!
+//! `S_LOCK` and friends are macros in storage/s_lock.h, so the fast paths are
+//! reimplemented here with the same memory layout and semantics. Only when
+//! the lock is contended do we call into the backend's `s_lock`, which spins
+//! with backoff and raises a "stuck spinlock" PANIC if that goes on for too
+//! long.
+#![allow(non_upper_case_globals)]
+
+use crate::hint;
+use crate::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use libc::{c_char, c_int};
+
+// storage/s_lock.h: `unsigned char` on x86, `int` on ARM.
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+type AtomicSlock = crate::sync::atomic::AtomicU8;
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+type AtomicSlock = crate::sync::atomic::AtomicI32;
+
+backend! {
+    fn s_lock(
+        lock: *const AtomicSlock,
+        file: *const c_char,
+        line: c_int,
+        func: *const c_char,
+    ) -> c_int;
+}
+
+/// An `slock_t`.
+#[repr(transparent)]
+pub struct SpinLock(AtomicSlock);
+
+impl SpinLock {
+    /// `S_INIT_LOCK`
+    pub const fn new() -> SpinLock {
+        SpinLock(AtomicSlock::new(0))
+    }
+
+    /// `TAS`: returns whether the lock was taken.
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        self.0.swap(1, Acquire) == 0
+    }
+
+    /// `S_LOCK`
+    #[inline]
+    pub fn lock(&self) {
+        if !self.try_lock() {
+            self.lock_contended()
+        }
+    }
+
+    #[cold]
+    fn lock_contended(&self) {
+        match s_lock.get() {
+            Some(s_lock) => unsafe {
+                s_lock(
+                    &self.0,
+                    concat!(file!(), "\0").as_ptr().cast(),
+                    line!() as c_int,
+                    "lock_contended\0".as_ptr().cast(),
+                );
+            },
+            // Outside a backend there is nobody to share the memory with,
+            // but spin like `TAS_SPIN` anyway.
+            None => {
+                while self.0.load(Relaxed) != 0 || !self.try_lock() {
+                    hint::spin_loop();
+                }
+            }
+        }
+    }
+
+    /// `S_UNLOCK`
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        self.0.store(0, Release);
+    }
+}
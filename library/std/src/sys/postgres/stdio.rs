@@ -1,5 +1,6 @@
+use crate::fmt;
 use crate::io;
-use crate::sys::unsupported;
+use crate::sys::{elog, unsupported};
 
 pub struct Stdin(());
 pub struct Stdout(());
@@ -56,3 +57,14 @@ pub const STDIN_BUF_SIZE: usize = crate::sys_common::io::DEFAULT_BUF_SIZE;
 pub fn panic_output() -> Option<impl io::Write> {
     None::<Stderr>
 }
+
+/// Reports `message` from `dbg!` at `DEBUG1`, attributed to `line` of
+/// `file`, rather than to a stderr that a backend does not have. Outside of
+/// a backend, it goes nowhere.
+pub fn debug(file: &str, line: u32, message: fmt::Arguments<'_>) {
+    if let Ok(Some(mut report)) = elog::Report::start(elog::Level::Debug1) {
+        report.message(message);
+        report.at(file, line);
+        report.finish();
+    }
+}
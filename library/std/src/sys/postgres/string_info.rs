@@ -0,0 +1,94 @@
+//! `StringInfo`, postgres's growable string buffer, from lib/stringinfo.h.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use super::elog;
+use crate::ffi::{CStr, VaList};
+use crate::io;
+use crate::sys::pg;
+use libc::{c_char, c_int};
+
+/// `StringInfoData`, as of postgres 11 through 16.
+#[repr(C)]
+pub struct StringInfoData {
+    pub data: *mut c_char,
+    pub len: c_int,
+    pub maxlen: c_int,
+    pub cursor: c_int,
+}
+
+backend! {
+    fn makeStringInfo() -> *mut StringInfoData;
+    fn appendBinaryStringInfo(info: *mut StringInfoData, data: *const c_char, datalen: c_int);
+    fn appendStringInfoVA(
+        info: *mut StringInfoData,
+        fmt: *const c_char,
+        args: VaList<'_, '_>,
+    ) -> c_int;
+    fn enlargeStringInfo(info: *mut StringInfoData, needed: c_int);
+    fn pfree(pointer: *mut libc::c_void);
+}
+
+fn error(inner: elog::ErrorData) -> io::Error {
+    io::Error::new(io::ErrorKind::OutOfMemory, crate::os::postgres::PgError { inner })
+}
+
+/// Makes an empty `StringInfo`, palloc'd in the current memory context.
+pub fn make() -> io::Result<*mut StringInfoData> {
+    let make = pg::require(&makeStringInfo)?;
+    elog::try_catch(|| unsafe { make() }).map_err(error)
+}
+
+/// Appends `bytes` to `info`, failing with the `ERROR` postgres raises once
+/// it would be over 1GB.
+///
+/// # Safety
+///
+/// `info` is a valid `StringInfo`, and its memory context is still there.
+pub unsafe fn append(info: *mut StringInfoData, bytes: &[u8]) -> io::Result<()> {
+    let append = pg::require(&appendBinaryStringInfo)?;
+    let len = c_int::try_from(bytes.len()).map_err(|_| {
+        io::const_io_error!(io::ErrorKind::OutOfMemory, "string buffer too long for postgres")
+    })?;
+    elog::try_catch(|| unsafe { append(info, bytes.as_ptr().cast(), len) }).map_err(error)
+}
+
+/// Appends `fmt` formatted with `args`, like `appendStringInfo()`, with
+/// postgres's own `printf`, which knows `%m` too.
+///
+/// # Safety
+///
+/// `info` is a valid `StringInfo`, and its memory context is still there.
+/// `args` are what `fmt` calls for.
+pub unsafe fn append_va(
+    info: *mut StringInfoData,
+    fmt: &CStr,
+    args: VaList<'_, '_>,
+) -> io::Result<()> {
+    let append = pg::require(&appendStringInfoVA)?;
+    let enlarge = pg::require(&enlargeStringInfo)?;
+    elog::try_catch(|| {
+        loop {
+            // Each attempt consumes the arguments, so it takes a copy of them.
+            let needed = unsafe { args.with_copy(|args| append(info, fmt.as_ptr(), args)) };
+            if needed == 0 {
+                break;
+            }
+            unsafe { enlarge(info, needed) };
+        }
+    })
+    .map_err(error)
+}
+
+/// Frees `info` and its buffer.
+///
+/// # Safety
+///
+/// `info` was made by [`make`], and its memory context is still there.
+pub unsafe fn free(info: *mut StringInfoData) {
+    if let Some(pfree) = pfree.get() {
+        unsafe {
+            pfree((*info).data.cast());
+            pfree(info.cast());
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! The backend's symbols, from error reporting and memory contexts to
+//! shared memory areas and hash tables.
+//!
+//! By default, these are looked up with `dlsym` on first use, so that a
+//! binary built against this std still runs outside of a backend.
+//! Every call then goes through a function pointer loaded from an atomic,
+//! which no optimizer sees through.
+//!
+//! With the `pg_link_backend` feature, std is only ever loaded into a
+//! backend: [`backend!`] declares the symbols like any C extension does, for
+//! the dynamic linker to resolve when the extension is loaded. Calls to them
+//! are then direct, for the compiler to inline around, and, under
+//! cross-language LTO with a server built to bitcode, to inline entirely.
+
+#[cfg(not(feature = "pg_link_backend"))]
+use crate::sys::weak::DlsymWeak;
+
+/// A symbol of the backend, declared with [`backend!`]: a function pointer,
+/// or a reference to a global variable.
+pub(crate) struct Backend<F: 'static> {
+    // A reference, for a weak symbol to be read at run time: whether it is
+    // there is only known once the extension is loaded.
+    #[cfg(feature = "pg_link_backend")]
+    sym: &'static Option<F>,
+    #[cfg(not(feature = "pg_link_backend"))]
+    sym: DlsymWeak<F>,
+}
+
+impl<F: Copy> Backend<F> {
+    #[cfg(feature = "pg_link_backend")]
+    pub(crate) const fn linked(sym: &'static Option<F>) -> Backend<F> {
+        Backend { sym }
+    }
+
+    #[cfg(not(feature = "pg_link_backend"))]
+    pub(crate) const fn lookup(name: &'static str) -> Backend<F> {
+        Backend { sym: DlsymWeak::new(name) }
+    }
+
+    /// Returns the symbol, or `None` outside of a backend, which a build
+    /// with `pg_link_backend` is never in, or for a `weak` one the server
+    /// does not have.
+    #[inline(always)]
+    pub(crate) fn get(&self) -> Option<F> {
+        #[cfg(feature = "pg_link_backend")]
+        return *self.sym;
+        #[cfg(not(feature = "pg_link_backend"))]
+        self.sym.get()
+    }
+}
+
+/// A symbol of the backend, however it is bound, for `pg::require`.
+pub(crate) trait Symbol<F> {
+    fn get(&self) -> Option<F>;
+}
+
+impl<F: Copy> Symbol<F> for Backend<F> {
+    #[inline(always)]
+    fn get(&self) -> Option<F> {
+        Backend::get(self)
+    }
+}
+
+/// Declares functions and global variables of the backend, as statics of
+/// type [`Backend`] named after them:
+///
+/// ```ignore (illustrative)
+/// backend! {
+///     fn errstart(elevel: c_int, domain: *const c_char) -> bool;
+///     fn errmsg_internal(fmt: *const c_char, ...) -> c_int;
+///     static CurrentMemoryContext: AtomicPtr<c_void>;
+/// }
+/// ```
+///
+/// `errstart` is then a `Backend<unsafe extern "C" fn(c_int, *const c_char)
+/// -> bool>`, and `CurrentMemoryContext` a `Backend<&'static
+/// AtomicPtr<c_void>>`.
+///
+/// A function only some server versions have is declared `weak fn`, which
+/// links it weakly, for `get` to return `None` on the others rather than the
+/// extension failing to load.
+macro_rules! backend {
+    () => {};
+    (
+        $(#[$attr:meta])*
+        fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        #[cfg(feature = "pg_link_backend")]
+        static $name: $crate::sys::symbol::Backend<unsafe extern "C" fn($($ty),*) $(-> $ret)?> = {
+            extern "C" {
+                fn $name($($arg: $ty),*) $(-> $ret)?;
+            }
+            static SYM: Option<unsafe extern "C" fn($($ty),*) $(-> $ret)?> = Some($name);
+            $crate::sys::symbol::Backend::linked(&SYM)
+        };
+        $(#[$attr])*
+        #[cfg(not(feature = "pg_link_backend"))]
+        static $name: $crate::sys::symbol::Backend<unsafe extern "C" fn($($ty),*) $(-> $ret)?> =
+            $crate::sys::symbol::Backend::lookup(concat!(stringify!($name), "\0"));
+        backend! { $($rest)* }
+    };
+    (
+        $(#[$attr:meta])*
+        fn $name:ident($($arg:ident: $ty:ty,)+ ...) $(-> $ret:ty)?;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        #[cfg(feature = "pg_link_backend")]
+        static $name: $crate::sys::symbol::Backend<unsafe extern "C" fn($($ty,)+ ...) $(-> $ret)?> = {
+            extern "C" {
+                fn $name($($arg: $ty,)+ ...) $(-> $ret)?;
+            }
+            static SYM: Option<unsafe extern "C" fn($($ty,)+ ...) $(-> $ret)?> = Some($name);
+            $crate::sys::symbol::Backend::linked(&SYM)
+        };
+        $(#[$attr])*
+        #[cfg(not(feature = "pg_link_backend"))]
+        static $name: $crate::sys::symbol::Backend<unsafe extern "C" fn($($ty,)+ ...) $(-> $ret)?> =
+            $crate::sys::symbol::Backend::lookup(concat!(stringify!($name), "\0"));
+        backend! { $($rest)* }
+    };
+    (
+        $(#[$attr:meta])*
+        weak fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        #[cfg(feature = "pg_link_backend")]
+        static $name: $crate::sys::symbol::Backend<unsafe extern "C" fn($($ty),*) $(-> $ret)?> = {
+            extern "C" {
+                #[linkage = "extern_weak"]
+                static $name: Option<unsafe extern "C" fn($($ty),*) $(-> $ret)?>;
+            }
+            $crate::sys::symbol::Backend::linked(unsafe { &$name })
+        };
+        $(#[$attr])*
+        #[cfg(not(feature = "pg_link_backend"))]
+        static $name: $crate::sys::symbol::Backend<unsafe extern "C" fn($($ty),*) $(-> $ret)?> =
+            $crate::sys::symbol::Backend::lookup(concat!(stringify!($name), "\0"));
+        backend! { $($rest)* }
+    };
+    (
+        $(#[$attr:meta])*
+        static $name:ident: $ty:ty;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        #[cfg(feature = "pg_link_backend")]
+        static $name: $crate::sys::symbol::Backend<&'static $ty> = {
+            extern "C" {
+                static $name: $ty;
+            }
+            static SYM: Option<&'static $ty> = Some(unsafe { &$name });
+            $crate::sys::symbol::Backend::linked(&SYM)
+        };
+        $(#[$attr])*
+        #[cfg(not(feature = "pg_link_backend"))]
+        static $name: $crate::sys::symbol::Backend<&'static $ty> =
+            $crate::sys::symbol::Backend::lookup(concat!(stringify!($name), "\0"));
+        backend! { $($rest)* }
+    };
+}
@@ -1,20 +1,63 @@
 use super::unsupported;
-use crate::ffi::CStr;
+use crate::ffi::{CStr, CString};
 use crate::io;
 use crate::num::NonZeroUsize;
-use crate::time::Duration;
+use crate::time::{Duration, Instant};
 
 // Used from some functions that are never called.
 pub const DEFAULT_MIN_STACK_SIZE: usize = 2 * 1024 * 1024;
 
+/// Always fails: postgres is not thread-safe, and a second thread calling
+/// into the backend corrupts its state, so backends must stay
+/// single-threaded.
+pub fn check_spawn() -> io::Result<()> {
+    Err(io::const_io_error!(
+        io::ErrorKind::Unsupported,
+        "a postgres backend must stay single-threaded, so threads cannot be spawned; \
+         use `std::os::postgres::parallel` (background workers) for parallelism instead",
+    ))
+}
+
+/// Fails if a thread with a `stack`-byte stack would be over the
+/// `max_stack_depth` limit, which postgres measures every stack against,
+/// for whatever runs on it may call into postgres.
+pub fn check_stack_size(stack: usize) -> io::Result<()> {
+    if pg::max_stack_size().map_or(false, |max| stack > max) {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "thread stack size exceeds the configuration parameter \"max_stack_depth\"",
+        ));
+    }
+    Ok(())
+}
+
+/// The name of the backend's only thread, as reported by `thread::current()`:
+/// `postgres:<database>:<pid>`, or `postgres:<pid>` in processes without a
+/// client connection. `None` outside a backend.
+///
+/// The name is computed once and kept for the lifetime of the backend, which
+/// stays connected to the same database throughout.
+pub fn main_thread_name() -> Option<CString> {
+    let pid = pg::my_proc_pid()?;
+    let name = match pg::database_name() {
+        Some(database) => format!("postgres:{database}:{pid}"),
+        None => format!("postgres:{pid}"),
+    };
+    CString::new(name).ok()
+}
+
 impl Thread {
     // unsafe: see thread::Builder::spawn_unchecked for safety requirements
-    pub unsafe fn new(_stack: usize, _p: Box<dyn FnOnce()>) -> io::Result<Thread> {
+    pub unsafe fn new(stack: usize, _p: Box<dyn FnOnce()>) -> io::Result<Thread> {
+        check_stack_size(stack)?;
+        check_spawn()?;
         unsupported()
     }
 
     pub fn yield_now() {
-        // do nothing
+        // There is no other thread to yield to, but this is where busy loops
+        // expect to be interrupted.
+        pg::panic_on_interrupt();
     }
 
     pub fn set_name(_: &CStr) {
@@ -22,9 +65,22 @@ impl Thread {
     }
 
     pub fn sleep(dur: Duration) {
+        // Inside a backend, sleep on the latch so that interrupts cut the
+        // sleep short.
+        let deadline = Instant::now().checked_add(dur);
+        loop {
+            match pg::wait_latch(deadline) {
+                Ok(_) if deadline.map_or(false, |deadline| Instant::now() >= deadline) => return,
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Unsupported => break,
+                Err(e) => panic!("{e}"),
+            }
+        }
+
         let mut secs = dur.as_secs();
         let mut nsecs = dur.subsec_nanos() as _;
 
+        blocking::uninterruptible("nanosleep");
         // If we're awoken with a signal then the return value will be -1 and
         // nanosleep will fill in `ts` with the remaining time.
         unsafe {
@@ -52,14 +108,41 @@ impl Thread {
     }
 }
 
+/// The number of processes a parallel operation started from this backend
+/// can run on: the backend itself, plus as many background workers as a
+/// single `Gather` may use. That is capped by the total number of workers
+/// the server allows, and by the CPU count.
+///
+/// Whether the worker slots are actually free is only known when trying to
+/// launch workers, so this is an upper bound, like it is for threads.
 pub fn available_parallelism() -> io::Result<NonZeroUsize> {
-    unsupported()
+    fn setting(name: &str) -> Option<usize> {
+        pg::config_option(name)?.parse().ok()
+    }
+
+    let per_gather = match setting("max_parallel_workers_per_gather\0") {
+        Some(workers) => workers,
+        None => return unsupported(),
+    };
+    let workers = [setting("max_parallel_workers\0"), setting("max_worker_processes\0")]
+        .into_iter()
+        .flatten()
+        .fold(per_gather, cmp::min);
+
+    let mut parallelism = workers.saturating_add(1);
+    match unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) } {
+        cpus if cpus > 0 => parallelism = cmp::min(parallelism, cpus as usize),
+        _ => {}
+    }
+    Ok(NonZeroUsize::new(parallelism).unwrap())
 }
 
 use crate::cmp;
 use crate::mem;
 use crate::ptr;
+use crate::sys::blocking;
 use crate::sys::os;
+use crate::sys::pg;
 
 #[cfg(all(target_os = "linux", target_env = "gnu"))]
 use crate::sys::weak::dlsym;
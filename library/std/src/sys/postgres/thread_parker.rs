@@ -0,0 +1,57 @@
+//! Thread parking on the backend latch.
+//!
+//! A backend has a single thread, so nothing but a signal handler can unpark
+//! it while it is parked. Parking therefore boils down to sleeping on the
+//! latch: every latch set is a (permitted) spurious wakeup, and interrupts
+//! are noticed while parked instead of after the park ends.
+
+use crate::io;
+use crate::pin::Pin;
+use crate::sync::atomic::AtomicBool;
+use crate::sync::atomic::Ordering::{Acquire, Release};
+use crate::sys::pg;
+use crate::sys::thread::Thread;
+use crate::time::{Duration, Instant};
+
+pub struct Parker {
+    notified: AtomicBool,
+}
+
+impl Parker {
+    pub unsafe fn new(parker: *mut Parker) {
+        parker.write(Parker { notified: AtomicBool::new(false) });
+    }
+
+    pub unsafe fn park(self: Pin<&Self>) {
+        if self.notified.swap(false, Acquire) {
+            return;
+        }
+        match pg::wait_latch(None) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                panic!("cannot park outside of a postgres backend, nothing could unpark us")
+            }
+            // There is no way to report an error from here, and returning
+            // would have the caller park again right away.
+            Err(e) => panic!("{e}"),
+        }
+        self.notified.store(false, Release);
+    }
+
+    pub unsafe fn park_timeout(self: Pin<&Self>, dur: Duration) {
+        if self.notified.swap(false, Acquire) {
+            return;
+        }
+        match pg::wait_latch(Instant::now().checked_add(dur)) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => Thread::sleep(dur),
+            Err(e) => panic!("{e}"),
+        }
+        self.notified.store(false, Release);
+    }
+
+    pub fn unpark(self: Pin<&Self>) {
+        self.notified.store(true, Release);
+        pg::set_latch();
+    }
+}
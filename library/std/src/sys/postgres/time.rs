@@ -159,6 +159,29 @@ impl Instant {
     }
 }
 
+/// portability/instr_time.h's `instr_time`, which `EXPLAIN ANALYZE` and
+/// the statistics collector time with: `CLOCK_MONOTONIC_RAW` on macOS, where
+/// it has the better resolution, and `CLOCK_MONOTONIC` elsewhere.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InstrTime {
+    t: Timespec,
+}
+
+impl InstrTime {
+    #[cfg(target_os = "macos")]
+    const CLOCK: libc::clockid_t = libc::CLOCK_MONOTONIC_RAW;
+    #[cfg(not(target_os = "macos"))]
+    const CLOCK: libc::clockid_t = libc::CLOCK_MONOTONIC;
+
+    pub fn now() -> InstrTime {
+        InstrTime { t: Timespec::now(Self::CLOCK) }
+    }
+
+    pub fn checked_sub(&self, other: &InstrTime) -> Option<Duration> {
+        self.t.sub_timespec(&other.t).ok()
+    }
+}
+
 impl fmt::Debug for Instant {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Instant")
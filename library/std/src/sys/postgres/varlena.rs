@@ -0,0 +1,114 @@
+//! `varlena`, the representation of every variable-length datum: `text`,
+//! `bytea`, arrays, and so on.
+//!
+//! A varlena starts with a header holding its total length, of four bytes,
+//! or of one for a short value that has not been moved to a 4-byte aligned
+//! place yet. The header is as laid out on little-endian machines, which
+//! every postgres target is.
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use super::elog;
+use crate::io;
+use crate::ptr;
+use crate::sys::pg;
+use libc::c_void;
+
+backend! {
+    fn pg_detoast_datum_packed(datum: *mut c_void) -> *mut c_void;
+    fn palloc(size: usize) -> *mut c_void;
+    fn pfree(pointer: *mut c_void);
+}
+
+// varatt.h
+pub const VARHDRSZ: usize = 4;
+const MAX_VARSIZE: usize = 0x3FFF_FFFF;
+
+fn error(inner: elog::ErrorData) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, crate::os::postgres::PgError { inner })
+}
+
+/// Detoasts the varlena at `ptr`, fetching it from its TOAST table and
+/// decompressing it as need be, but leaving a short header as it is.
+///
+/// This returns `ptr` itself if there was nothing to do, or a copy that was
+/// palloc'd in the current memory context.
+///
+/// # Safety
+///
+/// `ptr` is a varlena datum.
+pub unsafe fn detoast(ptr: *mut u8) -> io::Result<*mut u8> {
+    let detoast = pg::require(&pg_detoast_datum_packed)?;
+    elog::try_catch(|| unsafe { detoast(ptr.cast()) }).map(|p| p.cast()).map_err(error)
+}
+
+/// The contents of the detoasted varlena at `ptr`, after its header.
+///
+/// # Safety
+///
+/// `ptr` is a detoasted varlena, which lives for `'a`.
+pub unsafe fn payload<'a>(ptr: *const u8) -> &'a [u8] {
+    let first = unsafe { *ptr };
+    let (header, size) = if first & 0x01 == 0x01 {
+        // VARATT_IS_1B: the length, header included, in the other 7 bits.
+        (1, (first >> 1) as usize)
+    } else {
+        // VARATT_IS_4B_U: the length, header included, in the other 30 bits.
+        let word = unsafe { ptr::read_unaligned(ptr.cast::<u32>()) };
+        (VARHDRSZ, (word >> 2) as usize)
+    };
+    unsafe { crate::slice::from_raw_parts(ptr.add(header), size - header) }
+}
+
+fn check_size(size: usize) -> io::Result<()> {
+    if size > MAX_VARSIZE {
+        return Err(io::const_io_error!(
+            io::ErrorKind::InvalidInput,
+            "value too long for a varlena",
+        ));
+    }
+    Ok(())
+}
+
+/// Makes a varlena of `bytes`, palloc'd in the current memory context.
+pub fn alloc(bytes: &[u8]) -> io::Result<*mut u8> {
+    let size = bytes.len() + VARHDRSZ;
+    check_size(size)?;
+    let palloc = pg::require(&palloc)?;
+    let ptr = elog::try_catch(|| unsafe { palloc(size) }).map_err(error)?.cast::<u8>();
+    unsafe {
+        set_size(ptr, size);
+        ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.add(VARHDRSZ), bytes.len());
+    }
+    Ok(ptr)
+}
+
+/// Writes the 4-byte header of a varlena of `size` bytes, header included,
+/// over the first [`VARHDRSZ`] bytes at `ptr`, failing if that is too long
+/// for one.
+///
+/// # Safety
+///
+/// `ptr` is valid for `size` bytes, and `size` is at least [`VARHDRSZ`].
+pub unsafe fn init(ptr: *mut u8, size: usize) -> io::Result<()> {
+    debug_assert!(size >= VARHDRSZ);
+    check_size(size)?;
+    unsafe { set_size(ptr, size) };
+    Ok(())
+}
+
+// SET_VARSIZE
+unsafe fn set_size(ptr: *mut u8, size: usize) {
+    unsafe { ptr::write_unaligned(ptr.cast::<u32>(), (size as u32) << 2) }
+}
+
+/// Frees a varlena from [`detoast`] or [`alloc`], or one [`init`]ialized in
+/// palloc'd memory.
+///
+/// # Safety
+///
+/// `ptr` was palloc'd, and its memory context is still there.
+pub unsafe fn free(ptr: *mut u8) {
+    if let Some(pfree) = pfree.get() {
+        unsafe { pfree(ptr.cast()) }
+    }
+}
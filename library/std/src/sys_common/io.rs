@@ -2,6 +2,12 @@
 // (in the order of hundreds of KB)
 pub const DEFAULT_BUF_SIZE: usize = if cfg!(target_os = "espidf") { 512 } else { 8 * 1024 };
 
+// Shared with alloc's tests.
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../../../alloc/tests/counting.rs"]
+mod counting;
+
 #[cfg(test)]
 #[allow(dead_code)] // not used on emscripten
 pub mod test {
@@ -11,6 +17,8 @@ pub mod test {
     use crate::thread;
     use rand::RngCore;
 
+    pub use super::counting::CountingAlloc;
+
     pub struct TempDir(PathBuf);
 
     impl TempDir {
@@ -39,6 +39,8 @@ cfg_if::cfg_if! {
     ))] {
         mod futex;
         pub use futex::{Once, OnceState};
+    } else if #[cfg(target_family = "postgres")] {
+        pub use crate::sys::once::{Once, OnceState};
     } else {
         mod generic;
         pub use generic::{Once, OnceState};
@@ -2,12 +2,17 @@
 #![allow(unused_unsafe)] // thread_local with `const {}` triggers this liny
 
 use crate::cell::RefCell;
+#[cfg(target_family = "postgres")]
+use crate::sys::pg;
 use crate::sys::thread::guard::Guard;
 use crate::thread::Thread;
 
 struct ThreadInfo {
     stack_guard: Option<Guard>,
     thread: Thread,
+    /// The backend the info was set up in.
+    #[cfg(target_family = "postgres")]
+    pid: Option<u32>,
 }
 
 thread_local! { static THREAD_INFO: RefCell<Option<ThreadInfo>> = const { RefCell::new(None) } }
@@ -20,9 +25,20 @@ impl ThreadInfo {
         THREAD_INFO
             .try_with(move |thread_info| {
                 let mut thread_info = thread_info.borrow_mut();
+                // Thread-locals are plain statics on postgres, so a backend
+                // inherits the postmaster's across `fork`.
+                #[cfg(target_family = "postgres")]
+                if thread_info.as_ref().map_or(false, |info| info.pid != pg::my_proc_pid()) {
+                    *thread_info = None;
+                }
                 let thread_info = thread_info.get_or_insert_with(|| ThreadInfo {
                     stack_guard: None,
+                    #[cfg(not(target_family = "postgres"))]
                     thread: Thread::new(None),
+                    #[cfg(target_family = "postgres")]
+                    thread: Thread::new(crate::sys::thread::main_thread_name()),
+                    #[cfg(target_family = "postgres")]
+                    pid: pg::my_proc_pid(),
                 });
                 f(thread_info)
             })
@@ -42,6 +58,11 @@ pub fn set(stack_guard: Option<Guard>, thread: Thread) {
     THREAD_INFO.with(move |thread_info| {
         let mut thread_info = thread_info.borrow_mut();
         rtassert!(thread_info.is_none());
-        *thread_info = Some(ThreadInfo { stack_guard, thread });
+        *thread_info = Some(ThreadInfo {
+            stack_guard,
+            thread,
+            #[cfg(target_family = "postgres")]
+            pid: pg::my_proc_pid(),
+        });
     });
 }
@@ -190,11 +190,15 @@ macro_rules! __thread_local_inner {
 
             // wasm without atomics maps directly to `static mut`, and dtors
             // aren't implemented because thread dtors aren't really a thing
-            // on wasm right now
+            // on wasm right now. The same goes for postgres, where a backend
+            // only ever runs a single thread.
             //
             // FIXME(#84224) this should come after the `target_thread_local`
             // block.
-            #[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+            #[cfg(any(
+                all(target_family = "wasm", not(target_feature = "atomics")),
+                target_family = "postgres"
+            ))]
             {
                 static mut VAL: $t = INIT_EXPR;
                 unsafe { $crate::option::Option::Some(&VAL) }
@@ -203,7 +207,10 @@ macro_rules! __thread_local_inner {
             // If the platform has support for `#[thread_local]`, use it.
             #[cfg(all(
                 target_thread_local,
-                not(all(target_family = "wasm", not(target_feature = "atomics"))),
+                not(any(
+                    all(target_family = "wasm", not(target_feature = "atomics")),
+                    target_family = "postgres"
+                )),
             ))]
             {
                 #[thread_local]
@@ -259,7 +266,10 @@ macro_rules! __thread_local_inner {
             // same implementation as below for os thread locals.
             #[cfg(all(
                 not(target_thread_local),
-                not(all(target_family = "wasm", not(target_feature = "atomics"))),
+                not(any(
+                    all(target_family = "wasm", not(target_feature = "atomics")),
+                    target_family = "postgres"
+                )),
             ))]
             {
                 #[inline]
@@ -319,21 +329,30 @@ macro_rules! __thread_local_inner {
             unsafe fn __getit(
                 init: $crate::option::Option<&mut $crate::option::Option<$t>>,
             ) -> $crate::option::Option<&'static $t> {
-                #[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+                #[cfg(any(
+                    all(target_family = "wasm", not(target_feature = "atomics")),
+                    target_family = "postgres"
+                ))]
                 static __KEY: $crate::thread::__StaticLocalKeyInner<$t> =
                     $crate::thread::__StaticLocalKeyInner::new();
 
                 #[thread_local]
                 #[cfg(all(
                     target_thread_local,
-                    not(all(target_family = "wasm", not(target_feature = "atomics"))),
+                    not(any(
+                        all(target_family = "wasm", not(target_feature = "atomics")),
+                        target_family = "postgres"
+                    )),
                 ))]
                 static __KEY: $crate::thread::__FastLocalKeyInner<$t> =
                     $crate::thread::__FastLocalKeyInner::new();
 
                 #[cfg(all(
                     not(target_thread_local),
-                    not(all(target_family = "wasm", not(target_feature = "atomics"))),
+                    not(any(
+                        all(target_family = "wasm", not(target_feature = "atomics")),
+                        target_family = "postgres"
+                    )),
                 ))]
                 static __KEY: $crate::thread::__OsLocalKeyInner<$t> =
                     $crate::thread::__OsLocalKeyInner::new();
@@ -858,10 +877,13 @@ mod lazy {
     }
 }
 
-/// On some targets like wasm there's no threads, so no need to generate
-/// thread locals and we can instead just use plain statics!
+/// On some targets like wasm or postgres there's no threads, so no need to
+/// generate thread locals and we can instead just use plain statics!
 #[doc(hidden)]
-#[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+#[cfg(any(
+    all(target_family = "wasm", not(target_feature = "atomics")),
+    target_family = "postgres"
+))]
 pub mod statik {
     use super::lazy::LazyKeyInner;
     use crate::fmt;
@@ -901,7 +923,13 @@ pub mod statik {
 }
 
 #[doc(hidden)]
-#[cfg(all(target_thread_local, not(all(target_family = "wasm", not(target_feature = "atomics"))),))]
+#[cfg(all(
+    target_thread_local,
+    not(any(
+        all(target_family = "wasm", not(target_feature = "atomics")),
+        target_family = "postgres"
+    )),
+))]
 pub mod fast {
     use super::lazy::LazyKeyInner;
     use crate::cell::Cell;
@@ -1039,7 +1067,10 @@ pub mod fast {
 #[doc(hidden)]
 #[cfg(all(
     not(target_thread_local),
-    not(all(target_family = "wasm", not(target_feature = "atomics"))),
+    not(any(
+        all(target_family = "wasm", not(target_feature = "atomics")),
+        target_family = "postgres"
+    )),
 ))]
 pub mod os {
     use super::lazy::LazyKeyInner;
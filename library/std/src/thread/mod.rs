@@ -205,7 +205,10 @@ pub use self::local::{AccessError, LocalKey};
 #[cfg(not(test))]
 #[cfg(all(
     target_thread_local,
-    not(all(target_family = "wasm", not(target_feature = "atomics"))),
+    not(any(
+        all(target_family = "wasm", not(target_feature = "atomics")),
+        target_family = "postgres"
+    )),
 ))]
 #[doc(hidden)]
 pub use self::local::fast::Key as __FastLocalKeyInner;
@@ -215,20 +218,29 @@ pub use self::local::fast::Key as __FastLocalKeyInner;
 #[cfg(test)]
 #[cfg(all(
     target_thread_local,
-    not(all(target_family = "wasm", not(target_feature = "atomics"))),
+    not(any(
+        all(target_family = "wasm", not(target_feature = "atomics")),
+        target_family = "postgres"
+    )),
 ))]
 pub use realstd::thread::__FastLocalKeyInner;
 
 #[unstable(feature = "libstd_thread_internals", issue = "none")]
 #[cfg(all(
     not(target_thread_local),
-    not(all(target_family = "wasm", not(target_feature = "atomics"))),
+    not(any(
+        all(target_family = "wasm", not(target_feature = "atomics")),
+        target_family = "postgres"
+    )),
 ))]
 #[doc(hidden)]
 pub use self::local::os::Key as __OsLocalKeyInner;
 
 #[unstable(feature = "libstd_thread_internals", issue = "none")]
-#[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+#[cfg(any(
+    all(target_family = "wasm", not(target_feature = "atomics")),
+    target_family = "postgres"
+))]
 #[doc(hidden)]
 pub use self::local::statik::Key as __StaticLocalKeyInner;
 
@@ -347,6 +359,10 @@ impl Builder {
     /// The actual stack size may be greater than this value if
     /// the platform specifies a minimal stack size.
     ///
+    /// Inside a postgres backend, spawning fails with
+    /// [`io::ErrorKind::InvalidInput`] if this is larger than the
+    /// `max_stack_depth` setting allows.
+    ///
     /// For more information about the stack size for threads, see
     /// [this module-level documentation][stack-size].
     ///
@@ -489,6 +505,17 @@ impl Builder {
         T: Send + 'a,
         'scope: 'a,
     {
+        // Fail before touching any shared state (scope counters, output
+        // capture) on targets that can never spawn, with the more specific
+        // error first.
+        #[cfg(target_family = "postgres")]
+        {
+            if let Some(stack_size) = self.stack_size {
+                imp::check_stack_size(stack_size)?;
+            }
+            imp::check_spawn()?;
+        }
+
         let Builder { name, stack_size } = self;
 
         let stack_size = stack_size.unwrap_or_else(thread::min_stack);
@@ -704,6 +731,11 @@ where
     F: Send + 'static,
     T: Send + 'static,
 {
+    // Spawning always fails in a backend, so give the reason without the
+    // `Debug` noise of `expect`.
+    #[cfg(target_family = "postgres")]
+    return Builder::new().spawn(f).unwrap_or_else(|e| panic!("failed to spawn thread: {e}"));
+    #[cfg(not(target_family = "postgres"))]
     Builder::new().spawn(f).expect("failed to spawn thread")
 }
 
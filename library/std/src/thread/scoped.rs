@@ -130,9 +130,6 @@ pub fn scope<'env, F, T>(f: F) -> T
 where
     F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
 {
-    if cfg!(target_family = "postgres") {
-        panic!("threads are not supported in postgrestd");
-    }
     // We put the `ScopeData` into an `Arc` so that other threads can finish their
     // `decrement_num_running_threads` even after this function returns.
     let scope = Scope {
@@ -194,6 +191,13 @@ impl<'scope, 'env> Scope<'scope, 'env> {
         F: FnOnce() -> T + Send + 'scope,
         T: Send + 'scope,
     {
+        // Like `thread::spawn`, give the reason spawning always fails in a
+        // backend without the `Debug` noise of `expect`.
+        #[cfg(target_family = "postgres")]
+        return Builder::new()
+            .spawn_scoped(self, f)
+            .unwrap_or_else(|e| panic!("failed to spawn thread: {e}"));
+        #[cfg(not(target_family = "postgres"))]
         Builder::new().spawn_scoped(self, f).expect("failed to spawn thread")
     }
 }
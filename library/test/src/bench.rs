@@ -12,7 +12,13 @@ use std::cmp;
 use std::io;
 use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+// In a backend, time on the clock postgres's own instrumentation uses.
+#[cfg(target_family = "postgres")]
+use std::os::postgres::instr_time::InstrTime as Instant;
+#[cfg(not(target_family = "postgres"))]
+use std::time::Instant;
 
 /// An identity function that *__hints__* to the compiler to be maximally pessimistic about what
 /// `black_box` could do.
@@ -234,6 +240,24 @@ pub fn benchmark<F>(
     monitor_ch.send(message).unwrap();
 }
 
+/// Runs `f` as a benchmark on the calling thread, as is, for
+/// `postgres::run_benches`.
+#[cfg(target_family = "postgres")]
+pub(crate) fn run_in_place<F>(f: F) -> Result<BenchSamples, String>
+where
+    F: FnMut(&mut Bencher) -> Result<(), String>,
+{
+    let mut bs = Bencher { mode: BenchMode::Auto, summary: None, bytes: 0 };
+    let ns_iter_summ = match bs.bench(f)? {
+        Some(ns_iter_summ) => ns_iter_summ,
+        // iter not called, so no data.
+        None => stats::Summary::new(&[0.0_f64; 1]),
+    };
+    let ns_iter = cmp::max(ns_iter_summ.median as u64, 1);
+    let mb_s = bs.bytes * 1000 / ns_iter;
+    Ok(BenchSamples { ns_iter_summ, mb_s: mb_s as usize })
+}
+
 pub fn run_once<F>(f: F) -> Result<(), String>
 where
     F: FnMut(&mut Bencher) -> Result<(), String>,
@@ -22,6 +22,7 @@
 #![feature(process_exitcode_internals)]
 #![feature(panic_can_unwind)]
 #![feature(test)]
+#![cfg_attr(target_family = "postgres", feature(postgres_ext))]
 
 // Public reexports
 pub use self::bench::{black_box, Bencher};
@@ -71,6 +72,8 @@ mod event;
 mod formatters;
 mod helpers;
 mod options;
+#[cfg(target_family = "postgres")]
+pub mod postgres;
 pub mod stats;
 mod term;
 mod test_result;
@@ -0,0 +1,182 @@
+//! Running tests inside a postgres backend.
+//!
+//! A backend has no terminal for [`run_tests_console`] to print to, nor
+//! threads or processes to run tests in. [`run_tests`] runs them one after
+//! the other on the backend's own thread, reports each result as a `NOTICE`,
+//! which `psql` shows as it arrives, and hands back a [`Summary`] for the SQL
+//! function calling it to return, or to raise an `ERROR` with.
+//! [`run_benches`] does the same with benchmarks, and writes how long their
+//! iterations took to the server log.
+//!
+//! [`run_tests_console`]: crate::run_tests_console
+
+use std::any::Any;
+use std::os::postgres::error::{log, notice};
+use std::os::postgres::{check_interrupt, subtransaction, PgError};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use super::bench::{self, BenchSamples};
+use super::test_result::{calc_result, TestResult};
+use super::types::{TestDescAndFn, TestFn, TestName};
+
+/// What came of a call to [`run_tests`].
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    pub ignored: usize,
+    pub filtered_out: usize,
+    /// The name of each failed test, and why it failed.
+    pub failures: Vec<(TestName, String)>,
+    /// How long an iteration of each benchmark took, for [`run_benches`].
+    pub benches: Vec<(TestName, BenchSamples)>,
+    /// Whether the run stopped early, for the statement was cancelled.
+    pub cancelled: bool,
+    pub exec_time: Duration,
+}
+
+impl Summary {
+    /// Whether every test that ran passed, and the run was not cancelled.
+    pub fn success(&self) -> bool {
+        self.failed == 0 && !self.cancelled
+    }
+}
+
+/// Runs the tests whose names contain `filter`, or all of them, and reports
+/// each result as a `NOTICE` like `cargo test` prints it.
+///
+/// A test fails if it panics, or if postgres raises an `ERROR` in it, unless
+/// it is `#[should_panic]`. Each test runs in an internal subtransaction,
+/// which an `ERROR` rolls back, undoing whatever the test did in the
+/// database before the next one runs. Benchmarks run once, as tests.
+///
+/// Only static tests are supported, like for [`test_main_static`], which
+/// `#[test]` functions are.
+///
+/// The run stops early, with [`Summary::cancelled`] set, if the statement
+/// is cancelled in between tests: postgres raises the cancellation as soon as
+/// the caller returns to it.
+///
+/// [`test_main_static`]: crate::test_main_static
+pub fn run_tests(tests: &[&TestDescAndFn], filter: Option<&str>) -> Summary {
+    run(tests, filter, false)
+}
+
+/// Runs the benchmarks and tests whose names contain `filter`, or all of
+/// them, like [`run_tests`], but benchmarks as such, like `cargo bench`.
+///
+/// Iterations are timed on the clock of `EXPLAIN ANALYZE`, in a real backend
+/// with its caches and memory contexts. How long an iteration of each
+/// benchmark took goes to the server log, as a `LOG` line, with the median
+/// and the spread of the samples.
+pub fn run_benches(tests: &[&TestDescAndFn], filter: Option<&str>) -> Summary {
+    run(tests, filter, true)
+}
+
+fn run(tests: &[&TestDescAndFn], filter: Option<&str>, bench: bool) -> Summary {
+    let start = Instant::now();
+    let mut summary = Summary::default();
+
+    let (tests, filtered_out): (Vec<_>, Vec<_>) = tests
+        .iter()
+        .partition(|test| filter.map_or(true, |filter| test.desc.name.as_slice().contains(filter)));
+    summary.filtered_out = filtered_out.len();
+
+    let _ = notice(format_args!(
+        "running {} test{}",
+        tests.len(),
+        if tests.len() == 1 { "" } else { "s" }
+    ));
+
+    for test in tests {
+        if check_interrupt().is_err() {
+            summary.cancelled = true;
+            break;
+        }
+
+        let name = &test.desc.name;
+        if test.desc.ignore {
+            summary.ignored += 1;
+            let _ = match test.desc.ignore_message {
+                Some(message) => notice(format_args!("test {name} ... ignored, {message}")),
+                None => notice(format_args!("test {name} ... ignored")),
+            };
+            continue;
+        }
+
+        match run_test(test, bench) {
+            TestResult::TrOk => {
+                summary.passed += 1;
+                let _ = notice(format_args!("test {name} ... ok"));
+            }
+            TestResult::TrBench(samples) => {
+                summary.passed += 1;
+                let _ = log(format_args!(
+                    "bench {name} ... {} (median {:.0} ns, min {:.0} ns, max {:.0} ns)",
+                    bench::fmt_bench_samples(&samples),
+                    samples.ns_iter_summ.median,
+                    samples.ns_iter_summ.min,
+                    samples.ns_iter_summ.max,
+                ));
+                let _ = notice(format_args!(
+                    "test {name} ... bench: {}",
+                    bench::fmt_bench_samples(&samples)
+                ));
+                summary.benches.push((name.clone(), samples));
+            }
+            result => {
+                let reason = match result {
+                    TestResult::TrFailedMsg(message) => message,
+                    _ => "test failed".to_string(),
+                };
+                summary.failed += 1;
+                let _ = notice(format_args!("test {name} ... FAILED\n{reason}"));
+                summary.failures.push((name.clone(), reason));
+            }
+        }
+    }
+
+    summary.exec_time = start.elapsed();
+    let _ = notice(format_args!(
+        "test result: {}. {} passed; {} failed; {} ignored; {} filtered out; finished in {:.2}s",
+        if summary.success() { "ok" } else { "FAILED" },
+        summary.passed,
+        summary.failed,
+        summary.ignored,
+        summary.filtered_out,
+        summary.exec_time.as_secs_f64(),
+    ));
+    summary
+}
+
+fn run_test(test: &TestDescAndFn, bench: bool) -> TestResult {
+    let mut samples = None;
+    let result = subtransaction(|| {
+        catch_unwind(AssertUnwindSafe(|| match test.testfn {
+            TestFn::StaticTestFn(f) => f(),
+            TestFn::StaticBenchFn(f) if bench => {
+                samples = Some(bench::run_in_place(|b| f(b))?);
+                Ok(())
+            }
+            TestFn::StaticBenchFn(f) => bench::run_once(|b| f(b)),
+            _ => panic!("non-static tests passed to test::postgres"),
+        }))
+    });
+    let result: Result<(), Box<dyn Any + Send>> = match result {
+        Ok(Ok(Ok(()))) => Ok(()),
+        Ok(Ok(Err(message))) => Err(Box::new(message)),
+        // A `PgError` raised from Rust or from postgres counts as a panic
+        // with its message, for `#[should_panic(expected = ...)]`.
+        Ok(Err(payload)) => match payload.downcast::<PgError>() {
+            Ok(error) => Err(Box::new(error.message().to_string())),
+            Err(payload) => Err(payload),
+        },
+        Err(error) => Err(Box::new(error.message().to_string())),
+    };
+    match (result, samples) {
+        (Ok(()), Some(samples)) => TestResult::TrBench(samples),
+        (Ok(()), None) => calc_result(&test.desc, Ok(()), &None, &None),
+        (Err(e), _) => calc_result(&test.desc, Err(e.as_ref()), &None, &None),
+    }
+}